@@ -0,0 +1,49 @@
+//! Benchmarks of ledger parsing, since the whole-directory readers parse a lot of lines.
+//! Run them with `cargo bench`.
+
+use std::io::{self, BufRead};
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use pledger::pledger::{parse_ledger, parse_ledger_reader, ParseOptions};
+
+/// The number of entries in the benchmarked ledger.
+const ENTRIES: u64 = 100_000;
+
+fn ledger() -> String {
+    (0..ENTRIES)
+        .map(|n| {
+            format!(
+                "D {}.{:02} @{:02} lunch #food #weekday\n",
+                n % 100,
+                n % 97,
+                n % 28 + 1
+            )
+        })
+        .collect()
+}
+
+fn bench_parse_ledger(c: &mut Criterion) {
+    let contents = ledger();
+    let options = ParseOptions::default();
+
+    let mut group = c.benchmark_group("parse_ledger");
+    group.throughput(Throughput::Elements(ENTRIES));
+    group.sample_size(10);
+    group.bench_function("line by line", |b| {
+        // The lines are boxed, so they need their own copy of the contents.
+        b.iter_batched(
+            || contents.clone(),
+            |contents| {
+                parse_ledger("*", Box::new(io::Cursor::new(contents).lines()), &options).unwrap()
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("buffered", |b| {
+        b.iter(|| parse_ledger_reader("*", contents.as_bytes(), &options).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse_ledger);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+//! Parses arbitrary lines as entries, and checks that the entries that parse are written
+//! back out as lines that parse to the same entries.
+//!
+//! Run with `cargo +nightly fuzz run parse_entry` (with cargo-fuzz installed).
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pledger::pledger::Entry;
+
+fuzz_target!(|line: &str| {
+    if let Ok(entry) = line.parse::<Entry>() {
+        let written = entry.to_string();
+        let reparsed: Entry = written.parse().unwrap_or_else(|e| {
+            panic!(
+                "{:?} was written as {:?}, which fails: {}",
+                line, written, e
+            )
+        });
+        assert_eq!(reparsed, entry);
+    }
+});
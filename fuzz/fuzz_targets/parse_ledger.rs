@@ -0,0 +1,12 @@
+//! Parses arbitrary text as a ledger, at each strictness.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pledger::pledger::{parse_ledger_with, Strictness};
+
+fuzz_target!(|text: &[u8]| {
+    for strictness in [Strictness::Strict, Strictness::Normal, Strictness::Lenient] {
+        let _ = parse_ledger_with("2023-05", text, strictness);
+    }
+});
@@ -0,0 +1,6 @@
+//! The ledger format, reports, and everything else behind the `pledger` binary.
+
+pub mod pledger;
+
+/// The in-memory fixtures, for testing code that builds on pledger.
+pub use pledger::testing;
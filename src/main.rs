@@ -1,13 +1,25 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
 use std::process;
 
 use anyhow::{anyhow, Result};
-use chrono::{DateTime, Datelike, Local, Month};
-use clap::{value_parser, Arg, ArgAction, ArgGroup, Command};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc};
+use clap::parser::ValueSource;
+use clap::{value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
 use lazy_static::lazy_static;
-use num_traits::FromPrimitive;
 
-mod pledger;
+use ::pledger::pledger;
+
+use crate::pledger::aggregate::Summary;
+use crate::pledger::config::Config;
+use crate::pledger::diagnostics::Diagnostic;
+use crate::pledger::output::OutputFormat;
+use crate::pledger::query::Query;
+use crate::pledger::EntryKind::{Credit, Debit};
+use crate::pledger::{ParseOptions, Settings, Strictness};
 
 lazy_static! {
     static ref NOW: DateTime<Local> = Local::now();
@@ -20,7 +32,7 @@ fn app() -> Command {
         .about(env!("CARGO_PKG_DESCRIPTION"))
         .group(
             ArgGroup::new("selector")
-                .args(["all", "year", "date", "last"])
+                .args(["all", "year", "quarter", "half", "date", "last", "offset"])
                 .required(false)
                 // NOTE(ww): -d/--date has a default value, so at least one member of selector
                 // is always present. Thus, we need `multiple` to keep clap from dying
@@ -32,28 +44,57 @@ fn app() -> Command {
                 .help("combine all ledgers")
                 .short('a')
                 .long("all")
-                .action(ArgAction::SetTrue),
+                .action(ArgAction::SetTrue)
+                .global(true),
         )
         .arg(
             Arg::new("year")
                 .help("combine all ledgers from the given year")
                 .short('y')
                 .long("year")
-                .num_args(1),
+                .num_args(1)
+                .global(true),
+        )
+        .arg(
+            Arg::new("quarter")
+                .help("combine the ledgers from the given quarter, e.g. 2023-Q2")
+                .long("quarter")
+                .value_parser(parse_period("Q"))
+                .conflicts_with("year")
+                .global(true),
+        )
+        .arg(
+            Arg::new("half")
+                .help("combine the ledgers from the given half-year, e.g. 2023-H1")
+                .long("half")
+                .value_parser(parse_period("H"))
+                .conflicts_with_all(["year", "quarter"])
+                .global(true),
         )
         .arg(
             Arg::new("date")
                 .help("use a ledger by date")
                 .short('d')
                 .long("date")
-                .default_value(<String as AsRef<str>>::as_ref(&NOW_FMT)),
+                .default_value(<String as AsRef<str>>::as_ref(&NOW_FMT))
+                .global(true),
         )
         .arg(
             Arg::new("last")
-                .help("use the previous ledger")
+                .help("use the previous ledger (like --offset -1)")
                 .short('l')
                 .long("last")
-                .action(ArgAction::SetTrue),
+                .action(ArgAction::SetTrue)
+                .conflicts_with("offset")
+                .global(true),
+        )
+        .arg(
+            Arg::new("offset")
+                .help("use the ledger this many months after (or before, if negative) --date")
+                .long("offset")
+                .value_parser(value_parser!(i32))
+                .allow_negative_numbers(true)
+                .global(true),
         )
         .arg(
             Arg::new("edit")
@@ -62,126 +103,2880 @@ fn app() -> Command {
                 .long("edit")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("editor")
+                .help("the editor to use instead of $EDITOR or $VISUAL")
+                .long("editor")
+                .requires("edit")
+                .num_args(1),
+        )
+        .arg(
+            Arg::new("template")
+                .help("when editing a ledger that doesn't exist yet, create it from this file instead of template.ledger")
+                .long("template")
+                .requires("edit")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("format")
+                .help("the output format")
+                .long("format")
+                .value_name("FORMAT")
+                .value_parser(pledger::output::FORMATS),
+        )
         .arg(
             Arg::new("json")
-                .help("output in JSON format")
+                .help("output in JSON format; the same as --format json")
                 .short('j')
                 .long("json")
+                .conflicts_with("format")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("summary")
+                .help("with --json (or --format toml or yaml), output the summary report instead of the ledger's entries")
+                .long("summary")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("query")
+                .help("produce only ledger entries matching this query, e.g. 'kind == debit && amount > 50'")
+                .short('q')
+                .long("query")
+                .global(true),
+        )
+        .arg(
+            Arg::new("untagged")
+                .help("list the entries that have no tags, instead of summarizing")
+                .long("untagged")
+                .conflicts_with("summary")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("by-day")
+                .help("show debits per day of the month (from @DD markers), instead of summarizing")
+                .long("by-day")
+                .conflicts_with_all(["json", "format", "untagged"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("pivot")
+                .help("with --year or --all, show each tag's totals by month, instead of summarizing")
+                .long("pivot")
+                .conflicts_with_all(["json", "format", "untagged", "by-day"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("rolling")
+                .help("with --year or --all, show 3- and 6-month rolling averages of debits, in total and by tag, instead of summarizing")
+                .long("rolling")
+                .conflicts_with_all(["json", "format", "untagged", "by-day", "pivot"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("export")
+                .help("export the selected entries for another accounting tool, instead of summarizing")
+                .long("export")
+                .value_name("FORMAT")
+                .value_parser(["beancount", "csv"])
+                .conflicts_with_all(["json", "format", "untagged", "by-day", "pivot"]),
+        )
+        .arg(
+            Arg::new("top")
+                .help("list only the N largest tags, combining the rest into an (other) row")
+                .long("top")
+                .value_name("N")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("chart")
+                .help("also render the summary as an SVG chart, at the given path")
+                .long("chart")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            Arg::new("diagnostics")
+                .help("how to emit errors and warnings on stderr")
+                .long("diagnostics")
+                .value_name("FORMAT")
+                .value_parser(["human", "json"])
+                .default_value("human")
+                .global(true),
+        )
+        .arg(
+            Arg::new("strict")
+                .help("fail on any anomaly in a ledger, including entries without tags")
+                .long("strict")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("lenient")
+                .global(true),
+        )
+        .arg(
+            Arg::new("lenient")
+                .help("skip the lines of a ledger that can't be parsed, with a warning for each")
+                .long("lenient")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("loose-grouping")
+                .help("allow grouping separators anywhere in amounts, e.g. 1,2,3.45")
+                .long("loose-grouping")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("ignore-tag-case")
+                .help("match tags without regard to case, e.g. #Food as #food")
+                .long("ignore-tag-case")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("fail-if-over-budget")
+                .help("with a single month's ledger, exit with a failure if any tag is over its budget")
+                .long("fail-if-over-budget")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("fail-on-warnings")
+                .help("exit with a failure if there were any warnings, e.g. possible duplicates or skipped lines")
+                .long("fail-on-warnings")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("keep-backups")
+                .help("keep the previous version of each changed ledger as a .bak file")
+                .long("keep-backups")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .help("show the changes that would be made to ledgers as a diff, without making them")
+                .long("dry-run")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("no-cache")
+                .help("parse every ledger, rather than reusing cached ones")
+                .long("no-cache")
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .arg(
+            Arg::new("profile")
+                .help("use the named profile's ledger directory; give more than one for a combined view")
+                .long("profile")
+                .value_name("NAME")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .env("PLEDGER_PROFILE")
+                .global(true),
+        )
+        .arg(
+            Arg::new("account")
+                .help("produce only ledger entries for this account (marked with @account)")
+                .long("account")
+                .num_args(1)
+                .global(true),
+        )
         .arg(
             Arg::new("filter")
                 .help("produce only ledger entries containing these tags (comma-separated)")
                 .short('f')
                 .long("filter")
-                .num_args(1),
+                .num_args(1)
+                .global(true),
         )
         .arg(
-            Arg::new("directory")
-                .help("ledger directory")
-                .index(1)
-                .required(true)
+            Arg::new("ledger-file")
+                .help("use a single ledger file, rather than selecting one from a directory")
+                .long("file")
+                .value_name("FILE")
                 .value_parser(value_parser!(PathBuf))
-                .env("PLEDGER_DIR"),
+                .conflicts_with_all([
+                    "all", "year", "quarter", "half", "date", "last", "offset", "profile",
+                ]),
+        )
+        .arg(
+            directory_arg()
+                .help("ledger directory; give more than one for a combined view")
+                .num_args(1..)
+                .required_unless_present_any(["profile", "ledger-file"]),
+        )
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            Command::new("init")
+                .about("create a ledger directory with a ledger for the current month")
+                .arg(
+                    Arg::new("config")
+                        .help("also create a config skeleton")
+                        .long("config")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("git")
+                        .help("also initialize a git repository")
+                        .long("git")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("plot")
+                .about("plot the selected ledgers in the terminal")
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("dupes")
+                .about("list suspected duplicate entries in the selected ledgers")
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("cat")
+                .about("print a ledger with syntax highlighting and aligned amounts")
+                .arg(
+                    Arg::new("date")
+                        .help("the ledger to print, or a year to print all of its ledgers")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("color")
+                        .help("when to highlight the ledger")
+                        .long("color")
+                        .value_parser(["auto", "always", "never"])
+                        .default_value("auto"),
+                )
+                .arg(directory_arg().index(2)),
+        )
+        .subcommand(
+            Command::new("add")
+                .about("append an entry to the selected ledger")
+                .arg(
+                    Arg::new("entry")
+                        .help("the entry, in ledger syntax, e.g. 'D 12.50 lunch #food'")
+                        .index(1)
+                        .required_unless_present("interactive"),
+                )
+                .arg(
+                    Arg::new("interactive")
+                        .help("ask for each part of the entry instead, completing tags from the ledgers")
+                        .short('i')
+                        .long("interactive")
+                        .conflicts_with("entry")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(directory_flag()),
+        )
+        .subcommand(
+            Command::new("forecast")
+                .about("project the selected month's end-of-month debits, and compare them to the budget")
+                .arg(
+                    Arg::new("months")
+                        .help("average over this many previous months")
+                        .long("months")
+                        .value_name("N")
+                        .value_parser(value_parser!(usize))
+                        .default_value("3"),
+                )
+                .arg(
+                    Arg::new("day")
+                        .help("project as of this day of the month (default: today, or the whole month if it's over)")
+                        .long("day")
+                        .value_parser(value_parser!(u32)),
+                )
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("report")
+                .about("produce an annual report for the year selected with --year")
+                .arg(
+                    Arg::new("format")
+                        .help("the report's format")
+                        .long("format")
+                        .value_parser(["text", "json", "toml", "yaml", "html"])
+                        .default_value("text"),
+                )
+                .arg(
+                    Arg::new("send")
+                        .help("send the report (or a single month's summary) to the config's [webhook], instead of printing it")
+                        .long("send")
+                        .conflicts_with("format")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("email")
+                        .help("email the report (or a single month's summary) to this address with sendmail, instead of printing it")
+                        .long("email")
+                        .value_name("ADDRESS")
+                        .conflicts_with("send"),
+                )
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("balance")
+                .about("report each account's balance, and check it against the ledgers' balance directives")
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("envelopes")
+                .about("report what's left in each envelope, from the ledgers' allocations and debits")
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("debt")
+                .about("report the paydown of the config's debts, as of the selected month, and project their payoffs")
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("networth")
+                .about("report net worth at the end of each month, from accounts' balances and assets.ledger's snapshots, alongside monthly cash flow")
+                .arg(
+                    Arg::new("json")
+                        .help("output each month as JSON")
+                        .short('j')
+                        .long("json")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("goals")
+                .about("report progress toward the config's savings goals, as of the selected month, and project when they'll be met")
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("reconcile")
+                .about("mark a ledger's entries as cleared against a statement, one by one")
+                .arg(
+                    Arg::new("date")
+                        .help("the ledger to reconcile")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("list")
+                        .help("just list the uncleared entries")
+                        .long("list")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(directory_arg().index(2)),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("import a bank statement's (or journal's) transactions into their months' ledgers")
+                .arg(
+                    Arg::new("file")
+                        .help("the statement or journal to import")
+                        .index(1)
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("format")
+                        .help("the statement's format")
+                        .long("format")
+                        .value_parser(["ofx", "qfx", "ledger-cli"])
+                        .default_value("ofx"),
+                )
+                .arg(directory_arg().index(2)),
+        )
+        .subcommand(
+            Command::new("categorize")
+                .about("tag the selected ledgers' untagged entries with the config's rules")
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("retag")
+                .about("rewrite the selected ledgers' aliased tags with their canonical tags, or rename a tag in them")
+                .arg(
+                    Arg::new("rename")
+                        .help("rename a tag, instead of the config's aliases, e.g. '#resturant' '#restaurant'")
+                        .index(1)
+                        .num_args(2)
+                        .value_names(["OLD", "NEW"]),
+                )
+                .arg(directory_flag()),
+        )
+        .subcommand(
+            Command::new("serve")
+                .about("serve an HTTP API for reading the ledgers and appending entries")
+                .arg(
+                    Arg::new("listen")
+                        .help("the address to listen on")
+                        .long("listen")
+                        .value_name("ADDRESS")
+                        .default_value("127.0.0.1:8080"),
+                )
+                .arg(
+                    Arg::new("token")
+                        .help("the bearer token needed to append entries (appending is disabled without one)")
+                        .long("token")
+                        .env("PLEDGER_TOKEN")
+                        .hide_env_values(true),
+                )
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("site")
+                .about("generate a static HTML dashboard of all of the ledgers")
+                .arg(
+                    Arg::new("outdir")
+                        .help("the directory to write the site to")
+                        .index(1)
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(directory_arg().index(2)),
+        )
+        .subcommand(
+            Command::new("metrics")
+                .about("write the selected month's totals and budget as Prometheus metrics")
+                .arg(
+                    Arg::new("output")
+                        .help("write the metrics to a file, atomically, for node_exporter's textfile collector")
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("notify")
+                .about("check the selected month against the config's [notify] conditions, running its command if any hold")
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("archive")
+                .about("move old monthly ledgers into per-year archive files")
+                .arg(
+                    Arg::new("before")
+                        .help("archive the ledgers from before this year")
+                        .long("before")
+                        .value_name("YEAR")
+                        .required(true)
+                        .value_parser(value_parser!(u16).range(1000..=9999)),
+                )
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("check the ledgers and archives against the manifest of their hashes")
+                .arg(
+                    Arg::new("update")
+                        .help("record the current hashes of every ledger and archive, creating the manifest if needed")
+                        .long("update")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("signatures")
+                        .help("also check that every ledger and archive is signed by a known signer")
+                        .long("signatures")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("sign")
+                .about("sign the ledgers and archives that have changed since they were last signed")
+                .arg(
+                    Arg::new("key")
+                        .help("the secret key to sign with, for the config's signing tool")
+                        .long("key")
+                        .value_name("FILE")
+                        .env("PLEDGER_SIGNING_KEY")
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("merge")
+                .about("merge two versions of a ledger, e.g. a sync conflict copy, flagging conflicting edits")
+                .arg(
+                    Arg::new("ours")
+                        .help("a version of the ledger")
+                        .index(1)
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("theirs")
+                        .help("the other version of the ledger")
+                        .index(2)
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("write the merged ledger to a file rather than stdout")
+                        .long("output")
+                        .short('o')
+                        .value_name("FILE")
+                        .value_parser(value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("ical")
+                .about("export the config's recurring entries as an iCalendar file of reminders")
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("fmt")
+                .about("normalize the selected ledgers in place")
+                .arg(
+                    Arg::new("check")
+                        .help("don't write anything; fail if any ledger isn't formatted")
+                        .long("check")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("sort")
+                        .help("also sort each run of entries, credits first")
+                        .long("sort")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("check the selected ledgers for problems, like missing attachments or duplicate IDs")
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("check-grammar")
+                .about("check a file against the ledger format's grammar, line by line, e.g. to compare another parser with pledger's")
+                .arg(
+                    Arg::new("file")
+                        .help("the file to check")
+                        .index(1)
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("json")
+                        .help("output the file's entries, balances, and mismatches as JSON")
+                        .short('j')
+                        .long("json")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("anomalies")
+                .about("flag tags and entries in the selected ledgers that are far above their history")
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("open")
+                .about("open an entry's attachment (@file:PATH) with its default application")
+                .arg(entry_arg())
+                .arg(directory_arg().index(2)),
+        )
+        .subcommand(
+            Command::new("edit-entry")
+                .about("change a single entry in place, without an editor")
+                .arg(entry_arg())
+                .arg(
+                    Arg::new("amount")
+                        .help("the entry's new amount")
+                        .long("amount")
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new("add-tag")
+                        .help("add a tag to the entry (can be given more than once)")
+                        .long("add-tag")
+                        .value_name("TAG")
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("remove-tag")
+                        .help("remove a tag from the entry (can be given more than once)")
+                        .long("remove-tag")
+                        .value_name("TAG")
+                        .action(ArgAction::Append),
+                )
+                .group(
+                    ArgGroup::new("changes")
+                        .args(["amount", "add-tag", "remove-tag"])
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(directory_arg().index(2)),
+        )
+        .subcommand(
+            Command::new("rm")
+                .about("remove a single entry from its ledger")
+                .arg(entry_arg())
+                .arg(
+                    Arg::new("void")
+                        .help("comment the entry out, rather than deleting it")
+                        .long("void")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .help("don't ask for confirmation")
+                        .long("yes")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(directory_arg().index(2)),
+        )
+        .subcommand(
+            Command::new("show")
+                .about("show a single entry, and where it is")
+                .arg(entry_arg())
+                .arg(directory_arg().index(2)),
+        )
+        .subcommand(
+            Command::new("history")
+                .about("show when entries were added, modified, or removed, from the directory's journal")
+                .arg(
+                    Arg::new("enable")
+                        .help("start keeping a journal of changes to the directory's entries")
+                        .long("enable")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("days")
+                        .help("show the changes from the last N days")
+                        .long("days")
+                        .value_name("N")
+                        .default_value("7")
+                        .value_parser(value_parser!(u32)),
+                )
+                .arg(
+                    Arg::new("since")
+                        .help("show the changes since this date, e.g. 2023-05-01")
+                        .long("since")
+                        .value_name("DATE")
+                        .conflicts_with("days")
+                        .value_parser(|d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d")),
+                )
+                .arg(
+                    Arg::new("json")
+                        .help("output the changes as JSON")
+                        .short('j')
+                        .long("json")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("last-entries")
+                .about("show the most recently entered entries, across every ledger")
+                .arg(
+                    Arg::new("count")
+                        .help("how many entries to show")
+                        .short('n')
+                        .long("count")
+                        .default_value("10")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("json")
+                        .help("output the entries as JSON")
+                        .short('j')
+                        .long("json")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("lsp")
+                .about("run a language server for ledger files, on stdio")
+                .arg(directory_arg()),
+        )
+        .subcommand(
+            Command::new("sync-db")
+                .about("load every ledger into a SQLite database, updating only what changed")
+                .arg(
+                    Arg::new("database")
+                        .help("the SQLite database to sync into")
+                        .index(1)
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(directory_arg().index(2)),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("restore a ledger from the backup taken before it was last edited")
+                .arg(
+                    Arg::new("date")
+                        .help("the ledger to restore")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("list")
+                        .help("list the available backups instead of restoring")
+                        .long("list")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("backup")
+                        .help("restore the named backup instead of the most recent one")
+                        .long("backup")
+                        .num_args(1),
+                )
+                .arg(directory_arg().index(2)),
         )
 }
 
-fn run() -> Result<()> {
-    let matches = app().get_matches();
+fn entry_arg() -> Arg {
+    Arg::new("entry")
+        .help("the entry's ID, or DATE:N for the Nth entry in the date's ledger, e.g. 2023-05:3")
+        .index(1)
+        .required(true)
+}
+
+/// Returns a value parser for periods of the given part (`Q` for quarters, and `H` for
+/// halves), e.g. `2023-Q2`.
+fn parse_period(part: &'static str) -> impl Fn(&str) -> Result<String, String> + Clone {
+    move |period: &str| match pledger::parse_period(period).map_err(|e| e.to_string())? {
+        parsed if parsed.get(5..6) == Some(part) => Ok(parsed),
+        _ => Err(format!("expected e.g. 2023-{}1", part)),
+    }
+}
+
+fn directory_arg() -> Arg {
+    Arg::new("directory")
+        .help("ledger directory")
+        .index(1)
+        .required_unless_present("profile")
+        .value_parser(value_parser!(PathBuf))
+        .env("PLEDGER_DIR")
+}
+
+/// The ledger directory as a `--dir` flag, for commands whose positional arguments are
+/// optional, where a positional directory would be ambiguous.
+fn directory_flag() -> Arg {
+    directory_arg().index(None).long("dir").value_name("DIR")
+}
+
+/// Returns whether the ledger is to be read from stdin, i.e. the directory is `-`.
+fn reads_stdin(matches: &ArgMatches) -> bool {
+    matches
+        .get_one::<PathBuf>("directory")
+        .is_some_and(|d| d.as_os_str() == "-")
+}
+
+/// Returns the selected profiles, if any.
+fn profiles(matches: &ArgMatches) -> Result<Vec<pledger::profile::Profile>> {
+    let names: Vec<_> = match matches.get_many::<String>("profile") {
+        Some(names) => names.collect(),
+        None => return Ok(vec![]),
+    };
+
+    if matches.value_source("directory") == Some(ValueSource::CommandLine) {
+        return Err(anyhow!(
+            "give either a ledger directory or --profile, not both"
+        ));
+    }
+
+    let profiles = pledger::profile::Profiles::load()?;
+    Ok(names
+        .into_iter()
+        .map(|name| profiles.get(name).cloned())
+        .collect::<pledger::Result<_>>()?)
+}
+
+/// Returns the ledger directories to use: the selected profiles', or the ones given,
+/// along with their settings.
+fn ledger_dirs(matches: &ArgMatches) -> Result<(Vec<PathBuf>, Settings)> {
+    let profiles = profiles(matches)?;
+    let dirs: Vec<PathBuf> = match profiles.is_empty() {
+        true => matches
+            .get_many::<PathBuf>("directory")
+            .unwrap()
+            .cloned()
+            .collect(),
+        false => profiles.into_iter().map(|p| p.directory).collect(),
+    };
+
+    // Remote directories are used through their local mirrors, which are uploaded once
+    // the command is done (see main).
+    let dirs = dirs
+        .into_iter()
+        .map(|dir| match pledger::remote::is_remote(&dir) {
+            true => pledger::remote::pull(&dir.to_string_lossy()),
+            false => Ok(dir),
+        })
+        .collect::<pledger::Result<Vec<_>>>()?;
+
+    let configs = dirs
+        .iter()
+        .map(|dir| Config::load(dir))
+        .collect::<pledger::Result<Vec<_>>>()?;
+
+    let settings = settings(matches, &configs)?;
 
-    let ledger_dir = matches.get_one::<PathBuf>("directory").unwrap();
+    Ok((dirs, settings))
+}
+
+/// Returns the settings for ledger directories with the given configs, along with the
+/// command line's. The directories have to agree on how their ledgers are read.
+fn settings(matches: &ArgMatches, configs: &[Config]) -> Result<Settings> {
+    let mut formats: Vec<_> = configs.iter().map(|c| c.amount_format()).collect();
+    formats.dedup();
+    let amount_format = match formats.as_slice() {
+        [format] => *format,
+        [first, rest @ ..] if rest.iter().all(|f| f.decimal == first.decimal) => {
+            return Err(anyhow!(
+                "the profiles' ledgers have different decimal places"
+            ))
+        }
+        _ => {
+            return Err(anyhow!(
+                "the profiles' ledgers use different decimal separators"
+            ))
+        }
+    };
+
+    let mut starts: Vec<_> = configs
+        .iter()
+        .map(|c| c.fiscal_year_start.unwrap_or(1))
+        .collect();
+    starts.dedup();
+    let fiscal_year_start = match starts.as_slice() {
+        [start] => *start,
+        _ => return Err(anyhow!("the profiles' ledgers have different fiscal years")),
+    };
+
+    let mut aliases = BTreeMap::new();
+    for (alias, canonical) in configs.iter().flat_map(|c| c.aliases.clone()) {
+        match aliases.get(&alias) {
+            Some(other) if *other != canonical => {
+                return Err(anyhow!(
+                    "the profiles have different aliases for {}: {} and {}",
+                    alias,
+                    other,
+                    canonical
+                ))
+            }
+            _ => {
+                aliases.insert(alias, canonical);
+            }
+        }
+    }
+
+    let strictness = match (matches.get_flag("strict"), matches.get_flag("lenient")) {
+        (true, _) => Strictness::Strict,
+        (_, true) => Strictness::Lenient,
+        _ => Strictness::Normal,
+    };
+
+    Ok(Settings {
+        parse: ParseOptions {
+            strictness,
+            aliases,
+            ignore_tag_case: matches.get_flag("ignore-tag-case")
+                || configs.iter().any(|c| c.ignore_tag_case),
+            loose_grouping: matches.get_flag("loose-grouping")
+                || configs.iter().any(|c| c.loose_grouping),
+            amount_format,
+        },
+        fiscal_year_start,
+        keep_backups: matches.get_flag("keep-backups") || configs.iter().any(|c| c.keep_backups),
+    })
+}
+
+/// Returns the single ledger directory to use, for commands without a combined view.
+fn ledger_dir(matches: &ArgMatches) -> Result<(PathBuf, Settings)> {
+    if reads_stdin(matches) {
+        return Err(anyhow!("this command needs a ledger directory, not stdin"));
+    }
+
+    let (mut dirs, settings) = ledger_dirs(matches)?;
+    match dirs.len() {
+        1 => Ok((dirs.remove(0), settings)),
+        _ => Err(anyhow!(
+            "this command needs a single ledger directory or profile"
+        )),
+    }
+}
+
+fn init(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, _) = &ledger_dir(matches)?;
+
+    let created = pledger::init::init(
+        ledger_dir,
+        &NOW_FMT,
+        *matches.get_one::<bool>("config").unwrap(),
+        *matches.get_one::<bool>("git").unwrap(),
+    )?;
+
+    for path in created.iter() {
+        println!("created {}", path.display());
+    }
+
+    if created.is_empty() {
+        println!(
+            "nothing to do: {} is already initialized",
+            ledger_dir.display()
+        );
+    }
 
-    let (all, year, date, last) = (
+    Ok(())
+}
+
+fn restore(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let date = pledger::parse_date(matches.get_one::<String>("date").unwrap())?;
+
+    if *matches.get_one::<bool>("list").unwrap() {
+        for backup in pledger::backup::list_backups(ledger_dir, &date)? {
+            println!("{}", backup.file_name().unwrap().to_string_lossy());
+        }
+        return Ok(());
+    }
+
+    let backup = pledger::backup::restore_ledger(
+        ledger_dir,
+        &date,
+        settings,
+        matches.get_one::<String>("backup").map(String::as_str),
+    )?;
+    let done = match pledger::plan::dry_run() {
+        true => "would restore",
+        false => "restored",
+    };
+    println!("{} {} from {}", done, date, backup.display());
+
+    Ok(())
+}
+
+/// The ledger(s) chosen by --all, --year, --quarter, --half, --date, --last, or --offset.
+enum Selection {
+    All,
+    /// A year, quarter, or half-year, as returned by `pledger::parse_period`.
+    Period(String),
+    Month(String),
+}
+
+fn selection(matches: &ArgMatches) -> Result<Selection> {
+    let period = ["year", "quarter", "half"]
+        .iter()
+        .find_map(|p| matches.get_one::<String>(p));
+    let (all, period, date, offset) = (
         matches.get_one::<bool>("all").unwrap(),
-        matches.contains_id("year"),
+        period,
         matches.contains_id("date"),
-        matches.get_one::<bool>("last").unwrap(),
+        *matches.get_one::<bool>("last").unwrap() || matches.contains_id("offset"),
     );
 
     // NOTE(ww): Observe once again that `date` is always true, since it has a default.
     // This is pretty messy; there ought to be a better way to do this.
-    let mut ledger = match (all, year, date, last) {
-        (true, false, true, false) => {
-            pledger::parse_ledger("*", pledger::read_all_ledgers(ledger_dir)?)?
-        }
-        (false, true, true, false) => {
-            let year = matches.get_one::<String>("year").unwrap();
-            pledger::parse_ledger(year, pledger::read_ledgers_for_year(ledger_dir, year)?)?
-        }
-        (false, false, true, true) => {
-            let last_month = Month::from_u32(NOW.month())
-                .ok_or_else(|| {
-                    anyhow!(
-                        "unlikely failure converting {} into a chrono::Month",
-                        NOW.month()
-                    )
-                })?
-                .pred();
-
-            log::debug!("{:?}", last_month);
-
-            // If we've wrapped back around to December, correct the year as well.
-            let year = match last_month {
-                Month::December => NOW.year() - 1,
-                _ => NOW.year(),
+    match (all, period, date, offset) {
+        (true, None, true, false) => Ok(Selection::All),
+        (false, Some(period), true, false) => Ok(Selection::Period(period.clone())),
+        (false, None, true, _) => {
+            let date = pledger::parse_date(matches.get_one::<String>("date").unwrap())?;
+            let offset = match matches.get_one::<bool>("last").unwrap() {
+                true => -1,
+                false => matches.get_one::<i32>("offset").copied().unwrap_or(0),
             };
 
-            // NOTE(ww): Without `with_day`, we'd naively jump backyards to an invalid date
-            // on some months. For example, July 31st would become June 31st, which isn't a real
-            // day. Every month should have a first day, so `with_day(1)` should always succeed.
-            let last = NOW
-                .with_day(1)
-                .and_then(|d| d.with_month(last_month.number_from_month()))
-                .and_then(|d| d.with_year(year))
-                .ok_or_else(|| anyhow!("datetime calculation for the previous month failed"))?;
+            Ok(Selection::Month(pledger::offset_date(&date, offset)?))
+        }
+        _ => Err(anyhow!(
+            "conflicting uses of --all, --year, --quarter, --half, --date, --last, or --offset"
+        )),
+    }
+}
+
+fn filter(
+    matches: &ArgMatches,
+    options: &ParseOptions,
+    ledger: &mut pledger::Ledger,
+) -> pledger::Result<()> {
+    if let Some(filter) = matches.get_one::<String>("filter") {
+        let filter: Vec<&str> = filter.split(',').collect();
+        ledger.filter(&filter, options);
+    }
 
-            let date = last.format("%Y-%m").to_string();
+    if let Some(query) = matches.get_one::<String>("query") {
+        ledger.query(&Query::parse(query, options)?);
+    }
 
-            // TODO(ww): Dedupe with below.
-            if *matches.get_one::<bool>("edit").unwrap() {
-                return pledger::edit_ledger(&date, ledger_dir);
-            }
+    if let Some(account) = matches.get_one::<String>("account") {
+        ledger.account(account);
+    }
+
+    Ok(())
+}
+
+/// Returns the currency of the selected profiles, which must agree, for exports.
+fn currency(matches: &ArgMatches) -> Result<String> {
+    let mut currencies: Vec<_> = profiles(matches)?
+        .into_iter()
+        .map(|p| {
+            p.currency
+                .unwrap_or_else(|| pledger::export::CURRENCY.into())
+        })
+        .collect();
+    currencies.sort();
+    currencies.dedup();
+
+    match currencies.len() {
+        0 => Ok(pledger::export::CURRENCY.into()),
+        1 => Ok(currencies.remove(0)),
+        _ => Err(anyhow!(
+            "can't combine profiles in different currencies: {}",
+            currencies.join(", ")
+        )),
+    }
+}
 
-            pledger::parse_ledger(&date, pledger::read_ledger(ledger_dir, &date)?)?
+/// Returns the name that a directory's entries are annotated with in a combined view:
+/// its last component, e.g. `business` for `~/ledgers/business`.
+fn source_name(ledger_dir: &Path) -> String {
+    ledger_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| ledger_dir.display().to_string())
+}
+
+/// Marks the ledgers' entries as coming from `ledger_dir`, if there's more than one
+/// directory in the view.
+fn annotate(ledger_dirs: &[PathBuf], ledger_dir: &Path, ledgers: &mut [pledger::Ledger]) {
+    if ledger_dirs.len() > 1 {
+        let source = source_name(ledger_dir);
+        for ledger in ledgers.iter_mut() {
+            ledger.set_directory(&source);
         }
-        (false, false, true, false) => {
-            let date = pledger::parse_date(matches.get_one::<String>("date").unwrap())?;
+    }
+}
+
+/// Parses the selected ledger from each directory, combining them into one.
+fn combined_ledger(
+    ledger_dirs: &[PathBuf],
+    settings: &Settings,
+    selection: &Selection,
+) -> Result<pledger::Ledger> {
+    let period = match selection {
+        Selection::Month(date) => return combined_month(ledger_dirs, settings, date),
+        Selection::Period(period) => Some(period.as_str()),
+        Selection::All => None,
+    };
+
+    // Months are combined across directories first, so that the entries are in
+    // date order rather than in directory order.
+    let mut months = BTreeMap::new();
+    for ledger_dir in ledger_dirs.iter() {
+        let mut ledgers = pledger::parse_monthly_ledgers(ledger_dir, period, settings)?;
+        annotate(ledger_dirs, ledger_dir, &mut ledgers);
+        merge_months(&mut months, ledgers);
+    }
+
+    Ok(pledger::combine_ledgers(
+        period.unwrap_or("*"),
+        months.into_values(),
+    ))
+}
+
+fn combined_month(
+    ledger_dirs: &[PathBuf],
+    settings: &Settings,
+    date: &str,
+) -> Result<pledger::Ledger> {
+    let mut ledger: Option<pledger::Ledger> = None;
+    for ledger_dir in ledger_dirs.iter() {
+        let mut next = match pledger::load_ledger(ledger_dir, date, &settings.parse) {
+            // In a combined view, a month only needs to be in one directory.
+            Err(pledger::Error::NotFound(_)) if ledger_dirs.len() > 1 => continue,
+            result => result?,
+        };
+        annotate(ledger_dirs, ledger_dir, std::slice::from_mut(&mut next));
+
+        match ledger.as_mut() {
+            Some(ledger) => ledger.merge(next),
+            None => ledger = Some(next),
+        }
+    }
+
+    ledger.ok_or_else(|| anyhow!("no ledger in any of the directories"))
+}
 
-            if *matches.get_one::<bool>("edit").unwrap() {
-                return pledger::edit_ledger(&date, ledger_dir);
+/// Merges each ledger into the same month's ledger in `months`, if there is one.
+fn merge_months(months: &mut BTreeMap<String, pledger::Ledger>, ledgers: Vec<pledger::Ledger>) {
+    for ledger in ledgers {
+        match months.get_mut(ledger.date()) {
+            Some(existing) => existing.merge(ledger),
+            None => {
+                months.insert(ledger.date().into(), ledger);
             }
+        }
+    }
+}
 
-            pledger::parse_ledger(&date, pledger::read_ledger(ledger_dir, &date)?)?
+/// Prints the summary report for a year or all ledgers, along with each month's suspected
+/// duplicates, reading the ledgers a month at a time.
+fn stream_summary(
+    matches: &ArgMatches,
+    ledger_dirs: &[PathBuf],
+    settings: &Settings,
+    selection: &Selection,
+) -> Result<()> {
+    let (label, period) = match selection {
+        Selection::Period(period) => (period.as_str(), Some(period.as_str())),
+        _ => ("*", None),
+    };
+
+    let streams = ledger_dirs
+        .iter()
+        .map(|dir| {
+            let limits = pledger::config::Config::load(dir)?.limits;
+            Ok(pledger::stream_monthly_ledgers(dir, period, settings)?
+                .map(move |l| (dir, l, limits.clone())))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut dupes = vec![];
+    let mut exceeded = 0;
+    let ledgers = streams.into_iter().flatten().map(|(dir, ledger, limits)| {
+        let mut ledger = ledger?;
+        for err in ledger.errors().iter() {
+            Diagnostic::recovered(err).emit(json_diagnostics(matches));
         }
-        _ => {
-            return Err(anyhow!(
-                "conflicting uses of --all, --year, --date, or --last"
-            ))
+        filter(matches, &settings.parse, &mut ledger)?;
+        for dupe in pledger::dupes::find_duplicates(std::slice::from_ref(&ledger)) {
+            dupes.push((dir, dupe));
+        }
+        for limit in pledger::limits::check(&limits, &ledger)? {
+            limit_warning(dir, &limit).emit(json_diagnostics(matches));
+            exceeded += 1;
+        }
+        Ok(ledger)
+    });
+    let summary = pledger::aggregate::summarize_stream(label, ledgers)?;
+
+    let top = matches.get_one::<usize>("top").copied();
+    if *matches.get_one::<bool>("summary").unwrap() {
+        let report = pledger::aggregate::Report::new(&summary, top);
+        print!("{}", output_format(matches).render(&report)?);
+    } else if output_format(matches) == OutputFormat::Markdown {
+        print!(
+            "{}",
+            pledger::aggregate::Report::new(&summary, top).to_markdown()
+        );
+    } else {
+        pledger::summarize(&summary, top, exceeded);
+        for (dir, dupe) in dupes.iter() {
+            dupe.diagnostic(Some(dir)).emit(json_diagnostics(matches));
+        }
+    }
+
+    Ok(())
+}
+
+/// Like `monthly_ledgers`, but combining each month's ledgers from several directories.
+fn combined_monthly_ledgers(
+    matches: &ArgMatches,
+    ledger_dirs: &[PathBuf],
+    settings: &Settings,
+    selection: &Selection,
+) -> Result<Vec<pledger::Ledger>> {
+    if let [ledger_dir] = ledger_dirs {
+        return monthly_ledgers(matches, ledger_dir, settings, selection);
+    }
+
+    let mut combined = BTreeMap::new();
+    for ledger_dir in ledger_dirs.iter() {
+        let mut ledgers = match selection {
+            // A month can be missing from some of the directories, and can be
+            // compressed or archived in others.
+            Selection::Month(date) if !pledger::ledger_dates(ledger_dir)?.contains(date) => {
+                continue
+            }
+            selection => monthly_ledgers(matches, ledger_dir, settings, selection)?,
+        };
+        annotate(ledger_dirs, ledger_dir, &mut ledgers);
+
+        merge_months(&mut combined, ledgers);
+    }
+
+    Ok(combined.into_values().collect())
+}
+
+/// Parses each month's ledger in the selection separately, with any filters applied.
+fn monthly_ledgers(
+    matches: &ArgMatches,
+    ledger_dir: &Path,
+    settings: &Settings,
+    selection: &Selection,
+) -> Result<Vec<pledger::Ledger>> {
+    let mut ledgers = match selection {
+        Selection::Month(date) => vec![pledger::load_ledger(ledger_dir, date, &settings.parse)?],
+        Selection::Period(period) => {
+            pledger::parse_monthly_ledgers(ledger_dir, Some(period), settings)?
         }
+        Selection::All => pledger::parse_monthly_ledgers(ledger_dir, None, settings)?,
     };
 
-    if let Some(filter) = matches.get_one::<String>("filter") {
-        let filter: Vec<&str> = filter.split(',').collect();
-        ledger.filter(&filter);
+    for ledger in ledgers.iter_mut() {
+        filter(matches, &settings.parse, ledger)?;
     }
 
-    if *matches.get_one::<bool>("json").unwrap() {
-        println!("{}", serde_json::to_string(&ledger).unwrap());
-    } else {
-        pledger::summarize(&ledger);
+    Ok(ledgers)
+}
+
+fn plot(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let width = pledger::plot::terminal_width();
+
+    let series = match selection(matches)? {
+        Selection::Month(date) => {
+            let mut ledger = pledger::load_ledger(ledger_dir, &date, &settings.parse)?;
+            filter(matches, &settings.parse, &mut ledger)?;
+
+            println!("Ledger for {}\n", date);
+            [
+                pledger::aggregate::tag_totals(&ledger, &Debit)?,
+                pledger::aggregate::tag_totals(&ledger, &Credit)?,
+            ]
+        }
+        selection => {
+            let ledgers = monthly_ledgers(matches, ledger_dir, settings, &selection)?;
+
+            let period = match &selection {
+                Selection::Period(period) => period.as_str(),
+                _ => "*",
+            };
+            println!("Ledger for {}\n", period);
+            [
+                pledger::aggregate::monthly_totals(&ledgers, &Debit)?,
+                pledger::aggregate::monthly_totals(&ledgers, &Credit)?,
+            ]
+        }
+    };
+
+    for (idx, series) in series.iter().enumerate() {
+        if idx > 0 {
+            println!();
+        }
+        print!("{}", pledger::plot::bar_chart(series, width));
     }
 
     Ok(())
 }
 
-fn main() {
-    env_logger::init();
+fn dupes(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let ledgers = monthly_ledgers(matches, ledger_dir, settings, &selection(matches)?)?;
 
-    process::exit(match run() {
-        Ok(()) => 0,
-        Err(e) => {
-            eprintln!("Fatal: {}", e);
-            1
+    for dupe in pledger::dupes::find_duplicates(&ledgers).iter() {
+        println!("{} {:>3}x {}", dupe.date, dupe.count, dupe.entry);
+    }
+
+    Ok(())
+}
+
+fn cat(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let date = matches.get_one::<String>("date").unwrap();
+
+    // A year's ledgers are printed like an archive, each under a month header.
+    let year = date.len() == 4 && date.bytes().all(|b| b.is_ascii_digit());
+    let dates = match year {
+        true => selected_dates(ledger_dir, settings, &Selection::Period(date.into()))?,
+        false => vec![pledger::parse_date(date)?],
+    };
+    if dates.is_empty() {
+        return Err(anyhow!("no ledgers for {}", date));
+    }
+
+    let mut contents = String::new();
+    for item in pledger::source::LedgerSource::new(ledger_dir, dates) {
+        let (source, line) = item?;
+        if year && source.line == 1 {
+            contents.push_str(&format!("# month: {}\n", source.date));
+        }
+        contents.push_str(&line);
+        contents.push('\n');
+    }
+
+    let colored = match matches.get_one::<String>("color").unwrap().as_str() {
+        "always" => true,
+        "never" => false,
+        _ => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+    };
+
+    print!(
+        "{}",
+        pledger::syntax::highlight(&contents, colored, &settings.parse)
+    );
+
+    Ok(())
+}
+
+fn add(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+
+    let date = match selection(matches)? {
+        Selection::Month(date) => date,
+        _ => {
+            return Err(anyhow!(
+                "entries can only be added to a single month's ledger"
+            ))
+        }
+    };
+
+    let entry = match matches.get_one::<String>("entry") {
+        Some(entry) => entry.parse::<pledger::Entry>()?,
+        None => {
+            let existing = pledger::wizard::existing_tags(&pledger::parse_monthly_ledgers(
+                ledger_dir, None, settings,
+            )?);
+            let entry =
+                pledger::wizard::ask_entry(&mut io::stdin().lock(), &mut io::stderr(), &existing)?;
+            match entry {
+                Some(entry) => entry,
+                None => return Err(anyhow!("no entry given; nothing added")),
+            }
+        }
+    };
+
+    pledger::append_entry(ledger_dir, &date, settings, &entry)?;
+    println!("{}: {}", date, entry);
+
+    Ok(())
+}
+
+fn forecast(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let months = *matches.get_one::<usize>("months").unwrap();
+
+    let date = match selection(matches)? {
+        Selection::Month(date) => date,
+        _ => return Err(anyhow!("forecasts are for a single month's ledger")),
+    };
+
+    let mut current = pledger::load_ledger(ledger_dir, &date, &settings.parse)?;
+    filter(matches, &settings.parse, &mut current)?;
+
+    let mut previous = pledger::ledger_dates(ledger_dir)?;
+    previous.retain(|d| d.as_str() < date.as_str());
+
+    let mut history = vec![];
+    for date in previous.iter().skip(previous.len().saturating_sub(months)) {
+        let mut ledger = pledger::load_ledger(ledger_dir, date, &settings.parse)?;
+        filter(matches, &settings.parse, &mut ledger)?;
+        history.push(ledger);
+    }
+
+    let day = match matches.get_one::<u32>("day") {
+        Some(day) => *day,
+        None if date == NOW_FMT.as_str() => NOW.day(),
+        None => pledger::aggregate::days_in_month(&date)?,
+    };
+
+    let config = pledger::config::Config::load(ledger_dir)?;
+    let projections =
+        pledger::forecast::forecast(&current, &history, day, &config.budgets(&settings.parse))?;
+
+    println!(
+        "Forecast for {} (day {} of {})\n",
+        date,
+        day,
+        pledger::aggregate::days_in_month(&date)?
+    );
+    println!(
+        "{:<16} {:>10} {:>10} {:>10} {:>10}",
+        "tag", "spent", "linear", "average", "budget"
+    );
+
+    let dash = || "-".to_string();
+    for projection in projections.iter() {
+        println!(
+            "{:<16} {:>10} {:>10} {:>10} {:>10}{}",
+            projection.tag,
+            projection.spent,
+            projection.linear,
+            projection
+                .average
+                .map(|a| a.to_string())
+                .unwrap_or_else(dash),
+            projection.limit.map(|l| l.to_string()).unwrap_or_else(dash),
+            if projection.over() { " (over)" } else { "" }
+        );
+    }
+
+    Ok(())
+}
+
+fn report(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+
+    let selection = selection(matches)?;
+    if matches.get_flag("send") {
+        return send_report(matches, ledger_dir, settings, &selection);
+    }
+    if let Some(address) = matches.get_one::<String>("email") {
+        return email_report(matches, ledger_dir, settings, &selection, address);
+    }
+    let year = match &selection {
+        Selection::Period(year) if matches.contains_id("year") => year.clone(),
+        _ => return Err(anyhow!("reports are for a whole year; pass --year")),
+    };
+
+    let ledgers = monthly_ledgers(matches, ledger_dir, settings, &selection)?;
+    let report = pledger::report::AnnualReport::new(&year, &ledgers)?;
+
+    match matches.get_one::<String>("format").unwrap().as_str() {
+        "html" => print!("{}", report.to_html()),
+        "text" => print!("{}", report.to_text()),
+        format => print!("{}", format.parse::<OutputFormat>()?.render(&report)?),
+    }
+
+    Ok(())
+}
+
+/// Renders the annual report for --year, or a single month's summary, to be sent
+/// somewhere. Returns its title and text; annual reports are HTML if `html` is set.
+fn sendable_report(
+    matches: &ArgMatches,
+    ledger_dir: &Path,
+    settings: &Settings,
+    selection: &Selection,
+    html: bool,
+) -> Result<(String, String)> {
+    let ledgers = monthly_ledgers(matches, ledger_dir, settings, selection)?;
+    match selection {
+        Selection::Month(_) if html => Err(anyhow!("HTML reports are for a whole year")),
+        Selection::Month(date) => {
+            let summary = Summary::new(&ledgers[0])?;
+            Ok((
+                format!("pledger: {}", date),
+                pledger::aggregate::Report::new(&summary, None).to_markdown(),
+            ))
+        }
+        Selection::Period(year) if matches.contains_id("year") => {
+            let report = pledger::report::AnnualReport::new(year, &ledgers)?;
+            Ok((
+                format!("pledger: {}", year),
+                match html {
+                    true => report.to_html(),
+                    false => report.to_text(),
+                },
+            ))
+        }
+        _ => Err(anyhow!("reports are for a whole year or a single month")),
+    }
+}
+
+/// Sends the annual report for --year, or a single month's summary, to the webhook.
+fn send_report(
+    matches: &ArgMatches,
+    ledger_dir: &Path,
+    settings: &Settings,
+    selection: &Selection,
+) -> Result<()> {
+    let webhook = pledger::config::Config::load(ledger_dir)?
+        .webhook
+        .ok_or_else(|| anyhow!("there's no [webhook] in the config to send the report to"))?;
+
+    let (title, text) = sendable_report(matches, ledger_dir, settings, selection, false)?;
+    Ok(pledger::webhook::send(&webhook, &title, &text)?)
+}
+
+/// Emails the annual report for --year, or a single month's summary, to `address`.
+fn email_report(
+    matches: &ArgMatches,
+    ledger_dir: &Path,
+    settings: &Settings,
+    selection: &Selection,
+    address: &str,
+) -> Result<()> {
+    let email = pledger::config::Config::load(ledger_dir)?.email;
+    let html = match matches.get_one::<String>("format").unwrap().as_str() {
+        "html" => true,
+        "text" => false,
+        format => return Err(anyhow!("reports can't be emailed as {}", format)),
+    };
+
+    let (subject, body) = sendable_report(matches, ledger_dir, settings, selection, html)?;
+    let message = pledger::email::message(address, email.from.as_deref(), &subject, &body, html)?;
+    pledger::email::send(
+        email
+            .command
+            .as_deref()
+            .unwrap_or(pledger::email::DEFAULT_COMMAND),
+        &message,
+    )?;
+    Ok(())
+}
+
+fn balance(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+
+    // Balances accumulate from the very first ledger, so they ignore the
+    // selection and any filters.
+    let ledgers = pledger::parse_monthly_ledgers(ledger_dir, None, settings)?;
+    let balances = pledger::balance::balances(&ledgers);
+
+    for (account, balance) in balances.accounts.iter() {
+        println!(
+            "{:<16} {:>10}",
+            format!("@{}", account),
+            pledger::balance::signed(*balance, settings.parse.amount_format)
+        );
+    }
+
+    for mismatch in balances.mismatches.iter() {
+        Diagnostic::warning(
+            Some(ledger_dir.join(format!("{}.ledger", mismatch.date))),
+            format!(
+                "stated balance of @{} is {}, but its entries add up to {}",
+                mismatch.account,
+                pledger::balance::signed(mismatch.stated, settings.parse.amount_format),
+                pledger::balance::signed(mismatch.computed, settings.parse.amount_format)
+            ),
+        )
+        .emit(json_diagnostics(matches));
+    }
+
+    Ok(())
+}
+
+fn envelopes(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+
+    // Like balances, envelopes accumulate from the very first ledger.
+    let ledgers = pledger::parse_monthly_ledgers(ledger_dir, None, settings)?;
+    let envelopes = pledger::envelope::envelopes(&ledgers);
+    if envelopes.envelopes.is_empty() {
+        return Err(anyhow!(
+            "no envelopes in {}; allocate to one with e.g. 'A 200.00 #food'",
+            ledger_dir.display()
+        ));
+    }
+
+    let signed = |subunits| pledger::balance::signed(subunits, settings.parse.amount_format);
+    println!(
+        "{:<16} {:>10} {:>10} {:>10}",
+        "envelope", "allocated", "spent", "left"
+    );
+    for (tag, envelope) in envelopes.envelopes.iter() {
+        println!(
+            "{:<16} {:>10} {:>10} {:>10}{}",
+            tag,
+            signed(envelope.allocated),
+            signed(envelope.spent),
+            signed(envelope.balance()),
+            if envelope.balance() < 0 {
+                " (over)"
+            } else {
+                ""
+            }
+        );
+    }
+    println!("\nunallocated: {}", signed(envelopes.unallocated));
+
+    Ok(())
+}
+
+fn debt(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let date = match selection(matches)? {
+        Selection::Month(date) => date,
+        _ => return Err(anyhow!("debts are as of a single month; pass --date")),
+    };
+
+    let config = pledger::config::Config::load(ledger_dir)?;
+    if config.debts.is_empty() {
+        return Err(anyhow!(
+            "no debts in {}'s config; add one with [[debts]]",
+            ledger_dir.display()
+        ));
+    }
+
+    // Like goals, debts are paid down from the very first ledger.
+    let ledgers = pledger::parse_monthly_ledgers(ledger_dir, None, settings)?;
+
+    let signed = |subunits| pledger::balance::signed(subunits, settings.parse.amount_format);
+    let dash = || "-".to_string();
+    println!("Debts as of {}\n", date);
+    println!(
+        "{:<16} {:>10} {:>10} {:>10} {:>10} {:>8} {:>13}",
+        "debt", "principal", "balance", "interest", "payment", "payoff", "interest left"
+    );
+    for debt in config.debts.iter() {
+        let paydown = pledger::debt::paydown(debt, &ledgers, &date, &settings.parse)?;
+        let payoff = match (paydown.is_paid(), &paydown.payoff, paydown.payment) {
+            (true, _, _) => "paid".into(),
+            (false, Some(payoff), _) => payoff.clone(),
+            (false, None, Some(_)) => "never".into(),
+            (false, None, None) => dash(),
+        };
+        println!(
+            "{:<16} {:>10} {:>10} {:>10} {:>10} {:>8} {:>13}",
+            paydown.name,
+            paydown.principal,
+            signed(paydown.balance),
+            signed(paydown.interest),
+            paydown.payment.map(signed).unwrap_or_else(dash),
+            payoff,
+            paydown.interest_left.map(signed).unwrap_or_else(dash),
+        );
+    }
+
+    Ok(())
+}
+
+fn networth(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+
+    // Like balances, net worth accumulates from the very first ledger, so it
+    // ignores any filters; the selection only picks the months that are shown.
+    let ledgers = pledger::parse_monthly_ledgers(ledger_dir, None, settings)?;
+    let snapshots = pledger::networth::load_snapshots(ledger_dir, settings)?;
+
+    let selection = selection(matches)?;
+    let last = [
+        ledgers.last().map(|l| l.date().to_string()),
+        snapshots.last().map(|s| s.date.clone()),
+    ]
+    .into_iter()
+    .flatten()
+    .max();
+    // Months after the last ledger or snapshot wouldn't change anything, so
+    // the selected month (which defaults to the current one) is only an upper bound.
+    let through = match (&selection, last) {
+        (Selection::Month(date), Some(last)) => date.clone().min(last),
+        (_, last) => last.unwrap_or_default(),
+    };
+
+    let mut months = pledger::networth::net_worth(&ledgers, &snapshots, &through)?;
+    if let Selection::Period(period) = &selection {
+        months.retain(|m| pledger::in_period(&m.date, period, settings.fiscal_year_start));
+    }
+    if months.is_empty() {
+        return Err(anyhow!(
+            "no ledgers or {} in {}",
+            pledger::networth::ASSETS_FILE,
+            ledger_dir.display()
+        ));
+    }
+
+    if matches.get_flag("json") {
+        println!("{}", serde_json::to_string_pretty(&months)?);
+        return Ok(());
+    }
+
+    let signed = |subunits| pledger::balance::signed(subunits, settings.parse.amount_format);
+    let columns: Vec<_> = months
+        .iter()
+        .map(|m| (signed(m.net_worth), signed(m.cash_flow)))
+        .collect();
+    let worth_width = columns
+        .iter()
+        .map(|(w, _)| w.len())
+        .max()
+        .unwrap_or(0)
+        .max(9);
+    let flow_width = columns
+        .iter()
+        .map(|(_, f)| f.len())
+        .max()
+        .unwrap_or(0)
+        .max(9);
+    let bar_width = pledger::plot::terminal_width()
+        .saturating_sub(7 + worth_width + flow_width + 3)
+        .max(1);
+    let bars = pledger::plot::signed_bars(
+        &months.iter().map(|m| m.net_worth).collect::<Vec<_>>(),
+        bar_width,
+    );
+
+    println!(
+        "{:<7} {:>worth_width$} {:>flow_width$}",
+        "month", "net worth", "cash flow"
+    );
+    for ((month, (worth, flow)), bar) in months.iter().zip(columns.iter()).zip(bars.iter()) {
+        println!(
+            "{:<7} {:>worth_width$} {:>flow_width$} {}",
+            month.date, worth, flow, bar
+        );
+    }
+
+    Ok(())
+}
+
+fn goals(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let date = match selection(matches)? {
+        Selection::Month(date) => date,
+        _ => return Err(anyhow!("goals are as of a single month; pass --date")),
+    };
+
+    let config = pledger::config::Config::load(ledger_dir)?;
+    if config.goals.is_empty() {
+        return Err(anyhow!(
+            "no goals in {}'s config; add one with [[goals]]",
+            ledger_dir.display()
+        ));
+    }
+
+    // Like envelopes, goals accumulate from the very first ledger.
+    let ledgers = pledger::parse_monthly_ledgers(ledger_dir, None, settings)?;
+
+    let signed = |subunits| pledger::balance::signed(subunits, settings.parse.amount_format);
+    let dash = || "-".to_string();
+    println!("Goals as of {}\n", date);
+    println!(
+        "{:<16} {:>10} {:>10} {:>6} {:>10} {:>8} {:>9}",
+        "goal", "saved", "target", "%", "monthly", "by", "projected"
+    );
+    for goal in config.goals.iter() {
+        let progress = pledger::goal::progress(goal, &ledgers, &date, &settings.parse)?;
+        println!(
+            "{:<16} {:>10} {:>10} {:>6.1} {:>10} {:>8} {:>9}{}",
+            progress.name,
+            signed(progress.saved),
+            progress.target,
+            progress.percent(),
+            progress.monthly.map(signed).unwrap_or_else(dash),
+            progress.by.clone().unwrap_or_else(dash),
+            match progress.is_met() {
+                true => "met".into(),
+                false => progress.projected.clone().unwrap_or_else(dash),
+            },
+            if progress.is_behind() {
+                " (behind)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    Ok(())
+}
+
+fn reconcile(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let date = pledger::parse_date(matches.get_one::<String>("date").unwrap())?;
+
+    let uncleared = match *matches.get_one::<bool>("list").unwrap() {
+        true => pledger::reconcile::reconcile(ledger_dir, &date, settings, |_| Ok(None))?,
+        false => {
+            pledger::reconcile::reconcile(ledger_dir, &date, settings, pledger::reconcile::ask)?
+        }
+    };
+
+    let summary = Summary::new(&uncleared)?;
+    if summary.entries == 0 {
+        println!("{} is fully reconciled", date);
+        return Ok(());
+    }
+
+    println!("\nUncleared:");
+    pledger::list_entries(&uncleared);
+    println!(
+        "\n{} uncleared entries, totaling {} in credits and {} in debits",
+        summary.entries, summary.credits, summary.debits
+    );
+
+    Ok(())
+}
+
+fn import(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let file = matches.get_one::<PathBuf>("file").unwrap();
+    let account = matches.get_one::<String>("account").map(String::as_str);
+
+    let config = pledger::config::Config::load(ledger_dir)?;
+    let rules = pledger::categorize::Rules::new(&config.rules)?;
+
+    let statement =
+        fs::read_to_string(file).map_err(|e| anyhow!("couldn't read {}: {}", file.display(), e))?;
+
+    // QFX is just OFX with some extra Quicken-specific fields.
+    let stats = match matches.get_one::<String>("format").unwrap().as_str() {
+        "ofx" | "qfx" => {
+            pledger::import::import_ofx(ledger_dir, settings, &statement, account, &rules)?
+        }
+        "ledger-cli" => pledger::import::import_ledger_cli(
+            ledger_dir,
+            settings,
+            &statement,
+            account,
+            &config.accounts,
+            &rules,
+        )?,
+        format => return Err(anyhow!("unsupported import format: {}", format)),
+    };
+
+    println!(
+        "imported {} entries ({} already imported)",
+        stats.imported, stats.skipped
+    );
+
+    Ok(())
+}
+
+/// Returns the dates of the ledgers in the selection.
+fn selected_dates(
+    ledger_dir: &Path,
+    settings: &Settings,
+    selection: &Selection,
+) -> Result<Vec<String>> {
+    Ok(match selection {
+        Selection::Month(date) => vec![date.clone()],
+        Selection::Period(period) => pledger::ledger_dates(ledger_dir)?
+            .into_iter()
+            .filter(|d| pledger::in_period(d, period, settings.fiscal_year_start))
+            .collect(),
+        Selection::All => pledger::ledger_dates(ledger_dir)?,
+    })
+}
+
+fn fmt(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let check = *matches.get_one::<bool>("check").unwrap();
+    let sort = *matches.get_one::<bool>("sort").unwrap();
+
+    let mut unformatted = 0;
+    for date in selected_dates(ledger_dir, settings, &selection(matches)?)? {
+        if pledger::format::format_ledger_file(ledger_dir, &date, settings, sort, !check)? {
+            unformatted += 1;
+            match (check, pledger::plan::dry_run()) {
+                (true, _) => println!("{} needs formatting", date),
+                (false, true) => println!("would format {}", date),
+                (false, false) => println!("formatted {}", date),
+            }
+        }
+    }
+
+    if check && unformatted > 0 {
+        return Err(anyhow!("{} ledger(s) need formatting", unformatted));
+    }
+
+    Ok(())
+}
+
+/// Returns the warning for an amount over the config's limits.
+fn limit_warning(ledger_dir: &Path, exceeded: &pledger::limits::Exceeded) -> Diagnostic {
+    Diagnostic::warning(
+        Some(pledger::ledger_source(ledger_dir, exceeded.date())),
+        exceeded.to_string(),
+    )
+}
+
+fn lint(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let ledgers = monthly_ledgers(matches, ledger_dir, settings, &selection(matches)?)?;
+
+    let missing = pledger::attachment::missing_attachments(ledger_dir, &ledgers);
+    for missing in missing.iter() {
+        Diagnostic::warning(
+            Some(ledger_dir.join(format!("{}.ledger", missing.date))),
+            format!(
+                "entry {}'s attachment doesn't exist: {}",
+                missing.entry,
+                missing.path.display()
+            ),
+        )
+        .emit(json_diagnostics(matches));
+    }
+
+    let duplicates = pledger::id::duplicate_ids(&ledgers);
+    for (id, count) in duplicates.iter() {
+        Diagnostic::warning(
+            Some(ledger_dir.clone()),
+            format!("{} entries have the ID ^{}", count, id),
+        )
+        .emit(json_diagnostics(matches));
+    }
+
+    let limits = pledger::config::Config::load(ledger_dir)?.limits;
+    let mut exceeded = 0;
+    for ledger in ledgers.iter() {
+        for limit in pledger::limits::check(&limits, ledger)? {
+            limit_warning(ledger_dir, &limit).emit(json_diagnostics(matches));
+            exceeded += 1;
+        }
+    }
+
+    let problems = missing.len() + duplicates.len() + exceeded;
+    if problems > 0 {
+        return Err(anyhow!("{} problem(s) found", problems));
+    }
+
+    Ok(())
+}
+
+fn check_grammar(matches: &ArgMatches) -> Result<()> {
+    let file = matches.get_one::<PathBuf>("file").unwrap();
+    let text =
+        fs::read_to_string(file).map_err(|e| anyhow!("couldn't read {}: {}", file.display(), e))?;
+    let date = file
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+
+    let check = pledger::grammar::check(&date, &text)?;
+    if matches.get_flag("json") {
+        println!("{}", serde_json::to_string_pretty(&check)?);
+    } else {
+        for mismatch in check.mismatches.iter() {
+            Diagnostic {
+                file: Some(file.clone()),
+                line: mismatch.line,
+                column: mismatch.column,
+                message: mismatch.message.clone(),
+                severity: pledger::diagnostics::Severity::Error,
+            }
+            .emit(json_diagnostics(matches));
+        }
+        println!(
+            "{} entries and {} balances, with {} line(s) that don't match the grammar",
+            check.entries.len(),
+            check.balances.len(),
+            check.mismatches.len()
+        );
+    }
+
+    match check.mismatches.len() {
+        0 => Ok(()),
+        n => Err(anyhow!("{} line(s) don't match the grammar", n)),
+    }
+}
+
+fn anomalies(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let dates = selected_dates(ledger_dir, settings, &selection(matches)?)?;
+
+    // Anomalies are relative to all of the history, not just the selection.
+    let mut ledgers = pledger::parse_monthly_ledgers(ledger_dir, None, settings)?;
+    for ledger in ledgers.iter_mut() {
+        filter(matches, &settings.parse, ledger)?;
+    }
+
+    let anomalies: Vec<_> = pledger::trend::anomalies(&ledgers)?
+        .into_iter()
+        .filter(|a| dates.iter().any(|d| d == a.date()))
+        .collect();
+    for anomaly in anomalies.iter() {
+        println!("{}", anomaly);
+    }
+    if anomalies.is_empty() {
+        println!("no anomalies found");
+    }
+
+    Ok(())
+}
+
+fn open(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let address = matches.get_one::<String>("entry").unwrap();
+
+    let entry = pledger::id::find_entry(ledger_dir, address, settings)?.entry;
+    match pledger::attachment::attachment_path(ledger_dir, &entry) {
+        Some(path) => Ok(pledger::attachment::open(&path)?),
+        None => Err(anyhow!("{} has no attachment: {}", address, entry)),
+    }
+}
+
+fn edit_entry(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let found = pledger::id::find_entry(
+        ledger_dir,
+        matches.get_one::<String>("entry").unwrap(),
+        settings,
+    )?;
+
+    let tags = |name: &str| -> Vec<String> {
+        matches
+            .get_many::<String>(name)
+            .map(|tags| tags.cloned().collect())
+            .unwrap_or_default()
+    };
+    let edit = pledger::rewrite::Edit {
+        amount: matches
+            .get_one::<String>("amount")
+            .map(|a| {
+                pledger::Amount::parse_with(
+                    a,
+                    settings.parse.amount_format,
+                    settings.parse.loose_grouping,
+                )
+            })
+            .transpose()?,
+        add_tags: tags("add-tag"),
+        remove_tags: tags("remove-tag"),
+    };
+
+    let entry =
+        pledger::rewrite::edit_entry(ledger_dir, &found.date, settings, found.index, &edit)?;
+    println!("{}:{}: {}", found.date, found.index + 1, entry);
+
+    Ok(())
+}
+
+fn rm(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let found = pledger::id::find_entry(
+        ledger_dir,
+        matches.get_one::<String>("entry").unwrap(),
+        settings,
+    )?;
+    let void = *matches.get_one::<bool>("void").unwrap();
+
+    let (action, done) = match void {
+        true => ("void", "voided"),
+        false => ("remove", "removed"),
+    };
+    if !*matches.get_one::<bool>("yes").unwrap() {
+        let question = format!(
+            "{} {}:{}: {}?",
+            action,
+            found.date,
+            found.index + 1,
+            found.entry
+        );
+        if !pledger::rewrite::confirm(&question)? {
+            return Err(anyhow!("not confirmed; nothing changed"));
+        }
+    }
+
+    let entry =
+        pledger::rewrite::remove_entry(ledger_dir, &found.date, settings, found.index, void)?;
+    println!("{} {}: {}", done, found.date, entry);
+
+    Ok(())
+}
+
+fn show(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let found = pledger::id::find_entry(
+        ledger_dir,
+        matches.get_one::<String>("entry").unwrap(),
+        settings,
+    )?;
+
+    println!("{}", found.entry);
+    println!("{:<12}{}", "id:", found.id);
+    println!("{:<12}{}:{}", "position:", found.date, found.index + 1);
+    if let Some(path) = pledger::attachment::attachment_path(ledger_dir, &found.entry) {
+        println!("{:<12}{}", "attachment:", path.display());
+    }
+
+    Ok(())
+}
+
+fn history(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, _) = &ledger_dir(matches)?;
+
+    if matches.get_flag("enable") {
+        match pledger::journal::enable(ledger_dir)? {
+            true => println!("journaling changes to {}", ledger_dir.display()),
+            false => println!("{} already has a journal", ledger_dir.display()),
+        }
+        return Ok(());
+    }
+
+    let records = pledger::journal::load(ledger_dir)?.ok_or_else(|| {
+        anyhow!(
+            "no journal in {}; start one with --enable",
+            ledger_dir.display()
+        )
+    })?;
+
+    let since = match matches.get_one::<NaiveDate>("since") {
+        Some(since) => since
+            .and_hms_opt(0, 0, 0)
+            .and_then(|s| s.and_local_timezone(Local).earliest())
+            .ok_or_else(|| anyhow!("invalid date: {}", since))?
+            .with_timezone(&Utc),
+        None => Utc::now() - Duration::days(*matches.get_one::<u32>("days").unwrap() as i64),
+    };
+    let records: Vec<_> = records
+        .into_iter()
+        .filter(|r| r.time().is_some_and(|t| t >= since))
+        .collect();
+
+    if matches.get_flag("json") {
+        println!("{}", serde_json::to_string_pretty(&records)?);
+        return Ok(());
+    }
+
+    for record in records.iter() {
+        let time = record.time().unwrap().with_timezone(&Local);
+        let change = match record.change {
+            pledger::journal::Change::Added => "added",
+            pledger::journal::Change::Modified => "modified",
+            pledger::journal::Change::Removed => "removed",
+        };
+        println!(
+            "{}  {:<8}  {}  {:<8}  {}",
+            time.format("%Y-%m-%d %H:%M"),
+            change,
+            record.ledger,
+            record.id,
+            record.entry
+        );
+    }
+
+    Ok(())
+}
+
+fn last_entries(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let recent = pledger::recent::last_entries(
+        ledger_dir,
+        *matches.get_one::<usize>("count").unwrap(),
+        settings,
+    )?;
+
+    if matches.get_flag("json") {
+        println!("{}", serde_json::to_string_pretty(&recent)?);
+        return Ok(());
+    }
+
+    // Entries from before the journal (or without one) don't have a time, so
+    // their column is left blank rather than guessed at.
+    for r in recent.iter() {
+        let time = r
+            .time
+            .as_deref()
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let position = format!("{}:{}", r.date, r.number);
+        println!("{:<16}  {:<10}  {:<8}  {}", time, position, r.id, r.entry);
+    }
+
+    Ok(())
+}
+
+fn categorize(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+
+    let config = pledger::config::Config::load(ledger_dir)?;
+    if config.rules.is_empty() {
+        return Err(anyhow!(
+            "no categorization rules in {}",
+            ledger_dir.join(pledger::config::CONFIG_FILE).display()
+        ));
+    }
+    let rules = pledger::categorize::Rules::new(&config.rules)?;
+
+    for date in selected_dates(ledger_dir, settings, &selection(matches)?)? {
+        let changes = rules.categorize_ledger_file(ledger_dir, &date, settings)?;
+        if changes.is_empty() {
+            continue;
+        }
+
+        let done = match pledger::plan::dry_run() {
+            true => "would categorize",
+            false => "categorized",
+        };
+        println!("{} {} entries in {}", done, changes.len(), date);
+    }
+
+    Ok(())
+}
+
+fn retag(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+
+    let aliases = match matches.get_many::<String>("rename") {
+        Some(tags) => {
+            let tags: Vec<_> = tags.cloned().collect();
+            if let Some(tag) = tags.iter().find(|t| !pledger::is_tag(t)) {
+                return Err(anyhow!("invalid tag: {}", tag));
+            }
+            BTreeMap::from([(tags[0].clone(), tags[1].clone())])
+        }
+        None => {
+            let aliases = pledger::config::Config::load(ledger_dir)?.aliases;
+            if aliases.is_empty() {
+                return Err(anyhow!(
+                    "no tag aliases in {}",
+                    ledger_dir.join(pledger::config::CONFIG_FILE).display()
+                ));
+            }
+            aliases
+        }
+    };
+    let rename = |tag: &str| aliases.get(tag).cloned();
+
+    for date in selected_dates(ledger_dir, settings, &selection(matches)?)? {
+        let changes = pledger::retag::retag_ledger_file(ledger_dir, &date, settings, rename)?;
+        if changes.is_empty() {
+            continue;
+        }
+
+        let done = match pledger::plan::dry_run() {
+            true => "would retag",
+            false => "retagged",
+        };
+        println!("{} {} lines in {}", done, changes.len(), date);
+    }
+
+    Ok(())
+}
+
+fn serve(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let listen = matches.get_one::<String>("listen").unwrap();
+    let token = matches.get_one::<String>("token").map(String::as_str);
+
+    if token.is_none() {
+        log::warn!("no --token given; appending entries is disabled");
+    }
+
+    Ok(pledger::serve::serve(ledger_dir, settings, listen, token)?)
+}
+
+fn site(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let outdir = matches.get_one::<PathBuf>("outdir").unwrap();
+
+    let ledgers = monthly_ledgers(matches, ledger_dir, settings, &Selection::All)?;
+    let pages = pledger::site::generate(outdir, &ledgers)?;
+    println!("wrote {} pages to {}", pages, outdir.display());
+
+    Ok(())
+}
+
+fn metrics(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+
+    let date = match selection(matches)? {
+        Selection::Month(date) => date,
+        _ => return Err(anyhow!("metrics are for a single month")),
+    };
+    let metrics = pledger::metrics::for_month(ledger_dir, &date, settings)?;
+
+    match matches.get_one::<PathBuf>("output") {
+        Some(path) => pledger::metrics::write_textfile(path, &metrics)?,
+        None => print!("{}", metrics),
+    }
+
+    Ok(())
+}
+
+fn notify(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+
+    let date = match selection(matches)? {
+        Selection::Month(date) => date,
+        _ => return Err(anyhow!("notifications are for a single month")),
+    };
+    let notifications =
+        pledger::notify::check(ledger_dir, &date, settings, std::time::SystemTime::now())?;
+    if notifications.is_empty() {
+        return Ok(());
+    }
+
+    // Without a command, the payload goes to stdout, which cron mails.
+    let payload = pledger::notify::payload(&date, &notifications);
+    match pledger::config::Config::load(ledger_dir)?.notify.command {
+        Some(command) => Ok(pledger::notify::send(&command, &payload)?),
+        None => {
+            println!("{}", payload);
+            Ok(())
+        }
+    }
+}
+
+fn archive(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let before = matches.get_one::<u16>("before").unwrap();
+
+    let dates = pledger::archive::archive(ledger_dir, &before.to_string(), settings)?;
+    match dates.as_slice() {
+        [] => println!("nothing to archive from before {}", before),
+        [first, .., last] => println!("archived {} ledgers, {} to {}", dates.len(), first, last),
+        [only] => println!("archived {}", only),
+    }
+
+    Ok(())
+}
+
+fn verify(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, _) = &ledger_dir(matches)?;
+
+    if matches.get_flag("update") {
+        let recorded = pledger::manifest::update(ledger_dir)?;
+        println!("recorded {} ledger file(s) in the manifest", recorded);
+        return Ok(());
+    }
+
+    // With --signatures, the manifest is checked too if there is one, but it's
+    // not needed.
+    let signatures = matches.get_flag("signatures");
+    let mismatches = pledger::manifest::verify(ledger_dir)?;
+    if mismatches.is_none() && !signatures {
+        return Err(anyhow!(
+            "no manifest in {}; create one with --update",
+            ledger_dir.display()
+        ));
+    }
+
+    // Untracked files are only warnings, since they can't have been corrupted
+    // since they were recorded.
+    let mut failed = 0;
+    for mismatch in mismatches.iter().flatten() {
+        if !matches!(mismatch, pledger::manifest::Mismatch::Untracked(_)) {
+            failed += 1;
+        }
+        Diagnostic::warning(None, mismatch.to_string()).emit(json_diagnostics(matches));
+    }
+
+    if signatures {
+        let signing = signing_config(ledger_dir)?;
+        for name in pledger::manifest::ledger_files(ledger_dir)? {
+            let problem = match pledger::signing::verify(&signing, ledger_dir, &name)? {
+                pledger::signing::Verified::Signed(signer) => {
+                    println!("{}: signed by {}", name, signer);
+                    continue;
+                }
+                pledger::signing::Verified::Unsigned => format!("{} isn't signed", name),
+                pledger::signing::Verified::Invalid => format!(
+                    "{} doesn't match its signature, or wasn't signed by a known signer",
+                    name
+                ),
+            };
+            failed += 1;
+            Diagnostic::warning(None, problem).emit(json_diagnostics(matches));
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        failed => Err(anyhow!("{} ledger file(s) failed verification", failed)),
+    }
+}
+
+fn signing_config(ledger_dir: &Path) -> Result<pledger::config::Signing> {
+    pledger::config::Config::load(ledger_dir)?
+        .signing
+        .ok_or_else(|| anyhow!("no [signing] in {}'s config", ledger_dir.display()))
+}
+
+fn sign(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, _) = &ledger_dir(matches)?;
+    let key = matches.get_one::<PathBuf>("key").unwrap();
+    let signing = signing_config(ledger_dir)?;
+
+    // Files whose signatures are still valid are left alone, so that signing
+    // only ever claims the files that have changed since they were last signed.
+    let mut signed = 0;
+    for name in pledger::manifest::ledger_files(ledger_dir)? {
+        if let pledger::signing::Verified::Signed(_) =
+            pledger::signing::verify(&signing, ledger_dir, &name)?
+        {
+            continue;
+        }
+        pledger::signing::sign(&signing, ledger_dir, &name, key)?;
+        println!("signed {}", name);
+        signed += 1;
+    }
+
+    if signed == 0 {
+        println!("every ledger file is already signed");
+    }
+
+    Ok(())
+}
+
+fn merge(matches: &ArgMatches) -> Result<()> {
+    let ours = matches.get_one::<PathBuf>("ours").unwrap();
+    let theirs = matches.get_one::<PathBuf>("theirs").unwrap();
+
+    // The versions are parsed with the settings of the directory that they're in.
+    let directory = match ours.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let settings = settings(matches, &[Config::load(directory)?])?;
+
+    let merged = pledger::merge::merge_files(ours, theirs, &settings.parse)?;
+    match matches.get_one::<PathBuf>("output") {
+        Some(path) => fs::write(path, &merged.text)
+            .map_err(|e| anyhow!("couldn't write {}: {}", path.display(), e))?,
+        None => print!("{}", merged.text),
+    }
+
+    let output = matches.get_one::<PathBuf>("output").cloned();
+    for conflict in merged.conflicts.iter() {
+        let mut diagnostic = Diagnostic::warning(
+            output.clone(),
+            format!(
+                "conflicting edits: {} and {}",
+                conflict.ours, conflict.theirs
+            ),
+        );
+        diagnostic.line = Some(conflict.line);
+        diagnostic.emit(json_diagnostics(matches));
+    }
+
+    if !merged.conflicts.is_empty() {
+        return Err(anyhow!(
+            "{} conflict(s) to resolve in the merged ledger",
+            merged.conflicts.len()
+        ));
+    }
+
+    Ok(())
+}
+
+fn ical(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, _) = &ledger_dir(matches)?;
+
+    let config = pledger::config::Config::load(ledger_dir)?;
+    if config.recurring.is_empty() {
+        return Err(anyhow!(
+            "no recurring entries in {}",
+            ledger_dir.join(pledger::config::CONFIG_FILE).display()
+        ));
+    }
+
+    print!(
+        "{}",
+        pledger::recurring::ical(&config.recurring, NOW.date_naive(), Utc::now())?
+    );
+
+    Ok(())
+}
+
+fn lsp(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    Ok(pledger::lsp::serve(
+        ledger_dir,
+        settings,
+        io::stdin().lock(),
+        io::stdout().lock(),
+    )?)
+}
+
+fn sync_db(matches: &ArgMatches) -> Result<()> {
+    let (ledger_dir, settings) = &ledger_dir(matches)?;
+    let database = matches.get_one::<PathBuf>("database").unwrap();
+
+    let stats = pledger::db::sync(ledger_dir, database, settings)?;
+    println!(
+        "synced {}: {} updated, {} unchanged, {} removed",
+        database.display(),
+        stats.updated,
+        stats.unchanged,
+        stats.removed
+    );
+
+    Ok(())
+}
+
+/// The output format, per --format or --json.
+fn output_format(matches: &ArgMatches) -> OutputFormat {
+    match matches.get_flag("json") {
+        true => OutputFormat::Json,
+        // The value parser only allows valid formats.
+        false => matches
+            .get_one::<String>("format")
+            .map_or(OutputFormat::Text, |f| f.parse().unwrap()),
+    }
+}
+
+/// The accounts that entries are exported to, from each ledger directory's config.
+fn export_accounts(ledger_dirs: &[PathBuf]) -> Result<BTreeMap<String, String>> {
+    let mut accounts = BTreeMap::new();
+    for ledger_dir in ledger_dirs.iter() {
+        accounts.extend(pledger::config::Config::load(ledger_dir)?.accounts);
+    }
+    Ok(accounts)
+}
+
+/// Whether diagnostics should be emitted as JSON, per --diagnostics.
+fn json_diagnostics(matches: &ArgMatches) -> bool {
+    matches
+        .get_one::<String>("diagnostics")
+        .map(|d| d == "json")
+        .unwrap_or(false)
+}
+
+/// Prints the selected month's debits per day, with a sparkline and the average daily spend.
+fn by_day(ledger: &pledger::Ledger, date: &str) -> Result<()> {
+    let daily = pledger::aggregate::daily_totals(ledger, &Debit)?;
+    let total = pledger::aggregate::total(ledger, &Debit)?;
+
+    // The current month is only partially over, so average over the days so far.
+    let days = match date == NOW_FMT.as_str() {
+        true => NOW.day(),
+        false => daily.points.len() as u32,
+    };
+
+    println!("Ledger for {}\n", date);
+    println!("{}\n", pledger::plot::sparkline(&daily));
+    for (day, amount) in daily
+        .points
+        .iter()
+        .filter(|(_, a)| *a != pledger::Amount::ZERO)
+    {
+        println!("{:<9} {:>10}", day, amount);
+    }
+
+    let dated = daily
+        .points
+        .iter()
+        .try_fold(pledger::Amount::ZERO, |acc, (_, a)| acc.checked_add(*a))
+        .ok_or_else(|| anyhow!("daily totals out of range"))?;
+    if dated != total {
+        println!("{:<9} {:>10}", "(undated)", total.abs_diff(dated));
+    }
+
+    println!(
+        "\nAverage daily debits: {} over {} days",
+        total.with_subunits(total.subunits() / days as u64),
+        days
+    );
+
+    Ok(())
+}
+
+/// Prints each tag's totals by month, for both kinds of entries.
+fn pivot(ledgers: &[pledger::Ledger], dates: &[String]) -> Result<()> {
+    let dates: Vec<_> = dates.iter().map(String::as_str).collect();
+
+    for (idx, (kind, title)) in [(Debit, "Debit"), (Credit, "Credit")].iter().enumerate() {
+        if idx > 0 {
+            println!();
+        }
+        println!("{} tags by month:", title);
+        print!(
+            "{}",
+            pledger::report::matrix(&dates, &pledger::aggregate::tags_by_month(ledgers, kind)?)
+        );
+    }
+
+    Ok(())
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    if matches.get_flag("dry-run") {
+        pledger::plan::set_dry_run();
+    }
+    if matches.get_flag("no-cache") {
+        pledger::cache::disable();
+    }
+
+    match matches.subcommand() {
+        Some(("init", matches)) => return init(matches),
+        Some(("restore", matches)) => return restore(matches),
+        Some(("plot", matches)) => return plot(matches),
+        Some(("dupes", matches)) => return dupes(matches),
+        Some(("sync-db", matches)) => return sync_db(matches),
+        Some(("lsp", matches)) => return lsp(matches),
+        Some(("cat", matches)) => return cat(matches),
+        Some(("fmt", matches)) => return fmt(matches),
+        Some(("lint", matches)) => return lint(matches),
+        Some(("check-grammar", matches)) => return check_grammar(matches),
+        Some(("anomalies", matches)) => return anomalies(matches),
+        Some(("open", matches)) => return open(matches),
+        Some(("show", matches)) => return show(matches),
+        Some(("history", matches)) => return history(matches),
+        Some(("last-entries", matches)) => return last_entries(matches),
+        Some(("rm", matches)) => return rm(matches),
+        Some(("edit-entry", matches)) => return edit_entry(matches),
+        Some(("add", matches)) => return add(matches),
+        Some(("forecast", matches)) => return forecast(matches),
+        Some(("report", matches)) => return report(matches),
+        Some(("balance", matches)) => return balance(matches),
+        Some(("envelopes", matches)) => return envelopes(matches),
+        Some(("networth", matches)) => return networth(matches),
+        Some(("debt", matches)) => return debt(matches),
+        Some(("goals", matches)) => return goals(matches),
+        Some(("reconcile", matches)) => return reconcile(matches),
+        Some(("import", matches)) => return import(matches),
+        Some(("categorize", matches)) => return categorize(matches),
+        Some(("retag", matches)) => return retag(matches),
+        Some(("serve", matches)) => return serve(matches),
+        Some(("site", matches)) => return site(matches),
+        Some(("metrics", matches)) => return metrics(matches),
+        Some(("notify", matches)) => return notify(matches),
+        Some(("archive", matches)) => return archive(matches),
+        Some(("verify", matches)) => return verify(matches),
+        Some(("sign", matches)) => return sign(matches),
+        Some(("merge", matches)) => return merge(matches),
+        Some(("ical", matches)) => return ical(matches),
+        _ => {}
+    }
+
+    let stdin = reads_stdin(matches);
+    let file = matches.get_one::<PathBuf>("ledger-file");
+    let single = stdin || file.is_some();
+    let (ledger_dirs, settings) = match single {
+        true => (vec![], settings(matches, &[Config::default()])?),
+        false => ledger_dirs(matches)?,
+    };
+
+    let template = matches.get_one::<PathBuf>("template").map(PathBuf::as_path);
+    let editor = matches.get_one::<String>("editor").map(String::as_str);
+
+    let selection = selection(matches)?;
+    if let Selection::Month(date) = &selection {
+        if *matches.get_one::<bool>("edit").unwrap() {
+            if single {
+                return Err(anyhow!(
+                    "--edit needs a ledger directory, not --file or stdin"
+                ));
+            }
+            // The directories have already been resolved (and any remote ones pulled) above.
+            let [ledger_dir] = ledger_dirs.as_slice() else {
+                return Err(anyhow!(
+                    "this command needs a single ledger directory or profile"
+                ));
+            };
+            return Ok(pledger::edit_ledger(
+                date, ledger_dir, template, editor, &settings,
+            )?);
+        }
+    }
+
+    // A combined summary only needs running totals, so it's folded up a month at
+    // a time rather than from one ledger with every entry in it.
+    let flag = |name: &str| *matches.get_one::<bool>(name).unwrap();
+    let output = output_format(matches);
+    if flag("summary") && !output.is_structured() {
+        return Err(anyhow!("--summary needs --json, or --format toml or yaml"));
+    }
+    let budget_dir = match (&selection, ledger_dirs.as_slice()) {
+        (Selection::Month(date), [ledger_dir]) => Some((ledger_dir, date)),
+        _ => None,
+    };
+    if flag("fail-if-over-budget") && budget_dir.is_none() {
+        return Err(anyhow!(
+            "--fail-if-over-budget needs a single month's ledger, in a ledger directory"
+        ));
+    }
+    let summary_only = ["by-day", "pivot", "rolling", "untagged"]
+        .iter()
+        .all(|f| !flag(f))
+        && !matches.contains_id("export")
+        && !matches.contains_id("chart")
+        && (matches!(output, OutputFormat::Text | OutputFormat::Markdown) || flag("summary"));
+    if !single && summary_only && !matches!(selection, Selection::Month(_)) {
+        return stream_summary(matches, &ledger_dirs, &settings, &selection);
+    }
+
+    let mut ledger = match (file, stdin) {
+        (Some(file), _) => pledger::load_ledger_file(file, &settings.parse)?,
+        // The ledger on stdin is labeled with the selected month, e.g. for --by-day.
+        (None, true) => match &selection {
+            Selection::Month(date) => {
+                pledger::parse_ledger_reader(date, io::stdin().lock(), &settings.parse)?
+            }
+            _ => return Err(anyhow!("a ledger on stdin is a single month's")),
+        },
+        (None, false) => combined_ledger(&ledger_dirs, &settings, &selection)?,
+    };
+    for err in ledger.errors().iter() {
+        Diagnostic::recovered(err).emit(json_diagnostics(matches));
+    }
+
+    filter(matches, &settings.parse, &mut ledger)?;
+
+    let summary = Summary::new(&ledger)?;
+
+    if let Some(path) = matches.get_one::<PathBuf>("chart") {
+        let trend = match &selection {
+            Selection::Month(_) => vec![],
+            selection => {
+                let ledgers =
+                    combined_monthly_ledgers(matches, &ledger_dirs, &settings, selection)?;
+                vec![
+                    pledger::aggregate::monthly_totals(&ledgers, &Debit)?,
+                    pledger::aggregate::monthly_totals(&ledgers, &Credit)?,
+                ]
+            }
+        };
+
+        pledger::chart::render(path, &summary, &trend)?;
+    }
+
+    let top = matches.get_one::<usize>("top").copied();
+    if *matches.get_one::<bool>("by-day").unwrap() {
+        match &selection {
+            Selection::Month(_) => by_day(&ledger, ledger.date())?,
+            _ => return Err(anyhow!("--by-day needs a single month's ledger")),
+        }
+    } else if *matches.get_one::<bool>("pivot").unwrap() {
+        if let Selection::Month(_) = selection {
+            return Err(anyhow!("--pivot needs --year or --all"));
+        }
+        let ledgers = combined_monthly_ledgers(matches, &ledger_dirs, &settings, &selection)?;
+        let dates: Vec<_> = ledgers.iter().map(|l| l.date().to_string()).collect();
+        pivot(&ledgers, &dates)?;
+    } else if *matches.get_one::<bool>("rolling").unwrap() {
+        if let Selection::Month(_) = selection {
+            return Err(anyhow!("--rolling needs --year or --all"));
+        }
+        let ledgers = combined_monthly_ledgers(matches, &ledger_dirs, &settings, &selection)?;
+        print!("{}", pledger::trend::Rolling::new(&ledgers)?.to_text());
+    } else if let Some(format) = matches
+        .get_one::<String>("export")
+        .map(String::as_str)
+        .or((output == OutputFormat::Csv && !flag("untagged")).then_some("csv"))
+    {
+        let combined;
+        let ledgers = match single {
+            true => std::slice::from_ref(&ledger),
+            false => {
+                combined = combined_monthly_ledgers(matches, &ledger_dirs, &settings, &selection)?;
+                combined.as_slice()
+            }
+        };
+        let accounts = export_accounts(&ledger_dirs)?;
+        match format {
+            "beancount" => print!(
+                "{}",
+                pledger::export::beancount(ledgers, &accounts, &currency(matches)?)?
+            ),
+            "csv" => print!("{}", pledger::export::double_entry_csv(ledgers, &accounts)?),
+            format => return Err(anyhow!("unsupported export format: {}", format)),
+        }
+    } else if *matches.get_one::<bool>("untagged").unwrap() {
+        ledger.untagged();
+        match output {
+            OutputFormat::Text => pledger::list_entries(&ledger),
+            OutputFormat::Csv => print!(
+                "{}",
+                pledger::export::double_entry_csv(
+                    std::slice::from_ref(&ledger),
+                    &export_accounts(&ledger_dirs)?
+                )?
+            ),
+            output => print!("{}", output.render(&ledger)?),
+        }
+    } else if *matches.get_one::<bool>("summary").unwrap() {
+        let report = pledger::aggregate::Report::new(&summary, top);
+        print!("{}", output.render(&report)?);
+    } else if output.is_structured() {
+        print!("{}", output.render(&ledger)?);
+    } else {
+        let mut dupes = vec![];
+        let mut exceeded = 0;
+        for ledger_dir in ledger_dirs.iter() {
+            if let Selection::Month(date) = &selection {
+                if !pledger::ledger_dates(ledger_dir)?.contains(date) {
+                    continue;
+                }
+            }
+            let limits = pledger::config::Config::load(ledger_dir)?.limits;
+            let ledgers = monthly_ledgers(matches, ledger_dir, &settings, &selection)?;
+            for ledger in ledgers.iter() {
+                for limit in pledger::limits::check(&limits, ledger)? {
+                    limit_warning(ledger_dir, &limit).emit(json_diagnostics(matches));
+                    exceeded += 1;
+                }
+            }
+            for dupe in pledger::dupes::find_duplicates(&ledgers) {
+                dupes.push(dupe.diagnostic(Some(ledger_dir)));
+            }
+        }
+
+        let report = pledger::aggregate::Report::new(&summary, top);
+        match output {
+            OutputFormat::Markdown => print!("{}", report.to_markdown()),
+            OutputFormat::Org => print!("{}", report.to_org(&ledger)),
+            _ => pledger::summarize(&summary, top, exceeded),
+        }
+        if single {
+            for dupe in pledger::dupes::find_duplicates(std::slice::from_ref(&ledger)).iter() {
+                dupe.diagnostic(None).emit(json_diagnostics(matches));
+            }
+        }
+        for dupe in dupes {
+            dupe.emit(json_diagnostics(matches));
+        }
+
+        // Budgets are monthly, so they're only meaningful for single-month selections.
+        // Each profile has its own budget, so there isn't one for a combined view.
+        if let (Some((ledger_dir, date)), OutputFormat::Text) = (budget_dir, output) {
+            let config = pledger::config::Config::load(ledger_dir)?;
+            let budgets = pledger::budget::budgets(ledger_dir, date, &config, &settings)?;
+            pledger::summarize_budget(&summary, &budgets);
+        }
+    }
+
+    if let (Some((ledger_dir, date)), true) = (budget_dir, flag("fail-if-over-budget")) {
+        let config = pledger::config::Config::load(ledger_dir)?;
+        let budgets = pledger::budget::budgets(ledger_dir, date, &config, &settings)?;
+        let over = pledger::over_budget(&summary, &budgets);
+        if !over.is_empty() {
+            return Err(PolicyFailure(format!("over budget for {}", over.join(", "))).into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Exit codes, for scripts: 2 is clap's, for invalid arguments.
+const EXIT_ERROR: i32 = 1;
+const EXIT_PARSE_ERROR: i32 = 3;
+const EXIT_POLICY_FAILURE: i32 = 4;
+
+/// A failure of one of the checks that scripts can ask for, like `--fail-if-over-budget`,
+/// rather than an error.
+#[derive(Debug)]
+struct PolicyFailure(String);
+
+impl std::fmt::Display for PolicyFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for PolicyFailure {}
+
+/// Returns the exit code for an error: distinct ones for policy failures and for ledgers
+/// that couldn't be parsed, so that scripts can tell them apart from other errors.
+fn exit_code(err: &anyhow::Error) -> i32 {
+    if err.is::<PolicyFailure>() {
+        EXIT_POLICY_FAILURE
+    } else if let Some(pledger::Error::Parse { .. }) = err.downcast_ref::<pledger::Error>() {
+        EXIT_PARSE_ERROR
+    } else {
+        EXIT_ERROR
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let matches = app().get_matches();
+    let result = run(&matches).and_then(|()| {
+        if pledger::plan::dry_run() {
+            print!("{}", pledger::plan::diff());
+        } else {
+            pledger::remote::push_all()?;
+        }
+
+        let warnings = pledger::diagnostics::warnings();
+        match matches.get_flag("fail-on-warnings") && warnings > 0 {
+            true => Err(
+                PolicyFailure(format!("{} warning(s), with --fail-on-warnings", warnings)).into(),
+            ),
+            false => Ok(()),
+        }
+    });
+    process::exit(match result {
+        Ok(()) => 0,
+        Err(e) => {
+            if json_diagnostics(&matches) {
+                Diagnostic::error(e.as_ref()).emit(true);
+            } else {
+                eprintln!("Fatal: {}", e);
+            }
+            exit_code(&e)
         }
     });
 }
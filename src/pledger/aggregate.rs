@@ -0,0 +1,931 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use chrono::{Months, NaiveDate};
+use serde::Serialize;
+
+use crate::pledger::{Amount, Entry, EntryKind, Error, Ledger, Result};
+
+/// A sequence of labeled amounts, e.g. debits by tag or credits by month.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Series {
+    pub name: String,
+    pub points: Vec<(String, Amount)>,
+}
+
+impl Series {
+    /// Returns the largest amount in the series, or zero if it's empty.
+    pub fn max(&self) -> Amount {
+        self.points
+            .iter()
+            .map(|(_, v)| *v)
+            .max()
+            .unwrap_or(Amount::ZERO)
+    }
+
+    /// Returns the series' first `n` points, with the remainder (if any) combined into
+    /// a trailing `(other)` point.
+    ///
+    /// Tags can overlap, so the remainder can exceed any real total; it saturates rather
+    /// than overflowing.
+    pub fn top(&self, n: usize) -> Series {
+        if self.points.len() <= n {
+            return self.clone();
+        }
+
+        let (head, tail) = self.points.split_at(n);
+        let mut points = head.to_vec();
+        points.push((
+            "(other)".into(),
+            tail.iter()
+                .fold(Amount::ZERO, |acc, (_, v)| acc.saturating_add(*v)),
+        ));
+
+        Series {
+            name: self.name.clone(),
+            points,
+        }
+    }
+}
+
+/// The totals and per-tag breakdowns that make up a ledger's summary report.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    pub date: String,
+    pub entries: usize,
+    pub credits: Amount,
+    pub debits: Amount,
+    pub credit_tags: Series,
+    pub debit_tags: Series,
+    pub accounts: Vec<AccountTotals>,
+    /// The number of lines that were skipped, when parsing leniently.
+    pub errors: usize,
+}
+
+impl Summary {
+    pub fn new(ledger: &Ledger) -> Result<Self> {
+        let mut totals = Totals::default();
+        for entry in ledger.entries.iter() {
+            totals.add(entry)?;
+        }
+
+        let mut summary = totals.summary(&ledger.date);
+        summary.errors = ledger.errors.len();
+        Ok(summary)
+    }
+
+    /// Returns the net amount, and whether it's a net credit or debit.
+    pub fn net(&self) -> (Amount, EntryKind) {
+        let net = self.credits.abs_diff(self.debits);
+        if self.credits >= self.debits {
+            (net, EntryKind::Credit)
+        } else {
+            (net, EntryKind::Debit)
+        }
+    }
+}
+
+/// The running totals behind a summary, which entries are added to one at a time.
+#[derive(Debug, Default)]
+pub struct Totals {
+    entries: usize,
+    credits: Amount,
+    debits: Amount,
+    credit_tags: HashMap<String, Amount>,
+    debit_tags: HashMap<String, Amount>,
+    accounts: BTreeMap<Option<String>, (Amount, Amount)>,
+}
+
+impl Totals {
+    pub fn add(&mut self, entry: &Entry) -> Result<()> {
+        self.entries += 1;
+        match entry.kind {
+            EntryKind::Credit => {
+                self.credits = checked_sum(self.credits, entry.amount)?;
+                add_tags(&mut self.credit_tags, entry)?;
+            }
+            EntryKind::Debit => {
+                self.debits = checked_sum(self.debits, entry.amount)?;
+                add_tags(&mut self.debit_tags, entry)?;
+            }
+            EntryKind::Transfer | EntryKind::Allocation => {}
+        }
+
+        add_accounts(&mut self.accounts, entry)
+    }
+
+    pub fn summary(self, date: &str) -> Summary {
+        Summary {
+            date: date.into(),
+            entries: self.entries,
+            credits: self.credits,
+            debits: self.debits,
+            credit_tags: tag_series(self.credit_tags, &EntryKind::Credit),
+            debit_tags: tag_series(self.debit_tags, &EntryKind::Debit),
+            accounts: account_list(self.accounts),
+            errors: 0,
+        }
+    }
+}
+
+/// Summarizes a sequence of ledgers (e.g. every month's) as if they were one ledger
+/// labeled `date`, but only holding one of them in memory at a time.
+pub fn summarize_stream(
+    date: &str,
+    ledgers: impl IntoIterator<Item = Result<Ledger>>,
+) -> Result<Summary> {
+    let mut totals = Totals::default();
+    let mut errors = 0;
+    for ledger in ledgers {
+        let ledger = ledger?;
+        for entry in ledger.entries.iter() {
+            totals.add(entry)?;
+        }
+        errors += ledger.errors.len();
+    }
+
+    let mut summary = totals.summary(date);
+    summary.errors = errors;
+    Ok(summary)
+}
+
+/// A tag's total, and its share of the total for its entry kind.
+///
+/// Entries can have more than one tag, so the shares in a breakdown can sum
+/// to more than 100%.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TagShare {
+    pub tag: String,
+    pub amount: Amount,
+    pub percent: f64,
+}
+
+/// The headline ratios between credits (income) and debits (expenses).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Ratios {
+    /// The share of credits that wasn't spent, as a percentage. Negative when more was
+    /// spent than earned.
+    pub savings_rate: f64,
+    /// Debits as a percentage of credits.
+    pub expense_ratio: f64,
+    /// The largest debit tag, with its share of credits (rather than of debits).
+    pub top_expense: Option<TagShare>,
+}
+
+impl Ratios {
+    /// Computes the ratios from the total credits and debits, and the largest debit tag
+    /// and its total, if there are any debits.
+    pub fn new(credits: Amount, debits: Amount, top: Option<(&str, Amount)>) -> Self {
+        let net = credits.abs_diff(debits);
+        let savings_rate = match credits >= debits {
+            true => percent(net, credits),
+            false => -percent(net, credits),
+        };
+
+        Self {
+            savings_rate,
+            expense_ratio: percent(debits, credits),
+            top_expense: top.map(|(tag, amount)| TagShare {
+                tag: tag.into(),
+                amount,
+                percent: percent(amount, credits),
+            }),
+        }
+    }
+}
+
+/// The structured form of a summary report, as output by `--json --summary`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Report {
+    pub date: String,
+    pub entries: usize,
+    pub credits: Amount,
+    pub debits: Amount,
+    pub net: Amount,
+    pub net_kind: EntryKind,
+    pub credit_tags: Vec<TagShare>,
+    pub debit_tags: Vec<TagShare>,
+    pub accounts: Vec<AccountTotals>,
+    #[serde(flatten)]
+    pub ratios: Ratios,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub errors: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+impl Report {
+    /// Builds a report from the summary, limiting each tag breakdown to its `top`
+    /// largest tags (and an `(other)` row) if given.
+    pub fn new(summary: &Summary, top: Option<usize>) -> Self {
+        let shares = |series: &Series, total: Amount| {
+            let series = match top {
+                Some(n) => series.top(n),
+                None => series.clone(),
+            };
+
+            series
+                .points
+                .into_iter()
+                .map(|(tag, amount)| TagShare {
+                    tag,
+                    amount,
+                    percent: percent(amount, total),
+                })
+                .collect()
+        };
+
+        let (net, net_kind) = summary.net();
+        Self {
+            date: summary.date.clone(),
+            entries: summary.entries,
+            credits: summary.credits,
+            debits: summary.debits,
+            net,
+            net_kind,
+            credit_tags: shares(&summary.credit_tags, summary.credits),
+            debit_tags: shares(&summary.debit_tags, summary.debits),
+            accounts: summary.accounts.clone(),
+            ratios: Ratios::new(
+                summary.credits,
+                summary.debits,
+                summary
+                    .debit_tags
+                    .points
+                    .first()
+                    .map(|(tag, amount)| (tag.as_str(), *amount)),
+            ),
+            errors: summary.errors,
+        }
+    }
+
+    /// Renders the report as GitHub-flavored Markdown, with a table for each tag
+    /// breakdown (and for the accounts, if the ledgers use them).
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "## Ledger for {}\n", self.date).unwrap();
+        writeln!(
+            out,
+            "{} entries, totaling {} in credits and {} in debits for a net of {} in {}.\n",
+            self.entries,
+            self.credits,
+            self.debits,
+            self.net,
+            self.net_kind.name()
+        )
+        .unwrap();
+        if self.errors > 0 {
+            writeln!(
+                out,
+                "{} line(s) couldn't be parsed, and were skipped.\n",
+                self.errors
+            )
+            .unwrap();
+        }
+        // The ratios are all relative to credits, so they're meaningless without any.
+        if self.credits > Amount::ZERO {
+            writeln!(
+                out,
+                "- {:.1}% of credits saved, and {:.1}% spent",
+                self.ratios.savings_rate, self.ratios.expense_ratio
+            )
+            .unwrap();
+            if let Some(top) = &self.ratios.top_expense {
+                writeln!(
+                    out,
+                    "- the largest debit tag, {}, was {:.1}% of credits",
+                    markdown_cell(&top.tag),
+                    top.percent
+                )
+                .unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        for (title, shares) in [
+            ("Credit tags", &self.credit_tags),
+            ("Debit tags", &self.debit_tags),
+        ] {
+            writeln!(out, "### {}\n", title).unwrap();
+            writeln!(out, "| Tag | Amount | Share |\n| --- | ---: | ---: |").unwrap();
+            for share in shares.iter() {
+                writeln!(
+                    out,
+                    "| {} | {} | {:.1}% |",
+                    markdown_cell(&share.tag),
+                    share.amount,
+                    share.percent
+                )
+                .unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        // Ledgers that don't use accounts don't need an accounts table.
+        if self.accounts.iter().any(|a| a.account != NO_ACCOUNT) {
+            writeln!(out, "### Accounts\n").unwrap();
+            writeln!(out, "| Account | In | Out |\n| --- | ---: | ---: |").unwrap();
+            for account in self.accounts.iter() {
+                writeln!(
+                    out,
+                    "| {} | {} | {} |",
+                    markdown_cell(&account.account),
+                    account.credits,
+                    account.debits
+                )
+                .unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        // Each section ends with a blank line, but the report itself doesn't.
+        out.truncate(out.trim_end().len());
+        out.push('\n');
+        out
+    }
+}
+
+impl Report {
+    /// Renders the report as org-mode, with an org table for each tag breakdown (and for
+    /// the accounts, if the ledgers use them), followed by the ledger's entries as a list.
+    pub fn to_org(&self, ledger: &Ledger) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "* Ledger for {}\n", self.date).unwrap();
+        writeln!(
+            out,
+            "{} entries, totaling {} in credits and {} in debits for a net of {} in {}.\n",
+            self.entries,
+            self.credits,
+            self.debits,
+            self.net,
+            self.net_kind.name()
+        )
+        .unwrap();
+        if self.errors > 0 {
+            writeln!(
+                out,
+                "{} line(s) couldn't be parsed, and were skipped.\n",
+                self.errors
+            )
+            .unwrap();
+        }
+        if self.credits > Amount::ZERO {
+            writeln!(
+                out,
+                "- {:.1}% of credits saved, and {:.1}% spent",
+                self.ratios.savings_rate, self.ratios.expense_ratio
+            )
+            .unwrap();
+            if let Some(top) = &self.ratios.top_expense {
+                writeln!(
+                    out,
+                    "- the largest debit tag, {}, was {:.1}% of credits",
+                    org_cell(&top.tag),
+                    top.percent
+                )
+                .unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        for (title, shares) in [
+            ("Credit tags", &self.credit_tags),
+            ("Debit tags", &self.debit_tags),
+        ] {
+            writeln!(out, "** {}\n", title).unwrap();
+            writeln!(out, "| Tag | Amount | Share |\n|-----+--------+-------|").unwrap();
+            for share in shares.iter() {
+                writeln!(
+                    out,
+                    "| {} | {} | {:.1}% |",
+                    org_cell(&share.tag),
+                    share.amount,
+                    share.percent
+                )
+                .unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        if self.accounts.iter().any(|a| a.account != NO_ACCOUNT) {
+            writeln!(out, "** Accounts\n").unwrap();
+            writeln!(out, "| Account | In | Out |\n|---------+----+-----|").unwrap();
+            for account in self.accounts.iter() {
+                writeln!(
+                    out,
+                    "| {} | {} | {} |",
+                    org_cell(&account.account),
+                    account.credits,
+                    account.debits
+                )
+                .unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        writeln!(out, "** Entries\n").unwrap();
+        for entry in ledger.entries.iter() {
+            writeln!(out, "- {}", entry).unwrap();
+        }
+
+        out
+    }
+}
+
+/// Escapes the characters that would break an org table cell.
+fn org_cell(text: &str) -> String {
+    text.replace('|', "\\vert{}")
+}
+
+/// Escapes the characters that would break a Markdown table cell.
+fn markdown_cell(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+/// Returns `part` as a percentage of `whole`, rounded to one decimal place.
+pub fn percent(part: Amount, whole: Amount) -> f64 {
+    match whole.subunits() {
+        0 => 0.0,
+        whole => (part.subunits() as f64 * 1000.0 / whole as f64).round() / 10.0,
+    }
+}
+
+fn checked_sum(acc: Amount, amount: Amount) -> Result<Amount> {
+    acc.checked_add(amount)
+        .ok_or_else(|| Error::Amount(format!("total out of range: {} + {}", acc, amount)))
+}
+
+/// Returns the sum of all entries of the given kind.
+pub fn total(ledger: &Ledger, kind: &EntryKind) -> Result<Amount> {
+    ledger
+        .entries
+        .iter()
+        .filter(|e| e.kind == *kind)
+        .try_fold(Amount::ZERO.with_format(ledger.format), |acc, e| {
+            checked_sum(acc, e.amount)
+        })
+}
+
+/// Returns the per-tag totals for entries of the given kind, largest first. Entries
+/// without any tags are totaled under `(untagged)`.
+pub fn tag_totals(ledger: &Ledger, kind: &EntryKind) -> Result<Series> {
+    let mut totals = HashMap::new();
+    for entry in ledger.entries.iter().filter(|e| e.kind == *kind) {
+        add_tags(&mut totals, entry)?;
+    }
+
+    Ok(tag_series(totals, kind))
+}
+
+fn add_tags(totals: &mut HashMap<String, Amount>, entry: &Entry) -> Result<()> {
+    if entry.tags.is_empty() {
+        let total = totals.entry("(untagged)".into()).or_insert(Amount::ZERO);
+        *total = checked_sum(*total, entry.amount)?;
+    }
+
+    for tag in entry.tags.iter() {
+        let total = totals.entry(tag.clone()).or_insert(Amount::ZERO);
+        *total = checked_sum(*total, entry.amount)?;
+    }
+
+    Ok(())
+}
+
+fn tag_series(totals: HashMap<String, Amount>, kind: &EntryKind) -> Series {
+    let mut points: Vec<_> = totals.into_iter().collect();
+    points.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    Series {
+        name: format!("{} by tag", kind.plural()),
+        points,
+    }
+}
+
+/// Returns the per-ledger totals for entries of the given kind, in the order given.
+pub fn monthly_totals(ledgers: &[Ledger], kind: &EntryKind) -> Result<Series> {
+    Ok(Series {
+        name: format!("{} by month", kind.plural()),
+        points: ledgers
+            .iter()
+            .map(|l| Ok((l.date.clone(), total(l, kind)?)))
+            .collect::<Result<_>>()?,
+    })
+}
+
+/// The label that entries without an `@account` marker are totaled under.
+pub const NO_ACCOUNT: &str = "(none)";
+
+/// An account's credit and debit totals.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AccountTotals {
+    pub account: String,
+    pub credits: Amount,
+    pub debits: Amount,
+}
+
+fn add_accounts(
+    totals: &mut BTreeMap<Option<String>, (Amount, Amount)>,
+    entry: &Entry,
+) -> Result<()> {
+    // Transfers are a debit from one account, and a credit to the other. Allocations
+    // don't move any money.
+    let (credit, debit) = match entry.kind {
+        EntryKind::Credit => (Some(&entry.account), None),
+        EntryKind::Debit => (None, Some(&entry.account)),
+        EntryKind::Transfer => (Some(&entry.to), Some(&entry.account)),
+        EntryKind::Allocation => return Ok(()),
+    };
+
+    if let Some(account) = credit {
+        let (credits, _) = totals
+            .entry(account.clone())
+            .or_insert((Amount::ZERO, Amount::ZERO));
+        *credits = checked_sum(*credits, entry.amount)?;
+    }
+    if let Some(account) = debit {
+        let (_, debits) = totals
+            .entry(account.clone())
+            .or_insert((Amount::ZERO, Amount::ZERO));
+        *debits = checked_sum(*debits, entry.amount)?;
+    }
+
+    Ok(())
+}
+
+/// Returns each account's totals, ordered by account name. Entries without an account
+/// are totaled under `(none)`, last.
+fn account_list(totals: BTreeMap<Option<String>, (Amount, Amount)>) -> Vec<AccountTotals> {
+    // `None` sorts first, but reads best last.
+    let mut accounts: Vec<_> = totals
+        .into_iter()
+        .map(|(account, (credits, debits))| AccountTotals {
+            account: account.unwrap_or_else(|| NO_ACCOUNT.into()),
+            credits,
+            debits,
+        })
+        .collect();
+    if accounts.first().map(|a| a.account == NO_ACCOUNT) == Some(true) {
+        accounts.rotate_left(1);
+    }
+
+    accounts
+}
+
+/// A tag's totals for each of a sequence of ledgers, e.g. the months of a year.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TagRow {
+    pub tag: String,
+    pub months: Vec<Amount>,
+    pub total: Amount,
+}
+
+/// Returns each tag's per-ledger totals for entries of the given kind, with a column
+/// for every ledger (in the order given). Rows are ordered largest total first.
+pub fn tags_by_month(ledgers: &[Ledger], kind: &EntryKind) -> Result<Vec<TagRow>> {
+    let mut tags: BTreeMap<String, Vec<Amount>> = BTreeMap::new();
+    for (idx, ledger) in ledgers.iter().enumerate() {
+        for (tag, amount) in tag_totals(ledger, kind)?.points {
+            tags.entry(tag)
+                .or_insert_with(|| vec![Amount::ZERO; ledgers.len()])[idx] = amount;
+        }
+    }
+
+    let mut rows = tags
+        .into_iter()
+        .map(|(tag, months)| {
+            Ok(TagRow {
+                tag,
+                total: months
+                    .iter()
+                    .try_fold(Amount::ZERO, |acc, a| checked_sum(acc, *a))?,
+                months,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    rows.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.tag.cmp(&b.tag)));
+
+    Ok(rows)
+}
+
+/// Returns the number of days in the month of `date`, e.g. `2024-02`.
+pub fn days_in_month(date: &str) -> Result<u32> {
+    let first = NaiveDate::parse_from_str(&format!("{}-01", date), "%Y-%m-%d")
+        .map_err(|_| Error::Date(format!("not a month: {}", date)))?;
+    let next = first
+        .checked_add_months(Months::new(1))
+        .ok_or_else(|| Error::Date(format!("month out of range: {}", date)))?;
+
+    Ok(next.signed_duration_since(first).num_days() as u32)
+}
+
+/// Returns the per-day totals for a month's entries of the given kind, for every day
+/// of the month. Entries without an `@DD` day marker aren't included.
+pub fn daily_totals(ledger: &Ledger, kind: &EntryKind) -> Result<Series> {
+    let days = days_in_month(&ledger.date)?;
+
+    let mut totals = vec![Amount::ZERO; days as usize];
+    for entry in ledger.entries.iter().filter(|e| e.kind == *kind) {
+        if let Some(day) = entry.day {
+            let total = totals.get_mut(day as usize - 1).ok_or_else(|| {
+                Error::Date(format!("{} has no day {}: {}", ledger.date, day, entry))
+            })?;
+            *total = checked_sum(*total, entry.amount)?;
+        }
+    }
+
+    Ok(Series {
+        name: format!("{} by day", kind.plural()),
+        points: totals
+            .into_iter()
+            .enumerate()
+            .map(|(idx, amount)| (format!("{:02}", idx + 1), amount))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+    use crate::pledger::parse_ledger;
+    use crate::pledger::ParseOptions;
+
+    fn ledger(date: &str, contents: &'static str) -> Ledger {
+        parse_ledger(
+            date,
+            Box::new(contents.as_bytes().lines()),
+            &ParseOptions::default(),
+        )
+        .unwrap()
+    }
+
+    fn amt(subunits: u64) -> Amount {
+        Amount::from_subunits(subunits)
+    }
+
+    #[test]
+    fn test_tag_totals() {
+        let ledger = ledger(
+            "2023-05",
+            "C 10.00 #pay\nD 1.00 #food\nD 3.00 #food #fun\nD 2.00 #bar",
+        );
+
+        assert_eq!(total(&ledger, &EntryKind::Debit).unwrap(), amt(600));
+        assert_eq!(total(&ledger, &EntryKind::Credit).unwrap(), amt(1000));
+
+        let series = tag_totals(&ledger, &EntryKind::Debit).unwrap();
+        assert_eq!(series.name, "debits by tag");
+        assert_eq!(
+            series.points,
+            vec![
+                ("#food".into(), amt(400)),
+                ("#fun".into(), amt(300)),
+                ("#bar".into(), amt(200))
+            ]
+        );
+        assert_eq!(series.max(), amt(400));
+
+        assert_eq!(series.top(3), series);
+        assert_eq!(
+            series.top(1).points,
+            vec![("#food".into(), amt(400)), ("(other)".into(), amt(500))]
+        );
+        assert_eq!(series.top(0).points, vec![("(other)".into(), amt(900))]);
+
+        let untagged = self::ledger("2023-05", "D 1.00 lunch\nD 2.00 dinner\nD 2.50 #food");
+        assert_eq!(
+            tag_totals(&untagged, &EntryKind::Debit).unwrap().points,
+            vec![("(untagged)".into(), amt(300)), ("#food".into(), amt(250))]
+        );
+    }
+
+    #[test]
+    fn test_totals_overflow() {
+        let ledger = ledger(
+            "2023-05",
+            "D 184467440737095516.15 #a\nD 0.01 #a\nC 184467440737095516.15 #b",
+        );
+
+        assert!(total(&ledger, &EntryKind::Debit).is_err());
+        assert!(tag_totals(&ledger, &EntryKind::Debit).is_err());
+        assert!(Summary::new(&ledger).is_err());
+        assert_eq!(total(&ledger, &EntryKind::Credit).unwrap(), amt(u64::MAX));
+
+        let series = Series {
+            name: "debits by tag".into(),
+            points: vec![
+                ("#a".into(), amt(1)),
+                ("#b".into(), amt(u64::MAX)),
+                ("#c".into(), amt(1)),
+            ],
+        };
+        assert_eq!(series.top(1).points[1], ("(other)".into(), amt(u64::MAX)));
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary::new(&ledger("2023-05", "C 1.00 #pay\nD 3.00 #food")).unwrap();
+
+        assert_eq!(summary.entries, 2);
+        assert_eq!(summary.net(), (amt(200), EntryKind::Debit));
+        assert_eq!(summary.debit_tags.points, vec![("#food".into(), amt(300))]);
+        assert_eq!(
+            summary.accounts,
+            vec![AccountTotals {
+                account: NO_ACCOUNT.into(),
+                credits: amt(100),
+                debits: amt(300),
+            }]
+        );
+
+        // Transfers aren't income or spending.
+        let summary = Summary::new(&ledger("2023-05", "D 3.00 #food\nT 5.00 @a @b #move")).unwrap();
+        assert_eq!((summary.credits, summary.debits), (amt(0), amt(300)));
+        assert!(summary.debit_tags.points.iter().all(|(t, _)| t != "#move"));
+
+        let summary = Summary::new(&ledger(
+            "2023-05",
+            "C 10.00 @bank pay\nD 1.00 @cash lunch\nD 2.00 @bank rent\nD 3.00 other\nT 4.00 @bank @cash atm",
+        ))
+        .unwrap();
+        let accounts: Vec<_> = summary
+            .accounts
+            .iter()
+            .map(|a| (a.account.as_str(), a.credits, a.debits))
+            .collect();
+        assert_eq!(
+            accounts,
+            vec![
+                ("bank", amt(1000), amt(600)),
+                ("cash", amt(400), amt(100)),
+                (NO_ACCOUNT, amt(0), amt(300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_summarize_stream() {
+        let months = [
+            ledger("2023-05", "C 10.00 @bank #pay\nD 1.00 #food"),
+            ledger("2023-06", "D 2.00 #food\nD 3.00 dinner"),
+        ];
+        let summary = summarize_stream("2023", months.into_iter().map(Ok)).unwrap();
+
+        let mut combined = ledger("2023", "C 10.00 @bank #pay\nD 1.00 #food");
+        combined.merge(ledger("2023-06", "D 2.00 #food\nD 3.00 dinner"));
+        assert_eq!(summary, Summary::new(&combined).unwrap());
+        assert_eq!(summary.entries, 4);
+        assert_eq!(
+            summary.debit_tags.points,
+            vec![("#food".into(), amt(300)), ("(untagged)".into(), amt(300))]
+        );
+
+        let failing = vec![
+            Err(Error::NotFound("unreadable".into())),
+            Ok(ledger("2023-05", "D 1.00 x")),
+        ];
+        assert!(summarize_stream("2023", failing).is_err());
+    }
+
+    #[test]
+    fn test_report() {
+        let summary = Summary::new(&ledger(
+            "2023-05",
+            "C 1.00 #pay\nD 2.00 #food\nD 1.00 #fun\nD 0.50 #food #bar",
+        ))
+        .unwrap();
+
+        let report = Report::new(&summary, None);
+        assert_eq!(report.net, amt(250));
+        assert_eq!(report.net_kind, EntryKind::Debit);
+        assert_eq!(
+            report.debit_tags,
+            vec![
+                TagShare {
+                    tag: "#food".into(),
+                    amount: amt(250),
+                    percent: 71.4
+                },
+                TagShare {
+                    tag: "#fun".into(),
+                    amount: amt(100),
+                    percent: 28.6
+                },
+                TagShare {
+                    tag: "#bar".into(),
+                    amount: amt(50),
+                    percent: 14.3
+                },
+            ]
+        );
+        assert_eq!(report.credit_tags[0].percent, 100.0);
+        assert_eq!(report.ratios.savings_rate, -250.0);
+        assert_eq!(report.ratios.expense_ratio, 350.0);
+        assert_eq!(
+            report.ratios.top_expense,
+            Some(TagShare {
+                tag: "#food".into(),
+                amount: amt(250),
+                percent: 250.0
+            })
+        );
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["savings_rate"], -250.0);
+        assert_eq!(json["top_expense"]["tag"], "#food");
+
+        let report = Report::new(&summary, Some(1));
+        assert_eq!(report.debit_tags[1].tag, "(other)");
+        assert_eq!(report.debit_tags[1].percent, 42.9);
+
+        assert_eq!(percent(amt(1), Amount::ZERO), 0.0);
+
+        let markdown = Report::new(&summary, Some(1)).to_markdown();
+        assert!(markdown.starts_with(
+            "## Ledger for 2023-05\n\n4 entries, totaling 01.00 in credits and 03.50 in debits for a net of 02.50 in debit.\n\n- -250.0% of credits saved"
+        ));
+        assert!(markdown.contains(
+            "### Debit tags\n\n| Tag | Amount | Share |\n| --- | ---: | ---: |\n| #food | 02.50 | 71.4% |\n| (other) | 01.50 | 42.9% |\n"
+        ));
+        assert!(!markdown.contains("### Accounts"));
+        assert_eq!(markdown_cell("a|b"), "a\\|b");
+
+        let org = Report::new(&summary, Some(1)).to_org(&ledger("2023-05", "D 2.00 #food"));
+        assert!(org.starts_with("* Ledger for 2023-05\n\n4 entries,"));
+        assert!(org.contains(
+            "** Debit tags\n\n| Tag | Amount | Share |\n|-----+--------+-------|\n| #food | 02.50 | 71.4% |\n"
+        ));
+        assert!(org.ends_with("** Entries\n\n- D 2.00 #food\n"));
+        assert_eq!(org_cell("a|b"), "a\\vert{}b");
+    }
+
+    #[test]
+    fn test_monthly_totals() {
+        let ledgers = vec![
+            ledger("2023-01", "D 1.00 #a\nD 1.00 #b"),
+            ledger("2023-02", "C 1.00 #a"),
+        ];
+
+        let series = monthly_totals(&ledgers, &EntryKind::Debit).unwrap();
+        assert_eq!(
+            series.points,
+            vec![("2023-01".into(), amt(200)), ("2023-02".into(), amt(0))]
+        );
+    }
+
+    #[test]
+    fn test_tags_by_month() {
+        let ledgers = vec![
+            ledger("2023-01", "D 20.00 #food\nD 5.00 #fun"),
+            ledger("2023-02", "D 50.00 #home\nD 5.00 #food"),
+        ];
+
+        let rows = tags_by_month(&ledgers, &EntryKind::Debit).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                TagRow {
+                    tag: "#home".into(),
+                    months: vec![amt(0), amt(5000)],
+                    total: amt(5000),
+                },
+                TagRow {
+                    tag: "#food".into(),
+                    months: vec![amt(2000), amt(500)],
+                    total: amt(2500),
+                },
+                TagRow {
+                    tag: "#fun".into(),
+                    months: vec![amt(500), amt(0)],
+                    total: amt(500),
+                },
+            ]
+        );
+        assert!(tags_by_month(&[], &EntryKind::Debit).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_daily_totals() {
+        assert_eq!(days_in_month("2024-02").unwrap(), 29);
+        assert_eq!(days_in_month("2023-12").unwrap(), 31);
+        assert!(days_in_month("2023-13").is_err());
+
+        let ledger = ledger(
+            "2023-02",
+            "D 1.00 @01 #a\nD 2.00 @01\nD 5.00 @28 #b\nD 9.00 undated\nC 3.00 @02 pay",
+        );
+        let series = daily_totals(&ledger, &EntryKind::Debit).unwrap();
+        assert_eq!(series.name, "debits by day");
+        assert_eq!(series.points.len(), 28);
+        assert_eq!(series.points[0], ("01".into(), amt(300)));
+        assert_eq!(series.points[1], ("02".into(), amt(0)));
+        assert_eq!(series.points[27], ("28".into(), amt(500)));
+
+        let ledger = self::ledger("2023-02", "D 1.00 @30");
+        assert!(daily_totals(&ledger, &EntryKind::Debit).is_err());
+    }
+}
@@ -0,0 +1,638 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use phf::phf_map;
+
+use serde::de::Error as _;
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::pledger::{Error, Result};
+
+/// The currency symbols that can be written before an entry's amount, e.g. `D $12.50`,
+/// and the currencies that they stand for.
+pub static CURRENCY_SYMBOLS: phf::Map<char, &'static str> = phf_map! {
+    '$' => "USD",
+    '€' => "EUR",
+    '£' => "GBP",
+    '¥' => "JPY",
+    '₹' => "INR",
+    '₩' => "KRW",
+    '₽' => "RUB",
+    '₺' => "TRY",
+    '₪' => "ILS",
+    '₫' => "VND",
+    '฿' => "THB",
+    '₴' => "UAH",
+    '₦' => "NGN",
+    '₱' => "PHP",
+};
+
+/// Returns the symbol for a currency, e.g. `$` for `USD`.
+pub fn currency_symbol(currency: &str) -> Option<char> {
+    CURRENCY_SYMBOLS
+        .entries()
+        .find(|(_, c)| **c == currency)
+        .map(|(s, _)| *s)
+}
+
+/// How a ledger directory writes its amounts. Each amount keeps the format that it was
+/// parsed with, and is displayed with it.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct AmountFormat {
+    /// The decimal separator: `.` (`1,200.00`) or `,` (`1.200,00`).
+    pub decimal: char,
+    /// How many decimal places amounts have, e.g. 0 for yen or 3 for dinars, up to
+    /// `MAX_DECIMAL_PLACES`.
+    pub places: u32,
+}
+
+impl AmountFormat {
+    /// Amounts with a decimal point and two decimal places, like `1,200.00`.
+    pub const DEFAULT: AmountFormat = AmountFormat {
+        decimal: '.',
+        places: 2,
+    };
+
+    /// Returns this format with a decimal point, for amounts outside of ledgers (in the
+    /// config, queries, or statements), which always have one.
+    pub fn with_point(self) -> Self {
+        Self {
+            decimal: '.',
+            ..self
+        }
+    }
+
+    /// Returns the separator that groups the digits of amounts in this format.
+    pub fn grouping(&self) -> char {
+        grouping_separator(self.decimal)
+    }
+}
+
+impl Default for AmountFormat {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Returns the separator that groups the digits of amounts with `decimal`.
+pub fn grouping_separator(decimal: char) -> char {
+    match decimal {
+        ',' => '.',
+        _ => ',',
+    }
+}
+
+/// The most decimal places that amounts can have.
+pub const MAX_DECIMAL_PLACES: u32 = 3;
+
+/// Describes `places` decimal places, for errors.
+pub fn describe_places(places: u32) -> &'static str {
+    match places {
+        0 => "no decimal places",
+        1 => "one decimal place",
+        2 => "two decimal places",
+        _ => "three decimal places",
+    }
+}
+
+/// Returns how many subunits make up a unit, with `places` decimal places.
+pub fn subunits_per_unit(places: u32) -> u64 {
+    10_u64.pow(places)
+}
+
+/// Checks where the `grouping` separators are in an amount's `units`, i.e. the part
+/// before its decimal separator, failing with the offset of the misplaced separator.
+/// Amounts have `places` decimal places.
+///
+/// Units like `9,99` without a decimal place (whose last group has as many digits as amounts
+/// have decimal places) are always an error: the amount was almost certainly written with
+/// the other decimal separator, and would otherwise be read as far too large. Otherwise,
+/// unless grouping is `loose`, the units have to be grouped in threes, e.g. `1,234,567`.
+///
+/// With three decimal places, `1,234` is ambiguous too, but it's also how a
+/// thousand is grouped, so it's allowed.
+pub fn check_grouping(
+    units: &str,
+    grouping: char,
+    has_decimal: bool,
+    places: u32,
+    loose: bool,
+) -> std::result::Result<(), (usize, String)> {
+    let Some(last) = units.rfind(grouping) else {
+        return Ok(());
+    };
+
+    let places = places as usize;
+    let last_group = units.len() - last - grouping.len_utf8();
+    if !has_decimal && places > 0 && places != 3 && last_group == places {
+        return Err((
+            last,
+            format!(
+                "amount has {} digit(s) after '{}', which groups digits here; \
+                 set decimal = \"{}\" in {} if it's a decimal separator",
+                places,
+                grouping,
+                grouping,
+                crate::pledger::config::CONFIG_FILE
+            ),
+        ));
+    }
+
+    if loose {
+        return Ok(());
+    }
+
+    let mut offset = 0;
+    for (idx, group) in units.split(grouping).enumerate() {
+        let expected = match idx {
+            0 => (1..=3).contains(&group.len()),
+            _ => group.len() == 3,
+        };
+        if !expected {
+            // The first group's problem is the separator after it; every other
+            // group's is the separator before it.
+            let separator = match idx {
+                0 => group.len(),
+                _ => offset - grouping.len_utf8(),
+            };
+            return Err((
+                separator,
+                format!(
+                    "misplaced '{}' in amount; digits are grouped in threes, e.g. 1{}234{}567",
+                    grouping, grouping, grouping
+                ),
+            ));
+        }
+        offset += group.len() + grouping.len_utf8();
+    }
+
+    Ok(())
+}
+
+/// An amount of money, in subunits (e.g. cents), along with the format that it's
+/// written in.
+///
+/// Amounts are displayed and parsed with their format's decimal places (`1,200.00`, or
+/// `1.200,00` with a decimal comma), and serialized as a `[units, subunits]` pair, or
+/// `[units, subunits, places]` without two decimal places. They're compared by their
+/// subunits alone, so amounts in different formats shouldn't be compared.
+#[derive(Copy, Clone, Debug)]
+pub struct Amount {
+    subunits: u64,
+    format: AmountFormat,
+}
+
+impl Amount {
+    pub const ZERO: Amount = Amount::new(0, AmountFormat::DEFAULT);
+
+    pub const fn new(subunits: u64, format: AmountFormat) -> Self {
+        Self { subunits, format }
+    }
+
+    /// Returns an amount in the default format.
+    pub fn from_subunits(subunits: u64) -> Self {
+        Self::new(subunits, AmountFormat::DEFAULT)
+    }
+
+    /// Returns an amount of `subunits` in this amount's format, e.g. for a total of it.
+    pub fn with_subunits(self, subunits: u64) -> Self {
+        Self::new(subunits, self.format)
+    }
+
+    pub fn subunits(&self) -> u64 {
+        self.subunits
+    }
+
+    pub fn format(&self) -> AmountFormat {
+        self.format
+    }
+
+    /// Returns the amount in `format`.
+    pub fn with_format(self, format: AmountFormat) -> Self {
+        Self::new(self.subunits, format)
+    }
+
+    /// The format of a sum: a zero amount (e.g. `Amount::ZERO` as a sum's start) takes
+    /// the other amount's format.
+    fn sum_format(self, other: Amount) -> AmountFormat {
+        match self.subunits {
+            0 => other.format,
+            _ => self.format,
+        }
+    }
+
+    pub fn units(&self) -> u64 {
+        self.subunits / subunits_per_unit(self.format.places)
+    }
+
+    /// Returns the part of the amount that isn't a whole unit, in subunits.
+    pub fn fraction(&self) -> u64 {
+        self.subunits % subunits_per_unit(self.format.places)
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        let format = self.sum_format(other);
+        self.subunits
+            .checked_add(other.subunits)
+            .map(|subunits| Amount::new(subunits, format))
+    }
+
+    pub fn saturating_add(self, other: Amount) -> Amount {
+        Amount::new(
+            self.subunits.saturating_add(other.subunits),
+            self.sum_format(other),
+        )
+    }
+
+    pub fn abs_diff(self, other: Amount) -> Amount {
+        Amount::new(
+            self.subunits.abs_diff(other.subunits),
+            self.sum_format(other),
+        )
+    }
+
+    /// Parses a standalone amount in `format`, e.g. `1.200,00` with a decimal comma.
+    /// Grouping separators can go anywhere if `loose_grouping`.
+    pub fn parse_with(amount: &str, format: AmountFormat, loose_grouping: bool) -> Result<Self> {
+        let places = format.places;
+        let mut parts = amount.split(format.decimal);
+        let (units, subunits) = (parts.next().unwrap_or_default(), parts.next());
+
+        if parts.next().is_some() {
+            return Err(Error::Amount(format!(
+                "more than one decimal supplied in value: {}",
+                amount
+            )));
+        }
+
+        let grouping = format.grouping();
+        check_grouping(units, grouping, subunits.is_some(), places, loose_grouping)
+            .map_err(|(_, e)| Error::Amount(format!("{}: {}", e, amount)))?;
+
+        let units = units.replace(grouping, "");
+        if units.is_empty() || !units.chars().all(|c| c.is_ascii_digit()) {
+            return Err(Error::Amount(format!("invalid amount: {}", amount)));
+        }
+
+        let subunits = match subunits {
+            None => 0,
+            Some(s)
+                if places > 0
+                    && s.len() == places as usize
+                    && s.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                s.parse::<u64>().unwrap()
+            }
+            Some(_) => {
+                return Err(Error::Amount(format!(
+                    "expected {} in value: {}",
+                    describe_places(places),
+                    amount
+                )))
+            }
+        };
+
+        units
+            .parse::<u64>()
+            .ok()
+            .and_then(|u| u.checked_mul(subunits_per_unit(places)))
+            .and_then(|u| u.checked_add(subunits))
+            .map(|subunits| Amount::new(subunits, format))
+            .ok_or_else(|| Error::Amount(format!("amount out of range: {}", amount)))
+    }
+
+    /// Returns the amount as ledgers write it, e.g. `9,99` with a decimal comma, or
+    /// `3.00` (without the zero padding of reports' `05.50`).
+    pub fn to_ledger_string(self) -> String {
+        self.to_string_in(self.format.decimal, self.format.places, 1)
+    }
+
+    /// Returns the amount with a decimal point, whatever its format, for formats that
+    /// other programs read.
+    pub fn to_point_string(self) -> String {
+        self.to_string_in('.', self.format.places, 2)
+    }
+
+    /// Returns the amount with `decimal` before its `places` decimal places, and its
+    /// units zero-padded to at least `units` digits.
+    fn to_string_in(self, decimal: char, places: u32, units: usize) -> String {
+        let per_unit = subunits_per_unit(places);
+        match places {
+            0 => self.subunits.to_string(),
+            _ => format!(
+                "{:0units$}{}{:0width$}",
+                self.subunits / per_unit,
+                decimal,
+                self.subunits % per_unit,
+                width = places as usize
+            ),
+        }
+    }
+
+    /// Returns the amount as a fractional number of units, e.g. for plotting.
+    pub fn as_f64(&self) -> f64 {
+        self.subunits as f64 / subunits_per_unit(self.format.places) as f64
+    }
+}
+
+impl Default for Amount {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl PartialEq for Amount {
+    fn eq(&self, other: &Self) -> bool {
+        self.subunits == other.subunits
+    }
+}
+
+impl Eq for Amount {}
+
+impl PartialOrd for Amount {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Amount {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.subunits.cmp(&other.subunits)
+    }
+}
+
+impl Hash for Amount {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.subunits.hash(state);
+    }
+}
+
+impl fmt::Display for Amount {
+    /// Formats the amount for reports, in its format and with its units zero-padded to
+    /// two digits, e.g. `05.50`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.to_string_in(self.format.decimal, self.format.places, 2))
+    }
+}
+
+impl FromStr for Amount {
+    type Err = Error;
+
+    /// Parses a standalone amount in the default format, e.g. `1,200.00` or `15`.
+    fn from_str(amount: &str) -> Result<Self> {
+        Self::parse_with(amount, AmountFormat::DEFAULT, false)
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S>(&self, s: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let places = self.format.places;
+        let mut tup = s.serialize_tuple(if places == 2 { 2 } else { 3 })?;
+        tup.serialize_element(&self.units())?;
+        tup.serialize_element(&self.fraction())?;
+        if places != 2 {
+            tup.serialize_element(&places)?;
+        }
+        tup.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D>(d: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Parts {
+            Default(u64, u64),
+            Places(u64, u64, u32),
+        }
+
+        let (units, subunits, places) = match Parts::deserialize(d)? {
+            Parts::Default(units, subunits) => (units, subunits, 2),
+            Parts::Places(_, _, places) if places > MAX_DECIMAL_PLACES => {
+                return Err(D::Error::custom(format!(
+                    "decimal places out of range: {}",
+                    places
+                )))
+            }
+            Parts::Places(units, subunits, places) => (units, subunits, places),
+        };
+        let format = AmountFormat {
+            places,
+            ..AmountFormat::DEFAULT
+        };
+
+        let per_unit = subunits_per_unit(places);
+        if subunits >= per_unit {
+            return Err(D::Error::custom(format!(
+                "subunits out of range: {}",
+                subunits
+            )));
+        }
+
+        units
+            .checked_mul(per_unit)
+            .and_then(|u| u.checked_add(subunits))
+            .map(|subunits| Amount::new(subunits, format))
+            .ok_or_else(|| D::Error::custom("amount out of range"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMA: AmountFormat = AmountFormat {
+        decimal: ',',
+        places: 2,
+    };
+
+    fn places(places: u32) -> AmountFormat {
+        AmountFormat {
+            places,
+            ..AmountFormat::DEFAULT
+        }
+    }
+
+    #[test]
+    fn test_parse_amount() {
+        assert_eq!(
+            "1.00".parse::<Amount>().unwrap(),
+            Amount::from_subunits(100)
+        );
+        assert_eq!("15".parse::<Amount>().unwrap(), Amount::from_subunits(1500));
+        assert_eq!(
+            "20,000.12".parse::<Amount>().unwrap(),
+            Amount::from_subunits(2000012)
+        );
+
+        assert!("".parse::<Amount>().is_err());
+        assert!("1.0".parse::<Amount>().is_err());
+        assert!("1.0.0".parse::<Amount>().is_err());
+        assert!("abc".parse::<Amount>().is_err());
+        assert!("99999999999999999999".parse::<Amount>().is_err());
+
+        assert_eq!(
+            Amount::parse_with("1.200,00", COMMA, false).unwrap(),
+            Amount::from_subunits(120000)
+        );
+        assert_eq!(
+            Amount::parse_with("9,99", COMMA, false).unwrap(),
+            Amount::from_subunits(999)
+        );
+        assert!("9,99".parse::<Amount>().is_err());
+        assert!(Amount::parse_with("9.99", COMMA, false).is_err());
+        assert!("1,2,3.45".parse::<Amount>().is_err());
+        assert!("1234,567".parse::<Amount>().is_err());
+        assert!(",123".parse::<Amount>().is_err());
+        assert_eq!(
+            "1,234,567".parse::<Amount>().unwrap(),
+            Amount::from_subunits(123456700)
+        );
+    }
+
+    #[test]
+    fn test_check_grouping() {
+        assert_eq!(check_grouping("1234", ',', false, 2, false), Ok(()));
+        assert_eq!(check_grouping("12,345", ',', false, 2, false), Ok(()));
+        assert_eq!(check_grouping("1.234.567", '.', true, 2, false), Ok(()));
+
+        assert_eq!(
+            check_grouping("9,99", ',', false, 2, false).unwrap_err().0,
+            1
+        );
+        assert_eq!(
+            check_grouping("1,2,345", ',', true, 2, false)
+                .unwrap_err()
+                .0,
+            1
+        );
+        assert_eq!(
+            check_grouping("1234,567", ',', true, 2, false)
+                .unwrap_err()
+                .0,
+            4
+        );
+        assert_eq!(
+            check_grouping("12,3456", ',', true, 2, false)
+                .unwrap_err()
+                .0,
+            2
+        );
+        assert_eq!(check_grouping("12,", ',', true, 2, false).unwrap_err().0, 2);
+
+        // Loose grouping allows separators anywhere, but not ones that look like decimals.
+        assert_eq!(check_grouping("1,2,345", ',', true, 2, true), Ok(()));
+        assert_eq!(
+            check_grouping("9,99", ',', false, 2, true).unwrap_err().0,
+            1
+        );
+    }
+
+    #[test]
+    fn test_parse_amount_places() {
+        assert_eq!(
+            Amount::parse_with("1,500", places(0), false).unwrap(),
+            Amount::from_subunits(1500)
+        );
+        assert!(Amount::parse_with("15.00", places(0), false).is_err());
+        assert_eq!(
+            Amount::parse_with("1.250", places(3), false).unwrap(),
+            Amount::from_subunits(1250)
+        );
+        assert_eq!(
+            Amount::parse_with("1,234", places(3), false).unwrap(),
+            Amount::from_subunits(1234000)
+        );
+        assert!(Amount::parse_with("1.25", places(3), false).is_err());
+
+        assert!(check_grouping("1,2", ',', false, 1, false).is_err());
+        assert_eq!(
+            check_grouping("1,23", ',', false, 0, false).unwrap_err().0,
+            1
+        );
+    }
+
+    #[test]
+    fn test_amount_format() {
+        assert_eq!(Amount::from_subunits(0).to_string(), "00.00");
+        assert_eq!(Amount::from_subunits(550).to_string(), "05.50");
+        assert_eq!(Amount::from_subunits(123456).to_string(), "1234.56");
+        assert_eq!(format!("{:>7}", Amount::from_subunits(550)), "  05.50");
+        assert_eq!(Amount::from_subunits(123456).to_point_string(), "1234.56");
+        assert_eq!(Amount::from_subunits(1500).to_string_in('.', 0, 2), "1500");
+        assert_eq!(
+            Amount::from_subunits(1250).to_string_in(',', 3, 2),
+            "01,250"
+        );
+        assert_eq!(Amount::from_subunits(300).to_string_in('.', 2, 1), "3.00");
+        assert_eq!(Amount::from_subunits(5).to_string_in('.', 2, 1), "0.05");
+
+        // Amounts are shown in the format that they were parsed in.
+        let amount = Amount::parse_with("1.234,50", COMMA, false).unwrap();
+        assert_eq!(amount.format(), COMMA);
+        assert_eq!(amount.to_string(), "1234,50");
+        assert_eq!(amount.to_ledger_string(), "1234,50");
+        assert_eq!(amount.to_point_string(), "1234.50");
+        assert_eq!(amount, Amount::from_subunits(123450));
+    }
+
+    #[test]
+    fn test_amount_serde() {
+        let json = serde_json::to_string(&Amount::from_subunits(123456)).unwrap();
+        assert_eq!(json, "[1234,56]");
+        assert_eq!(
+            serde_json::from_str::<Amount>(&json).unwrap(),
+            Amount::from_subunits(123456)
+        );
+
+        // Serialized amounts don't depend on their decimal separator, but have their
+        // decimal places unless they're the usual two.
+        let comma = Amount::new(123456, COMMA);
+        assert_eq!(serde_json::to_string(&comma).unwrap(), json);
+        let dinars = Amount::new(123456, places(3));
+        let json = serde_json::to_string(&dinars).unwrap();
+        assert_eq!(json, "[123,456,3]");
+        let parsed = serde_json::from_str::<Amount>(&json).unwrap();
+        assert_eq!((parsed, parsed.format()), (dinars, places(3)));
+        assert_eq!(parsed.to_string(), "123.456");
+
+        assert!(serde_json::from_str::<Amount>("[1,100]").is_err());
+        assert!(serde_json::from_str::<Amount>("[1,1000,3]").is_err());
+        assert!(serde_json::from_str::<Amount>("[1,0,4]").is_err());
+        assert!(serde_json::from_str::<Amount>("[1,0,0,0]").is_err());
+        assert!(serde_json::from_str::<Amount>("[184467440737095517,0]").is_err());
+    }
+
+    #[test]
+    fn test_amount_arithmetic() {
+        assert_eq!(
+            Amount::from_subunits(1).checked_add(Amount::from_subunits(2)),
+            Some(Amount::from_subunits(3))
+        );
+        assert_eq!(
+            Amount::from_subunits(u64::MAX).checked_add(Amount::from_subunits(1)),
+            None
+        );
+        assert_eq!(
+            Amount::from_subunits(u64::MAX).saturating_add(Amount::from_subunits(1)),
+            Amount::from_subunits(u64::MAX)
+        );
+        assert_eq!(
+            Amount::from_subunits(1).abs_diff(Amount::from_subunits(3)),
+            Amount::from_subunits(2)
+        );
+
+        // Sums take their amounts' format, even when they start from zero.
+        let sum = Amount::ZERO.checked_add(Amount::new(100, COMMA)).unwrap();
+        assert_eq!(sum.format(), COMMA);
+    }
+}
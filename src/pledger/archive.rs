@@ -0,0 +1,246 @@
+//! Per-year archives of old monthly ledgers, as made by `pledger archive`.
+//!
+//! An archive is a `YYYY.archive` file in the ledger directory, with each month's ledger
+//! in turn under a `# month: YYYY-MM` header. Headers are comments, so an archive is
+//! itself a valid (year-long) ledger. The readers treat archived months like any other,
+//! but they can't be changed without taking them back out of the archive.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::pledger::{
+    compress, ledger_dates, lock_ledger, manifest, plan, write_atomically, Error, Result, Settings,
+    DATE_PATTERN,
+};
+
+/// The extension of archive files.
+pub const ARCHIVE_EXTENSION: &str = "archive";
+
+const HEADER_PREFIX: &str = "# month: ";
+
+/// Returns the path of `year`'s archive in the directory.
+pub fn archive_path(directory: &Path, year: &str) -> PathBuf {
+    directory.join(format!("{}.{}", year, ARCHIVE_EXTENSION))
+}
+
+/// Splits an archive's contents into its months, in order.
+fn sections(contents: &str) -> Vec<(String, Vec<String>)> {
+    let mut sections: Vec<(String, Vec<String>)> = vec![];
+    for line in contents.lines() {
+        match line.strip_prefix(HEADER_PREFIX).map(str::trim) {
+            Some(date) if DATE_PATTERN.is_match(date) => sections.push((date.into(), vec![])),
+            _ => match sections.last_mut() {
+                Some((_, lines)) => lines.push(line.into()),
+                None => log::warn!("skipping archive line outside of a month: {:?}", line),
+            },
+        }
+    }
+    sections
+}
+
+fn read_archive(directory: &Path, year: &str) -> Result<Vec<(String, Vec<String>)>> {
+    let path = archive_path(directory, year);
+    if !path.is_file() {
+        if let Some(compressed) = compress::find(&path) {
+            return Ok(sections(&compress::decompress(&compressed)?));
+        }
+    }
+
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(sections(&contents)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(Error::io(format!("couldn't read {}", path.display()))(e)),
+    }
+}
+
+/// Returns the dates of every archived ledger in the directory, in no particular order.
+pub fn archived_dates(directory: &Path) -> Result<Vec<String>> {
+    let mut dates = vec![];
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?.path();
+        let name = entry.file_name().unwrap_or_default().to_string_lossy();
+        let year = match compress::strip_extension(&name).strip_suffix(ARCHIVE_EXTENSION) {
+            Some(year) => year.strip_suffix('.').unwrap_or(year),
+            None => continue,
+        };
+
+        if year.len() != 4 || !year.bytes().all(|b| b.is_ascii_digit()) {
+            log::debug!("skipping non-year archive: {:?}", entry);
+            continue;
+        }
+
+        for (date, _) in read_archive(directory, year)? {
+            if !dates.contains(&date) {
+                dates.push(date);
+            }
+        }
+    }
+
+    Ok(dates)
+}
+
+/// Returns the lines of the archived ledger for `date`, if it's archived.
+pub fn read_archived(directory: &Path, date: &str) -> Result<Option<Vec<String>>> {
+    let year = date.get(..4).unwrap_or(date);
+    Ok(read_archive(directory, year)?
+        .into_iter()
+        .find(|(d, _)| d == date)
+        .map(|(_, lines)| lines))
+}
+
+/// Moves every monthly ledger from before `year` into its year's archive, returning the
+/// dates archived. Archives are written before any monthly ledger is removed, and each
+/// month stays locked from when its ledger is read until it's removed.
+pub fn archive(directory: &Path, year: &str, settings: &Settings) -> Result<Vec<String>> {
+    let dates: Vec<_> = ledger_dates(directory)?
+        .into_iter()
+        .filter(|d| d.as_str() < year && directory.join(format!("{d}.ledger")).is_file())
+        .collect();
+
+    // Otherwise, a write to a month after it's read would be lost when its
+    // ledger is removed. The dates are in order, so the locks are always taken in order.
+    let _locks = dates
+        .iter()
+        .map(|date| lock_ledger(directory, date))
+        .collect::<Result<Vec<_>>>()?;
+
+    for chunk in dates.chunk_by(|a, b| a[..4] == b[..4]) {
+        let year = &chunk[0][..4];
+        let path = archive_path(directory, year);
+        if !path.is_file() {
+            if let Some(compressed) = compress::find(&path) {
+                return Err(Error::Archived(format!(
+                    "{} is compressed; decompress it to archive more of {}",
+                    compressed.display(),
+                    year
+                )));
+            }
+        }
+        let mut sections = read_archive(directory, year)?;
+
+        for date in chunk.iter() {
+            if sections.iter().any(|(d, _)| d == date) {
+                return Err(Error::Archived(format!(
+                    "{} is already archived, and also has a ledger file",
+                    date
+                )));
+            }
+
+            let path = directory.join(format!("{date}.ledger"));
+            let contents = fs::read_to_string(&path)
+                .map_err(Error::io(format!("couldn't read {}", path.display())))?;
+            sections.push((date.clone(), contents.lines().map(String::from).collect()));
+        }
+        sections.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut contents = String::new();
+        for (date, lines) in sections.iter() {
+            contents.push_str(&format!("{}{}\n", HEADER_PREFIX, date));
+            for line in lines.iter() {
+                contents.push_str(line);
+                contents.push('\n');
+            }
+        }
+
+        if plan::change(
+            &path,
+            fs::read_to_string(&path).ok().as_deref(),
+            Some(&contents),
+        ) {
+            continue;
+        }
+        write_atomically(&path, &contents, settings.keep_backups)?;
+    }
+
+    for date in dates.iter() {
+        let path = directory.join(format!("{date}.ledger"));
+        let contents = fs::read_to_string(&path).ok();
+        if plan::change(&path, contents.as_deref(), None) {
+            continue;
+        }
+        fs::remove_file(&path).map_err(Error::io(format!(
+            "couldn't remove the ledger for {}",
+            date
+        )))?;
+        manifest::record(directory, &format!("{date}.ledger"), None)?;
+    }
+
+    Ok(dates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::store::{load_ledgers, DirectoryStore};
+    use crate::pledger::{load_ledger, update_ledger, ParseOptions, Settings};
+
+    #[test]
+    fn test_archive() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(directory.join("2020-01.ledger"), "D 1.00 a #x\n").unwrap();
+        fs::write(directory.join("2020-02.ledger"), "# feb\nD 2.00 b #x\n").unwrap();
+        fs::write(directory.join("2021-01.ledger"), "D 3.00 c #x\n").unwrap();
+
+        assert_eq!(
+            archive(directory, "2021", &Settings::default()).unwrap(),
+            ["2020-01", "2020-02"]
+        );
+        assert!(!directory.join("2020-01.ledger").exists());
+        assert_eq!(
+            fs::read_to_string(directory.join("2020.archive")).unwrap(),
+            "# month: 2020-01\nD 1.00 a #x\n# month: 2020-02\n# feb\nD 2.00 b #x\n"
+        );
+
+        assert_eq!(
+            ledger_dates(directory).unwrap(),
+            ["2020-01", "2020-02", "2021-01"]
+        );
+        assert_eq!(
+            load_ledger(directory, "2020-02", &ParseOptions::default())
+                .unwrap()
+                .entries
+                .len(),
+            1
+        );
+        assert_eq!(
+            load_ledgers(
+                &DirectoryStore::new(directory, Settings::default()),
+                Some("2020"),
+                &Settings::default()
+            )
+            .unwrap()
+            .entries
+            .len(),
+            2
+        );
+
+        assert!(matches!(
+            update_ledger(directory, "2020-01", &Settings::default(), Ok),
+            Err(Error::Archived(_))
+        ));
+
+        // Archiving again adds to the year's archive.
+        fs::write(directory.join("2020-03.ledger"), "D 4.00 d #x\n").unwrap();
+        assert_eq!(
+            archive(directory, "2021", &Settings::default()).unwrap(),
+            ["2020-03"]
+        );
+        assert_eq!(read_archive(directory, "2020").unwrap().len(), 3);
+
+        // Archiving waits for a month that's being written.
+        fs::write(directory.join("2020-04.ledger"), "D 5.00 e #x\n").unwrap();
+        let lock = lock_ledger(directory, "2020-04").unwrap();
+        let archiving = {
+            let directory = directory.to_path_buf();
+            std::thread::spawn(move || archive(&directory, "2021", &Settings::default()).unwrap())
+        };
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert!(directory.join("2020-04.ledger").is_file());
+        assert_eq!(read_archive(directory, "2020").unwrap().len(), 3);
+
+        drop(lock);
+        assert_eq!(archiving.join().unwrap(), ["2020-04"]);
+        assert!(!directory.join("2020-04.ledger").exists());
+    }
+}
@@ -0,0 +1,117 @@
+//! Files attached to entries, e.g. receipts, with an `@file:PATH` marker.
+//!
+//! Attachments are relative to the ledger directory, unless they're absolute.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::pledger::{Entry, Error, Ledger, Result};
+
+/// The program that opens files with their default application.
+#[cfg(target_os = "macos")]
+const OPENER: &str = "open";
+#[cfg(not(target_os = "macos"))]
+const OPENER: &str = "xdg-open";
+
+/// An entry whose attachment doesn't exist.
+#[derive(Debug, PartialEq)]
+pub struct Missing {
+    pub date: String,
+    /// The entry's number in its ledger, from 1.
+    pub entry: usize,
+    pub path: PathBuf,
+}
+
+/// Returns the path of an entry's attachment, if it has one.
+pub fn attachment_path(directory: &Path, entry: &Entry) -> Option<PathBuf> {
+    entry.attachment.as_ref().map(|path| directory.join(path))
+}
+
+/// Returns the entries in the ledgers whose attachments don't exist.
+pub fn missing_attachments(directory: &Path, ledgers: &[Ledger]) -> Vec<Missing> {
+    ledgers
+        .iter()
+        .flat_map(|ledger| {
+            ledger
+                .entries
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, entry)| {
+                    let path = attachment_path(directory, entry)?;
+                    (!path.exists()).then(|| Missing {
+                        date: ledger.date.clone(),
+                        entry: idx + 1,
+                        path,
+                    })
+                })
+        })
+        .collect()
+}
+
+/// Opens a file with its default application.
+pub fn open(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Err(Error::NotFound(format!(
+            "missing attachment: {}",
+            path.display()
+        )));
+    }
+
+    let status = Command::new(OPENER)
+        .arg(path)
+        .status()
+        .map_err(Error::io(format!("couldn't run {}", OPENER)))?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(Error::NotFound(format!(
+            "{} couldn't open {}",
+            OPENER,
+            path.display()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::pledger::{ParseOptions, Settings};
+
+    #[test]
+    fn test_attachments() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::create_dir_all(directory.join("receipts")).unwrap();
+        fs::write(directory.join("receipts/tires.pdf"), "").unwrap();
+        fs::write(
+            directory.join("2023-05.ledger"),
+            "D 42.00 new tires #car @file:receipts/tires.pdf\nD 9.00 lunch @file:lunch.pdf\n",
+        )
+        .unwrap();
+
+        let entry = crate::pledger::id::find_entry(directory, "2023-05:1", &Settings::default())
+            .unwrap()
+            .entry;
+        assert_eq!(entry.attachment.as_deref(), Some("receipts/tires.pdf"));
+        assert_eq!(entry.account, None);
+        assert_eq!(
+            attachment_path(directory, &entry),
+            Some(directory.join("receipts/tires.pdf"))
+        );
+
+        let ledger =
+            crate::pledger::load_ledger(directory, "2023-05", &ParseOptions::default()).unwrap();
+        assert_eq!(
+            missing_attachments(directory, &[ledger]),
+            [Missing {
+                date: "2023-05".into(),
+                entry: 2,
+                path: directory.join("lunch.pdf"),
+            }]
+        );
+
+        assert!("D 1.00 a @file:".parse::<Entry>().is_err());
+        assert!("D 1.00 a @file:x @file:y".parse::<Entry>().is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+
+use crate::pledger::{plan, update_ledger, Error, Result, Settings};
+
+/// Where ledger snapshots live, relative to the ledger directory.
+pub const BACKUP_DIR: &str = ".pledger/backups";
+
+fn backup_dir(directory: &Path) -> PathBuf {
+    directory.join(BACKUP_DIR)
+}
+
+/// Snapshots the ledger for `date`, returning the snapshot's path. Ledgers that don't
+/// exist yet have nothing to snapshot, and produce `None`.
+pub fn backup_ledger(directory: &Path, date: &str) -> Result<Option<PathBuf>> {
+    let ledger_file = directory.join(format!("{date}.ledger"));
+    if !ledger_file.is_file() {
+        return Ok(None);
+    }
+
+    let backup_dir = backup_dir(directory);
+    fs::create_dir_all(&backup_dir).map_err(Error::io(format!(
+        "couldn't create {}",
+        backup_dir.display()
+    )))?;
+
+    // The timestamp format sorts lexicographically, which `list_backups` relies on.
+    let timestamp = Utc::now().format("%Y%m%dT%H%M%S%.3fZ");
+    let mut backup_file = backup_dir.join(format!("{date}.ledger.{timestamp}"));
+
+    // Two snapshots within the same millisecond shouldn't clobber each other.
+    let mut n = 0;
+    while backup_file.exists() {
+        n += 1;
+        backup_file = backup_dir.join(format!("{date}.ledger.{timestamp}-{n}"));
+    }
+
+    fs::copy(&ledger_file, &backup_file).map_err(Error::io("ledger backup failed"))?;
+    log::debug!("backed up {:?} to {:?}", ledger_file, backup_file);
+
+    Ok(Some(backup_file))
+}
+
+/// Returns every snapshot of the ledger for `date`, oldest first.
+pub fn list_backups(directory: &Path, date: &str) -> Result<Vec<PathBuf>> {
+    let backup_dir = backup_dir(directory);
+    if !backup_dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let prefix = format!("{date}.ledger.");
+    let mut backups = vec![];
+    for entry in fs::read_dir(&backup_dir)? {
+        let entry = entry?.path();
+        if entry
+            .file_name()
+            .map(|n| n.to_string_lossy().starts_with(&prefix))
+            .unwrap_or(false)
+        {
+            backups.push(entry);
+        }
+    }
+
+    backups.sort();
+    Ok(backups)
+}
+
+/// Restores the ledger for `date` from a snapshot: the named one if given, otherwise
+/// the most recent. The ledger's current contents are snapshotted first, so a restore
+/// can itself be undone. Like any other change, the restore is made with `update_ledger`.
+///
+/// Returns the snapshot that was restored.
+pub fn restore_ledger(
+    directory: &Path,
+    date: &str,
+    settings: &Settings,
+    name: Option<&str>,
+) -> Result<PathBuf> {
+    let backups = list_backups(directory, date)?;
+
+    let backup_file = match name {
+        Some(name) => backups
+            .into_iter()
+            .find(|b| b.file_name().map(|n| n == name).unwrap_or(false))
+            .ok_or_else(|| Error::NotFound(format!("no such backup for {}: {}", date, name)))?,
+        None => backups
+            .into_iter()
+            .last()
+            .ok_or_else(|| Error::NotFound(format!("no backups for {}", date)))?,
+    };
+
+    let restored = fs::read_to_string(&backup_file).map_err(Error::io("ledger restore failed"))?;
+    update_ledger(directory, date, settings, |current| {
+        // The snapshot is taken under the ledger's lock, so that it's of the
+        // version that's actually replaced.
+        if current != restored && !plan::dry_run() {
+            backup_ledger(directory, date)?;
+        }
+        Ok(restored)
+    })?;
+
+    Ok(backup_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::manifest;
+
+    #[test]
+    fn test_backup_and_restore() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+
+        let ledger_file = directory.join("2023-05.ledger");
+
+        assert!(backup_ledger(directory, "2023-05").unwrap().is_none());
+        assert!(restore_ledger(directory, "2023-05", &Settings::default(), None).is_err());
+
+        fs::write(&ledger_file, "D 1.00 #good\n").unwrap();
+        backup_ledger(directory, "2023-05").unwrap().unwrap();
+
+        fs::write(&ledger_file, "mangled").unwrap();
+        manifest::update(directory).unwrap();
+        restore_ledger(directory, "2023-05", &Settings::default(), None).unwrap();
+
+        // The restore is recorded in the manifest, like any other change.
+        assert_eq!(fs::read_to_string(&ledger_file).unwrap(), "D 1.00 #good\n");
+        assert_eq!(manifest::verify(directory).unwrap(), Some(vec![]));
+
+        // The mangled version was snapshotted before the restore.
+        let backups = list_backups(directory, "2023-05").unwrap();
+        assert_eq!(backups.len(), 2);
+        assert_eq!(fs::read_to_string(&backups[1]).unwrap(), "mangled");
+    }
+}
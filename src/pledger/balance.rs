@@ -0,0 +1,157 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::pledger::amount::AmountFormat;
+use crate::pledger::EntryKind::*;
+use crate::pledger::{Amount, Ledger};
+
+/// A `balance` directive that didn't match the balance computed from the entries
+/// before it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+    pub date: String,
+    pub account: String,
+    pub stated: i128,
+    pub computed: i128,
+}
+
+/// Each account's running balance at the end of the ledgers, and every point where
+/// the ledgers disagreed with their own `balance` directives.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Balances {
+    pub accounts: BTreeMap<String, i128>,
+    pub mismatches: Vec<Mismatch>,
+    /// The accounts that have had a `balance` directive, and so have been opened.
+    opened: BTreeSet<String>,
+}
+
+impl Balances {
+    /// Adds the next ledger's entries and directives to the running balances.
+    ///
+    /// An account's first `balance` directive is its opening balance. Each later one is
+    /// checked against the balance computed up to that point, and then replaces it, so
+    /// that a single mistake isn't reported again in every later month.
+    pub fn add(&mut self, ledger: &Ledger) {
+        let mut directives = ledger.balances.iter().peekable();
+        for position in 0..=ledger.entries.len() {
+            while let Some(directive) = directives.next_if(|d| d.position == position) {
+                let stated = directive.subunits();
+                let previous = self.accounts.insert(directive.account.clone(), stated);
+                if !self.opened.insert(directive.account.clone()) {
+                    let computed = previous.unwrap_or(0);
+                    if computed != stated {
+                        self.mismatches.push(Mismatch {
+                            date: ledger.date.clone(),
+                            account: directive.account.clone(),
+                            stated,
+                            computed,
+                        });
+                    }
+                }
+            }
+
+            let entry = match ledger.entries.get(position) {
+                Some(entry) => entry,
+                None => break,
+            };
+
+            // i128 can't overflow on any realistic number of u64 amounts.
+            let amount = entry.amount.subunits() as i128;
+            let (credit, debit) = match entry.kind {
+                Credit => (&entry.account, &None),
+                Debit => (&None, &entry.account),
+                Transfer => (&entry.to, &entry.account),
+                Allocation => continue,
+            };
+            if let Some(account) = credit {
+                *self.accounts.entry(account.clone()).or_default() += amount;
+            }
+            if let Some(account) = debit {
+                *self.accounts.entry(account.clone()).or_default() -= amount;
+            }
+        }
+    }
+}
+
+/// Formats a signed balance in subunits, in the given format, e.g. `-12.50`.
+pub fn signed(subunits: i128, format: AmountFormat) -> String {
+    let amount = Amount::new(subunits.unsigned_abs() as u64, format);
+    match subunits < 0 {
+        true => format!("-{}", amount),
+        false => amount.to_string(),
+    }
+}
+
+/// Computes each account's running balance over the ledgers, which should be in order.
+/// See [`Balances::add`].
+pub fn balances(ledgers: &[Ledger]) -> Balances {
+    let mut balances = Balances::default();
+    for ledger in ledgers.iter() {
+        balances.add(ledger);
+    }
+
+    balances
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+    use crate::pledger::parse_ledger;
+    use crate::pledger::ParseOptions;
+
+    fn ledger(date: &str, contents: &'static str) -> Ledger {
+        parse_ledger(
+            date,
+            Box::new(contents.as_bytes().lines()),
+            &ParseOptions::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_balances() {
+        let ledgers = vec![
+            ledger(
+                "2023-01",
+                "balance 100.00 @bank\nD 10.00 @bank rent\nT 20.00 @bank @cash atm\nD 5.00 @cash lunch\nD 1.00 no account\nbalance 70.00 @bank",
+            ),
+            ledger(
+                "2023-02",
+                "D 30.00 @card stuff\nbalance 60.00 @bank\nD 1.00 @bank fee\nbalance -30.00 @card",
+            ),
+        ];
+
+        let balances = balances(&ledgers);
+        assert_eq!(
+            balances.accounts,
+            BTreeMap::from([
+                ("bank".into(), 5900),
+                ("card".into(), -3000),
+                ("cash".into(), 1500),
+            ])
+        );
+        assert_eq!(
+            balances.mismatches,
+            vec![Mismatch {
+                date: "2023-02".into(),
+                account: "bank".into(),
+                stated: 6000,
+                computed: 7000,
+            }]
+        );
+
+        assert_eq!(signed(-1250, AmountFormat::DEFAULT), "-12.50");
+        assert_eq!(signed(1250, AmountFormat::DEFAULT), "12.50");
+        assert_eq!(
+            signed(
+                -1250,
+                AmountFormat {
+                    decimal: ',',
+                    places: 2
+                }
+            ),
+            "-12,50"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Monthly budgets, and their rollover from month to month.
+//!
+//! With `budget_rollover`, what's left of each tag's budget at the end of a month carries
+//! over into the next month's, and what it went over by comes out of it. A month's
+//! effective budget is then its limit plus everything carried over from the months
+//! before it: from the first ledger, or from `budget_rollover_from`.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::pledger::aggregate::Summary;
+use crate::pledger::balance::signed;
+use crate::pledger::config::Config;
+use crate::pledger::{
+    budget_spent, ledger_dates, load_ledger, offset_date, Amount, Result, Settings,
+};
+
+/// A tag's budget for a month.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Budget {
+    /// The tag's monthly limit, from the config.
+    pub limit: Amount,
+    /// What carried over from the months before, in subunits: negative if they went over.
+    pub carried: i128,
+}
+
+impl Budget {
+    /// The budget for the month, with what carried over, in subunits.
+    pub fn effective(&self) -> i128 {
+        self.limit.subunits() as i128 + self.carried
+    }
+
+    /// Returns whether `spent` is over the effective budget.
+    pub fn is_over(&self, spent: Amount) -> bool {
+        spent.subunits() as i128 > self.effective()
+    }
+
+    /// Describes the budget's rollover, e.g. `400.00 + 25.00 carried`.
+    pub fn describe(&self) -> String {
+        match self.carried < 0 {
+            true => format!(
+                "{} - {} carried",
+                self.limit,
+                signed(-self.carried, self.limit.format())
+            ),
+            false => format!(
+                "{} + {} carried",
+                self.limit,
+                signed(self.carried, self.limit.format())
+            ),
+        }
+    }
+}
+
+/// Returns each budgeted tag's budget for the month of `date`, with what carried over
+/// into it if the config has `budget_rollover`.
+pub fn budgets(
+    directory: &Path,
+    date: &str,
+    config: &Config,
+    settings: &Settings,
+) -> Result<BTreeMap<String, Budget>> {
+    let mut budgets: BTreeMap<_, _> = config
+        .budgets(&settings.parse)
+        .into_iter()
+        .map(|(tag, limit)| (tag, Budget { limit, carried: 0 }))
+        .collect();
+    if !config.budget_rollover || budgets.is_empty() {
+        return Ok(budgets);
+    }
+
+    let dates = ledger_dates(directory)?;
+    let first = match &config.budget_rollover_from {
+        Some(from) => from.clone(),
+        None => match dates.first() {
+            Some(first) => first.clone(),
+            None => return Ok(budgets),
+        },
+    };
+
+    // Every month counts, including those without a ledger, which spent nothing
+    // and so carry their whole budget over.
+    let mut month = first;
+    while month.as_str() < date {
+        if dates.contains(&month) {
+            let summary = Summary::new(&load_ledger(directory, &month, &settings.parse)?)?;
+            for (tag, budget) in budgets.iter_mut() {
+                budget.carried =
+                    budget.effective() - budget_spent(&summary, tag).subunits() as i128;
+            }
+        } else {
+            for budget in budgets.values_mut() {
+                budget.carried = budget.effective();
+            }
+        }
+        month = offset_date(&month, 1)?;
+    }
+
+    Ok(budgets)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_budgets() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(directory.join("2023-01.ledger"), "D 30.00 a #food\n").unwrap();
+        fs::write(directory.join("2023-03.ledger"), "D 150.00 b #food\n").unwrap();
+
+        let mut config: Config = Config::parse("[budget]\n\"#food\" = \"100.00\"\n").unwrap();
+        let food = |config: &Config, date: &str| {
+            budgets(directory, date, config, &Settings::default()).unwrap()["#food"]
+        };
+
+        // Without rollover, every month's budget is its limit.
+        assert_eq!(food(&config, "2023-04").carried, 0);
+
+        // 70.00 left in January, all 100.00 of February (without a ledger), and 50.00 over
+        // in March.
+        config.budget_rollover = true;
+        assert_eq!(food(&config, "2023-01").carried, 0);
+        assert_eq!(food(&config, "2023-02").carried, 7000);
+        assert_eq!(food(&config, "2023-04").carried, 12000);
+        assert_eq!(food(&config, "2023-04").effective(), 22000);
+        assert_eq!(
+            food(&config, "2023-04").describe(),
+            "100.00 + 120.00 carried"
+        );
+
+        config.budget_rollover_from = Some("2023-03".into());
+        let budget = food(&config, "2023-04");
+        assert_eq!(budget.carried, -5000);
+        assert_eq!(budget.describe(), "100.00 - 50.00 carried");
+        assert!(budget.is_over(Amount::from_subunits(5001)));
+        assert!(!budget.is_over(Amount::from_subunits(5000)));
+    }
+}
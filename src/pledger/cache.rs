@@ -0,0 +1,188 @@
+//! A cache of parsed monthly ledgers, so that `--all` and `--year` only re-parse the
+//! months that have changed.
+//!
+//! Each month's parsed ledger is kept as JSON under `.pledger/cache`, along with the
+//! size and mtime of the file that it was parsed from. A cached ledger is only used while
+//! its file is unchanged, and is replaced the next time the month is loaded otherwise.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use crate::pledger::amount::AmountFormat;
+use crate::pledger::{ledger_source, Ledger, ParseOptions, Result, Strictness};
+
+/// Where cached ledgers live, relative to the ledger directory.
+pub const CACHE_DIR: &str = ".pledger/cache";
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turns off the cache for the rest of the process, e.g. for `--no-cache`.
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// What a cached ledger was parsed from. The version is included since the cache's
+/// format can change between releases.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Key {
+    version: String,
+    source: PathBuf,
+    size: u64,
+    mtime: u128,
+    /// The amount format that the ledger was parsed with.
+    format: AmountFormat,
+    /// Whether the ledger was parsed with loose grouping separators.
+    loose_grouping: bool,
+    /// The tag aliases that the ledger was parsed with.
+    aliases: BTreeMap<String, String>,
+    /// Whether the ledger was parsed with case-insensitive tags.
+    ignore_tag_case: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Cached {
+    key: Key,
+    ledger: Ledger,
+}
+
+fn cache_file(directory: &Path, date: &str) -> PathBuf {
+    directory.join(CACHE_DIR).join(format!("{date}.json"))
+}
+
+fn key(directory: &Path, date: &str, options: &ParseOptions) -> Option<Key> {
+    let source = ledger_source(directory, date);
+    let metadata = fs::metadata(&source).ok()?;
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+
+    Some(Key {
+        version: env!("CARGO_PKG_VERSION").into(),
+        source,
+        size: metadata.len(),
+        mtime: mtime.as_nanos(),
+        format: options.amount_format,
+        loose_grouping: options.loose_grouping,
+        aliases: options.aliases.clone(),
+        ignore_tag_case: options.ignore_tag_case,
+    })
+}
+
+/// Reads a cached ledger, if it was cached under `key`. Amounts are cached without
+/// their format, so they're given the one they were parsed with.
+fn read(path: &Path, key: &Key) -> Option<Ledger> {
+    let cached: Cached = serde_json::from_slice(&fs::read(path).ok()?).ok()?;
+    if cached.key != *key {
+        return None;
+    }
+
+    let mut ledger = cached.ledger;
+    ledger.format = key.format;
+    for entry in &mut ledger.entries {
+        entry.amount = entry.amount.with_format(key.format);
+    }
+    for balance in &mut ledger.balances {
+        balance.amount = balance.amount.with_format(key.format);
+    }
+    Some(ledger)
+}
+
+fn write(path: &Path, cached: &Cached) -> std::io::Result<()> {
+    fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+
+    let temp_file = path.with_extension("json.tmp");
+    fs::write(&temp_file, serde_json::to_vec(cached)?)?;
+    fs::rename(&temp_file, path)
+}
+
+/// Loads the ledger for `date` like `pledger::load_ledger`, but from the cache when its
+/// file hasn't changed since it was last parsed.
+///
+/// The cache is best-effort: a cached ledger that can't be read is re-parsed, and one
+/// that can't be written (e.g. in a read-only directory) is skipped. Ledgers are only
+/// cached when they're parsed normally, since strictness changes what parses.
+pub fn load_ledger(directory: &Path, date: &str, options: &ParseOptions) -> Result<Ledger> {
+    let enabled = ENABLED.load(Ordering::Relaxed) && options.strictness == Strictness::Normal;
+    let key = match enabled {
+        true => key(directory, date, options),
+        false => None,
+    };
+    let key = match key {
+        Some(key) => key,
+        None => return crate::pledger::load_ledger(directory, date, options),
+    };
+
+    let path = cache_file(directory, date);
+    if let Some(ledger) = read(&path, &key) {
+        log::debug!("using the cached ledger for {}", date);
+        return Ok(ledger);
+    }
+
+    let cached = Cached {
+        key,
+        ledger: crate::pledger::load_ledger(directory, date, options)?,
+    };
+    if let Err(e) = write(&path, &cached) {
+        log::debug!("couldn't cache the ledger for {}: {}", date, e);
+    }
+
+    Ok(cached.ledger)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_ledger() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        let ledger_file = directory.join("2020-01.ledger");
+        fs::write(&ledger_file, "D 1.00 a #x\nbalance 5.00 @bank\n").unwrap();
+
+        let ledger = load_ledger(directory, "2020-01", &ParseOptions::default()).unwrap();
+        assert_eq!(ledger.entries.len(), 1);
+        assert!(cache_file(directory, "2020-01").is_file());
+
+        let cached = load_ledger(directory, "2020-01", &ParseOptions::default()).unwrap();
+        assert_eq!(cached.entries, ledger.entries);
+        assert_eq!(cached.balances, ledger.balances);
+
+        // A changed ledger invalidates its cached version.
+        fs::write(&ledger_file, "D 1.00 a #x\nD 2.00 b #y\n").unwrap();
+        assert_eq!(
+            load_ledger(directory, "2020-01", &ParseOptions::default())
+                .unwrap()
+                .entries
+                .len(),
+            2
+        );
+
+        // So does a corrupt cache file.
+        fs::write(cache_file(directory, "2020-01"), "{").unwrap();
+        assert_eq!(
+            load_ledger(directory, "2020-01", &ParseOptions::default())
+                .unwrap()
+                .entries
+                .len(),
+            2
+        );
+
+        // Cached amounts are given the format that they were parsed in.
+        let comma = ParseOptions {
+            amount_format: AmountFormat {
+                decimal: ',',
+                places: 2,
+            },
+            ..Default::default()
+        };
+        fs::write(&ledger_file, "D 1,50 a #x\n").unwrap();
+        load_ledger(directory, "2020-01", &comma).unwrap();
+        let cached = load_ledger(directory, "2020-01", &comma).unwrap();
+        assert_eq!(cached.entries[0].amount.to_string(), "01,50");
+        assert_eq!(cached.amount_format(), comma.amount_format);
+    }
+}
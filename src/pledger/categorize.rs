@@ -0,0 +1,301 @@
+//! Rule-based categorization of untagged entries, by their comments.
+//!
+//! Rules come from the `[[rules]]` in the config, and are tried in order: the first
+//! one that matches an entry's comment adds its tags to the entry, and changes its
+//! kind if it has one.
+
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::pledger::config::Rule;
+use crate::pledger::EntryKind::{self, *};
+use crate::pledger::{
+    is_tag, logical_lines, parse_entry, update_ledger, Error, ParseOptions, Result, Settings,
+};
+
+/// Returns the comment after its leading `*` and `@DD` markers, so that rules match what
+/// the entry says, e.g. `^PAYROLL` for an imported `* @05 PAYROLL`.
+fn unmarked(comment: &str) -> &str {
+    let skip = |comment: &str, is_marker: fn(&str) -> bool| -> usize {
+        match comment.split_once(char::is_whitespace) {
+            Some((word, rest)) if is_marker(word) => comment.len() - rest.trim_start().len(),
+            None if is_marker(comment) => comment.len(),
+            _ => 0,
+        }
+    };
+
+    let comment = &comment[skip(comment, |w| w == "*")..];
+    let is_day = |w: &str| match w.strip_prefix('@') {
+        Some(day) => !day.is_empty() && day.bytes().all(|b| b.is_ascii_digit()),
+        None => false,
+    };
+    &comment[skip(comment, is_day)..]
+}
+
+#[derive(Debug)]
+enum Matcher {
+    /// A lowercased substring, matched case-insensitively.
+    Substring(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn matches(&self, text: &str) -> bool {
+        match self {
+            Matcher::Substring(substring) => text.to_lowercase().contains(substring),
+            Matcher::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CompiledRule {
+    matcher: Matcher,
+    tags: Vec<String>,
+    kind: Option<EntryKind>,
+}
+
+/// The config's rules, checked and ready to apply.
+#[derive(Debug, Default)]
+pub struct Rules(Vec<CompiledRule>);
+
+/// A line that categorization changed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    /// The line's number, from 1.
+    pub line: usize,
+    pub old: String,
+    pub new: String,
+}
+
+impl Rules {
+    pub fn new(rules: &[Rule]) -> Result<Self> {
+        let mut compiled = vec![];
+
+        for (idx, rule) in rules.iter().enumerate() {
+            let matcher = match (&rule.substring, &rule.regex) {
+                (Some(substring), None) => Matcher::Substring(substring.to_lowercase()),
+                (None, Some(regex)) => Matcher::Regex(Regex::new(regex).map_err(|e| {
+                    Error::Config(format!("rule {}: invalid regex: {}", idx + 1, e))
+                })?),
+                _ => {
+                    return Err(Error::Config(format!(
+                        "rule {}: needs exactly one of match or regex",
+                        idx + 1
+                    )))
+                }
+            };
+
+            if let Some(tag) = rule.tags.iter().find(|t| !is_tag(t)) {
+                return Err(Error::Config(format!(
+                    "rule {}: invalid tag: {}",
+                    idx + 1,
+                    tag
+                )));
+            }
+
+            let kind = match rule.kind.as_deref() {
+                None => None,
+                Some("debit") => Some(Debit),
+                Some("credit") => Some(Credit),
+                Some(kind) => {
+                    return Err(Error::Config(format!(
+                        "rule {}: invalid kind: {}",
+                        idx + 1,
+                        kind
+                    )))
+                }
+            };
+
+            compiled.push(CompiledRule {
+                matcher,
+                tags: rule.tags.clone(),
+                kind,
+            });
+        }
+
+        Ok(Self(compiled))
+    }
+
+    /// Applies the first matching rule to an entry's line, if it's an untagged entry.
+    /// Returns the categorized line, or `None` if the line is unchanged.
+    pub fn categorize(&self, line: &str, options: &ParseOptions) -> Option<String> {
+        let entry = parse_entry(line, options)
+            .ok()
+            .filter(|e| e.tags.is_empty())?;
+        let rule = self
+            .0
+            .iter()
+            .find(|r| r.matcher.matches(unmarked(&entry.comment)))?;
+
+        let mut categorized = line.trim_end().to_string();
+        // Transfers can't become debits or credits, since they have two accounts.
+        if let Some(kind) = rule.kind.as_ref().filter(|_| entry.kind != Transfer) {
+            // Entries always begin with their kind's (ASCII) symbol.
+            categorized.replace_range(..1, &kind.symbol().to_string());
+        }
+        for tag in rule.tags.iter() {
+            categorized.push(' ');
+            categorized.push_str(tag);
+        }
+
+        Some(categorized).filter(|c| c != line)
+    }
+
+    /// Categorizes every untagged entry in a ledger's text, returning the new text and
+    /// the lines that changed. An entry with continuation lines gets its tags on its
+    /// last line.
+    pub fn categorize_ledger(&self, text: &str, options: &ParseOptions) -> (String, Vec<Change>) {
+        let old: Vec<_> = text.lines().collect();
+        let mut new: Vec<String> = old.iter().map(|l| l.to_string()).collect();
+
+        for logical in logical_lines(text) {
+            let categorized = match self.categorize(&logical.text, options) {
+                Some(categorized) => categorized,
+                None => continue,
+            };
+
+            let (first, last) = (
+                logical.parts[0].idx,
+                logical.parts[logical.parts.len() - 1].idx,
+            );
+            new[first].replace_range(..1, &categorized[..1]);
+            let end = new[last].trim_end().len();
+            new[last].truncate(end);
+            new[last].push_str(&categorized[logical.text.trim_end().len()..]);
+        }
+
+        let mut categorized = String::with_capacity(text.len());
+        let mut changes = vec![];
+        for (idx, (old, new)) in old.into_iter().zip(new).enumerate() {
+            categorized.push_str(&new);
+            categorized.push('\n');
+            if old != new {
+                changes.push(Change {
+                    line: idx + 1,
+                    old: old.into(),
+                    new,
+                });
+            }
+        }
+
+        (categorized, changes)
+    }
+
+    /// Categorizes the ledger for `date`, writing it back (or planning to, in a dry run).
+    /// Returns the lines that changed.
+    pub fn categorize_ledger_file(
+        &self,
+        directory: &Path,
+        date: &str,
+        settings: &Settings,
+    ) -> Result<Vec<Change>> {
+        let ledger_file = directory.join(format!("{}.ledger", date));
+        if !ledger_file.is_file() {
+            return Err(Error::NotFound(format!(
+                "no ledger for {} in {}",
+                date,
+                directory.display()
+            )));
+        }
+
+        let mut changes = vec![];
+        update_ledger(directory, date, settings, |text| {
+            let (categorized, c) = self.categorize_ledger(&text, &settings.parse);
+            changes = c;
+            // Leave the file alone when nothing changed.
+            Ok(match changes.is_empty() {
+                true => text,
+                false => categorized,
+            })
+        })?;
+
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::config::Config;
+
+    #[test]
+    fn test_categorize() {
+        let config: Config = Config::parse(
+            r##"
+            [[rules]]
+            match = "grocery"
+            tags = ["#food"]
+
+            [[rules]]
+            regex = "^ ?PAYROLL"
+            tags = ["#pay", "#job"]
+            kind = "credit"
+            "##,
+        )
+        .unwrap();
+        let rules = Rules::new(&config.rules).unwrap();
+
+        assert_eq!(
+            rules
+                .categorize("D 5.00 @05 Big GROCERY store", &ParseOptions::default())
+                .as_deref(),
+            Some("D 5.00 @05 Big GROCERY store #food")
+        );
+        assert_eq!(
+            rules
+                .categorize("D 100.00 * @15 PAYROLL", &ParseOptions::default())
+                .as_deref(),
+            Some("C 100.00 * @15 PAYROLL #pay #job")
+        );
+        assert_eq!(
+            rules
+                .categorize("D 100.00 PAYROLL  ", &ParseOptions::default())
+                .as_deref(),
+            Some("C 100.00 PAYROLL #pay #job")
+        );
+        assert_eq!(
+            rules.categorize("D 5.00 grocery #snacks", &ParseOptions::default()),
+            None
+        );
+        assert_eq!(
+            rules.categorize("D 5.00 hardware", &ParseOptions::default()),
+            None
+        );
+        assert_eq!(
+            rules.categorize("# grocery", &ParseOptions::default()),
+            None
+        );
+
+        let (text, changes) =
+            rules.categorize_ledger("D 1.00 a\nD 2.00 grocery\n", &ParseOptions::default());
+        assert_eq!(text, "D 1.00 a\nD 2.00 grocery #food\n");
+        assert_eq!(
+            changes,
+            vec![Change {
+                line: 2,
+                old: "D 2.00 grocery".into(),
+                new: "D 2.00 grocery #food".into(),
+            }]
+        );
+
+        let (text, changes) =
+            rules.categorize_ledger("D 2.00 weekly\n  grocery run\n", &ParseOptions::default());
+        assert_eq!(text, "D 2.00 weekly\n  grocery run #food\n");
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].line, 2);
+        let (_, changes) = rules.categorize_ledger(
+            "D 2.00 grocery run\n  #shopping\n",
+            &ParseOptions::default(),
+        );
+        assert!(changes.is_empty());
+
+        let bad = |rules: &str| Rules::new(&Config::parse(rules).unwrap().rules);
+        assert!(bad("[[rules]]\ntags = [\"#a\"]").is_err());
+        assert!(bad("[[rules]]\nmatch = \"a\"\nregex = \"a\"").is_err());
+        assert!(bad("[[rules]]\nregex = \"(\"").is_err());
+        assert!(bad("[[rules]]\nmatch = \"a\"\ntags = [\"food\"]").is_err());
+        assert!(bad("[[rules]]\nmatch = \"a\"\nkind = \"transfer\"").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::path::Path;
+
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use crate::pledger::aggregate::{Series, Summary};
+use crate::pledger::{Amount, Error, Result};
+
+/// The most slices a pie chart gets before the smallest are combined.
+const MAX_SLICES: usize = 8;
+
+const PANEL_HEIGHT: u32 = 400;
+const WIDTH: u32 = 1000;
+
+fn color(idx: usize) -> RGBColor {
+    let (r, g, b) = Palette99::COLORS[idx % Palette99::COLORS.len()];
+    RGBColor(r, g, b)
+}
+
+/// Returns the series with everything past its largest `MAX_SLICES - 1` points
+/// combined into a single `(other)` point.
+fn slices(series: &Series) -> Vec<(String, Amount)> {
+    if series.points.len() <= MAX_SLICES {
+        return series.points.clone();
+    }
+
+    series.top(MAX_SLICES - 1).points
+}
+
+fn draw_pie<DB: DrawingBackend>(area: &DrawingArea<DB, Shift>, series: &Series) -> Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    let area = area
+        .titled(&series.name, ("sans-serif", 24))
+        .map_err(|e| Error::Output(format!("chart rendering failed: {}", e)))?;
+
+    let slices = slices(series);
+    if slices.is_empty() {
+        return Ok(());
+    }
+
+    let (width, height) = area.dim_in_pixel();
+    let center = (width as i32 / 2, height as i32 / 2);
+    let radius = (width.min(height) as f64 / 2.0) * 0.7;
+
+    let sizes: Vec<_> = slices.iter().map(|(_, v)| v.as_f64()).collect();
+    let colors: Vec<_> = (0..slices.len()).map(color).collect();
+    let labels: Vec<_> = slices.iter().map(|(l, _)| l.as_str()).collect();
+
+    let mut pie = Pie::new(&center, &radius, &sizes, &colors, &labels);
+    pie.label_style(("sans-serif", 14).into_font());
+    pie.percentages(("sans-serif", 12).into_font().color(&WHITE));
+
+    area.draw(&pie)
+        .map_err(|e| Error::Output(format!("chart rendering failed: {}", e)))?;
+
+    Ok(())
+}
+
+fn draw_bars<DB: DrawingBackend>(area: &DrawingArea<DB, Shift>, series: &Series) -> Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    let labels: Vec<_> = series.points.iter().map(|(l, _)| l.clone()).collect();
+    let max = series.max().as_f64().max(1.0);
+
+    let mut chart = ChartBuilder::on(area)
+        .caption(&series.name, ("sans-serif", 24))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(70)
+        .build_cartesian_2d((0..labels.len()).into_segmented(), 0.0..max * 1.1)
+        .map_err(|e| Error::Output(format!("chart rendering failed: {}", e)))?;
+
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_labels(labels.len())
+        .x_label_formatter(&|v| match v {
+            SegmentValue::CenterOf(idx) => labels.get(*idx).cloned().unwrap_or_default(),
+            _ => String::new(),
+        })
+        .y_label_formatter(&|v| format!("{:.2}", v))
+        .draw()
+        .map_err(|e| Error::Output(format!("chart rendering failed: {}", e)))?;
+
+    chart
+        .draw_series(series.points.iter().enumerate().map(|(idx, (_, v))| {
+            let mut bar = Rectangle::new(
+                [
+                    (SegmentValue::Exact(idx), 0.0),
+                    (SegmentValue::Exact(idx + 1), v.as_f64()),
+                ],
+                color(idx).filled(),
+            );
+            bar.set_margin(0, 0, 5, 5);
+            bar
+        }))
+        .map_err(|e| Error::Output(format!("chart rendering failed: {}", e)))?;
+
+    Ok(())
+}
+
+fn draw_trend<DB: DrawingBackend>(area: &DrawingArea<DB, Shift>, trend: &[Series]) -> Result<()>
+where
+    DB::ErrorType: 'static,
+{
+    let labels: Vec<_> = trend[0].points.iter().map(|(l, _)| l.clone()).collect();
+    let max = trend.iter().map(|s| s.max().as_f64()).fold(1.0, f64::max);
+
+    let mut chart = ChartBuilder::on(area)
+        .caption("Trend by month", ("sans-serif", 24))
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(70)
+        .build_cartesian_2d(0..labels.len().saturating_sub(1).max(1), 0.0..max * 1.1)
+        .map_err(|e| Error::Output(format!("chart rendering failed: {}", e)))?;
+
+    chart
+        .configure_mesh()
+        .x_labels(labels.len().min(12))
+        .x_label_formatter(&|idx| labels.get(*idx).cloned().unwrap_or_default())
+        .y_label_formatter(&|v| format!("{:.2}", v))
+        .draw()
+        .map_err(|e| Error::Output(format!("chart rendering failed: {}", e)))?;
+
+    for (sidx, series) in trend.iter().enumerate() {
+        let style = color(sidx).stroke_width(2);
+        chart
+            .draw_series(LineSeries::new(
+                series
+                    .points
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, (_, v))| (idx, v.as_f64())),
+                style,
+            ))
+            .map_err(|e| Error::Output(format!("chart rendering failed: {}", e)))?
+            .label(&series.name)
+            .legend(move |(x, y)| PathElement::new([(x, y), (x + 20, y)], style));
+    }
+
+    chart
+        .configure_series_labels()
+        .border_style(BLACK)
+        .background_style(WHITE.mix(0.8))
+        .draw()
+        .map_err(|e| Error::Output(format!("chart rendering failed: {}", e)))?;
+
+    Ok(())
+}
+
+/// Renders the summary as an SVG chart: pie charts of debits and credits by tag, a bar
+/// chart of the largest tags, and (if `trend` is non-empty) a line chart of each
+/// series in `trend`, which should share the same labels.
+pub fn render(path: &Path, summary: &Summary, trend: &[Series]) -> Result<()> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("svg") => {}
+        _ => {
+            return Err(Error::Output(format!(
+                "unsupported chart format (expected .svg): {}",
+                path.display()
+            )))
+        }
+    }
+
+    let panels = if trend.is_empty() { 2 } else { 3 };
+    let root = SVGBackend::new(path, (WIDTH, PANEL_HEIGHT * panels)).into_drawing_area();
+    root.fill(&WHITE)
+        .map_err(|e| Error::Output(format!("chart rendering failed: {}", e)))?;
+
+    let rows = root.split_evenly((panels as usize, 1));
+
+    let pies = rows[0].split_evenly((1, 2));
+    draw_pie(&pies[0], &summary.debit_tags)?;
+    draw_pie(&pies[1], &summary.credit_tags)?;
+
+    let mut top_debits = summary.debit_tags.clone();
+    top_debits.points.truncate(MAX_SLICES * 2);
+    draw_bars(&rows[1], &top_debits)?;
+
+    if !trend.is_empty() {
+        draw_trend(&rows[2], trend)?;
+    }
+
+    root.present()
+        .map_err(|e| Error::Output(format!("chart rendering failed: {}", e)))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+    use crate::pledger::parse_ledger;
+    use crate::pledger::ParseOptions;
+
+    #[test]
+    fn test_slices() {
+        let series = Series {
+            name: "debits by tag".into(),
+            points: (0..10)
+                .map(|i| (format!("#{}", i), Amount::from_subunits(100 - i)))
+                .collect(),
+        };
+
+        let slices = slices(&series);
+        assert_eq!(slices.len(), MAX_SLICES);
+        assert_eq!(
+            slices[MAX_SLICES - 1],
+            ("(other)".into(), Amount::from_subunits(93 + 92 + 91))
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let ledger = parse_ledger(
+            "2023-05",
+            Box::new("C 1.00 #pay\nD 1.00 #food".as_bytes().lines()),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let summary = Summary::new(&ledger).unwrap();
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("chart.svg");
+        render(&path, &summary, std::slice::from_ref(&summary.debit_tags)).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().starts_with("<svg"));
+
+        assert!(render(Path::new("chart.png"), &summary, &[]).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+//! Reading compressed ledgers, e.g. `2019-05.ledger.gz` or `2019.archive.zst`.
+//!
+//! Like archived ledgers, compressed ones can be read but not changed.
+
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+
+use crate::pledger::{Error, Result};
+
+/// The extensions of compressed files: gzip's and zstd's.
+pub const COMPRESSED_EXTENSIONS: &[&str] = &["gz", "zst"];
+
+/// Returns `path` with a compressed extension added, e.g. `2019-05.ledger.gz`.
+fn with_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".");
+    name.push(extension);
+    name.into()
+}
+
+/// Returns the compressed version of `path` that exists, if any.
+pub fn find(path: &Path) -> Option<PathBuf> {
+    COMPRESSED_EXTENSIONS
+        .iter()
+        .map(|extension| with_extension(path, extension))
+        .find(|p| p.is_file())
+}
+
+/// Strips a compressed extension from a file name, if it has one.
+pub fn strip_extension(name: &str) -> &str {
+    COMPRESSED_EXTENSIONS
+        .iter()
+        .find_map(|extension| name.strip_suffix(&format!(".{}", extension)))
+        .unwrap_or(name)
+}
+
+/// Decompresses the file at `path`, which must have a compressed extension. Corrupt
+/// files, and ones that don't decompress to UTF-8, are I/O errors.
+pub fn decompress(path: &Path) -> Result<String> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    if !COMPRESSED_EXTENSIONS.contains(&extension) {
+        return Err(Error::NotFound(format!(
+            "not a compressed file: {}",
+            path.display()
+        )));
+    }
+
+    let file = File::open(path).map_err(Error::io(format!("couldn't open {}", path.display())))?;
+    let context = || Error::io(format!("couldn't decompress {}", path.display()));
+    let mut decoder: Box<dyn Read> = match extension {
+        "gz" => Box::new(MultiGzDecoder::new(BufReader::new(file))),
+        _ => Box::new(zstd::Decoder::new(file).map_err(context())?),
+    };
+
+    let mut contents = String::new();
+    decoder.read_to_string(&mut contents).map_err(context())?;
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use super::*;
+    use crate::pledger::ParseOptions;
+    use crate::pledger::Settings;
+    use crate::pledger::{ledger_dates, load_ledger, update_ledger};
+
+    #[test]
+    fn test_compressed_ledger() {
+        assert_eq!(strip_extension("2019-05.ledger.zst"), "2019-05.ledger");
+        assert_eq!(strip_extension("2019-05.ledger"), "2019-05.ledger");
+
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        let ledger_file = directory.join("2019-05.ledger");
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(b"D 1.00 a #x\nD 2.00 b #x\n").unwrap();
+        fs::write(directory.join("2019-05.ledger.gz"), gz.finish().unwrap()).unwrap();
+
+        assert_eq!(
+            find(&ledger_file),
+            Some(directory.join("2019-05.ledger.gz"))
+        );
+        assert_eq!(ledger_dates(directory).unwrap(), ["2019-05"]);
+        assert_eq!(
+            load_ledger(directory, "2019-05", &ParseOptions::default())
+                .unwrap()
+                .entries
+                .len(),
+            2
+        );
+        assert!(matches!(
+            update_ledger(directory, "2019-05", &Settings::default(), Ok),
+            Err(Error::Archived(_))
+        ));
+
+        let zst = zstd::encode_all(&b"C 3.00 c #y\n"[..], 0).unwrap();
+        fs::write(directory.join("2019-06.ledger.zst"), zst).unwrap();
+        assert_eq!(
+            load_ledger(directory, "2019-06", &ParseOptions::default())
+                .unwrap()
+                .entries
+                .len(),
+            1
+        );
+
+        fs::write(directory.join("2019-07.ledger.gz"), "not gzip").unwrap();
+        assert!(matches!(
+            load_ledger(directory, "2019-07", &ParseOptions::default()),
+            Err(Error::Io { .. })
+        ));
+    }
+}
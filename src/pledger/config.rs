@@ -0,0 +1,596 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::pledger::amount::{self, AmountFormat};
+use crate::pledger::debt::Debt;
+use crate::pledger::goal::Goal;
+use crate::pledger::recurring::Recurring;
+use crate::pledger::{is_tag, Amount, Error, ParseOptions, Result, DATE_PATTERN};
+
+pub const CONFIG_FILE: &str = "pledger.toml";
+
+/// The skeleton written by `pledger init --config`.
+pub const CONFIG_SKELETON: &str = r##"# pledger configuration.
+
+# The decimal separator in amounts, "." (1,200.00) or "," (1.200,00).
+# decimal = "."
+
+# How many decimal places amounts have (0-3), e.g. 0 for yen or 3 for dinars. The
+# amounts in this file have them too.
+# decimal_places = 2
+
+# Whether grouping separators can go anywhere in amounts, rather than only between
+# groups of three digits (1,234,567.00) so that typos like 1,2,3.45 are errors.
+# loose_grouping = false
+
+# Whether tags are matched without regard to case, so that e.g. #Food and #food
+# are the same tag (and shown as #food).
+# ignore_tag_case = false
+
+# The month (1-12) that years start in, for --year, --quarter, --half, and
+# `pledger report`. Years are named for the calendar year they start in, so with
+# a start of 4, --year 2023 is April 2023 through March 2024.
+# fiscal_year_start = 1
+
+# Whether what's left of each tag's budget at the end of a month carries over
+# into the next month's (and what it went over by comes out of it), from the
+# first ledger on, or from budget_rollover_from.
+# budget_rollover = false
+# budget_rollover_from = "2023-01"
+
+# Sanity limits on amounts, to catch typos like a misplaced decimal point.
+# Entries (and months' total debits) over them are warned about when
+# summarizing, and are problems for `pledger lint`.
+[limits]
+# entry = "5,000.00"
+# month = "10,000.00"
+
+# Monthly spending limits, by tag. When summarizing a single month, pledger
+# reports each tag's debits against its limit.
+[budget]
+# "#food" = "400.00"
+# "#rent" = "1,200.00"
+
+# Categorization rules, used by `pledger import` and `pledger categorize`. The
+# first rule whose `match` (a case-insensitive substring) or `regex` matches an
+# untagged entry's comment adds its tags, and changes its kind if given.
+# [[rules]]
+# match = "grocery"
+# tags = ["#food"]
+#
+# [[rules]]
+# regex = "^PAYROLL"
+# tags = ["#pay"]
+# kind = "credit"
+
+# Tag aliases. Entries' aliased tags are counted as their canonical tags, and
+# `pledger retag` rewrites the ledgers with the canonical tags.
+[aliases]
+# "#grocery" = "#food"
+# "#uber" = "#transit"
+
+# Account names for `--export`, by tag or @account marker. Tags and accounts
+# without one are exported as Expenses:Tag, Income:Tag, or Assets:Account.
+[accounts]
+# "#food" = "Expenses:Groceries"
+# "@bank" = "Assets:Checking"
+
+# Recurring entries, due on a day (1-28) every month, quarter, or year. Quarterly
+# and yearly entries can give a month that they're due in (January by default).
+# `pledger ical` exports them as calendar reminders.
+# [[recurring]]
+# entry = "D 1,200.00 rent #rent"
+# every = "monthly"
+# day = 1
+#
+# [[recurring]]
+# entry = "D 300.00 car insurance #car"
+# every = "quarterly"
+# day = 15
+# month = 2
+
+# Savings goals, for `pledger goals`: a target to save (by a month, optionally),
+# counting either the credits with a tag or the balance of an @account.
+# [[goals]]
+# name = "vacation"
+# target = "3,000.00"
+# by = "2024-06"
+# tag = "#vacation"
+#
+# [[goals]]
+# name = "emergency fund"
+# target = "10,000.00"
+# account = "@savings"
+
+# Debts, for `pledger debt`: a principal (as of the first ledger, or since a
+# month), an APR, and the tag of the debits that pay it down. Payoffs are projected
+# with a monthly payment, or the average of the payments so far.
+# [[debts]]
+# name = "car loan"
+# principal = "15,000.00"
+# apr = 6.5
+# tag = "#car-loan"
+# since = "2023-01"
+# payment = "350.00"
+
+# What `pledger notify` checks for: tags over their budgets, a month's debits over
+# a total, or no new entries in some days. When any of them hold, the command is
+# run with a JSON description of them on its stdin.
+[notify]
+# command = "curl -s -H 'Content-Type: application/json' --data @- https://example.com/hook"
+# budget = true
+# month = "3,000.00"
+# quiet_days = 7
+
+# Where `pledger report --send` sends reports: a webhook's URL, and its style,
+# "slack", "discord", "matrix", or "pushover" (which also needs a token and user).
+# [webhook]
+# url = "https://hooks.slack.com/services/..."
+# style = "slack"
+
+# How `pledger report --email` sends mail: a sendmail-compatible command that
+# reads the message (and its recipients) on stdin, and the address it's from.
+# [email]
+# command = "sendmail -t"
+# from = "pledger@example.com"
+"##;
+
+/// How the ledgers in a directory write the decimal places of amounts.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum Decimal {
+    /// `1,200.00`
+    #[default]
+    #[serde(rename = ".")]
+    Point,
+    /// `1.200,00`
+    #[serde(rename = ",")]
+    Comma,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    /// The decimal separator in the directory's ledgers. Amounts in the config itself
+    /// always have a decimal point.
+    #[serde(default)]
+    pub decimal: Decimal,
+    /// How many decimal places amounts have, if not two.
+    #[serde(default, deserialize_with = "decimal_places_deserialize")]
+    pub decimal_places: Option<u32>,
+    /// Whether amounts' grouping separators can go anywhere, rather than only between
+    /// groups of three digits. See [`crate::pledger::amount::check_grouping`].
+    #[serde(default)]
+    pub loose_grouping: bool,
+    /// Whether tags are matched case-insensitively. See [`crate::pledger::retag`].
+    #[serde(default)]
+    pub ignore_tag_case: bool,
+    /// Whether the previous version of each changed ledger is kept as a `.bak` file.
+    #[serde(default)]
+    pub keep_backups: bool,
+    /// The month (from 1) that years start in, if not January.
+    #[serde(default, deserialize_with = "fiscal_year_start_deserialize")]
+    pub fiscal_year_start: Option<u32>,
+    #[serde(default, deserialize_with = "budget_deserialize")]
+    pub budget: BTreeMap<String, Amount>,
+    /// Whether budgets carry over from month to month. See [`crate::pledger::budget`].
+    #[serde(default)]
+    pub budget_rollover: bool,
+    /// The month that budgets start carrying over from, if not the first ledger's.
+    #[serde(default, deserialize_with = "month_deserialize")]
+    pub budget_rollover_from: Option<String>,
+    #[serde(default)]
+    pub limits: Limits,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Canonical tags, by their aliases.
+    #[serde(default, deserialize_with = "aliases_deserialize")]
+    pub aliases: BTreeMap<String, String>,
+    /// Account names for exports, by tag or `@account` marker.
+    #[serde(default)]
+    pub accounts: BTreeMap<String, String>,
+    #[serde(default)]
+    pub recurring: Vec<Recurring>,
+    #[serde(default)]
+    pub goals: Vec<Goal>,
+    #[serde(default)]
+    pub debts: Vec<Debt>,
+    #[serde(default)]
+    pub notify: Notify,
+    pub webhook: Option<Webhook>,
+    pub signing: Option<Signing>,
+    #[serde(default)]
+    pub email: Email,
+}
+
+/// A categorization rule, as written in the config. See [`crate::pledger::categorize`].
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    #[serde(rename = "match")]
+    pub substring: Option<String>,
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub kind: Option<String>,
+}
+
+/// Sanity limits on amounts, as written in the config. See [`crate::pledger::limits`].
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    /// The largest amount that a single entry should have.
+    #[serde(default, deserialize_with = "limit_deserialize")]
+    pub entry: Option<Amount>,
+    /// The largest total that a month's debits should have.
+    #[serde(default, deserialize_with = "limit_deserialize")]
+    pub month: Option<Amount>,
+}
+
+/// What `pledger notify` checks for, and how it notifies. See [`crate::pledger::notify`].
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Notify {
+    /// The command to run with the notifications, through the shell.
+    pub command: Option<String>,
+    /// Whether to notify about tags over their budgets.
+    #[serde(default)]
+    pub budget: bool,
+    /// The total that a month's debits shouldn't go over.
+    #[serde(default, deserialize_with = "limit_deserialize")]
+    pub month: Option<Amount>,
+    /// How many days without new entries to notify after.
+    pub quiet_days: Option<u64>,
+}
+
+/// How `pledger report --email` sends mail. See [`crate::pledger::email`].
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Email {
+    /// A sendmail-compatible command to pipe messages to, through the shell.
+    pub command: Option<String>,
+    /// The address that messages are from, if not the mail command's default.
+    pub from: Option<String>,
+}
+
+/// Where `pledger report --send` sends reports. See [`crate::pledger::webhook`].
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default)]
+    pub style: WebhookStyle,
+    /// Pushover's application token and user key.
+    pub token: Option<String>,
+    pub user: Option<String>,
+}
+
+/// How ledger files are signed and verified, by `pledger sign` and `pledger verify
+/// --signatures`. See [`crate::pledger::signing`]. Signing keys aren't part of the config,
+/// since each signer has their own.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Signing {
+    #[serde(default)]
+    pub tool: SigningTool,
+    /// For `ssh`: the allowed signers file, relative to the ledger directory.
+    pub allowed_signers: Option<PathBuf>,
+    /// For `minisign`: each signer's public key file, relative to the ledger directory.
+    #[serde(default)]
+    pub public_keys: BTreeMap<String, PathBuf>,
+}
+
+/// The program that signs ledger files.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningTool {
+    /// `ssh-keygen -Y`.
+    #[default]
+    Ssh,
+    Minisign,
+}
+
+/// The kind of service that a webhook is for, which decides its JSON body.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookStyle {
+    #[default]
+    Slack,
+    Discord,
+    Matrix,
+    Pushover,
+}
+
+/// Parses one of the config's own amounts, which always have a decimal point, with as
+/// many decimal places as it's written with. See `ledger_amount`.
+fn config_amount(raw: &str) -> Result<Amount> {
+    let places = raw
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.len());
+    match u32::try_from(places) {
+        Ok(places) if places <= amount::MAX_DECIMAL_PLACES => Amount::parse_with(
+            raw,
+            AmountFormat {
+                decimal: '.',
+                places,
+            },
+            false,
+        ),
+        _ => Err(Error::Amount(format!(
+            "more than {} in value: {}",
+            amount::describe_places(amount::MAX_DECIMAL_PLACES),
+            raw
+        ))),
+    }
+}
+
+/// Gives one of the config's amounts the ledgers' `format`, failing unless it was
+/// written with their decimal places, or as whole units.
+fn ledger_amount(amount: Amount, format: AmountFormat) -> Result<Amount> {
+    match amount.format().places {
+        0 => amount
+            .subunits()
+            .checked_mul(amount::subunits_per_unit(format.places))
+            .map(|subunits| Amount::new(subunits, format))
+            .ok_or_else(|| Error::Amount(format!("amount out of range: {}", amount))),
+        places if places == format.places => Ok(amount.with_format(format)),
+        _ => Err(Error::Amount(format!(
+            "expected {} in value: {}",
+            amount::describe_places(format.places),
+            amount.to_ledger_string()
+        ))),
+    }
+}
+
+fn limit_deserialize<'de, D>(d: D) -> std::result::Result<Option<Amount>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = String::deserialize(d)?;
+    match config_amount(&raw) {
+        Ok(amount) => Ok(Some(amount)),
+        Err(e) => Err(serde::de::Error::custom(format!("limit: {}", e))),
+    }
+}
+
+fn decimal_places_deserialize<'de, D>(d: D) -> std::result::Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match u32::deserialize(d)? {
+        places @ 0..=amount::MAX_DECIMAL_PLACES => Ok(Some(places)),
+        places => Err(serde::de::Error::custom(format!(
+            "decimal_places: out of range: {}",
+            places
+        ))),
+    }
+}
+
+fn fiscal_year_start_deserialize<'de, D>(d: D) -> std::result::Result<Option<u32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match u32::deserialize(d)? {
+        month @ 1..=12 => Ok(Some(month)),
+        month => Err(serde::de::Error::custom(format!(
+            "fiscal_year_start: month out of range: {}",
+            month
+        ))),
+    }
+}
+
+fn month_deserialize<'de, D>(d: D) -> std::result::Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let month = String::deserialize(d)?;
+    match DATE_PATTERN.is_match(&month) {
+        true => Ok(Some(month)),
+        false => Err(serde::de::Error::custom(format!(
+            "expected a month as YYYY-MM, got {}",
+            month
+        ))),
+    }
+}
+
+fn budget_deserialize<'de, D>(d: D) -> std::result::Result<BTreeMap<String, Amount>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = BTreeMap::<String, String>::deserialize(d)?;
+    raw.into_iter()
+        .map(|(tag, amount)| match config_amount(&amount) {
+            Ok(amount) => Ok((tag, amount)),
+            Err(e) => Err(serde::de::Error::custom(format!(
+                "budget for {}: {}",
+                tag, e
+            ))),
+        })
+        .collect()
+}
+
+fn aliases_deserialize<'de, D>(d: D) -> std::result::Result<BTreeMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let aliases = BTreeMap::<String, String>::deserialize(d)?;
+    for (alias, canonical) in aliases.iter() {
+        if let Some(tag) = [alias, canonical].into_iter().find(|t| !is_tag(t)) {
+            return Err(serde::de::Error::custom(format!("invalid tag: {}", tag)));
+        }
+        // Aliases aren't followed transitively, so a canonical tag can't be
+        // an alias itself.
+        if aliases.contains_key(canonical) {
+            return Err(serde::de::Error::custom(format!(
+                "{} is an alias for {}, which is itself an alias",
+                alias, canonical
+            )));
+        }
+    }
+    Ok(aliases)
+}
+
+impl Config {
+    /// Loads the config from the given ledger directory, falling back on the default
+    /// (empty) config if the directory doesn't have one.
+    pub fn load(directory: &Path) -> Result<Self> {
+        let config_file = directory.join(CONFIG_FILE);
+        if !config_file.is_file() {
+            return Ok(Default::default());
+        }
+
+        let contents =
+            fs::read_to_string(&config_file).map_err(Error::io("config file read failed"))?;
+
+        Self::parse(&contents).map_err(|e| {
+            Error::Config(format!(
+                "invalid config file {}: {}",
+                config_file.display(),
+                e
+            ))
+        })
+    }
+
+    /// Parses a config file's contents.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Config = toml::from_str(contents).map_err(|e| Error::Config(e.to_string()))?;
+        config.with_amount_format()
+    }
+
+    /// Returns how amounts are written in the directory's ledgers.
+    pub fn amount_format(&self) -> AmountFormat {
+        AmountFormat {
+            decimal: match self.decimal {
+                Decimal::Point => '.',
+                Decimal::Comma => ',',
+            },
+            places: self.decimal_places.unwrap_or(2),
+        }
+    }
+
+    /// Gives the config's own amounts the ledgers' format, so that they're shown the
+    /// way the ledgers' amounts are. They're parsed before the ledgers' decimal places
+    /// are known, so they're checked against them here.
+    fn with_amount_format(mut self) -> Result<Self> {
+        let format = self.amount_format();
+        let in_format = |name: &str, amount: &mut Amount| {
+            *amount = ledger_amount(*amount, format)
+                .map_err(|e| Error::Config(format!("{}: {}", name, e)))?;
+            Ok::<_, Error>(())
+        };
+
+        for (tag, limit) in self.budget.iter_mut() {
+            in_format(&format!("budget for {}", tag), limit)?;
+        }
+        for (name, limit) in [
+            ("limits.entry", &mut self.limits.entry),
+            ("limits.month", &mut self.limits.month),
+            ("notify.month", &mut self.notify.month),
+        ] {
+            if let Some(limit) = limit {
+                in_format(name, limit)?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Returns the budgets by canonical tag under `options`, so that they match entries'
+    /// tags. The budgets for tags that are the same canonical tag are added together.
+    pub fn budgets(&self, options: &ParseOptions) -> BTreeMap<String, Amount> {
+        let mut budgets = BTreeMap::new();
+        for (tag, limit) in self.budget.iter() {
+            let total: &mut Amount = budgets.entry(options.canonical_tag(tag)).or_default();
+            *total = total.saturating_add(*limit);
+        }
+        budgets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_budget() {
+        let config: Config = Config::parse(CONFIG_SKELETON).unwrap();
+        assert!(config.budget.is_empty());
+        assert!(config.rules.is_empty());
+        assert!(config.accounts.is_empty());
+        assert!(config.aliases.is_empty());
+        assert_eq!(config.limits.entry, None);
+        assert!(config.recurring.is_empty());
+        assert!(config.goals.is_empty());
+        assert!(config.debts.is_empty());
+        assert_eq!(config.fiscal_year_start, None);
+        assert!(!config.loose_grouping);
+        assert_eq!(config.decimal_places, None);
+
+        // The skeleton's top-level keys come before any table, so they can be uncommented.
+        let uncommented = CONFIG_SKELETON
+            .replace("# budget_rollover = false", "budget_rollover = true")
+            .replace("# budget_rollover_from", "budget_rollover_from");
+        let config: Config = Config::parse(&uncommented).unwrap();
+        assert!(config.budget_rollover);
+        assert_eq!(config.budget_rollover_from.as_deref(), Some("2023-01"));
+
+        let config: Config = Config::parse("decimal_places = 0").unwrap();
+        assert_eq!(config.decimal_places, Some(0));
+        assert!(Config::parse("decimal_places = 4").is_err());
+
+        let config: Config = Config::parse("fiscal_year_start = 4").unwrap();
+        assert_eq!(config.fiscal_year_start, Some(4));
+        assert!(Config::parse("fiscal_year_start = 13").is_err());
+
+        let config: Config =
+            Config::parse("[budget]\n\"#food\" = \"400.00\"\n\"#rent\" = \"1,200\"").unwrap();
+        assert_eq!(config.budget["#food"], Amount::from_subunits(40000));
+        assert_eq!(config.budget["#rent"], Amount::from_subunits(120000));
+
+        // Budgets for tags that are the same canonical tag are added together.
+        let options = ParseOptions {
+            aliases: [("#grocery".into(), "#food".into())].into(),
+            ignore_tag_case: true,
+            ..ParseOptions::default()
+        };
+        let config: Config = Config::parse(
+            "[budget]\n\"#food\" = \"400.00\"\n\"#Grocery\" = \"50.00\"\n\"#Rent\" = \"1,200\"",
+        )
+        .unwrap();
+        let budgets = config.budgets(&options);
+        assert_eq!(budgets["#food"], Amount::from_subunits(45000));
+        assert_eq!(budgets["#rent"], Amount::from_subunits(120000));
+        assert_eq!(budgets.len(), 2);
+
+        assert!(Config::parse("[budget]\n\"#food\" = \"4.0.0\"").is_err());
+
+        let config: Config =
+            Config::parse("budget_rollover = true\nbudget_rollover_from = \"2023-01\"").unwrap();
+        assert!(config.budget_rollover);
+        assert_eq!(config.budget_rollover_from.as_deref(), Some("2023-01"));
+        assert!(Config::parse("budget_rollover_from = \"2023\"").is_err());
+
+        // The config's amounts have the ledgers' decimal places, or none.
+        let config =
+            Config::parse("decimal_places = 3\n[budget]\n\"#a\" = \"1.250\"\n\"#b\" = \"1,200\"")
+                .unwrap();
+        assert_eq!(config.budget["#a"].subunits(), 1250);
+        assert_eq!(config.budget["#b"].subunits(), 1200000);
+        assert_eq!(config.budget["#a"].to_string(), "01.250");
+        assert!(Config::parse("decimal_places = 3\n[budget]\n\"#a\" = \"1.25\"").is_err());
+        assert!(Config::parse("decimal_places = 0\n[limits]\nentry = \"15.00\"").is_err());
+        let config = Config::parse("decimal = \",\"\n[limits]\nmonth = \"15.00\"").unwrap();
+        assert_eq!(config.limits.month.unwrap().to_string(), "15,00");
+
+        let config: Config = Config::parse("[limits]\nentry = \"5,000.00\"").unwrap();
+        assert_eq!(config.limits.entry, Some(Amount::from_subunits(500000)));
+        assert_eq!(config.limits.month, None);
+        assert!(Config::parse("[limits]\nentry = \"5.0.0\"").is_err());
+        assert!(Config::parse("[limits]\ntotal = \"5.00\"").is_err());
+
+        let config: Config = Config::parse("[aliases]\n\"#grocery\" = \"#food\"").unwrap();
+        assert_eq!(config.aliases["#grocery"], "#food");
+        assert!(Config::parse("[aliases]\n\"#a\" = \"b\"").is_err());
+        assert!(Config::parse("[aliases]\n\"#a\" = \"#b\"\n\"#b\" = \"#c\"").is_err());
+    }
+}
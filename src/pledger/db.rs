@@ -0,0 +1,227 @@
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+
+use crate::pledger::{ledger_dates, ledger_source, load_ledger, Error, Result, Settings};
+
+const SCHEMA: &str = r"
+PRAGMA foreign_keys = ON;
+
+CREATE TABLE IF NOT EXISTS ledgers (
+    id INTEGER PRIMARY KEY,
+    date TEXT UNIQUE NOT NULL,
+    mtime INTEGER NOT NULL,
+    hash TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS entries (
+    id INTEGER PRIMARY KEY,
+    ledger_id INTEGER NOT NULL REFERENCES ledgers(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    comment TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS tags (
+    id INTEGER PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS entry_tags (
+    entry_id INTEGER NOT NULL REFERENCES entries(id) ON DELETE CASCADE,
+    tag_id INTEGER NOT NULL REFERENCES tags(id),
+    PRIMARY KEY (entry_id, tag_id)
+);
+";
+
+/// What a sync did, ledger by ledger.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncStats {
+    pub updated: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+}
+
+fn hash(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Loads every ledger in `directory` into the SQLite database at `db`, creating it if
+/// necessary.
+///
+/// Only ledgers that have changed since the last sync (by mtime, then by content hash)
+/// are re-loaded, and ledgers that no longer exist are removed. Amounts are stored
+/// in subunits, e.g. `12.34` as `1234`.
+pub fn sync(directory: &Path, db: &Path, settings: &Settings) -> Result<SyncStats> {
+    let mut conn = Connection::open(db)
+        .map_err(|e| Error::Database(format!("couldn't open database: {}", e)))?;
+    conn.execute_batch(SCHEMA)?;
+
+    let tx = conn.transaction()?;
+    let mut stats = SyncStats::default();
+
+    let dates = ledger_dates(directory)?;
+    for date in dates.iter() {
+        // An archived month changes with its year's archive.
+        let ledger_file = ledger_source(directory, date);
+        let mtime = fs::metadata(&ledger_file)
+            .and_then(|m| m.modified())
+            .map_err(Error::io("ledger file stat failed"))?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as i64;
+
+        let existing: Option<(i64, i64, String)> = tx
+            .query_row(
+                "SELECT id, mtime, hash FROM ledgers WHERE date = ?1",
+                params![date],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+            )
+            .optional()?;
+
+        if let Some((_, old_mtime, _)) = &existing {
+            if *old_mtime == mtime {
+                stats.unchanged += 1;
+                continue;
+            }
+        }
+
+        let hash = hash(&fs::read(&ledger_file).map_err(Error::io("ledger file read failed"))?);
+        if let Some((id, _, old_hash)) = &existing {
+            if *old_hash == hash {
+                tx.execute(
+                    "UPDATE ledgers SET mtime = ?1 WHERE id = ?2",
+                    params![mtime, id],
+                )?;
+                stats.unchanged += 1;
+                continue;
+            }
+
+            tx.execute("DELETE FROM ledgers WHERE id = ?1", params![id])?;
+        }
+
+        let ledger = load_ledger(directory, date, &settings.parse)?;
+        log::debug!("syncing {} ({} entries)", date, ledger.entries.len());
+
+        tx.execute(
+            "INSERT INTO ledgers (date, mtime, hash) VALUES (?1, ?2, ?3)",
+            params![date, mtime, hash],
+        )?;
+        let ledger_id = tx.last_insert_rowid();
+
+        for (position, entry) in ledger.entries.iter().enumerate() {
+            let kind = entry.kind.name();
+
+            // SQLite integers are signed, so not every amount fits.
+            let amount = i64::try_from(entry.amount.subunits()).map_err(|_| {
+                Error::Amount(format!("amount out of range for database: {}", entry))
+            })?;
+
+            tx.execute(
+                "INSERT INTO entries (ledger_id, position, kind, amount, comment)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![ledger_id, position as i64, kind, amount, entry.comment],
+            )?;
+            let entry_id = tx.last_insert_rowid();
+
+            for tag in entry.tags.iter() {
+                tx.execute(
+                    "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
+                    params![tag],
+                )?;
+                tx.execute(
+                    "INSERT INTO entry_tags (entry_id, tag_id)
+                     SELECT ?1, id FROM tags WHERE name = ?2",
+                    params![entry_id, tag],
+                )?;
+            }
+        }
+
+        stats.updated += 1;
+    }
+
+    // Anything we didn't see on this pass no longer exists on disk.
+    let stale: Vec<String> = {
+        let mut stmt = tx.prepare("SELECT date FROM ledgers")?;
+        let known = stmt.query_map([], |r| r.get::<_, String>(0))?;
+        known
+            .collect::<rusqlite::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|d| !dates.contains(d))
+            .collect()
+    };
+    for date in stale.iter() {
+        tx.execute("DELETE FROM ledgers WHERE date = ?1", params![date])?;
+        stats.removed += 1;
+    }
+
+    tx.execute(
+        "DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM entry_tags)",
+        [],
+    )?;
+    tx.commit()?;
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        let db = directory.join("pledger.sqlite");
+
+        fs::write(
+            directory.join("2023-01.ledger"),
+            "C 1.00 #pay\nD 2.50 #food #fun\n",
+        )
+        .unwrap();
+        fs::write(directory.join("2023-02.ledger"), "D 1.00 #food\n").unwrap();
+
+        assert_eq!(
+            sync(directory, &db, &Settings::default()).unwrap(),
+            SyncStats {
+                updated: 2,
+                unchanged: 0,
+                removed: 0
+            }
+        );
+        assert_eq!(
+            sync(directory, &db, &Settings::default()).unwrap(),
+            SyncStats {
+                updated: 0,
+                unchanged: 2,
+                removed: 0
+            }
+        );
+
+        fs::remove_file(directory.join("2023-02.ledger")).unwrap();
+        assert_eq!(
+            sync(directory, &db, &Settings::default()).unwrap().removed,
+            1
+        );
+
+        let conn = Connection::open(&db).unwrap();
+        let food: i64 = conn
+            .query_row(
+                "SELECT SUM(amount) FROM entries
+                 JOIN entry_tags ON entry_tags.entry_id = entries.id
+                 JOIN tags ON tags.id = entry_tags.tag_id
+                 WHERE tags.name = '#food'",
+                [],
+                |r| r.get(0),
+            )
+            .unwrap();
+        assert_eq!(food, 250);
+    }
+}
@@ -0,0 +1,278 @@
+//! Debts, like a car loan, and their paydown.
+//!
+//! A debt starts at its principal, as of its `since` month (or the first ledger's). Each
+//! month after that accrues interest at its APR, and is paid down by the debits with the
+//! debt's tag. The payoff is projected from the configured monthly payment, or from the
+//! average of the payments so far. See [`crate::pledger::finance`] for the math.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::pledger::aggregate::tag_totals;
+use crate::pledger::finance::{interest, payoff};
+use crate::pledger::EntryKind::Debit;
+use crate::pledger::{
+    is_tag, offset_date, Amount, Error, Ledger, ParseOptions, Result, DATE_PATTERN,
+};
+
+/// A debt, as written in the config.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Debt {
+    pub name: String,
+    /// What was owed as of `since`, e.g. `15,000.00`.
+    pub principal: String,
+    /// The annual percentage rate, e.g. `6.5` for 6.5%.
+    pub apr: f64,
+    /// The tag of the debits that pay the debt down.
+    pub tag: String,
+    /// The month that the principal is as of, if not the first ledger's.
+    pub since: Option<String>,
+    /// The monthly payment to project the payoff with, if not the average so far.
+    pub payment: Option<String>,
+}
+
+impl Debt {
+    /// Checks the definition, returning its principal, tag, and payment.
+    fn validate(&self, options: &ParseOptions) -> Result<(Amount, String, Option<Amount>)> {
+        let amount = |field: &str, amount: &str| {
+            Amount::parse_with(amount, options.amount_format.with_point(), false)
+                .map(|amount| amount.with_format(options.amount_format))
+                .map_err(|e| {
+                    Error::Config(format!("debt {:?}: invalid {}: {}", self.name, field, e))
+                })
+        };
+
+        let principal = amount("principal", &self.principal)?;
+        let payment = match &self.payment {
+            Some(payment) => Some(amount("payment", payment)?),
+            None => None,
+        };
+
+        if !self.apr.is_finite() || self.apr < 0.0 {
+            return Err(Error::Config(format!(
+                "debt {:?}: invalid APR {}",
+                self.name, self.apr
+            )));
+        }
+        if !is_tag(&self.tag) {
+            return Err(Error::Config(format!(
+                "debt {:?}: invalid tag: {}",
+                self.name, self.tag
+            )));
+        }
+        if let Some(since) = &self.since {
+            if !DATE_PATTERN.is_match(since) {
+                return Err(Error::Config(format!(
+                    "debt {:?}: expected a month as YYYY-MM, got {}",
+                    self.name, since
+                )));
+            }
+        }
+
+        Ok((principal, options.canonical_tag(&self.tag), payment))
+    }
+}
+
+/// A debt's paydown, as of some month. Amounts are in subunits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Paydown {
+    pub name: String,
+    pub principal: Amount,
+    /// What's still owed.
+    pub balance: i128,
+    /// What's been paid.
+    pub paid: i128,
+    /// The interest that's accrued so far.
+    pub interest: i128,
+    /// The monthly payment that the payoff is projected with, if there is one.
+    pub payment: Option<i128>,
+    /// The month of the last payment, if the debt hasn't been paid off yet and the
+    /// payment covers its interest.
+    pub payoff: Option<String>,
+    /// The interest that'll accrue until the payoff, if there's a payoff.
+    pub interest_left: Option<i128>,
+}
+
+impl Paydown {
+    /// Whether the debt has been paid off.
+    pub fn is_paid(&self) -> bool {
+        self.balance == 0
+    }
+}
+
+/// Returns the debt's paydown as of the month of `date`, from `ledgers` (every month's,
+/// in order).
+pub fn paydown(
+    debt: &Debt,
+    ledgers: &[Ledger],
+    date: &str,
+    options: &ParseOptions,
+) -> Result<Paydown> {
+    let (principal, tag, payment) = debt.validate(options)?;
+
+    let ledgers: BTreeMap<_, _> = ledgers.iter().map(|l| (l.date.as_str(), l)).collect();
+    let mut month = match &debt.since {
+        Some(since) => since.clone(),
+        None => match ledgers.keys().next() {
+            Some(first) => first.to_string(),
+            None => date.to_string(),
+        },
+    };
+
+    let mut paydown = Paydown {
+        name: debt.name.clone(),
+        principal,
+        balance: principal.subunits() as i128,
+        paid: 0,
+        interest: 0,
+        payment: payment.map(|p| p.subunits() as i128),
+        payoff: None,
+        interest_left: None,
+    };
+
+    let mut months = 0;
+    while month.as_str() <= date && !paydown.is_paid() {
+        let charged = interest(paydown.balance, debt.apr);
+        paydown.interest += charged;
+        paydown.balance += charged;
+
+        if let Some(ledger) = ledgers.get(month.as_str()) {
+            let paid = tag_totals(ledger, &Debit)?
+                .points
+                .iter()
+                .find(|(t, _)| *t == tag)
+                .map(|(_, amount)| amount.subunits() as i128)
+                .unwrap_or(0);
+            // Overpayments don't count; the debt is just paid off.
+            let paid = paid.min(paydown.balance);
+            paydown.paid += paid;
+            paydown.balance -= paid;
+        }
+
+        months += 1;
+        month = offset_date(&month, 1)?;
+    }
+
+    if paydown.is_paid() {
+        return Ok(paydown);
+    }
+
+    if paydown.payment.is_none() && paydown.paid > 0 {
+        paydown.payment = Some(paydown.paid / months);
+    }
+    if let Some(projected) = paydown
+        .payment
+        .and_then(|payment| payoff(paydown.balance, debt.apr, payment))
+    {
+        let months = i32::try_from(projected.months)
+            .map_err(|_| Error::Amount(format!("debt {:?}: projection out of range", debt.name)))?;
+        paydown.payoff = offset_date(date, months).ok();
+        paydown.interest_left = Some(projected.interest);
+    }
+
+    Ok(paydown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::{parse_ledger_reader, ParseOptions};
+
+    fn debt(apr: f64, since: Option<&str>, payment: Option<&str>) -> Debt {
+        Debt {
+            name: "car".into(),
+            principal: "1,000.00".into(),
+            apr,
+            tag: "#car".into(),
+            since: since.map(Into::into),
+            payment: payment.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn test_paydown() {
+        let ledgers = [
+            parse_ledger_reader(
+                "2023-01",
+                "D 100.00 car payment #car\n".as_bytes(),
+                &ParseOptions::default(),
+            )
+            .unwrap(),
+            parse_ledger_reader(
+                "2023-03",
+                "D 200.00 car payment #car\nD 50.00 gas #gas\n".as_bytes(),
+                &ParseOptions::default(),
+            )
+            .unwrap(),
+        ];
+
+        // Without interest, 300.00 over three months leaves 700.00, which takes seven
+        // more of the average 100.00 payments.
+        let paydown = paydown(
+            &debt(0.0, None, None),
+            &ledgers,
+            "2023-03",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(paydown.balance, 70000);
+        assert_eq!(paydown.paid, 30000);
+        assert_eq!(paydown.interest, 0);
+        assert_eq!(paydown.payment, Some(10000));
+        assert_eq!(paydown.payoff.as_deref(), Some("2023-10"));
+        assert_eq!(paydown.interest_left, Some(0));
+
+        // At 12%: 10.00 of interest in January, 9.10 in February, and 9.19 in March.
+        let paydown = super::paydown(
+            &debt(12.0, None, Some("350.00")),
+            &ledgers,
+            "2023-03",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(paydown.interest, 2829);
+        assert_eq!(paydown.balance, 72829);
+        assert_eq!(paydown.payoff.as_deref(), Some("2023-06"));
+
+        // Payments before `since` don't count, and a payment that doesn't cover the
+        // interest never pays the debt off.
+        let paydown = super::paydown(
+            &debt(12.0, Some("2023-02"), Some("5.00")),
+            &ledgers,
+            "2023-03",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(paydown.paid, 20000);
+        assert_eq!(paydown.payoff, None);
+
+        let paid = Debt {
+            principal: "250.00".into(),
+            ..debt(0.0, None, None)
+        };
+        let paydown = super::paydown(&paid, &ledgers, "2023-03", &ParseOptions::default()).unwrap();
+        assert!(paydown.is_paid());
+        assert_eq!(paydown.paid, 25000);
+
+        for invalid in [
+            Debt {
+                principal: "abc".into(),
+                ..debt(0.0, None, None)
+            },
+            Debt {
+                tag: "car".into(),
+                ..debt(0.0, None, None)
+            },
+            debt(-1.0, None, None),
+            debt(f64::NAN, None, None),
+            debt(0.0, Some("2023"), None),
+            debt(0.0, None, Some("5.0.0")),
+        ] {
+            assert!(
+                super::paydown(&invalid, &ledgers, "2023-03", &ParseOptions::default()).is_err()
+            );
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::Serialize;
+
+use crate::pledger::Error;
+
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns how many warnings have been emitted so far, e.g. for `--fail-on-warnings`.
+pub fn warnings() -> usize {
+    WARNINGS.load(Ordering::Relaxed)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem with a ledger, in a form that's easy for other tools (e.g. editor plugins)
+/// to consume. Lines and columns are 1-based.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub file: Option<PathBuf>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+    pub severity: Severity,
+}
+
+impl Diagnostic {
+    pub fn warning(file: Option<PathBuf>, message: impl Into<String>) -> Self {
+        Self {
+            file,
+            line: None,
+            column: None,
+            message: message.into(),
+            severity: Severity::Warning,
+        }
+    }
+
+    /// Returns a diagnostic for a fatal error, with as much location information
+    /// as the error carries.
+    pub fn error(err: &(dyn std::error::Error + 'static)) -> Self {
+        match err.downcast_ref::<Error>() {
+            Some(err) => Self::located(err, Severity::Error),
+            None => Self {
+                file: None,
+                line: None,
+                column: None,
+                message: err.to_string(),
+                severity: Severity::Error,
+            },
+        }
+    }
+
+    /// Returns a warning for an error that was recovered from, e.g. a line that was
+    /// skipped when parsing leniently.
+    pub fn recovered(err: &Error) -> Self {
+        Self::located(err, Severity::Warning)
+    }
+
+    fn located(err: &Error, severity: Severity) -> Self {
+        match err {
+            Error::Parse {
+                file,
+                line,
+                offset,
+                message,
+            } => Self {
+                file: file.clone(),
+                line: *line,
+                column: offset.map(|o| o + 1),
+                message: message.clone(),
+                severity,
+            },
+            _ => Self {
+                file: None,
+                line: None,
+                column: None,
+                message: err.to_string(),
+                severity,
+            },
+        }
+    }
+
+    /// Prints the diagnostic on stderr: as a line of JSON if `json` is set, or
+    /// for humans otherwise.
+    pub fn emit(&self, json: bool) {
+        if self.severity == Severity::Warning {
+            WARNINGS.fetch_add(1, Ordering::Relaxed);
+        }
+        if json {
+            eprintln!("{}", serde_json::to_string(self).unwrap());
+        } else {
+            eprintln!("{}", self);
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: ")?,
+            Severity::Warning => write!(f, "warning: ")?,
+        }
+
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+            if let Some(line) = self.line {
+                write!(f, "{}:", line)?;
+                if let Some(column) = self.column {
+                    write!(f, "{}:", column)?;
+                }
+            }
+            write!(f, " ")?;
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_diagnostic() {
+        let err = Error::Parse {
+            file: None,
+            line: Some(2),
+            offset: Some(5),
+            message: "expected digit or whitespace, got x".into(),
+        }
+        .with_file(Path::new("2023-05.ledger"));
+
+        let diagnostic = Diagnostic::error(&err);
+        assert_eq!(
+            serde_json::to_string(&diagnostic).unwrap(),
+            r#"{"file":"2023-05.ledger","line":2,"column":6,"message":"expected digit or whitespace, got x","severity":"error"}"#
+        );
+        assert_eq!(
+            diagnostic.to_string(),
+            "error: 2023-05.ledger:2:6: expected digit or whitespace, got x"
+        );
+
+        let diagnostic = Diagnostic::error(&std::io::Error::other("something else"));
+        assert_eq!(diagnostic.file, None);
+        assert_eq!(diagnostic.message, "something else");
+
+        let diagnostic = Diagnostic::warning(Some("2023-05.ledger".into()), "looks odd");
+        assert_eq!(diagnostic.to_string(), "warning: 2023-05.ledger: looks odd");
+    }
+}
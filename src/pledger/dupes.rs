@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::pledger::diagnostics::Diagnostic;
+use crate::pledger::{Amount, Entry, EntryKind, Ledger};
+
+/// A group of entries in a single ledger that look like the same transaction, entered
+/// more than once.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Duplicate {
+    pub date: String,
+    pub entry: Entry,
+    pub count: usize,
+}
+
+impl Duplicate {
+    /// Returns a warning about the duplicate, for the ledger in `directory` (if it's
+    /// from one).
+    pub fn diagnostic(&self, directory: Option<&Path>) -> Diagnostic {
+        Diagnostic::warning(
+            directory.map(|d| d.join(format!("{}.ledger", self.date))),
+            format!(
+                "possible duplicate entry ({} times): {}",
+                self.count, self.entry
+            ),
+        )
+    }
+}
+
+/// Normalizes a comment for comparison: case and whitespace differences don't matter.
+pub fn normalize(comment: &str) -> String {
+    comment
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Returns the suspected duplicates in each ledger: entries with the same kind,
+/// amount, and (normalized) comment as another in the same ledger.
+pub fn find_duplicates(ledgers: &[Ledger]) -> Vec<Duplicate> {
+    let mut duplicates = vec![];
+
+    for ledger in ledgers.iter() {
+        let mut groups: HashMap<(&EntryKind, Amount, String), (usize, &Entry)> = HashMap::new();
+        let mut order = vec![];
+
+        for entry in ledger.entries.iter() {
+            let key = (&entry.kind, entry.amount, normalize(&entry.comment));
+            groups
+                .entry(key.clone())
+                .or_insert_with(|| {
+                    order.push(key);
+                    (0, entry)
+                })
+                .0 += 1;
+        }
+
+        for key in order.iter() {
+            let (count, entry) = groups[key];
+            if count > 1 {
+                duplicates.push(Duplicate {
+                    date: ledger.date.clone(),
+                    entry: entry.clone(),
+                    count,
+                });
+            }
+        }
+    }
+
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+    use crate::pledger::parse_ledger;
+    use crate::pledger::ParseOptions;
+
+    #[test]
+    fn test_find_duplicates() {
+        let ledgers = vec![
+            parse_ledger(
+                "2023-01",
+                Box::new(
+                    "D 3.00 Coffee  #fun\nD 3.00 coffee #fun\nC 3.00 coffee #fun\nD 3.00 coffee #fun\nD 4.00 coffee #fun"
+                        .as_bytes()
+                        .lines(),
+                ), &ParseOptions::default(),
+            )
+            .unwrap(),
+            parse_ledger("2023-02", Box::new("D 3.00 coffee #fun".as_bytes().lines()), &ParseOptions::default()).unwrap(),
+        ];
+
+        let duplicates = find_duplicates(&ledgers);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].date, "2023-01");
+        assert_eq!(duplicates[0].count, 3);
+        assert_eq!(duplicates[0].entry.to_string(), "D 3.00 Coffee  #fun");
+    }
+}
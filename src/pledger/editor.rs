@@ -0,0 +1,144 @@
+use std::env;
+
+use crate::pledger::{Error, Result};
+
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+#[cfg(not(windows))]
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Splits a command line into words, shell-style: whitespace separates words,
+/// single quotes preserve everything literally, and double quotes preserve everything
+/// except backslash escapes.
+pub fn split_words(command: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word = String::new();
+    // Tracked separately from `word` so that quoted empty strings (`''`) survive.
+    let mut in_word = false;
+
+    let mut chars = command.chars();
+    while let Some(chr) = chars.next() {
+        match chr {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => {
+                            return Err(Error::Editor(format!(
+                                "unterminated single quote in: {}",
+                                command
+                            )))
+                        }
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => break,
+                        },
+                        Some(c) => word.push(c),
+                        None => {
+                            return Err(Error::Editor(format!(
+                                "unterminated double quote in: {}",
+                                command
+                            )))
+                        }
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+/// Finds the editor to use, as a program followed by its arguments.
+///
+/// The `editor` override takes precedence, followed by `$EDITOR`, `$VISUAL`, and
+/// finally a platform default.
+pub fn find_editor(editor: Option<&str>) -> Result<Vec<String>> {
+    let command = match editor {
+        Some(editor) => editor.to_string(),
+        None => ["EDITOR", "VISUAL"]
+            .iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|e| !e.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_EDITOR.into()),
+    };
+
+    let words = split_words(&command)?;
+    if words.is_empty() {
+        return Err(Error::Editor("empty editor command".into()));
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(split_words("vim").unwrap(), vec!["vim"]);
+        assert_eq!(
+            split_words("  code --wait ").unwrap(),
+            vec!["code", "--wait"]
+        );
+        assert_eq!(
+            split_words(r#"'/Applications/Sublime Text' -w"#).unwrap(),
+            vec!["/Applications/Sublime Text", "-w"]
+        );
+        assert_eq!(
+            split_words(r#"emacs "-eval" "(setq x \"y\")""#).unwrap(),
+            vec!["emacs", "-eval", r#"(setq x "y")"#]
+        );
+        assert_eq!(
+            split_words(r"my\ editor ''").unwrap(),
+            vec!["my editor", ""]
+        );
+        assert!(split_words("'vim").is_err());
+        assert!(split_words("\"vim").is_err());
+        assert!(split_words("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_editor() {
+        assert_eq!(
+            find_editor(Some("code --wait")).unwrap(),
+            vec!["code", "--wait"]
+        );
+        assert!(find_editor(Some(" ")).is_err());
+    }
+}
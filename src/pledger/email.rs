@@ -0,0 +1,104 @@
+//! Emailing reports, for `pledger report --email`.
+//!
+//! Messages are piped to a sendmail-compatible command (`sendmail -t` by default, or the
+//! config's `[email]` command), which reads the recipients from the message's headers.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::pledger::{Error, Result};
+
+/// The command that messages are piped to when the config doesn't give one.
+pub const DEFAULT_COMMAND: &str = "sendmail -t";
+
+/// Returns whether an email header's value is safe to use, i.e. can't add headers of
+/// its own.
+fn header_safe(value: &str) -> bool {
+    !value.contains(['\r', '\n'])
+}
+
+/// Returns an email message with `body`, as HTML if `html` is set, and as plain text
+/// otherwise.
+pub fn message(
+    to: &str,
+    from: Option<&str>,
+    subject: &str,
+    body: &str,
+    html: bool,
+) -> Result<String> {
+    if let Some(header) = [Some(to), from, Some(subject)]
+        .into_iter()
+        .flatten()
+        .find(|h| !header_safe(h))
+    {
+        return Err(Error::Config(format!("invalid email header: {:?}", header)));
+    }
+
+    let mut message = format!("To: {}\n", to);
+    if let Some(from) = from {
+        message.push_str(&format!("From: {}\n", from));
+    }
+    message.push_str(&format!(
+        "Subject: {}\nMIME-Version: 1.0\nContent-Type: text/{}; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\n",
+        subject,
+        if html { "html" } else { "plain" }
+    ));
+    message.push_str(body);
+    if !body.ends_with('\n') {
+        message.push('\n');
+    }
+    Ok(message)
+}
+
+/// Pipes a message to the mail command, through the shell.
+pub fn send(command: &str, message: &str) -> Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Notify(format!("couldn't run the mail command: {}", e)))?;
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(message.as_bytes())
+        .map_err(|e| Error::Notify(format!("couldn't write to the mail command: {}", e)))?;
+    let status = child.wait()?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(Error::Notify(format!(
+            "the mail command failed: {}",
+            status
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        assert_eq!(
+            message(
+                "me@example.com",
+                Some("pledger@example.com"),
+                "pledger: 2023",
+                "<p>hi</p>",
+                true
+            )
+            .unwrap(),
+            "To: me@example.com\nFrom: pledger@example.com\nSubject: pledger: 2023\n\
+             MIME-Version: 1.0\nContent-Type: text/html; charset=utf-8\n\
+             Content-Transfer-Encoding: 8bit\n\n<p>hi</p>\n"
+        );
+        assert!(message("me@example.com\nBcc: x@example.com", None, "s", "b", false).is_err());
+
+        let text = message("me@example.com", None, "s", "b\n", false).unwrap();
+        assert!(text.starts_with("To: me@example.com\nSubject: s\n"));
+        send("cat > /dev/null", &text).unwrap();
+        assert!(send("exit 1", &text).is_err());
+    }
+}
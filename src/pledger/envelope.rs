@@ -0,0 +1,105 @@
+//! Envelope budgeting: money is set aside in envelopes with allocations (`A 200.00 #food`),
+//! and debits with an envelope's tag draw it down. Unlike monthly budgets, envelopes
+//! accumulate over every ledger, so whatever's left in one stays there until it's spent.
+
+use std::collections::BTreeMap;
+
+use crate::pledger::EntryKind::*;
+use crate::pledger::{Amount, Ledger};
+
+/// An envelope: what's been allocated to it, and what's been spent from it, in subunits.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Envelope {
+    pub allocated: i128,
+    pub spent: i128,
+}
+
+impl Envelope {
+    /// What's left in the envelope: negative if more was spent than was allocated.
+    pub fn balance(&self) -> i128 {
+        self.allocated - self.spent
+    }
+}
+
+/// Every envelope, by tag, and how much of the ledgers' income hasn't been allocated.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Envelopes {
+    pub envelopes: BTreeMap<String, Envelope>,
+    /// The ledgers' credits, less their allocations.
+    pub unallocated: i128,
+}
+
+/// Computes each envelope's balance over the ledgers. Only tags that have had something
+/// allocated to them are envelopes; debits with other tags don't draw on any.
+pub fn envelopes(ledgers: &[Ledger]) -> Envelopes {
+    let mut envelopes = Envelopes::default();
+
+    // i128 can't overflow on any realistic number of u64 amounts.
+    let subunits = |amount: Amount| amount.subunits() as i128;
+    let entries = || ledgers.iter().flat_map(|l| l.entries.iter());
+    for entry in entries() {
+        match entry.kind {
+            Credit => envelopes.unallocated += subunits(entry.amount),
+            Allocation => {
+                envelopes.unallocated -= subunits(entry.amount);
+                for tag in entry.tags.iter() {
+                    envelopes
+                        .envelopes
+                        .entry(tag.clone())
+                        .or_default()
+                        .allocated += subunits(entry.amount);
+                }
+            }
+            Debit | Transfer => {}
+        }
+    }
+
+    for entry in entries().filter(|e| e.kind == Debit) {
+        for tag in entry.tags.iter() {
+            if let Some(envelope) = envelopes.envelopes.get_mut(tag) {
+                envelope.spent += subunits(entry.amount);
+            }
+        }
+    }
+
+    envelopes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::{parse_ledger_reader, ParseOptions};
+
+    #[test]
+    fn test_envelopes() {
+        let ledgers = [
+            parse_ledger_reader(
+                "2023-05",
+                "C 1000.00 pay\nA 300.00 #food\nA 50.00 #fun\nD 120.00 #food\n".as_bytes(),
+                &ParseOptions::default(),
+            )
+            .unwrap(),
+            parse_ledger_reader(
+                "2023-06",
+                "D 200.00 #food #dinner\nD 10.00 #fun\nD 5.00 #other\n".as_bytes(),
+                &ParseOptions::default(),
+            )
+            .unwrap(),
+        ];
+
+        let envelopes = envelopes(&ledgers);
+        assert_eq!(envelopes.unallocated, 65000);
+        assert_eq!(
+            envelopes.envelopes.keys().collect::<Vec<_>>(),
+            ["#food", "#fun"]
+        );
+        assert_eq!(envelopes.envelopes["#food"].balance(), -2000);
+        assert_eq!(envelopes.envelopes["#fun"].balance(), 4000);
+
+        assert!("A 10.00 no envelope"
+            .parse::<crate::pledger::Entry>()
+            .is_err());
+        assert!("A 10.00 #a #b".parse::<crate::pledger::Entry>().is_err());
+        assert!("A 10.00 #a @bank".parse::<crate::pledger::Entry>().is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The errors that pledger's ledger handling can produce.
+#[derive(Debug)]
+pub enum Error {
+    /// A ledger, or a single entry, couldn't be parsed. The file and line are known
+    /// when the entry came from a ledger file, and the offset (in characters) when the
+    /// entry's syntax (rather than its reading) was the problem.
+    Parse {
+        file: Option<PathBuf>,
+        line: Option<usize>,
+        offset: Option<usize>,
+        message: String,
+    },
+    /// An I/O operation failed.
+    Io { context: String, source: io::Error },
+    /// A date couldn't be parsed, or was out of range.
+    Date(String),
+    /// An amount couldn't be parsed, or was out of range.
+    Amount(String),
+    /// The editor couldn't be found or run, or exited unsuccessfully.
+    Editor(String),
+    /// A ledger directory, ledger, backup, or profile doesn't exist.
+    NotFound(String),
+    /// A ledger is archived or compressed, so it can't be changed.
+    Archived(String),
+    /// The config file, or something in it (a rule, budget, goal, profile, ...), is
+    /// invalid.
+    Config(String),
+    /// A query couldn't be parsed.
+    Query(String),
+    /// A statement, or a ledger in another format, couldn't be imported.
+    Import(String),
+    /// A remote ledger directory couldn't be pulled or pushed.
+    Remote(String),
+    /// The HTTP API couldn't listen, or a request to it was malformed or too large.
+    Serve(String),
+    /// A language server message was malformed.
+    Lsp(String),
+    /// An external program (git, a signing tool, the mail command, ...) couldn't be run,
+    /// or exited unsuccessfully.
+    Command(String),
+    /// A notification couldn't be sent by the mail or notify command, or to a webhook.
+    Notify(String),
+    /// Output couldn't be rendered, e.g. in an unsupported format or as a chart.
+    Output(String),
+    /// The SQLite database couldn't be written.
+    Database(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Returns a parse error for an entry's syntax, at the given offset in its line.
+    pub(crate) fn syntax(offset: Option<usize>, message: impl Into<String>) -> Self {
+        Error::Parse {
+            file: None,
+            line: None,
+            offset,
+            message: message.into(),
+        }
+    }
+
+    /// Returns a function that wraps an `io::Error` with the given context, for use with
+    /// `map_err`.
+    pub(crate) fn io(context: impl Into<String>) -> impl FnOnce(io::Error) -> Self {
+        let context = context.into();
+        move |source| Error::Io { context, source }
+    }
+
+    /// Attaches the ledger file a parse error came from. Other errors are returned as-is.
+    pub fn with_file(self, path: &Path) -> Self {
+        match self {
+            Error::Parse {
+                line,
+                offset,
+                message,
+                ..
+            } => Error::Parse {
+                file: Some(path.into()),
+                line,
+                offset,
+                message,
+            },
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse {
+                file,
+                line,
+                offset,
+                message,
+            } => {
+                write!(f, "parse error")?;
+                if let Some(file) = file {
+                    write!(f, " in {}", file.display())?;
+                }
+                if let Some(line) = line {
+                    write!(f, " on line {}", line)?;
+                }
+                if let Some(offset) = offset {
+                    write!(f, ": offset {}", offset)?;
+                }
+                write!(f, ": {}", message)
+            }
+            Error::Io { context, source } => write!(f, "{}: {}", context, source),
+            Error::Date(message)
+            | Error::Amount(message)
+            | Error::Editor(message)
+            | Error::NotFound(message)
+            | Error::Archived(message)
+            | Error::Config(message)
+            | Error::Query(message)
+            | Error::Import(message)
+            | Error::Remote(message)
+            | Error::Serve(message)
+            | Error::Lsp(message)
+            | Error::Command(message)
+            | Error::Notify(message)
+            | Error::Output(message)
+            | Error::Database(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io {
+            context: "I/O error".into(),
+            source,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Database(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::pledger::config::{Config, CONFIG_FILE};
+    use crate::pledger::query::Query;
+    use crate::pledger::{
+        append_entry, archive, ledger_dates, load_ledger, parse_date, Entry, ParseOptions, Settings,
+    };
+
+    #[test]
+    fn test_error_display() {
+        let err = Error::syntax(Some(3), "expected digit, got a");
+        assert_eq!(
+            err.to_string(),
+            "parse error: offset 3: expected digit, got a"
+        );
+
+        let err = match err {
+            Error::Parse {
+                offset, message, ..
+            } => Error::Parse {
+                file: None,
+                line: Some(7),
+                offset,
+                message,
+            },
+            _ => unreachable!(),
+        }
+        .with_file(Path::new("2023-05.ledger"));
+        assert_eq!(
+            err.to_string(),
+            "parse error in 2023-05.ledger on line 7: offset 3: expected digit, got a"
+        );
+
+        let err = Error::io("ledger file read failed")(io::Error::other("oops"));
+        assert_eq!(err.to_string(), "ledger file read failed: oops");
+        assert!(std::error::Error::source(&err).is_some());
+    }
+
+    #[test]
+    fn test_error_variants() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        let options = ParseOptions::default();
+        fs::write(directory.join("2022-12.ledger"), "D 1.00 a\n").unwrap();
+        fs::write(directory.join("2023-01.ledger"), "D 1.00 a\nD 1.0 b\n").unwrap();
+        fs::write(directory.join("2023-02.ledger.gz"), "not gzip").unwrap();
+
+        // A ledger that's read but can't be parsed is a parse error, which says where...
+        match load_ledger(directory, "2023-01", &options) {
+            Err(Error::Parse {
+                file, line, offset, ..
+            }) => {
+                assert_eq!(file, Some(directory.join("2023-01.ledger")));
+                assert_eq!((line, offset), (Some(2), Some(5)));
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+        match "D 1.0 b".parse::<Entry>() {
+            Err(Error::Parse {
+                file, line, offset, ..
+            }) => assert_eq!((file, line, offset), (None, None, Some(5))),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+
+        // ...while one that can't be read at all is an I/O error, with the cause as its
+        // source, or missing.
+        let err = load_ledger(directory, "2023-02", &options).unwrap_err();
+        assert!(matches!(err, Error::Io { .. }));
+        assert!(std::error::Error::source(&err).is_some());
+        assert!(matches!(
+            load_ledger(directory, "2023-03", &options),
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            ledger_dates(&directory.join("nope")),
+            Err(Error::Io { .. })
+        ));
+
+        // Archived months can be read, but not changed.
+        assert_eq!(
+            archive::archive(directory, "2023", &Settings::default()).unwrap(),
+            ["2022-12"]
+        );
+        assert!(load_ledger(directory, "2022-12", &options).is_ok());
+        assert!(matches!(
+            append_entry(
+                directory,
+                "2022-12",
+                &Settings::default(),
+                &"D 1.00 c".parse().unwrap()
+            ),
+            Err(Error::Archived(_))
+        ));
+
+        assert!(matches!(parse_date("2023-13"), Err(Error::Date(_))));
+        assert!(matches!(
+            Query::parse("amount >", &options),
+            Err(Error::Query(_))
+        ));
+        fs::write(directory.join(CONFIG_FILE), "[budgets\n").unwrap();
+        assert!(matches!(Config::load(directory), Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,292 @@
+//! Exports of ledgers to other (double-entry) accounting tools.
+//!
+//! pledger's entries are single-entry, so each one becomes a transaction with two legs:
+//! one for its account (an asset) and one for the category named by its first tag
+//! (an expense or an income). Transfers move money between their two accounts.
+//!
+//! The config's `[accounts]` table can map tags and `@account` markers to other
+//! account names, e.g. `"#food" = "Expenses:Groceries"`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::NaiveDate;
+
+use crate::pledger::EntryKind::*;
+use crate::pledger::{Entry, Error, Ledger, Result};
+
+/// The currency that exported amounts are in by default, since ledgers don't record one.
+pub const CURRENCY: &str = "USD";
+
+/// The asset account for entries without an `@account` marker.
+const DEFAULT_ACCOUNT: &str = "Cash";
+
+/// The category for entries without any tags.
+const UNCATEGORIZED: &str = "Uncategorized";
+
+/// Turns a tag or account name into a valid account name component: capitalized, with
+/// anything but letters, digits, and dashes replaced by dashes.
+fn component(name: &str) -> String {
+    let mut component: String = name
+        .trim_start_matches('#')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+
+    match component.chars().next() {
+        Some(c) if c.is_ascii_alphanumeric() => {
+            component[..1].make_ascii_uppercase();
+            component
+        }
+        _ => {
+            component.insert(0, 'X');
+            component
+        }
+    }
+}
+
+/// Returns the entry's full date, from its ledger's month and its `@DD` marker (or the
+/// first of the month, without one).
+fn entry_date(ledger: &Ledger, entry: &Entry) -> Result<NaiveDate> {
+    let date = format!("{}-{:02}", ledger.date, entry.day.unwrap_or(1));
+    NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|_| {
+        Error::Date(format!(
+            "invalid date for entry in {}: {}",
+            ledger.date, entry
+        ))
+    })
+}
+
+/// Returns the entry's legs: the account that's debited (i.e. that the money goes to),
+/// and the account that's credited. Allocations don't have any, since they don't move
+/// money.
+fn legs(entry: &Entry, accounts: &BTreeMap<String, String>) -> Option<(String, String)> {
+    let asset = |account: &Option<String>| {
+        let account = account.as_deref().unwrap_or(DEFAULT_ACCOUNT);
+        accounts
+            .get(&format!("@{}", account))
+            .cloned()
+            .unwrap_or_else(|| format!("Assets:{}", component(account)))
+    };
+    let category = |parent: &str| match entry.tags.first() {
+        Some(tag) => accounts
+            .get(tag)
+            .cloned()
+            .unwrap_or_else(|| format!("{}:{}", parent, component(tag))),
+        None => format!("{}:{}", parent, UNCATEGORIZED),
+    };
+
+    match entry.kind {
+        Debit => Some((category("Expenses"), asset(&entry.account))),
+        Credit => Some((asset(&entry.account), category("Income"))),
+        Transfer => Some((asset(&entry.to), asset(&entry.account))),
+        Allocation => None,
+    }
+}
+
+/// Returns the entry's comment without its tags and markers.
+fn narration(entry: &Entry) -> String {
+    entry
+        .comment
+        .split_whitespace()
+        .skip(entry.cleared as usize)
+        .filter(|w| !w.starts_with('#') && !w.starts_with('@'))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Renders the ledgers, which should be in order, as a beancount file: an `open`
+/// directive for each account on the date it's first used, then each entry as a
+/// transaction. Cleared entries are flagged `*`, and the rest `!`.
+pub fn beancount(
+    ledgers: &[Ledger],
+    accounts: &BTreeMap<String, String>,
+    currency: &str,
+) -> Result<String> {
+    let mut opened = BTreeMap::new();
+    let mut transactions = String::new();
+
+    for ledger in ledgers.iter() {
+        for entry in ledger.entries.iter() {
+            let (debit, credit) = match legs(entry, accounts) {
+                Some(legs) => legs,
+                None => continue,
+            };
+            let date = entry_date(ledger, entry)?;
+
+            let narration = narration(entry).replace('\\', "\\\\").replace('"', "\\\"");
+            write!(
+                transactions,
+                "\n{} {} \"{}\"",
+                date,
+                if entry.cleared { '*' } else { '!' },
+                narration
+            )
+            .unwrap();
+            for tag in entry.tags.iter() {
+                // Beancount tags are more restrictive than ours.
+                let tag: String = tag
+                    .trim_start_matches('#')
+                    .chars()
+                    .map(|c| match c {
+                        c if c.is_ascii_alphanumeric() || "-_/.".contains(c) => c,
+                        _ => '-',
+                    })
+                    .collect();
+                write!(transactions, " #{}", tag).unwrap();
+            }
+            transactions.push('\n');
+
+            let amount = entry.amount.to_point_string();
+            for (account, amount) in [(debit, amount.clone()), (credit, format!("-{}", amount))] {
+                writeln!(
+                    transactions,
+                    "  {:<32} {:>12} {}",
+                    account,
+                    amount,
+                    entry.currency.as_deref().unwrap_or(currency)
+                )
+                .unwrap();
+                opened.entry(account).or_insert(date);
+            }
+        }
+    }
+
+    let mut opened: Vec<_> = opened.into_iter().collect();
+    opened.sort_by(|(a1, d1), (a2, d2)| d1.cmp(d2).then(a1.cmp(a2)));
+
+    let mut out = String::new();
+    writeln!(out, "option \"operating_currency\" \"{}\"\n", currency).unwrap();
+    for (account, date) in opened {
+        writeln!(out, "{} open {}", date, account).unwrap();
+    }
+    out.push_str(&transactions);
+
+    Ok(out)
+}
+
+/// Quotes a CSV field, if it needs it.
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.into(),
+    }
+}
+
+/// Renders the ledgers as a double-entry CSV, with a row for each entry's date,
+/// description, debit and credit accounts, and amount. GnuCash's transaction importer
+/// accepts this with its "Transfer Account" column set to the credit account.
+///
+/// The last column is where each entry came from, as `file:line` (or `date:line`, for
+/// ledgers that weren't read from a file), which importers can ignore.
+pub fn double_entry_csv(ledgers: &[Ledger], accounts: &BTreeMap<String, String>) -> Result<String> {
+    let mut out = String::from("Date,Description,Debit Account,Credit Account,Amount,Source\n");
+
+    for ledger in ledgers.iter() {
+        for entry in ledger.entries.iter() {
+            let (debit, credit) = match legs(entry, accounts) {
+                Some(legs) => legs,
+                None => continue,
+            };
+            writeln!(
+                out,
+                "{},{},{},{},{},{}",
+                entry_date(ledger, entry)?,
+                csv_field(&narration(entry)),
+                csv_field(&debit),
+                csv_field(&credit),
+                entry.amount.to_point_string(),
+                csv_field(&entry.source().map(ToString::to_string).unwrap_or_default())
+            )
+            .unwrap();
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+    use crate::pledger::parse_ledger;
+    use crate::pledger::ParseOptions;
+
+    fn ledger(date: &str, contents: &'static str) -> Ledger {
+        parse_ledger(
+            date,
+            Box::new(contents.as_bytes().lines()),
+            &ParseOptions::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_beancount() {
+        assert_eq!(component("#eating-out"), "Eating-out");
+        assert_eq!(component("my_bank"), "My-bank");
+        assert_eq!(component("_x"), "X-x");
+
+        let ledgers = vec![
+            ledger(
+                "2023-01",
+                "C 100.00 @05 pay @bank #job\nD 12.50 * @07 \"lunch\" #food #work",
+            ),
+            ledger("2023-02", "T 20.00 @bank @cash atm\nD 1.00 gum"),
+        ];
+        assert_eq!(
+            beancount(&ledgers, &BTreeMap::new(), CURRENCY).unwrap(),
+            r#"option "operating_currency" "USD"
+
+2023-01-05 open Assets:Bank
+2023-01-05 open Income:Job
+2023-01-07 open Assets:Cash
+2023-01-07 open Expenses:Food
+2023-02-01 open Expenses:Uncategorized
+
+2023-01-05 ! "pay" #job
+  Assets:Bank                            100.00 USD
+  Income:Job                            -100.00 USD
+
+2023-01-07 * "\"lunch\"" #food #work
+  Expenses:Food                           12.50 USD
+  Assets:Cash                            -12.50 USD
+
+2023-02-01 ! "atm"
+  Assets:Cash                             20.00 USD
+  Assets:Bank                            -20.00 USD
+
+2023-02-01 ! "gum"
+  Expenses:Uncategorized                  01.00 USD
+  Assets:Cash                            -01.00 USD
+"#
+        );
+
+        assert!(beancount(
+            &[ledger("2023-02", "D 1.00 @30")],
+            &BTreeMap::new(),
+            CURRENCY
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_double_entry_csv() {
+        let accounts = BTreeMap::from([
+            ("#food".into(), "Expenses:Groceries".into()),
+            ("@bank".into(), "Assets:Checking".into()),
+        ]);
+        let ledgers = vec![ledger(
+            "2023-01",
+            "D 12.50 @03 milk, eggs @bank #food\nC 5.00 \"refund\" #misc\nT 1.00 @bank @cash",
+        )];
+        assert_eq!(
+            double_entry_csv(&ledgers, &accounts).unwrap(),
+            "Date,Description,Debit Account,Credit Account,Amount,Source\n\
+             2023-01-03,\"milk, eggs\",Expenses:Groceries,Assets:Checking,12.50,2023-01:1\n\
+             2023-01-01,\"\"\"refund\"\"\",Assets:Cash,Income:Misc,05.00,2023-01:2\n\
+             2023-01-01,,Assets:Cash,Assets:Checking,01.00,2023-01:3\n"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! A little financial math, for amortizing debts. Balances are in subunits, and
+//! interest compounds monthly at a twelfth of the annual percentage rate.
+
+/// The longest that a payoff is projected for, in months: a hundred years.
+pub const MAX_MONTHS: u32 = 1200;
+
+/// Returns the monthly rate for an APR given as a percentage, e.g. `0.005` for `6.0`.
+pub fn monthly_rate(apr: f64) -> f64 {
+    apr / 100.0 / 12.0
+}
+
+/// Returns a month's interest on `balance`, rounded to the nearest subunit.
+pub fn interest(balance: i128, apr: f64) -> i128 {
+    (balance as f64 * monthly_rate(apr)).round() as i128
+}
+
+/// A projected payoff: how many more monthly payments it takes, and the interest that
+/// accrues until then.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Payoff {
+    pub months: u32,
+    pub interest: i128,
+}
+
+/// Projects paying off `balance` with a `payment` each month, after that month's
+/// interest. Returns `None` if the payment doesn't cover the interest, or if it'd take
+/// more than [`MAX_MONTHS`].
+pub fn payoff(balance: i128, apr: f64, payment: i128) -> Option<Payoff> {
+    let mut payoff = Payoff {
+        months: 0,
+        interest: 0,
+    };
+
+    let mut balance = balance;
+    while balance > 0 {
+        let charged = interest(balance, apr);
+        if payment <= charged || payoff.months == MAX_MONTHS {
+            return None;
+        }
+
+        balance += charged - payment;
+        payoff.interest += charged;
+        payoff.months += 1;
+    }
+
+    Some(payoff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payoff() {
+        assert_eq!(monthly_rate(6.0), 0.005);
+        assert_eq!(interest(100000, 6.0), 500);
+        assert_eq!(interest(100000, 0.0), 0);
+
+        // 1,000.00 at 12% with 100.00 payments takes 11 months, with 10.00 of interest
+        // in the first.
+        let payoff = payoff(100000, 12.0, 10000).unwrap();
+        assert_eq!(payoff.months, 11);
+        assert_eq!(payoff.interest, 5898);
+
+        assert_eq!(
+            super::payoff(100000, 0.0, 25000),
+            Some(Payoff {
+                months: 4,
+                interest: 0
+            })
+        );
+        assert_eq!(
+            super::payoff(0, 12.0, 0),
+            Some(Payoff {
+                months: 0,
+                interest: 0
+            })
+        );
+
+        // The payment only covers the interest.
+        assert_eq!(super::payoff(100000, 12.0, 1000), None);
+        assert_eq!(super::payoff(100000, 0.0, 1), None);
+    }
+}
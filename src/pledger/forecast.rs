@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::pledger::aggregate::{days_in_month, tag_totals, total};
+use crate::pledger::EntryKind::Debit;
+use crate::pledger::{Amount, Error, Ledger, Result};
+
+/// The label of the projection for all debits, rather than a single tag.
+pub const TOTAL: &str = "(total)";
+
+/// A projection of a tag's debits at the end of the month.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Projection {
+    pub tag: String,
+    /// What's been spent so far.
+    pub spent: Amount,
+    /// The spend so far, extrapolated linearly over the whole month.
+    pub linear: Amount,
+    /// The average over the previous months, if there are any.
+    pub average: Option<Amount>,
+    /// The tag's budget limit, if it has one.
+    pub limit: Option<Amount>,
+}
+
+impl Projection {
+    /// Whether either model projects the tag going over its budget.
+    pub fn over(&self) -> bool {
+        match self.limit {
+            Some(limit) => self.linear > limit || self.average.map(|a| a > limit).unwrap_or(false),
+            None => false,
+        }
+    }
+}
+
+fn spent(points: &[(String, Amount)], tag: &str) -> Amount {
+    points
+        .iter()
+        .find(|(t, _)| t == tag)
+        .map(|(_, amount)| *amount)
+        .unwrap_or(Amount::ZERO)
+}
+
+/// Projects the end-of-month debits for each tag in `current` (and each tag with a
+/// budget), as of `day`. `history` holds the previous months' ledgers, for the
+/// average model. The overall projection comes first, labeled `(total)`.
+pub fn forecast(
+    current: &Ledger,
+    history: &[Ledger],
+    day: u32,
+    budget: &BTreeMap<String, Amount>,
+) -> Result<Vec<Projection>> {
+    let days = days_in_month(&current.date)?;
+    if day == 0 || day > days {
+        return Err(Error::Date(format!("{} has no day {}", current.date, day)));
+    }
+
+    let tags = tag_totals(current, &Debit)?.points;
+    let history_tags = history
+        .iter()
+        .map(|l| tag_totals(l, &Debit).map(|s| s.points))
+        .collect::<Result<Vec<_>>>()?;
+    let history_totals = history
+        .iter()
+        .map(|l| total(l, &Debit))
+        .collect::<Result<Vec<_>>>()?;
+
+    let linear = |spent: Amount| {
+        let projected = spent.subunits() as u128 * days as u128 / day as u128;
+        u64::try_from(projected)
+            .map(|subunits| spent.with_subunits(subunits))
+            .map_err(|_| Error::Amount(format!("projection out of range: {}", spent)))
+    };
+    let average = |totals: Vec<Amount>| match totals.len() {
+        0 => None,
+        n => {
+            let sum: u128 = totals.iter().map(|t| t.subunits() as u128).sum();
+            // The average of u64s always fits in a u64.
+            Some(totals[0].with_subunits((sum / n as u128) as u64))
+        }
+    };
+
+    let spent_total = total(current, &Debit)?;
+    let mut projections = vec![Projection {
+        tag: TOTAL.into(),
+        spent: spent_total,
+        linear: linear(spent_total)?,
+        average: average(history_totals),
+        limit: None,
+    }];
+
+    let names: BTreeSet<_> = tags
+        .iter()
+        .map(|(t, _)| t.as_str())
+        .chain(budget.keys().map(String::as_str))
+        .collect();
+    for tag in names {
+        let spent = spent(&tags, tag);
+        projections.push(Projection {
+            tag: tag.into(),
+            spent,
+            linear: linear(spent)?,
+            average: average(history_tags.iter().map(|p| self::spent(p, tag)).collect()),
+            limit: budget.get(tag).copied(),
+        });
+    }
+
+    Ok(projections)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+    use crate::pledger::parse_ledger;
+    use crate::pledger::ParseOptions;
+
+    fn ledger(date: &str, contents: &'static str) -> Ledger {
+        parse_ledger(
+            date,
+            Box::new(contents.as_bytes().lines()),
+            &ParseOptions::default(),
+        )
+        .unwrap()
+    }
+
+    fn amt(subunits: u64) -> Amount {
+        Amount::from_subunits(subunits)
+    }
+
+    #[test]
+    fn test_forecast() {
+        let current = ledger("2023-02", "D 10.00 #food\nD 4.00 #fun\nC 100.00 #pay");
+        let history = vec![
+            ledger("2022-12", "D 20.00 #food"),
+            ledger("2023-01", "D 40.00 #food\nD 6.00 #fun"),
+        ];
+        let budget = BTreeMap::from([("#food".into(), amt(2500)), ("#rent".into(), amt(100))]);
+
+        let projections = forecast(&current, &history, 7, &budget).unwrap();
+        assert_eq!(
+            projections,
+            vec![
+                Projection {
+                    tag: TOTAL.into(),
+                    spent: amt(1400),
+                    linear: amt(5600),
+                    average: Some(amt(3300)),
+                    limit: None,
+                },
+                Projection {
+                    tag: "#food".into(),
+                    spent: amt(1000),
+                    linear: amt(4000),
+                    average: Some(amt(3000)),
+                    limit: Some(amt(2500)),
+                },
+                Projection {
+                    tag: "#fun".into(),
+                    spent: amt(400),
+                    linear: amt(1600),
+                    average: Some(amt(300)),
+                    limit: None,
+                },
+                Projection {
+                    tag: "#rent".into(),
+                    spent: amt(0),
+                    linear: amt(0),
+                    average: Some(amt(0)),
+                    limit: Some(amt(100)),
+                },
+            ]
+        );
+        assert!(projections[1].over());
+        assert!(!projections[3].over());
+
+        let projections = forecast(&current, &[], 28, &BTreeMap::new()).unwrap();
+        assert_eq!(projections[0].linear, amt(1400));
+        assert_eq!(projections[0].average, None);
+
+        assert!(forecast(&current, &[], 29, &BTreeMap::new()).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+use std::fs;
+use std::path::Path;
+
+use crate::pledger::EntryKind::*;
+use crate::pledger::{
+    logical_lines, parse_entry_ref, parse_line, update_ledger, Entry, Error, ParseOptions, Parsed,
+    Result, Settings,
+};
+
+/// A line of a ledger file, as far as the formatter is concerned.
+enum Line {
+    /// An entry, with its amount as it's written, and the part of its comment that's on
+    /// each of its lines.
+    Entry(Box<Entry>, String, Vec<String>),
+    /// A directive, as it's written.
+    Directive(String),
+    Comment(String),
+    Blank,
+}
+
+fn collapse(comment: &str) -> String {
+    comment.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Renders an entry with its amount right-aligned to `width`, and its comment's
+/// whitespace collapsed. Each of the comment's continuation lines is indented by four
+/// spaces.
+///
+/// The amount is kept as it's written, e.g. with or without grouping
+/// separators, since they're the user's choice.
+fn format_entry(entry: &Entry, amount: &str, comments: &[String], width: usize) -> String {
+    let mut formatted = format!(
+        "{} {:>width$} {}",
+        entry.kind.symbol(),
+        amount,
+        collapse(comments.first().map_or("", String::as_str))
+    );
+    formatted.truncate(formatted.trim_end().len());
+
+    for comment in comments.iter().skip(1) {
+        formatted.push_str("\n    ");
+        formatted.push_str(&collapse(comment));
+    }
+    formatted
+}
+
+/// Normalizes a ledger's text: amounts are right-aligned (as they're written) so that
+/// their comments line up, whitespace is trimmed and collapsed, and runs of blank lines
+/// become a single blank line. Comments are preserved as-is.
+///
+/// If `sort` is set, each run of consecutive entries is sorted with credits first, then
+/// debits, then transfers. Comments, blank lines, and directives stay where they are,
+/// and break up the runs.
+pub fn format_ledger(text: &str, sort: bool, options: &ParseOptions) -> Result<String> {
+    let mut lines = vec![];
+    for logical in logical_lines(text) {
+        let indent = logical.text.len() - logical.text.trim_start().len();
+        let trimmed = logical.text.trim();
+        match parse_line(trimmed, options) {
+            Ok(Parsed::Entry(entry)) => {
+                // The comment is the rest of the logical line, so the part of it on each
+                // line is everything from where it starts.
+                let start = trimmed.len() - entry.comment.len() + indent;
+                let comments = logical
+                    .lines()
+                    .scan(0, |position, line| {
+                        let end = *position + line.len();
+                        let comment = logical.text.get(start.max(*position)..end);
+                        *position = end + 1;
+                        Some(comment.unwrap_or_default().to_string())
+                    })
+                    .collect();
+                // The line parsed as an entry, so it parses as a reference too.
+                let amount = match parse_entry_ref(trimmed, options) {
+                    Ok(entry) => trimmed[entry.amount_span].to_string(),
+                    Err(_) => entry.amount_text(),
+                };
+                lines.push(Line::Entry(Box::new(entry), amount, comments))
+            }
+            Ok(Parsed::Balance(_)) => lines.push(Line::Directive(collapse(trimmed))),
+            Err(None) if trimmed.is_empty() => lines.push(Line::Blank),
+            Err(None) => lines.push(Line::Comment(trimmed.into())),
+            Err(Some(Error::Parse {
+                offset, message, ..
+            })) => {
+                // The offset refers to the trimmed line, so shift it back.
+                let (idx, offset) = logical.locate(offset.map(|o| o + indent));
+                return Err(Error::Parse {
+                    file: None,
+                    line: Some(idx + 1),
+                    offset,
+                    message,
+                });
+            }
+            Err(Some(e)) => return Err(e),
+        }
+    }
+
+    if sort {
+        for run in lines.split_mut(|l| !matches!(l, Line::Entry(..))) {
+            run.sort_by_key(|l| match l {
+                Line::Entry(entry, ..) => match entry.kind {
+                    Credit => 0,
+                    Debit => 1,
+                    Transfer => 2,
+                    Allocation => 3,
+                },
+                _ => unreachable!(),
+            });
+        }
+    }
+
+    let width = lines
+        .iter()
+        .filter_map(|l| match l {
+            Line::Entry(_, amount, _) => Some(amount.chars().count()),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+
+    let mut formatted = String::new();
+    let mut blank = true;
+    for line in lines.iter() {
+        match line {
+            Line::Entry(entry, amount, comments) => {
+                formatted.push_str(&format_entry(entry, amount, comments, width))
+            }
+            Line::Directive(directive) => formatted.push_str(directive),
+            Line::Comment(comment) => formatted.push_str(comment),
+            // Leading blank lines, and all but the first of a run, are dropped.
+            Line::Blank if blank => continue,
+            Line::Blank => {}
+        }
+        formatted.push('\n');
+        blank = matches!(line, Line::Blank);
+    }
+
+    // A trailing blank line is dropped too.
+    if formatted.ends_with("\n\n") {
+        formatted.pop();
+    }
+
+    Ok(formatted)
+}
+
+/// Formats the ledger for `date` in place, returning whether it changed. If `write`
+/// isn't set, the ledger is only checked.
+pub fn format_ledger_file(
+    directory: &Path,
+    date: &str,
+    settings: &Settings,
+    sort: bool,
+    write: bool,
+) -> Result<bool> {
+    let ledger_file = directory.join(format!("{}.ledger", date));
+    if !ledger_file.is_file() {
+        return Err(Error::NotFound(format!(
+            "no ledger for {} in {}",
+            date,
+            directory.display()
+        )));
+    }
+
+    if !write {
+        let text =
+            fs::read_to_string(&ledger_file).map_err(Error::io("ledger file read failed"))?;
+        let formatted =
+            format_ledger(&text, sort, &settings.parse).map_err(|e| e.with_file(&ledger_file))?;
+        return Ok(formatted != text);
+    }
+
+    let mut changed = false;
+    update_ledger(directory, date, settings, |text| {
+        let formatted = format_ledger(&text, sort, &settings.parse)?;
+        changed = formatted != text;
+        if changed {
+            log::info!("formatting {}", ledger_file.display());
+        }
+        Ok(formatted)
+    })
+    .map_err(|e| e.with_file(&ledger_file))?;
+
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_ledger() {
+        let text = "\n# groceries\nD 5   lunch   #food\n\n\n  C 1,200.00 pay #job\nD 12.50 dinner\t#food  \n\nbalance  1,000.00 @bank\n";
+        assert_eq!(
+            format_ledger(text, false, &ParseOptions::default()).unwrap(),
+            "# groceries\nD        5 lunch #food\n\nC 1,200.00 pay #job\nD    12.50 dinner #food\n\nbalance 1,000.00 @bank\n"
+        );
+
+        assert_eq!(
+            format_ledger(
+                "D 1.00 a\nC 2.00 b\n# x\nD 3.00 c\nC 4.00 d\n",
+                true,
+                &ParseOptions::default()
+            )
+            .unwrap(),
+            "C 2.00 b\nD 1.00 a\n# x\nC 4.00 d\nD 3.00 c\n"
+        );
+
+        // Formatting is idempotent.
+        let formatted = format_ledger(text, true, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            format_ledger(&formatted, true, &ParseOptions::default()).unwrap(),
+            formatted
+        );
+
+        match format_ledger("# a\n  D 1.0x b\n", false, &ParseOptions::default()) {
+            Err(Error::Parse { line, offset, .. }) => {
+                assert_eq!(line, Some(2));
+                assert_eq!(offset, Some(7));
+            }
+            _ => panic!("expected a parse error"),
+        }
+
+        // Continuation lines stay with their entries, and keep their own comments.
+        assert_eq!(
+            format_ledger(
+                "D 9.00   dinner\n\t with  friends #food\nC 1.00 pay\n",
+                true,
+                &ParseOptions::default()
+            )
+            .unwrap(),
+            "C 1.00 pay\nD 9.00 dinner\n    with friends #food\n"
+        );
+        assert_eq!(
+            format_ledger(
+                "T 5.00\n  @checking @savings\n",
+                false,
+                &ParseOptions::default()
+            )
+            .unwrap(),
+            "T 5.00\n    @checking @savings\n"
+        );
+        match format_ledger("D 1.00 a\n  b # c\n", false, &ParseOptions::default()) {
+            Err(Error::Parse { line, offset, .. }) => {
+                assert_eq!(line, Some(2));
+                assert_eq!(offset, Some(5));
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+}
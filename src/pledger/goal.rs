@@ -0,0 +1,286 @@
+//! Savings goals, e.g. 3,000.00 for a vacation by 2024-06, and progress toward them.
+//!
+//! A goal's progress is what's been saved toward it: the credits with its tag, or the
+//! net of everything into and out of its account (including transfers). Its completion
+//! date is projected from the average monthly saving since the first month that saved
+//! anything toward it, like `pledger forecast`'s average model.
+
+use serde::Deserialize;
+
+use crate::pledger::aggregate::Summary;
+use crate::pledger::{
+    is_tag, offset_date, Amount, Error, Ledger, ParseOptions, Result, DATE_PATTERN,
+};
+
+/// A savings goal, as written in the config.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Goal {
+    pub name: String,
+    /// The amount to save, e.g. `3,000.00`.
+    pub target: String,
+    /// The month that the goal should be met by, if it has a deadline.
+    pub by: Option<String>,
+    /// The tag whose credits count toward the goal.
+    pub tag: Option<String>,
+    /// The `@account` whose balance counts toward the goal, instead of a tag.
+    pub account: Option<String>,
+}
+
+/// What counts toward a goal.
+#[derive(Clone, Debug, PartialEq)]
+enum Source {
+    Tag(String),
+    Account(String),
+}
+
+impl Goal {
+    /// Checks the definition, returning its target and what counts toward it.
+    fn validate(&self, options: &ParseOptions) -> Result<(Amount, Source)> {
+        let target = Amount::parse_with(&self.target, options.amount_format.with_point(), false)
+            .map(|target| target.with_format(options.amount_format))
+            .map_err(|e| Error::Config(format!("goal {:?}: invalid target: {}", self.name, e)))?;
+
+        if let Some(by) = &self.by {
+            if !DATE_PATTERN.is_match(by) {
+                return Err(Error::Config(format!(
+                    "goal {:?}: expected a month as YYYY-MM, got {}",
+                    self.name, by
+                )));
+            }
+        }
+
+        let source = match (&self.tag, &self.account) {
+            (Some(tag), None) if is_tag(tag) => Source::Tag(options.canonical_tag(tag)),
+            (None, Some(account)) => match account.strip_prefix('@') {
+                Some(account) if !account.is_empty() => Source::Account(account.into()),
+                _ => {
+                    return Err(Error::Config(format!(
+                        "goal {:?}: expected an @account, got {}",
+                        self.name, account
+                    )))
+                }
+            },
+            (Some(tag), None) => {
+                return Err(Error::Config(format!(
+                    "goal {:?}: invalid tag: {}",
+                    self.name, tag
+                )))
+            }
+            _ => {
+                return Err(Error::Config(format!(
+                    "goal {:?}: expected either a tag or an account",
+                    self.name
+                )))
+            }
+        };
+
+        Ok((target, source))
+    }
+}
+
+/// A goal's progress, as of some month.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Progress {
+    pub name: String,
+    pub target: Amount,
+    pub by: Option<String>,
+    /// What's been saved toward the goal, in subunits.
+    pub saved: i128,
+    /// The average monthly saving, in subunits, if anything's been saved.
+    pub monthly: Option<i128>,
+    /// The month that the goal is projected to be met in, if it hasn't been yet and the
+    /// average monthly saving is positive.
+    pub projected: Option<String>,
+}
+
+impl Progress {
+    /// Whether the goal has been met.
+    pub fn is_met(&self) -> bool {
+        self.saved >= self.target.subunits() as i128
+    }
+
+    /// Whether the goal is projected to miss its deadline: it has one, hasn't been met,
+    /// and won't be met by it (or at all, at the current rate).
+    pub fn is_behind(&self) -> bool {
+        match (&self.by, &self.projected) {
+            _ if self.is_met() => false,
+            (Some(by), Some(projected)) => projected > by,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    /// The percentage of the target that's been saved, rounded to one decimal place.
+    pub fn percent(&self) -> f64 {
+        match self.target.subunits() {
+            0 => 100.0,
+            target => (self.saved as f64 * 1000.0 / target as f64).round() / 10.0,
+        }
+    }
+}
+
+/// Returns what a month saved toward the goal, in subunits.
+fn saved_in(summary: &Summary, source: &Source) -> i128 {
+    match source {
+        Source::Tag(tag) => summary
+            .credit_tags
+            .points
+            .iter()
+            .find(|(t, _)| t == tag)
+            .map(|(_, amount)| amount.subunits() as i128)
+            .unwrap_or(0),
+        Source::Account(account) => summary
+            .accounts
+            .iter()
+            .find(|a| &a.account == account)
+            .map(|a| a.credits.subunits() as i128 - a.debits.subunits() as i128)
+            .unwrap_or(0),
+    }
+}
+
+/// Returns the number of months from `from` through `to`, counting both.
+fn months_through(from: &str, to: &str) -> Result<i128> {
+    let mut months = 1;
+    let mut month = from.to_string();
+    while month.as_str() < to {
+        month = offset_date(&month, 1)?;
+        months += 1;
+    }
+
+    Ok(months)
+}
+
+/// Returns the goal's progress as of the month of `date`, from `ledgers` (every month's,
+/// in order, up to and including `date`'s).
+pub fn progress(
+    goal: &Goal,
+    ledgers: &[Ledger],
+    date: &str,
+    options: &ParseOptions,
+) -> Result<Progress> {
+    let (target, source) = goal.validate(options)?;
+
+    let mut saved = 0;
+    let mut first = None;
+    for ledger in ledgers.iter().filter(|l| l.date.as_str() <= date) {
+        let month = saved_in(&Summary::new(ledger)?, &source);
+        if month != 0 && first.is_none() {
+            first = Some(ledger.date.clone());
+        }
+        saved += month;
+    }
+
+    // Months without a ledger count toward the average too, since they
+    // didn't save anything.
+    let monthly = match &first {
+        Some(first) => Some(saved / months_through(first, date)?),
+        None => None,
+    };
+
+    let remaining = target.subunits() as i128 - saved;
+    let projected = match monthly {
+        Some(monthly) if monthly > 0 && remaining > 0 => {
+            let months = (remaining + monthly - 1) / monthly;
+            let months = i32::try_from(months).map_err(|_| {
+                Error::Amount(format!("goal {:?}: projection out of range", goal.name))
+            })?;
+            offset_date(date, months).ok()
+        }
+        _ => None,
+    };
+
+    Ok(Progress {
+        name: goal.name.clone(),
+        target,
+        by: goal.by.clone(),
+        saved,
+        monthly,
+        projected,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::{parse_ledger_reader, ParseOptions};
+
+    fn goal(target: &str, by: Option<&str>, tag: Option<&str>, account: Option<&str>) -> Goal {
+        Goal {
+            name: "vacation".into(),
+            target: target.into(),
+            by: by.map(Into::into),
+            tag: tag.map(Into::into),
+            account: account.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn test_progress() {
+        let ledgers = [
+            parse_ledger_reader(
+                "2023-01",
+                "C 1000.00 pay\n".as_bytes(),
+                &ParseOptions::default(),
+            )
+            .unwrap(),
+            parse_ledger_reader(
+                "2023-02",
+                "C 300.00 saved #vacation\nT 200.00 @checking @savings\n".as_bytes(),
+                &ParseOptions::default(),
+            )
+            .unwrap(),
+            parse_ledger_reader(
+                "2023-04",
+                "C 300.00 saved #vacation\nT 50.00 @savings @checking\n".as_bytes(),
+                &ParseOptions::default(),
+            )
+            .unwrap(),
+        ];
+
+        // 600.00 over February through April is 200.00 a month, so the remaining
+        // 2,400.00 takes another 12 months.
+        let vacation = goal("3000.00", Some("2024-01"), Some("#vacation"), None);
+        let progress = progress(&vacation, &ledgers, "2023-04", &ParseOptions::default()).unwrap();
+        assert_eq!(progress.saved, 60000);
+        assert_eq!(progress.monthly, Some(20000));
+        assert_eq!(progress.projected.as_deref(), Some("2024-04"));
+        assert_eq!(progress.percent(), 20.0);
+        assert!(progress.is_behind());
+        assert!(!progress.is_met());
+
+        // Only the ledgers up to the month count.
+        let progress =
+            super::progress(&vacation, &ledgers, "2023-02", &ParseOptions::default()).unwrap();
+        assert_eq!(progress.saved, 30000);
+        assert_eq!(progress.projected.as_deref(), Some("2023-11"));
+        assert!(!progress.is_behind());
+
+        // Accounts count transfers in and out.
+        let savings = goal("150.00", None, None, Some("@savings"));
+        let progress =
+            super::progress(&savings, &ledgers, "2023-04", &ParseOptions::default()).unwrap();
+        assert_eq!(progress.saved, 15000);
+        assert!(progress.is_met());
+        assert_eq!(progress.projected, None);
+
+        let nothing = goal("10.00", Some("2023-12"), Some("#nothing"), None);
+        let progress =
+            super::progress(&nothing, &ledgers, "2023-04", &ParseOptions::default()).unwrap();
+        assert_eq!(progress.monthly, None);
+        assert!(progress.is_behind());
+
+        for invalid in [
+            goal("abc", None, Some("#vacation"), None),
+            goal("10.00", Some("2023"), Some("#vacation"), None),
+            goal("10.00", None, None, None),
+            goal("10.00", None, Some("#a"), Some("@b")),
+            goal("10.00", None, Some("vacation"), None),
+            goal("10.00", None, None, Some("savings")),
+        ] {
+            assert!(
+                super::progress(&invalid, &ledgers, "2023-04", &ParseOptions::default()).is_err()
+            );
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Checking files against the ledger format's grammar (see `GRAMMAR.md`), for
+//! `pledger check-grammar`: every line is parsed, rather than stopping at the first that
+//! doesn't parse, so that other implementations of the format can compare what they
+//! accept (and what they make of it) with pledger's.
+//!
+//! The conformance corpus in `tests/grammar` is checked here too: every line of
+//! `valid.ledger` has to parse, and every entry in `invalid.ledger` has to fail with the
+//! error in the `# error:` comment before it.
+
+use serde::Serialize;
+
+use crate::pledger::{
+    parse_ledger_reader, Balance, Entry, Error, ParseOptions, Result, Strictness,
+};
+
+/// A line that doesn't match the grammar. Lines and columns are 1-based.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Mismatch {
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+/// What a checked file holds: its entries and balances, and the lines that don't parse.
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub entries: Vec<Entry>,
+    pub balances: Vec<Balance>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+/// Checks a ledger's text against the grammar. `date` labels its entries' sources.
+pub fn check(date: &str, text: &str) -> Result<Check> {
+    let ledger = parse_ledger_reader(
+        date,
+        text.as_bytes(),
+        &ParseOptions::default().with_strictness(Strictness::Lenient),
+    )?;
+    let mismatches = ledger
+        .errors
+        .iter()
+        .map(|e| match e {
+            Error::Parse {
+                line,
+                offset,
+                message,
+                ..
+            } => Mismatch {
+                line: *line,
+                column: offset.map(|o| o + 1),
+                message: message.clone(),
+            },
+            e => Mismatch {
+                line: None,
+                column: None,
+                message: e.to_string(),
+            },
+        })
+        .collect();
+
+    Ok(Check {
+        entries: ledger.entries,
+        balances: ledger.balances,
+        mismatches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: &str = include_str!("../../tests/grammar/valid.ledger");
+    const INVALID: &str = include_str!("../../tests/grammar/invalid.ledger");
+
+    #[test]
+    fn test_valid_corpus() {
+        let check = check("2023-05", VALID).unwrap();
+        assert_eq!(check.mismatches, []);
+
+        // Every entry in the corpus starts a line with its kind, and every balance with
+        // `balance`.
+        let starts = |prefix: &[&str]| {
+            VALID
+                .lines()
+                .filter(|l| prefix.iter().any(|p| l.starts_with(p)))
+                .count()
+        };
+        assert_eq!(check.entries.len(), starts(&["A ", "C ", "D ", "T "]));
+        assert_eq!(check.balances.len(), starts(&["balance "]));
+    }
+
+    #[test]
+    fn test_invalid_corpus() {
+        // Each `# error: COLUMN: MESSAGE` (or `# error: MESSAGE`, without a column) is
+        // the error for the next line.
+        let mut expected = vec![];
+        let lines: Vec<_> = INVALID.lines().collect();
+        for (idx, line) in lines.iter().enumerate() {
+            let error = match line.strip_prefix("# error: ") {
+                Some(error) => error,
+                None => continue,
+            };
+            let (column, message) = match error.split_once(": ") {
+                Some((column, message)) if column.parse::<usize>().is_ok() => {
+                    (column.parse().ok(), message)
+                }
+                _ => (None, error),
+            };
+            expected.push(Mismatch {
+                line: Some(idx + 2),
+                column,
+                message: message.into(),
+            });
+        }
+
+        let check = check("2023-05", INVALID).unwrap();
+        assert_eq!(check.entries.len(), 0);
+        assert_eq!(check.mismatches, expected);
+    }
+}
@@ -0,0 +1,239 @@
+//! Identifying single entries, for commands that act on one (e.g. `pledger show`).
+//!
+//! An entry can be given an explicit ID with a `^id` marker. Otherwise, its ID is derived
+//! from its ledger's date and its contents, so that it stays the same as other entries
+//! come and go, but changes when the entry itself is edited. Identical entries in the
+//! same ledger are told apart by how many of them come before.
+//!
+//! Entries can also be addressed by position, as `DATE:N` for the `N`th entry (from 1)
+//! in the date's ledger, e.g. `2023-05:3`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::pledger::{
+    load_ledger, parse_date, parse_monthly_ledgers, Entry, Error, Ledger, Result, Settings,
+};
+
+/// How many hex digits a derived ID has.
+const DERIVED_LEN: usize = 8;
+
+/// Returns whether `id` can be an explicit ID: letters, digits, `-` and `_`.
+pub fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Returns the ID of each of the ledger's entries, in order.
+pub fn entry_ids(ledger: &Ledger) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    ledger
+        .entries
+        .iter()
+        .map(|entry| {
+            if let Some(id) = &entry.id {
+                return id.clone();
+            }
+
+            let text = entry.to_string();
+            let occurrence = seen.entry(text.clone()).or_default();
+            let digest = Sha256::digest(format!("{}\n{}\n{}", ledger.date, text, occurrence));
+            *occurrence += 1;
+
+            digest
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()[..DERIVED_LEN]
+                .into()
+        })
+        .collect()
+}
+
+/// Returns the explicit IDs that more than one of the ledgers' entries have, with how
+/// many have each.
+pub fn duplicate_ids(ledgers: &[Ledger]) -> Vec<(String, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for entry in ledgers.iter().flat_map(|l| l.entries.iter()) {
+        if let Some(id) = &entry.id {
+            *counts.entry(id).or_default() += 1;
+        }
+    }
+
+    let mut duplicates: Vec<_> = counts
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(id, count)| (id.to_string(), count))
+        .collect();
+    duplicates.sort();
+    duplicates
+}
+
+/// Parses an entry's position, `DATE:N`, into its ledger's date and its (1-based) number.
+pub fn parse_position(position: &str) -> Result<(String, usize)> {
+    let invalid = || {
+        Error::syntax(
+            None,
+            format!(
+                "expected an entry as DATE:N, e.g. 2023-05:3, got {}",
+                position
+            ),
+        )
+    };
+
+    let (date, number) = position.rsplit_once(':').ok_or_else(invalid)?;
+    let number = number
+        .parse::<usize>()
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(invalid)?;
+
+    Ok((parse_date(date)?, number))
+}
+
+/// A single entry, and where it is.
+#[derive(Debug)]
+pub struct Found {
+    pub date: String,
+    /// The entry's index in its ledger, from 0.
+    pub index: usize,
+    pub id: String,
+    pub entry: Entry,
+}
+
+/// Finds the entry with the given ID (or at the given `DATE:N` position) in the
+/// directory's ledgers.
+pub fn find_entry(directory: &Path, id: &str, settings: &Settings) -> Result<Found> {
+    let ledgers = match id.contains(':') {
+        true => {
+            let (date, number) = parse_position(id)?;
+            let ledger = load_ledger(directory, &date, &settings.parse)?;
+            if number > ledger.entries.len() {
+                return Err(Error::NotFound(format!(
+                    "no entry {} in {}, which has {}",
+                    number,
+                    date,
+                    ledger.entries.len()
+                )));
+            }
+            return Ok(found(ledger, number - 1));
+        }
+        false => parse_monthly_ledgers(directory, None, settings)?,
+    };
+
+    let mut matches = vec![];
+    for (idx, ledger) in ledgers.iter().enumerate() {
+        for (index, entry_id) in entry_ids(ledger).into_iter().enumerate() {
+            if entry_id == id {
+                matches.push((idx, index));
+            }
+        }
+    }
+
+    match matches.as_slice() {
+        [] => Err(Error::NotFound(format!("no entry with ID {}", id))),
+        [(idx, index)] => Ok(found(ledgers.into_iter().nth(*idx).unwrap(), *index)),
+        _ => Err(Error::NotFound(format!(
+            "{} entries have ID {}; address one as DATE:N instead",
+            matches.len(),
+            id
+        ))),
+    }
+}
+
+fn found(mut ledger: Ledger, index: usize) -> Found {
+    let id = entry_ids(&ledger).swap_remove(index);
+    Found {
+        date: ledger.date,
+        index,
+        id,
+        entry: ledger.entries.swap_remove(index),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::pledger::parse_ledger_reader;
+    use crate::pledger::ParseOptions;
+
+    #[test]
+    fn test_entry_ids() {
+        let ledger = parse_ledger_reader(
+            "2023-05",
+            "D 1.00 a\nD 1.00 a\nD 2.00 b ^lunch\n".as_bytes(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let ids = entry_ids(&ledger);
+        assert_eq!(ids.len(), 3);
+        assert_eq!(ids[0].len(), DERIVED_LEN);
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(ids[2], "lunch");
+
+        // Derived IDs don't depend on the other entries in the ledger.
+        let other = parse_ledger_reader(
+            "2023-05",
+            "C 5.00 pay\nD 1.00 a\n".as_bytes(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(entry_ids(&other)[1], ids[0]);
+
+        // Only a trailing `^id` is an ID; any other `^` is just text.
+        let id = |line: &str| line.parse::<Entry>().unwrap().id;
+        assert_eq!(id("D 1.00 a ^x-1_b").as_deref(), Some("x-1_b"));
+        assert_eq!(id("D 1.00 a ^x ^y").as_deref(), Some("y"));
+        assert_eq!(id("D 1.00 a ^x #food"), None);
+        assert_eq!(id("D 1.00 100% ^^ great"), None);
+        assert_eq!(id("D 1.00 price ^2 squared"), None);
+        assert_eq!(id("D 1.00 a ^"), None);
+        assert_eq!(id("D 1.00 a ^a/b"), None);
+
+        assert_eq!(parse_position("2023-05:3").unwrap(), ("2023-05".into(), 3));
+        assert!(parse_position("2023-05:0").is_err());
+        assert!(parse_position("2023-05:x").is_err());
+    }
+
+    #[test]
+    fn test_find_entry() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(directory.join("2023-05.ledger"), "D 1.00 a ^x\nD 2.00 b\n").unwrap();
+        fs::write(
+            directory.join("2023-06.ledger"),
+            "D 3.00 c ^y\nD 4.00 d ^y\n",
+        )
+        .unwrap();
+
+        let found = find_entry(directory, "x", &Settings::default()).unwrap();
+        assert_eq!((found.date.as_str(), found.index), ("2023-05", 0));
+
+        let by_position = find_entry(directory, "2023-05:2", &Settings::default()).unwrap();
+        let by_id = find_entry(directory, &by_position.id, &Settings::default()).unwrap();
+        assert_eq!(by_id.entry, by_position.entry);
+        assert_eq!(by_id.index, 1);
+
+        assert!(matches!(
+            find_entry(directory, "y", &Settings::default()),
+            Err(Error::NotFound(_))
+        ));
+        assert_eq!(
+            duplicate_ids(&parse_monthly_ledgers(directory, None, &Settings::default()).unwrap()),
+            [("y".to_string(), 2)]
+        );
+        assert!(matches!(
+            find_entry(directory, "z", &Settings::default()),
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            find_entry(directory, "2023-05:3", &Settings::default()),
+            Err(Error::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::pledger::categorize::Rules;
+use crate::pledger::ledger_cli::{self, Quantity};
+use crate::pledger::{
+    append_entry, ofx, plan, Amount, Entry, Error, ParseOptions, Result, Settings,
+};
+
+/// Where the IDs of already-imported transactions are kept, relative to the ledger
+/// directory.
+pub const STATE_FILE: &str = ".pledger/imported";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportStats {
+    pub imported: usize,
+    /// Transactions that were skipped because they'd already been imported.
+    pub skipped: usize,
+}
+
+fn state_file(directory: &Path) -> PathBuf {
+    directory.join(STATE_FILE)
+}
+
+fn load_state(directory: &Path) -> Result<HashSet<String>> {
+    match fs::read_to_string(state_file(directory)) {
+        Ok(state) => Ok(state.lines().map(String::from).collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(e) => Err(Error::io("import state read failed")(e)),
+    }
+}
+
+fn record_state(directory: &Path, id: &str) -> Result<()> {
+    if plan::dry_run() {
+        return Ok(());
+    }
+
+    let state_file = state_file(directory);
+    if let Some(parent) = state_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut state = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&state_file)
+        .map_err(Error::io("import state write failed"))?;
+    writeln!(state, "{}", id).map_err(Error::io("import state write failed"))?;
+
+    Ok(())
+}
+
+/// Makes a payee name safe to use as a comment, so that it can't introduce tags or
+/// markers of its own.
+fn sanitize(payee: &str) -> String {
+    let payee = payee
+        .replace(['#', '@'], "")
+        .split_whitespace()
+        .filter(|w| *w != "*")
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    match payee.is_empty() {
+        true => "(no payee)".into(),
+        false => payee,
+    }
+}
+
+/// Returns the entry for an imported transaction, in `account` if given and
+/// categorized by `rules`.
+pub fn entry(
+    transaction: &ofx::Transaction,
+    account: Option<&str>,
+    rules: &Rules,
+    options: &ParseOptions,
+) -> Result<Entry> {
+    let mut line = format!(
+        "{} {} @{:02} {}",
+        transaction.kind.symbol(),
+        transaction.amount,
+        transaction.day,
+        sanitize(&transaction.payee)
+    );
+    if let Some(account) = account {
+        line.push_str(&format!(" @{}", account));
+    }
+
+    let line = rules.categorize(&line, options).unwrap_or(line);
+    Entry::parse(&line, options)
+        .map_err(|e| Error::Import(format!("couldn't import {}: {}", transaction.fitid, e)))
+}
+
+/// Imports the transactions in an OFX statement into their months' ledgers, skipping
+/// any that were imported before.
+pub fn import_ofx(
+    directory: &Path,
+    settings: &Settings,
+    ofx: &str,
+    account: Option<&str>,
+    rules: &Rules,
+) -> Result<ImportStats> {
+    let mut seen = load_state(directory)?;
+    let mut stats = ImportStats::default();
+
+    // FITIDs are only unique within an account, so qualify them with it.
+    let account_id = ofx::account_id(ofx).unwrap_or_default();
+    for transaction in ofx::parse(ofx, settings.parse.amount_format)? {
+        let id = format!("ofx:{}:{}", account_id, transaction.fitid);
+        if seen.contains(&id) {
+            log::debug!("already imported: {}", id);
+            stats.skipped += 1;
+            continue;
+        }
+
+        append_entry(
+            directory,
+            &transaction.date,
+            settings,
+            &entry(&transaction, account, rules, &settings.parse)?,
+        )?;
+        record_state(directory, &id)?;
+        seen.insert(id);
+        stats.imported += 1;
+    }
+
+    Ok(stats)
+}
+
+/// Returns the name that a ledger-cli account is imported as: the tag or `@account`
+/// marker (by `prefix`) that the config's `[accounts]` maps to it, or else its
+/// components after the first, lowercased and joined with dashes (`Expenses:Food:Out`
+/// becomes `food-out`). Returns `None` for a top-level account, like `Expenses`.
+fn account_name(
+    account: &str,
+    accounts: &BTreeMap<String, String>,
+    prefix: char,
+) -> Option<String> {
+    if let Some((name, _)) = accounts
+        .iter()
+        .find(|(name, a)| *a == account && name.starts_with(prefix))
+    {
+        return Some(name[1..].into());
+    }
+
+    let name = account
+        .split(':')
+        .skip(1)
+        .map(|c| c.trim().to_lowercase().replace(char::is_whitespace, "-"))
+        .collect::<Vec<_>>()
+        .join("-");
+    Some(name).filter(|n| !n.is_empty())
+}
+
+/// Returns the entries for a ledger-cli transaction: one for each of its expense and
+/// income postings, in the account that the rest of the money is in (or `account`, if
+/// that's ambiguous), or else a transfer between its two other accounts.
+fn journal_entries(
+    transaction: &ledger_cli::Transaction,
+    account: Option<&str>,
+    accounts: &BTreeMap<String, String>,
+    rules: &Rules,
+    options: &ParseOptions,
+) -> Result<Vec<Entry>> {
+    let postings = transaction.balanced()?;
+    let (categories, holdings): (Vec<_>, Vec<_>) = postings
+        .iter()
+        .partition(|(a, q)| ledger_cli::category_kind(a, q).is_some());
+
+    let amount = |quantity: &Quantity| {
+        let amount =
+            Amount::new(quantity.subunits.unsigned_abs(), options.amount_format).to_ledger_string();
+        match quantity.symbol {
+            Some(symbol) => format!("{}{}", symbol, amount),
+            None => amount,
+        }
+    };
+    let mut header = format!("@{:02} {}", transaction.day, sanitize(&transaction.payee));
+    if transaction.cleared {
+        header.insert_str(0, "* ");
+    }
+
+    let mut lines = vec![];
+    if !categories.is_empty() {
+        let held = match holdings.as_slice() {
+            [(held, _)] => account_name(held, accounts, '@'),
+            _ => account.map(String::from),
+        };
+
+        for &(category, quantity) in categories.iter() {
+            // category_kind is always Some for the partitioned categories.
+            let kind = ledger_cli::category_kind(category, &quantity).unwrap();
+            let mut line = format!("{} {} {}", kind.symbol(), amount(&quantity), header);
+            if let Some(held) = &held {
+                line.push_str(&format!(" @{}", held));
+            }
+            if let Some(tag) = account_name(category, accounts, '#') {
+                line.push_str(&format!(" #{}", tag));
+            }
+            lines.push(rules.categorize(&line, options).unwrap_or(line));
+        }
+    } else if let [(first, quantity), (second, _)] = holdings.as_slice() {
+        // Money moves from the account whose balance goes down.
+        let (from, to) = match quantity.subunits < 0 {
+            true => (first, second),
+            false => (second, first),
+        };
+        let name = |a| {
+            account_name(a, accounts, '@').ok_or_else(|| {
+                Error::Import(format!(
+                    "line {}: can't transfer with {}",
+                    transaction.line, a
+                ))
+            })
+        };
+        lines.push(format!(
+            "T {} {} @{} @{}",
+            amount(quantity),
+            header,
+            name(from)?,
+            name(to)?
+        ));
+    } else {
+        return Err(Error::Import(format!(
+            "line {}: transactions without expenses or income need exactly two accounts",
+            transaction.line
+        )));
+    }
+
+    lines
+        .into_iter()
+        .map(|line| {
+            line.parse().map_err(|e| {
+                Error::Import(format!("couldn't import line {}: {}", transaction.line, e))
+            })
+        })
+        .collect()
+}
+
+/// Imports the transactions in a ledger-cli journal into their months' ledgers, skipping
+/// any that were imported before. `accounts` is the config's `[accounts]`, whose names
+/// are mapped back to the tags and `@account` markers that they're exported from.
+pub fn import_ledger_cli(
+    directory: &Path,
+    settings: &Settings,
+    journal: &str,
+    account: Option<&str>,
+    accounts: &BTreeMap<String, String>,
+    rules: &Rules,
+) -> Result<ImportStats> {
+    let mut seen = load_state(directory)?;
+    let mut stats = ImportStats::default();
+
+    // Every transaction (and its entries) is checked before any are imported, so that a
+    // bad one doesn't leave the journal half-imported.
+    let mut imports = vec![];
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    for transaction in ledger_cli::parse(journal, settings.parse.amount_format)? {
+        // Journals don't have IDs, so transactions are identified by their
+        // contents, and by how many identical ones came before them.
+        let mut contents = format!(
+            "{}-{:02}\n{}\n",
+            transaction.date, transaction.day, transaction.payee
+        );
+        for (account, quantity) in transaction.balanced()? {
+            contents.push_str(&format!("{}\t{}\n", account, quantity.subunits));
+        }
+        let occurrence = occurrences.entry(contents.clone()).or_default();
+        contents.push_str(&occurrence.to_string());
+        *occurrence += 1;
+
+        let digest: String = Sha256::digest(contents)
+            .iter()
+            .take(8)
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let entries = journal_entries(&transaction, account, accounts, rules, &settings.parse)?;
+        imports.push((format!("ledger-cli:{}", digest), transaction.date, entries));
+    }
+
+    for (id, date, entries) in imports {
+        if seen.contains(&id) {
+            log::debug!("already imported: {}", id);
+            stats.skipped += 1;
+            continue;
+        }
+
+        for entry in entries.iter() {
+            append_entry(directory, &date, settings, entry)?;
+        }
+        record_state(directory, &id)?;
+        seen.insert(id);
+        stats.imported += 1;
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::config::Config;
+
+    #[test]
+    fn test_import_ofx() {
+        assert_eq!(sanitize("STORE #123 @ * main st"), "STORE 123 main st");
+        assert_eq!(sanitize("#"), "(no payee)");
+
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+
+        let ofx =
+            "<ACCTID>99<STMTTRN><TRNAMT>-12.50<FITID>a<DTPOSTED>20230105<NAME>Diner #5</STMTTRN>\
+                   <STMTTRN><TRNAMT>100<FITID>b<DTPOSTED>20230201<NAME>Pay</STMTTRN>";
+        let config: Config =
+            Config::parse("[[rules]]\nmatch = \"pay\"\ntags = [\"#job\"]").unwrap();
+        let rules = Rules::new(&config.rules).unwrap();
+
+        let stats = import_ofx(
+            directory,
+            &Settings::default(),
+            ofx,
+            Some("checking"),
+            &rules,
+        )
+        .unwrap();
+        assert_eq!(
+            stats,
+            ImportStats {
+                imported: 2,
+                skipped: 0
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(directory.join("2023-01.ledger")).unwrap(),
+            "D 12.50 @05 Diner 5 @checking\n"
+        );
+        assert_eq!(
+            fs::read_to_string(directory.join("2023-02.ledger")).unwrap(),
+            "C 100.00 @01 Pay @checking #job\n"
+        );
+
+        let stats = import_ofx(
+            directory,
+            &Settings::default(),
+            ofx,
+            Some("checking"),
+            &rules,
+        )
+        .unwrap();
+        assert_eq!(
+            stats,
+            ImportStats {
+                imported: 0,
+                skipped: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_import_ledger_cli() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+
+        let journal = "2023/01/05 * Diner #5\n  Expenses:Food:Dining  $12.50\n  Expenses:Groceries  $3\n  Liabilities:Visa\n\n\
+                       2023/01/31 Pay\n  Assets:Checking  1000.00\n  Income:Job\n\n\
+                       2023/02/01 Savings\n  Assets:Savings  $100\n  Assets:Checking\n";
+        let accounts = BTreeMap::from([("#food".into(), "Expenses:Groceries".into())]);
+        let config: Config = Config::parse("").unwrap();
+        let rules = Rules::new(&config.rules).unwrap();
+
+        let stats = import_ledger_cli(
+            directory,
+            &Settings::default(),
+            journal,
+            None,
+            &accounts,
+            &rules,
+        )
+        .unwrap();
+        assert_eq!(
+            stats,
+            ImportStats {
+                imported: 3,
+                skipped: 0
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(directory.join("2023-01.ledger")).unwrap(),
+            "D $12.50 * @05 Diner 5 @visa #food-dining\nD $3.00 * @05 Diner 5 @visa #food\n\
+             C 1000.00 @31 Pay @checking #job\n"
+        );
+        assert_eq!(
+            fs::read_to_string(directory.join("2023-02.ledger")).unwrap(),
+            "T $100.00 @01 Savings @checking @savings\n"
+        );
+
+        let stats = import_ledger_cli(
+            directory,
+            &Settings::default(),
+            journal,
+            None,
+            &accounts,
+            &rules,
+        )
+        .unwrap();
+        assert_eq!(stats.skipped, 3);
+        assert!(import_ledger_cli(
+            directory,
+            &Settings::default(),
+            "2023/03/01 x\n  Assets:A  $1\n  Assets:B  $1\n  Equity:C\n",
+            None,
+            &accounts,
+            &rules
+        )
+        .is_err());
+    }
+}
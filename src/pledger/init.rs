@@ -0,0 +1,117 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::pledger::config::{CONFIG_FILE, CONFIG_SKELETON};
+use crate::pledger::{Error, Result};
+
+/// The header written at the top of a freshly initialized ledger.
+pub fn ledger_header(date: &str) -> String {
+    format!(
+        r"# pledger ledger for {date}
+#
+# Each line is an entry: a kind, an amount, and a comment. The kinds are
+# C for a credit, D for a debit, T for a transfer from one @account to
+# another, and A for an allocation to an envelope (its one #tag). Comments
+# can contain #tags, which pledger uses to group entries in its summaries.
+# For example:
+#
+#   C 1,500.00 paycheck #salary
+#   D 8.00 burger and fries #weekday #lunch
+#   T 500.00 @checking @savings rainy day fund
+#   A 400.00 #food
+#
+# A balance line states an account's balance at that point, with its decimal
+# places (balance 15 @cash is an error, since an entry's 15 would be 0.15):
+#
+#   balance 1,234.56 @checking
+#
+# Empty lines and lines beginning with # are ignored.
+"
+    )
+}
+
+/// Creates a ledger directory with an initial ledger for `date`, optionally along
+/// with a config skeleton and a git repository.
+///
+/// Existing files are never overwritten. Returns the paths that were created.
+pub fn init(directory: &Path, date: &str, config: bool, git: bool) -> Result<Vec<PathBuf>> {
+    let mut created = vec![];
+
+    if !directory.is_dir() {
+        fs::create_dir_all(directory).map_err(Error::io(format!(
+            "couldn't create {}",
+            directory.display()
+        )))?;
+        created.push(directory.to_path_buf());
+    }
+
+    let ledger_file = directory.join(format!("{date}.ledger"));
+    if !ledger_file.exists() {
+        fs::write(&ledger_file, ledger_header(date))
+            .map_err(Error::io("ledger file write failed"))?;
+        created.push(ledger_file);
+    }
+
+    if config {
+        let config_file = directory.join(CONFIG_FILE);
+        if !config_file.exists() {
+            fs::write(&config_file, CONFIG_SKELETON)
+                .map_err(Error::io("config file write failed"))?;
+            created.push(config_file);
+        }
+    }
+
+    if git && !directory.join(".git").exists() {
+        match Command::new("git")
+            .arg("init")
+            .arg("--quiet")
+            .arg(directory)
+            .status()
+        {
+            Ok(status) if status.success() => created.push(directory.join(".git")),
+            Ok(status) => return Err(Error::Command(format!("git init exited with: {}", status))),
+            Err(e) => return Err(Error::Command(format!("failed to execute git: {}", e))),
+        }
+    }
+
+    Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::{parse_ledger, parse_ledger_reader, read_ledger, ParseOptions};
+
+    #[test]
+    fn test_init() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path().join("ledgers");
+
+        let created = init(&directory, "2023-05", true, false).unwrap();
+        assert_eq!(created.len(), 3);
+
+        // The header is entirely comments, so the new ledger is valid and empty.
+        let ledger = parse_ledger(
+            "2023-05",
+            read_ledger(&directory, "2023-05").unwrap(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert!(ledger.entries.is_empty());
+
+        // Its examples are valid, once they're uncommented.
+        let examples: String = ledger_header("2023-05")
+            .lines()
+            .filter_map(|line| line.strip_prefix("#   "))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let ledger =
+            parse_ledger_reader("2023-05", examples.as_bytes(), &ParseOptions::default()).unwrap();
+        assert_eq!(ledger.entries.len(), 4);
+        assert_eq!(ledger.balances.len(), 1);
+
+        // A second init is a no-op.
+        assert!(init(&directory, "2023-05", true, false).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,314 @@
+//! An append-only log of when each entry was added, modified, or removed, for
+//! `pledger history`.
+//!
+//! Like the manifest, the journal is optional: it's created by `pledger history --enable`,
+//! and from then on every change that pledger makes to a ledger is appended to it, one
+//! JSON object per line. Entries are identified by their IDs (see `id`), so an entry's
+//! derived ID changes along with it; a `modified` record keeps the ID that it had before.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::pledger::{id, parse_ledger_reader, Error, Ledger, ParseOptions, Result, Strictness};
+
+/// Where the journal is kept, relative to the ledger directory.
+pub const JOURNAL_FILE: &str = ".pledger/journal.log";
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Added,
+    Modified,
+    Removed,
+}
+
+/// A single change to an entry.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Record {
+    /// When the change was made, in RFC 3339.
+    pub time: String,
+    pub change: Change,
+    /// The date of the ledger that the entry is in.
+    pub ledger: String,
+    /// The entry's ID: its new one, unless it was removed.
+    pub id: String,
+    /// The entry's ID before it was modified.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub previous: Option<String>,
+    /// The entry itself: as it is now, unless it was removed.
+    pub entry: String,
+}
+
+impl Record {
+    /// Returns when the change was made.
+    pub fn time(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(&self.time)
+            .ok()
+            .map(|t| t.with_timezone(&Utc))
+    }
+}
+
+/// Returns whether the directory has a journal.
+pub fn is_enabled(directory: &Path) -> bool {
+    directory.join(JOURNAL_FILE).is_file()
+}
+
+/// Creates the directory's journal, if it doesn't have one yet. Returns whether it was
+/// created.
+pub fn enable(directory: &Path) -> Result<bool> {
+    if is_enabled(directory) {
+        return Ok(false);
+    }
+
+    let path = directory.join(JOURNAL_FILE);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(Error::io(format!("couldn't create {}", parent.display())))?;
+    }
+    fs::File::create(&path).map_err(Error::io("journal creation failed"))?;
+    Ok(true)
+}
+
+/// An entry's ID, and its text.
+type Identified = (String, String);
+
+fn identified(ledger: &Ledger) -> Vec<Identified> {
+    id::entry_ids(ledger)
+        .into_iter()
+        .zip(ledger.entries.iter().map(ToString::to_string))
+        .collect()
+}
+
+/// Works out the changes between two versions of a ledger, with the entry before and after
+/// each.
+///
+/// Entries that are in both are unchanged. Of the rest, an entry that was removed and one
+/// that was added are the same entry, modified, if they have the same explicit ID, or
+/// otherwise if they're between the same unchanged entries (in order).
+fn diff(old: &Ledger, new: &Ledger) -> Vec<(Change, Option<Identified>, Option<Identified>)> {
+    let old = identified(old);
+    let new = identified(new);
+
+    let mut counts: HashMap<&Identified, usize> = HashMap::new();
+    for entry in new.iter() {
+        *counts.entry(entry).or_default() += 1;
+    }
+
+    // Each removed entry, with how many unchanged entries come before it.
+    let mut removed = vec![];
+    let mut kept = 0;
+    let mut unchanged: HashMap<&Identified, usize> = HashMap::new();
+    for entry in old.iter() {
+        match counts.get_mut(entry) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                *unchanged.entry(entry).or_default() += 1;
+                kept += 1;
+            }
+            _ => removed.push((kept, entry.clone())),
+        }
+    }
+
+    let mut added = vec![];
+    let mut kept = 0;
+    for entry in new.iter() {
+        match unchanged.get_mut(entry) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                kept += 1;
+            }
+            _ => added.push((kept, entry.clone())),
+        }
+    }
+
+    let mut changes = vec![];
+    for (anchor, entry) in added.into_iter() {
+        let paired = removed
+            .iter()
+            .position(|(_, (id, _))| *id == entry.0)
+            .or_else(|| removed.iter().position(|(a, _)| *a == anchor));
+        match paired {
+            Some(idx) => {
+                let (_, previous) = removed.remove(idx);
+                changes.push((Change::Modified, Some(previous), Some(entry)));
+            }
+            None => changes.push((Change::Added, None, Some(entry))),
+        }
+    }
+    changes.extend(
+        removed
+            .into_iter()
+            .map(|(_, entry)| (Change::Removed, Some(entry), None)),
+    );
+    changes
+}
+
+/// Returns the records for a change to the ledger for `date`, from `old` (or nothing, for
+/// a new ledger) to `new`. Lines that don't parse are ignored.
+fn records(date: &str, old: Option<&str>, new: &str, time: &str) -> Result<Vec<Record>> {
+    let options = ParseOptions::default().with_strictness(Strictness::Lenient);
+    let parse = |text: &str| parse_ledger_reader(date, text.as_bytes(), &options);
+    let old = parse(old.unwrap_or_default())?;
+    let new = parse(new)?;
+
+    Ok(diff(&old, &new)
+        .into_iter()
+        .map(|(change, old, new)| {
+            let (id, entry) = new.clone().or_else(|| old.clone()).unwrap();
+            Record {
+                time: time.into(),
+                change,
+                ledger: date.into(),
+                id,
+                previous: match change {
+                    Change::Modified => old.map(|(id, _)| id),
+                    _ => None,
+                },
+                entry,
+            }
+        })
+        .collect())
+}
+
+/// Appends the changes from `old` to `new` in the ledger for `date` to the directory's
+/// journal, if it has one.
+pub fn record(directory: &Path, date: &str, old: Option<&str>, new: &str) -> Result<()> {
+    if !is_enabled(directory) {
+        return Ok(());
+    }
+
+    let time = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+    let records = records(date, old, new, &time)?;
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let mut lines = String::new();
+    for record in records.iter() {
+        lines.push_str(&serde_json::to_string(record).map_err(io::Error::from)?);
+        lines.push('\n');
+    }
+
+    // Each change's records are appended with a single write, so that changes to
+    // different ledgers (which don't share a lock) can't interleave.
+    let path = directory.join(JOURNAL_FILE);
+    fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(lines.as_bytes()))
+        .map_err(Error::io(format!("couldn't append to {}", path.display())))
+}
+
+/// Loads the journal's records, oldest first, or `None` if the directory doesn't have one.
+pub fn load(directory: &Path) -> Result<Option<Vec<Record>>> {
+    let path = directory.join(JOURNAL_FILE);
+    let journal = match fs::read_to_string(&path) {
+        Ok(journal) => journal,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::io("journal read failed")(e)),
+    };
+
+    let mut records = vec![];
+    for (idx, line) in journal.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(line).map_err(|e| Error::Parse {
+            file: Some(path.clone()),
+            line: Some(idx + 1),
+            offset: None,
+            message: format!("invalid journal record: {}", e),
+        })?;
+        records.push(record);
+    }
+    Ok(Some(records))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::{update_ledger, Settings};
+
+    fn changes(old: &str, new: &str) -> Vec<(Change, String, Option<String>)> {
+        records("2023-05", Some(old), new, "2023-05-01T00:00:00Z")
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.change, r.entry, r.previous))
+            .collect()
+    }
+
+    #[test]
+    fn test_records() {
+        assert_eq!(changes("D 11.00 a\n", "D 11.00 a\n# note\n"), []);
+        assert_eq!(
+            changes("D 11.00 a\n", "D 11.00 a\nD 12.00 b\n"),
+            [(Change::Added, "D 12.00 b".into(), None)]
+        );
+        assert_eq!(
+            changes("D 11.00 a\nD 12.00 b\n", "D 12.00 b\n"),
+            [(Change::Removed, "D 11.00 a".into(), None)]
+        );
+
+        // An edited entry is modified, rather than removed and added, whether it keeps its
+        // (explicit) ID or not.
+        let old = "D 11.00 a\nD 12.00 b\nD 13.00 c\n";
+        let modified = changes(old, "D 11.00 a\nD 12.50 b\nD 13.00 c\n");
+        assert_eq!(modified.len(), 1);
+        assert_eq!(modified[0].0, Change::Modified);
+        assert_eq!(modified[0].1, "D 12.50 b");
+        assert!(modified[0].2.is_some());
+
+        assert_eq!(
+            changes("D 11.00 a ^x\nD 12.00 b\n", "D 12.00 b\nD 15.00 a ^x\n")[0],
+            (Change::Modified, "D 15.00 a ^x".into(), Some("x".into()))
+        );
+
+        // Duplicates are told apart.
+        assert_eq!(
+            changes("D 11.00 a\n", "D 11.00 a\nD 11.00 a\n"),
+            [(Change::Added, "D 11.00 a".into(), None)]
+        );
+    }
+
+    #[test]
+    fn test_record() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+
+        // Nothing is recorded without a journal.
+        update_ledger(directory, "2023-05", &Settings::default(), |_| {
+            Ok("D 11.00 a\n".into())
+        })
+        .unwrap();
+        assert!(load(directory).unwrap().is_none());
+
+        assert!(enable(directory).unwrap());
+        assert!(!enable(directory).unwrap());
+        update_ledger(directory, "2023-05", &Settings::default(), |t| {
+            Ok(t + "C 15.00 pay\n")
+        })
+        .unwrap();
+        update_ledger(directory, "2023-05", &Settings::default(), |_| {
+            Ok("C 15.00 pay\n".into())
+        })
+        .unwrap();
+
+        let records = load(directory).unwrap().unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|r| (r.change, r.entry.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (Change::Added, "C 15.00 pay"),
+                (Change::Removed, "D 11.00 a")
+            ]
+        );
+        assert!(records[0].time().is_some());
+    }
+}
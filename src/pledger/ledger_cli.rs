@@ -0,0 +1,322 @@
+//! A parser for the transactions in [ledger-cli](https://ledger-cli.org/) journals, for
+//! `pledger import --format ledger-cli`.
+//!
+//! Only what's needed to bring a journal's history over is read: each transaction's
+//! date, status, payee, and postings. Directives (`account`, `commodity`, `P`, and so
+//! on), automated and periodic transactions, and comments are skipped. Postings with
+//! prices or lots can't be expressed as single-entry amounts, and are errors.
+
+use crate::pledger::amount::{currency_symbol, AmountFormat, CURRENCY_SYMBOLS};
+use crate::pledger::ofx;
+use crate::pledger::{EntryKind, Error, Result};
+
+/// A posting's amount, in signed subunits, and its currency symbol (if it has one).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quantity {
+    pub subunits: i64,
+    pub symbol: Option<char>,
+}
+
+/// A single posting in a transaction. Its amount is `None` when it's elided, i.e. when
+/// it balances the transaction's other postings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Posting {
+    pub account: String,
+    pub amount: Option<Quantity>,
+}
+
+/// A single transaction from a journal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transaction {
+    /// The line that the transaction starts on, from 1.
+    pub line: usize,
+    /// The transaction's date, as `YYYY-MM`, and its day of the month.
+    pub date: String,
+    pub day: u32,
+    /// Whether the transaction is cleared (`*`).
+    pub cleared: bool,
+    pub payee: String,
+    pub postings: Vec<Posting>,
+}
+
+impl Transaction {
+    /// Returns the transaction's postings with every amount filled in, balancing the
+    /// (at most one) elided posting against the others.
+    pub fn balanced(&self) -> Result<Vec<(&str, Quantity)>> {
+        let symbols: Vec<_> = self
+            .postings
+            .iter()
+            .filter_map(|p| p.amount.map(|a| a.symbol))
+            .collect();
+        if symbols.windows(2).any(|w| w[0] != w[1]) {
+            return Err(Error::Import(format!(
+                "line {}: transactions in more than one commodity aren't supported",
+                self.line
+            )));
+        }
+
+        let sum: i64 = self
+            .postings
+            .iter()
+            .filter_map(|p| p.amount.map(|a| a.subunits))
+            .sum();
+        let elided = self.postings.iter().filter(|p| p.amount.is_none()).count();
+        match elided {
+            0 if sum != 0 => {
+                return Err(Error::Import(format!(
+                    "line {}: transaction doesn't balance",
+                    self.line
+                )))
+            }
+            0 | 1 => {}
+            _ => {
+                return Err(Error::Import(format!(
+                    "line {}: more than one posting without an amount",
+                    self.line
+                )))
+            }
+        }
+
+        Ok(self
+            .postings
+            .iter()
+            .map(|p| {
+                let amount = p.amount.unwrap_or(Quantity {
+                    subunits: -sum,
+                    symbol: symbols.first().copied().flatten(),
+                });
+                (p.account.as_str(), amount)
+            })
+            .collect())
+    }
+}
+
+/// Parses a posting's amount, like `$12.50`, `-$1,000`, or `12.50 EUR`, into subunits
+/// with `format`'s decimal places.
+fn parse_quantity(amount: &str, format: AmountFormat) -> Result<Quantity> {
+    let number: String = amount
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.' || *c == '-')
+        .collect();
+    let commodity = amount
+        .trim_matches(|c: char| c.is_whitespace())
+        .replace(|c: char| c.is_ascii_digit() || ".,-+\"".contains(c), "");
+    let commodity = commodity.trim();
+
+    let symbol = match commodity {
+        "" => None,
+        c if c.chars().count() == 1
+            && CURRENCY_SYMBOLS.contains_key(&c.chars().next().unwrap()) =>
+        {
+            c.chars().next()
+        }
+        c => Some(
+            currency_symbol(c)
+                .ok_or_else(|| Error::Import(format!("unsupported commodity: {}", c)))?,
+        ),
+    };
+
+    // The sign can come before or after the commodity (`-$5` or `$-5`), and
+    // OFX's amount parser already handles the rest.
+    let negative = number.starts_with('-');
+    let (_, amount) = ofx::parse_amount(number.trim_start_matches('-'), format)?;
+    let subunits = i64::try_from(amount.subunits())
+        .map_err(|_| Error::Amount(format!("amount out of range: {}", amount)))?;
+
+    Ok(Quantity {
+        subunits: if negative { -subunits } else { subunits },
+        symbol,
+    })
+}
+
+/// Parses a posting line (without its indentation).
+fn parse_posting(line: &str, format: AmountFormat) -> Result<Option<Posting>> {
+    // Everything after a `;` is a comment, and after a `=` a balance assertion.
+    let line = line.split(';').next().unwrap_or_default();
+    let line = line.split('=').next().unwrap_or_default().trim_end();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.contains('@') || line.contains('{') {
+        return Err(Error::Import(format!(
+            "prices and lots aren't supported: {}",
+            line.trim()
+        )));
+    }
+
+    // Accounts can have spaces in them, so an account ends at a tab or at two
+    // spaces in a row.
+    let (account, amount) = match line.find("  ").into_iter().chain(line.find('\t')).min() {
+        Some(idx) => (&line[..idx], Some(line[idx..].trim())),
+        None => (line, None),
+    };
+
+    // Virtual postings in parentheses don't have to balance, so they aren't real money.
+    if account.starts_with('(') {
+        return Ok(None);
+    }
+    let account = account.trim_matches(|c| c == '[' || c == ']');
+
+    Ok(Some(Posting {
+        account: account.into(),
+        amount: amount
+            .filter(|a| !a.is_empty())
+            .map(|amount| parse_quantity(amount, format))
+            .transpose()?,
+    }))
+}
+
+/// Parses a transaction's first line, like `2023/01/05 * (123) Diner`.
+fn parse_header(line: &str, number: usize) -> Result<Transaction> {
+    let (date, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    // An auxiliary date (`2023/01/05=2023/01/07`) is ignored.
+    let date = date.split('=').next().unwrap_or_default();
+
+    let parts: Vec<&str> = date.split(['/', '-', '.']).collect();
+    let valid = parts.len() == 3
+        && parts[0].len() == 4
+        && parts[1..].iter().all(|p| (1..=2).contains(&p.len()))
+        && parts.iter().all(|p| p.chars().all(|c| c.is_ascii_digit()));
+    if !valid {
+        return Err(Error::Import(format!(
+            "line {}: invalid date: {}",
+            number, date
+        )));
+    }
+
+    let mut payee = rest.split(';').next().unwrap_or_default().trim();
+    let mut cleared = false;
+    if let Some(rest) = payee.strip_prefix('*') {
+        (payee, cleared) = (rest.trim_start(), true);
+    } else if let Some(rest) = payee.strip_prefix('!') {
+        payee = rest.trim_start();
+    }
+    if payee.starts_with('(') {
+        payee = payee.split_once(')').map_or("", |(_, p)| p.trim_start());
+    }
+
+    Ok(Transaction {
+        line: number,
+        date: format!("{}-{:0>2}", parts[0], parts[1]),
+        day: parts[2].parse().unwrap(),
+        cleared,
+        payee: payee.into(),
+        postings: vec![],
+    })
+}
+
+/// Returns the kind of entry that a posting to an account becomes, if it's a category
+/// (expenses or income): a debit if money goes to it, and a credit otherwise. Other
+/// accounts (assets, liabilities, and so on) are where the money is.
+pub fn category_kind(account: &str, amount: &Quantity) -> Option<EntryKind> {
+    let root = account.split(':').next().unwrap_or_default().to_lowercase();
+    match root.as_str() {
+        "expenses" | "expense" | "income" | "revenue" | "revenues" => {
+            Some(match amount.subunits > 0 {
+                true => EntryKind::Debit,
+                false => EntryKind::Credit,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Parses every transaction in a journal, with amounts in `format`'s decimal places.
+pub fn parse(journal: &str, format: AmountFormat) -> Result<Vec<Transaction>> {
+    let mut transactions: Vec<Transaction> = vec![];
+    // Whether indented lines are the current transaction's postings, rather than an
+    // automated transaction's or a directive's.
+    let mut in_transaction = false;
+
+    for (idx, line) in journal.lines().enumerate() {
+        let number = idx + 1;
+        match line.chars().next() {
+            None => in_transaction = false,
+            Some(c) if c.is_whitespace() => {
+                if !in_transaction || line.trim_start().starts_with(';') {
+                    continue;
+                }
+                if let Some(posting) = parse_posting(line.trim(), format)
+                    .map_err(|e| Error::Import(format!("line {}: {}", number, e)))?
+                {
+                    // in_transaction is only set after a transaction is pushed.
+                    transactions.last_mut().unwrap().postings.push(posting);
+                }
+            }
+            Some(c) if c.is_ascii_digit() => {
+                transactions.push(parse_header(line, number)?);
+                in_transaction = true;
+            }
+            Some(_) if line.starts_with("include") => {
+                return Err(Error::Import(format!(
+                    "line {}: includes aren't supported; import each file instead",
+                    number
+                )))
+            }
+            Some(_) => in_transaction = false,
+        }
+    }
+
+    Ok(transactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let journal = "; a comment\naccount Assets:Checking\n\n\
+                       2023/01/05 * (12) Joe's Diner  ; lunch\n    \
+                       Expenses:Food:Dining    $12.50\n    ; a note\n    \
+                       Assets:Checking\n\n\
+                       = /Food/\n    (Budget:Food)    -1\n\n\
+                       2023-02-01 ! Transfer\n\tAssets:Savings\t-1,000 USD = $0\n\tAssets:Checking\n";
+
+        let transactions = parse(journal, AmountFormat::DEFAULT).unwrap();
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].line, 4);
+        assert_eq!(transactions[0].date, "2023-01");
+        assert_eq!(transactions[0].day, 5);
+        assert!(transactions[0].cleared);
+        assert_eq!(transactions[0].payee, "Joe's Diner");
+        assert_eq!(
+            transactions[0].balanced().unwrap(),
+            vec![
+                (
+                    "Expenses:Food:Dining",
+                    Quantity {
+                        subunits: 1250,
+                        symbol: Some('$')
+                    }
+                ),
+                (
+                    "Assets:Checking",
+                    Quantity {
+                        subunits: -1250,
+                        symbol: Some('$')
+                    }
+                ),
+            ]
+        );
+        assert!(!transactions[1].cleared);
+        assert_eq!(
+            transactions[1].postings[0].amount.unwrap().subunits,
+            -100000
+        );
+
+        assert!(parse("2023/13 x\n", AmountFormat::DEFAULT).is_err());
+        assert!(parse(
+            "2023/01/05 x\n  Expenses:Food  10 AAPL @ $1\n",
+            AmountFormat::DEFAULT
+        )
+        .is_err());
+        assert!(parse("include other.ledger\n", AmountFormat::DEFAULT).is_err());
+        let unbalanced = parse(
+            "2023/01/05 x\n  Expenses:Food  $1\n  Assets:Bank  $-2\n",
+            AmountFormat::DEFAULT,
+        )
+        .unwrap();
+        assert!(unbalanced[0].balanced().is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! Sanity limits on amounts, from the config's `[limits]`.
+//!
+//! Unlike budgets, limits aren't about spending less: they're a check that amounts were
+//! entered correctly, since a single misplaced decimal point can outweigh everything
+//! else in a report.
+
+use std::fmt;
+
+use crate::pledger::aggregate::total;
+use crate::pledger::config::Limits;
+use crate::pledger::{Amount, Entry, EntryKind, Ledger, Result};
+
+/// An amount over one of the limits.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Exceeded {
+    Entry {
+        date: String,
+        /// The entry's number in its ledger, from 1.
+        number: usize,
+        entry: Box<Entry>,
+        limit: Amount,
+    },
+    Month {
+        date: String,
+        debits: Amount,
+        limit: Amount,
+    },
+}
+
+impl Exceeded {
+    /// Returns the date of the ledger that's over the limit.
+    pub fn date(&self) -> &str {
+        match self {
+            Exceeded::Entry { date, .. } | Exceeded::Month { date, .. } => date,
+        }
+    }
+}
+
+impl fmt::Display for Exceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Exceeded::Entry {
+                date,
+                number,
+                entry,
+                limit,
+            } => write!(
+                f,
+                "{}:{}: {} is over the entry limit of {}",
+                date, number, entry, limit
+            ),
+            Exceeded::Month {
+                date,
+                debits,
+                limit,
+            } => write!(
+                f,
+                "{}: {} in debits is over the monthly limit of {}",
+                date, debits, limit
+            ),
+        }
+    }
+}
+
+/// Returns the entries in a month's ledger that are over the entry limit, and the
+/// month's debits if they're over the monthly limit.
+pub fn check(limits: &Limits, ledger: &Ledger) -> Result<Vec<Exceeded>> {
+    let mut exceeded = vec![];
+
+    if let Some(limit) = limits.entry {
+        for (idx, entry) in ledger.entries.iter().enumerate() {
+            if entry.amount > limit {
+                exceeded.push(Exceeded::Entry {
+                    date: ledger.date.clone(),
+                    number: idx + 1,
+                    entry: Box::new(entry.clone()),
+                    limit,
+                });
+            }
+        }
+    }
+
+    if let Some(limit) = limits.month {
+        let debits = total(ledger, &EntryKind::Debit)?;
+        if debits > limit {
+            exceeded.push(Exceeded::Month {
+                date: ledger.date.clone(),
+                debits,
+                limit,
+            });
+        }
+    }
+
+    Ok(exceeded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::{parse_ledger_reader, ParseOptions};
+
+    #[test]
+    fn test_check() {
+        let limits = Limits {
+            entry: Some(Amount::from_subunits(500000)),
+            month: Some(Amount::from_subunits(600000)),
+        };
+        let ledger = parse_ledger_reader(
+            "2023-05",
+            "D 12.00 lunch #food\nD 1200000 rent #rent\nC 9000.00 pay #pay\n".as_bytes(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        let exceeded = check(&limits, &ledger).unwrap();
+        assert_eq!(
+            exceeded.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "2023-05:2: D 12000.00 rent #rent is over the entry limit of 5000.00",
+                "2023-05:3: C 9000.00 pay #pay is over the entry limit of 5000.00",
+                "2023-05: 12012.00 in debits is over the monthly limit of 6000.00",
+            ]
+        );
+
+        assert!(check(&Limits::default(), &ledger).unwrap().is_empty());
+    }
+}
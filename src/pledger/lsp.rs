@@ -0,0 +1,407 @@
+//! A minimal language server for ledger files: diagnostics from the parser, tag
+//! completion, and hovers describing each entry.
+//!
+//! The server speaks JSON-RPC over stdio, and only supports full document syncs.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::pledger::{
+    ledger_dates, load_ledger, logical_lines, parse_entry_ref, parse_line, Error, ParseOptions,
+    Result, Settings, Span,
+};
+
+/// JSON-RPC's "method not found" error code.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Reads a single message, or `None` at EOF.
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input
+            .read_line(&mut header)
+            .map_err(Error::io("message read failed"))?
+            == 0
+        {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = Some(value.trim().parse::<usize>().map_err(|e| {
+                    Error::Lsp(format!("invalid Content-Length {:?}: {}", value.trim(), e))
+                })?);
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| Error::Lsp("message without a Content-Length".into()))?;
+    let mut body = vec![0; length];
+    input
+        .read_exact(&mut body)
+        .map_err(Error::io("message read failed"))?;
+
+    let message = serde_json::from_slice(&body)
+        .map_err(|e| Error::Lsp(format!("invalid JSON in message: {}", e)))?;
+    Ok(Some(message))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|()| output.flush())
+        .map_err(Error::io("message write failed"))
+}
+
+/// Returns the UTF-16 length of `text`, which is how LSP counts characters.
+fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+/// Returns the parser's diagnostics for every (logical) line of `text`.
+fn diagnostics(text: &str, options: &ParseOptions) -> Vec<Value> {
+    let lines: Vec<_> = text.lines().collect();
+    logical_lines(text)
+        .into_iter()
+        .filter_map(|logical| match parse_line(&logical.text, options) {
+            Err(Some(Error::Parse {
+                offset, message, ..
+            })) => {
+                let (idx, offset) = logical.locate(offset);
+                let line = lines[idx];
+                let start = offset
+                    .map(|o| line.chars().take(o).map(char::len_utf16).sum())
+                    .unwrap_or(0);
+                Some(json!({
+                    "range": {
+                        "start": {"line": idx, "character": start},
+                        "end": {"line": idx, "character": utf16_len(line).max(start + 1)},
+                    },
+                    "severity": 1,
+                    "source": "pledger",
+                    "message": message,
+                }))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the byte offset of the UTF-16 `character` on `line`.
+fn byte_offset(line: &str, character: usize) -> usize {
+    line.char_indices()
+        .scan(0, |units, (idx, chr)| {
+            let here = *units;
+            *units += chr.len_utf16();
+            Some((idx, here))
+        })
+        .find(|(_, units)| *units >= character)
+        .map(|(idx, _)| idx)
+        .unwrap_or(line.len())
+}
+
+/// Returns a markdown description of the entry on `line`, if it has one, along with
+/// the span that it's for: the amount or tag at `character`, or else the whole entry.
+fn hover(line: &str, character: usize, options: &ParseOptions) -> Option<(String, Span)> {
+    let entry = parse_entry_ref(line, options).ok()?;
+    let mut hover = format!("**{}** of {}", entry.kind.name(), entry.amount);
+    if let Some(currency) = entry.currency {
+        hover.push_str(&format!(" {}", currency));
+    }
+    if let (Some(from), Some(to)) = (entry.account, entry.to) {
+        hover.push_str(&format!(" from {} to {}", from, to));
+    }
+
+    let mut tags: Vec<_> = entry.tags.iter().map(|(t, _)| *t).collect();
+    tags.sort_unstable();
+    tags.dedup();
+    if !tags.is_empty() {
+        hover.push_str(&format!("\n\ntags: {}", tags.join(", ")));
+    }
+
+    let offset = byte_offset(line, character);
+    let span = std::iter::once(&entry.amount_span)
+        .chain(entry.tags.iter().map(|(_, span)| span))
+        .find(|span| span.contains(&offset))
+        .cloned()
+        .unwrap_or(0..line.len());
+
+    Some((hover, span))
+}
+
+/// Returns the partial tag (starting with `#`) that ends at `character` on `line`,
+/// if there is one.
+fn tag_prefix(line: &str, character: usize) -> Option<&str> {
+    let end = byte_offset(line, character);
+
+    line[..end]
+        .rsplit(char::is_whitespace)
+        .next()
+        .filter(|w| w.starts_with('#'))
+}
+
+struct Server {
+    /// Tags from the ledgers on disk, as of startup.
+    tags: BTreeSet<String>,
+    /// The text of each open document, by URI.
+    documents: HashMap<String, String>,
+    /// How the documents are parsed.
+    options: ParseOptions,
+}
+
+impl Server {
+    fn new(directory: &Path, options: &ParseOptions) -> Self {
+        let mut tags = BTreeSet::new();
+
+        // Broken ledgers are exactly what the server is for, so don't
+        // fail on them; they just don't contribute tags.
+        for date in ledger_dates(directory).unwrap_or_default() {
+            if let Ok(ledger) = load_ledger(directory, &date, options) {
+                tags.extend(ledger.entries.into_iter().flat_map(|e| e.tags));
+            }
+        }
+
+        Self {
+            tags,
+            documents: HashMap::new(),
+            options: options.clone(),
+        }
+    }
+
+    fn line(&self, params: &Value) -> Option<(&str, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let line = params["position"]["line"].as_u64()? as usize;
+        let character = params["position"]["character"].as_u64()? as usize;
+
+        let text = self.documents.get(uri)?;
+        Some((text.lines().nth(line).unwrap_or(""), character))
+    }
+
+    fn completion(&self, params: &Value) -> Value {
+        let prefix = match self.line(params).and_then(|(l, c)| tag_prefix(l, c)) {
+            Some(prefix) => prefix,
+            None => return json!([]),
+        };
+
+        let mut tags = self.tags.clone();
+        for text in self.documents.values() {
+            for line in text.lines() {
+                if let Ok(entry) = parse_entry_ref(line, &self.options) {
+                    tags.extend(entry.tags.into_iter().map(|(t, _)| t.to_string()));
+                }
+            }
+        }
+
+        tags.iter()
+            .filter(|t| t.starts_with(prefix) && t.as_str() != prefix)
+            .map(|t| json!({"label": t, "kind": 12}))
+            .collect()
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let line = params["position"]["line"].as_u64().unwrap_or(0);
+        match self
+            .line(params)
+            .and_then(|(l, c)| Some((l, hover(l, c, &self.options)?)))
+        {
+            Some((text, (hover, span))) => json!({
+                "contents": {"kind": "markdown", "value": hover},
+                "range": {
+                    "start": {"line": line, "character": utf16_len(&text[..span.start])},
+                    "end": {"line": line, "character": utf16_len(&text[..span.end])},
+                },
+            }),
+            None => Value::Null,
+        }
+    }
+
+    /// Updates the open document from a didOpen or didChange notification, returning
+    /// its new diagnostics.
+    fn update(&mut self, params: &Value) -> Option<Value> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let text = match params["textDocument"]["text"].as_str() {
+            Some(text) => text,
+            // Full syncs send a single change, containing the whole document.
+            None => params["contentChanges"].as_array()?.last()?["text"].as_str()?,
+        };
+
+        self.documents.insert(uri.into(), text.into());
+        Some(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {"uri": uri, "diagnostics": diagnostics(text, &self.options)},
+        }))
+    }
+}
+
+/// Serves the language server protocol on `input` and `output`, until the client
+/// sends `exit` or closes `input`. Tags are completed from the ledgers in `directory`.
+pub fn serve(
+    directory: &Path,
+    settings: &Settings,
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<()> {
+    let mut server = Server::new(directory, &settings.parse);
+
+    while let Some(message) = read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        log::debug!("lsp: {}", method);
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "completionProvider": {"triggerCharacters": ["#"]},
+                },
+                "serverInfo": {"name": "pledger", "version": env!("CARGO_PKG_VERSION")},
+            }),
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                if let Some(notification) = server.update(params) {
+                    write_message(&mut output, &notification)?;
+                }
+                continue;
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = params["textDocument"]["uri"].as_str() {
+                    server.documents.remove(uri);
+                }
+                continue;
+            }
+            "textDocument/completion" => server.completion(params),
+            "textDocument/hover" => server.hover(params),
+            "shutdown" => Value::Null,
+            "exit" => return Ok(()),
+            _ => {
+                // Anything else without an ID is a notification that we don't care about.
+                if message.get("id").is_some() {
+                    write_message(
+                        &mut output,
+                        &json!({
+                            "jsonrpc": "2.0",
+                            "id": message["id"],
+                            "error": {"code": METHOD_NOT_FOUND, "message": format!("unsupported method: {}", method)},
+                        }),
+                    )?;
+                }
+                continue;
+            }
+        };
+
+        write_message(
+            &mut output,
+            &json!({"jsonrpc": "2.0", "id": message["id"], "result": result}),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn frame(messages: &[Value]) -> Vec<u8> {
+        let mut framed = vec![];
+        for message in messages {
+            write_message(&mut framed, message).unwrap();
+        }
+        framed
+    }
+
+    #[test]
+    fn test_tag_prefix() {
+        assert_eq!(tag_prefix("D 1.00 lunch #fo", 16), Some("#fo"));
+        assert_eq!(tag_prefix("D 1.00 lunch #fo", 13), None);
+        assert_eq!(tag_prefix("D 1.00 lunch #fo bar", 16), Some("#fo"));
+        assert_eq!(tag_prefix("#", 1), Some("#"));
+    }
+
+    #[test]
+    fn test_serve() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(
+            directory.join("2023-01.ledger"),
+            "D 1.00 #food\nD 1.00 #fun\n",
+        )
+        .unwrap();
+
+        let uri = "file:///expenses/2023-02.ledger";
+        let input = frame(&[
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+            json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+            json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+                "textDocument": {"uri": uri, "text": "C 10.00 pay #job\nD 1.0x bad\nD 2.00 #f"},
+            }}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/hover", "params": {
+                "textDocument": {"uri": uri}, "position": {"line": 0, "character": 3},
+            }}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "textDocument/completion", "params": {
+                "textDocument": {"uri": uri}, "position": {"line": 2, "character": 9},
+            }}),
+            json!({"jsonrpc": "2.0", "id": 4, "method": "textDocument/definition", "params": {}}),
+            json!({"jsonrpc": "2.0", "id": 5, "method": "shutdown"}),
+            json!({"jsonrpc": "2.0", "method": "exit"}),
+        ]);
+
+        let mut output = vec![];
+        serve(
+            directory,
+            &Settings::default(),
+            input.as_slice(),
+            &mut output,
+        )
+        .unwrap();
+
+        let mut output = output.as_slice();
+        let mut responses = vec![];
+        while let Some(message) = read_message(&mut output).unwrap() {
+            responses.push(message);
+        }
+
+        assert_eq!(responses.len(), 6);
+        assert_eq!(
+            responses[0]["result"]["capabilities"]["hoverProvider"],
+            true
+        );
+
+        let diagnostics = &responses[1]["params"]["diagnostics"];
+        assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+        assert_eq!(diagnostics[0]["range"]["start"]["character"], 5);
+
+        assert_eq!(
+            responses[2]["result"]["contents"]["value"],
+            "**credit** of 10.00\n\ntags: #job"
+        );
+        assert_eq!(
+            responses[2]["result"]["range"],
+            json!({"start": {"line": 0, "character": 2}, "end": {"line": 0, "character": 7}})
+        );
+
+        let labels: Vec<_> = responses[3]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["label"].as_str().unwrap())
+            .collect();
+        assert_eq!(labels, vec!["#food", "#fun"]);
+
+        assert_eq!(responses[4]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[5]["id"], 5);
+    }
+}
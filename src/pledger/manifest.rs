@@ -0,0 +1,254 @@
+//! Content hashes of a ledger directory's ledgers and archives, for `pledger verify`.
+//!
+//! The manifest is optional: it's created by `pledger verify --update`, and from then on
+//! every change that pledger makes to a ledger is recorded in it. Any other change (an
+//! edit behind pledger's back, a bad sync, or bit-rot in old archives) shows up as a
+//! mismatch. The manifest is in `sha256sum`'s format, so `sha256sum -c` can check it too.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::pledger::archive::ARCHIVE_EXTENSION;
+use crate::pledger::{compress, lock_ledger, write_atomically, Error, Result, DATE_PATTERN};
+
+/// Where the manifest is kept, relative to the ledger directory.
+pub const MANIFEST_FILE: &str = ".pledger/manifest";
+
+/// A ledger file that doesn't match the manifest.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mismatch {
+    /// The file's contents have changed since they were recorded.
+    Modified(String),
+    /// The file was recorded, but doesn't exist anymore.
+    Missing(String),
+    /// The file exists, but was never recorded.
+    Untracked(String),
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Modified(name) => write!(f, "{} has been modified", name),
+            Mismatch::Missing(name) => write!(f, "{} is missing", name),
+            Mismatch::Untracked(name) => write!(f, "{} isn't in the manifest", name),
+        }
+    }
+}
+
+/// Returns whether a file name is a ledger's or an archive's, compressed or not.
+pub fn is_tracked(name: &str) -> bool {
+    let name = compress::strip_extension(name);
+    match name.rsplit_once('.') {
+        Some((date, "ledger")) => DATE_PATTERN.is_match(date),
+        Some((year, ARCHIVE_EXTENSION)) => {
+            year.len() == 4 && year.bytes().all(|b| b.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+fn hash(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Returns the name of every ledger file (ledger or archive) in `directory`, in order.
+pub fn ledger_files(directory: &Path) -> Result<Vec<String>> {
+    let mut names = vec![];
+    for entry in fs::read_dir(directory).map_err(Error::io("couldn't list the ledgers"))? {
+        let path = entry
+            .map_err(Error::io("couldn't list the ledgers"))?
+            .path();
+        match path.file_name().and_then(OsStr::to_str) {
+            Some(name) if is_tracked(name) && path.is_file() => names.push(name.to_string()),
+            _ => continue,
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Returns the hash of every ledger file in `directory`, by file name.
+fn hash_files(directory: &Path) -> Result<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+    for name in ledger_files(directory)? {
+        let path = directory.join(&name);
+        let contents =
+            fs::read(&path).map_err(Error::io(format!("couldn't read {}", path.display())))?;
+        hashes.insert(name, hash(&contents));
+    }
+    Ok(hashes)
+}
+
+/// Loads the manifest, if the directory has one.
+fn load(directory: &Path) -> Result<Option<BTreeMap<String, String>>> {
+    let path = directory.join(MANIFEST_FILE);
+    let manifest = match fs::read_to_string(&path) {
+        Ok(manifest) => manifest,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::io("manifest read failed")(e)),
+    };
+
+    let mut hashes = BTreeMap::new();
+    for (idx, line) in manifest.lines().enumerate() {
+        match line.split_once("  ") {
+            Some((hash, name)) if hash.len() == 64 => {
+                hashes.insert(name.to_string(), hash.to_string());
+            }
+            _ => {
+                return Err(Error::Parse {
+                    file: Some(path),
+                    line: Some(idx + 1),
+                    offset: None,
+                    message: "invalid manifest line".into(),
+                })
+            }
+        }
+    }
+    Ok(Some(hashes))
+}
+
+fn save(directory: &Path, hashes: &BTreeMap<String, String>) -> Result<()> {
+    let manifest: String = hashes
+        .iter()
+        .map(|(name, hash)| format!("{}  {}\n", hash, name))
+        .collect();
+    write_atomically(&directory.join(MANIFEST_FILE), &manifest, false)
+}
+
+/// Records the current hash of every ledger file in `directory`, creating the manifest if
+/// it doesn't exist yet. Returns the number of files recorded.
+pub fn update(directory: &Path) -> Result<usize> {
+    let _lock = lock_ledger(directory, "manifest")?;
+    let hashes = hash_files(directory)?;
+    fs::create_dir_all(directory.join(MANIFEST_FILE).parent().unwrap())
+        .map_err(Error::io("couldn't create the manifest's directory"))?;
+    save(directory, &hashes)?;
+    Ok(hashes.len())
+}
+
+/// Records a change that pledger made to the ledger file `name` in `directory`: its new
+/// contents, or `None` if it was removed. Does nothing if there's no manifest.
+pub(crate) fn record(directory: &Path, name: &str, contents: Option<&str>) -> Result<()> {
+    // The manifest has its own lock, since changes to different ledgers (which
+    // are only locked individually) can happen concurrently.
+    let _lock = lock_ledger(directory, "manifest")?;
+    let mut hashes = match load(directory)? {
+        Some(hashes) => hashes,
+        None => return Ok(()),
+    };
+
+    match contents {
+        Some(contents) => hashes.insert(name.into(), hash(contents.as_bytes())),
+        None => hashes.remove(name),
+    };
+    save(directory, &hashes)
+}
+
+/// Returns every ledger file in `directory` that doesn't match the manifest, in file name
+/// order, or `None` if there's no manifest.
+pub fn verify(directory: &Path) -> Result<Option<Vec<Mismatch>>> {
+    let recorded = match load(directory)? {
+        Some(recorded) => recorded,
+        None => return Ok(None),
+    };
+    let actual = hash_files(directory)?;
+
+    let mut names: Vec<&String> = recorded.keys().chain(actual.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    Ok(Some(
+        names
+            .into_iter()
+            .filter_map(|name| match (recorded.get(name), actual.get(name)) {
+                (Some(a), Some(b)) if a == b => None,
+                (Some(_), Some(_)) => Some(Mismatch::Modified(name.clone())),
+                (Some(_), None) => Some(Mismatch::Missing(name.clone())),
+                (None, _) => Some(Mismatch::Untracked(name.clone())),
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::{append_entry, edit_ledger, Settings};
+
+    #[test]
+    fn test_verify() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(directory.join("2023-05.ledger"), "D 1.00 a #x\n").unwrap();
+        fs::write(directory.join("2019.archive"), "# month: 2019-01\n").unwrap();
+        fs::write(directory.join("notes.txt"), "not a ledger\n").unwrap();
+
+        assert_eq!(verify(directory).unwrap(), None);
+        assert_eq!(update(directory).unwrap(), 2);
+        assert_eq!(verify(directory).unwrap(), Some(vec![]));
+
+        // Changes made by pledger are recorded, and others aren't.
+        append_entry(
+            directory,
+            "2023-05",
+            &Settings::default(),
+            &"D 2.00 b #x".parse().unwrap(),
+        )
+        .unwrap();
+        append_entry(
+            directory,
+            "2023-06",
+            &Settings::default(),
+            &"D 3.00 c #x".parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(verify(directory).unwrap(), Some(vec![]));
+
+        // So are edits, with an "editor" that replaces the ledger.
+        let edited = directory.join("edited.txt");
+        fs::write(
+            &edited,
+            "D 4.00 d #x
+",
+        )
+        .unwrap();
+        let editor = format!("cp {}", edited.display());
+        edit_ledger(
+            "2023-06",
+            directory,
+            None,
+            Some(&editor),
+            &Settings::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(directory.join("2023-06.ledger")).unwrap(),
+            "D 4.00 d #x\n"
+        );
+        assert_eq!(verify(directory).unwrap(), Some(vec![]));
+
+        fs::write(directory.join("2019.archive"), "# month: 2019-02\n").unwrap();
+        fs::remove_file(directory.join("2023-06.ledger")).unwrap();
+        fs::write(directory.join("2023-07.ledger"), "").unwrap();
+        assert_eq!(
+            verify(directory).unwrap(),
+            Some(vec![
+                Mismatch::Modified("2019.archive".into()),
+                Mismatch::Missing("2023-06.ledger".into()),
+                Mismatch::Untracked("2023-07.ledger".into()),
+            ])
+        );
+
+        assert!(is_tracked("2023-05.ledger.gz"));
+        assert!(!is_tracked("2023-05.ledger.bak"));
+    }
+}
@@ -0,0 +1,241 @@
+//! Merging two versions of the same ledger, e.g. a ledger and its sync conflict copy.
+//!
+//! Lines that are in both versions are kept once, in order, and lines that are only in
+//! one are kept as well, since entries are usually only ever added. Entries count as the
+//! same when they'd be duplicates of each other (see `dupes`). An entry that was changed
+//! differently in each version is a conflict: both versions are kept, between
+//! conflict markers that are ledger comments, so that the merged ledger still parses.
+//! An entry's continuation lines are merged along with it.
+
+use std::fs;
+use std::path::Path;
+
+use crate::pledger::dupes::normalize;
+use crate::pledger::{logical_lines, parse_line, Entry, Error, ParseOptions, Parsed, Result};
+
+/// An entry that was changed differently in each version of a ledger.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    /// The line of the merged ledger that the conflict starts on, 1-based.
+    pub line: usize,
+    pub ours: Entry,
+    pub theirs: Entry,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Merged {
+    pub text: String,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// A (logical) line of a ledger, with what identifies it for merging.
+struct Line {
+    text: String,
+    key: String,
+    entry: Option<Entry>,
+}
+
+fn lines(text: &str, options: &ParseOptions) -> Result<Vec<Line>> {
+    let physical: Vec<_> = text.lines().collect();
+    logical_lines(text)
+        .into_iter()
+        .map(|logical| {
+            let text = logical
+                .parts
+                .iter()
+                .map(|p| physical[p.idx])
+                .collect::<Vec<_>>()
+                .join("\n");
+            (logical, text)
+        })
+        .map(|(logical, text)| match parse_line(&logical.text, options) {
+            Ok(Parsed::Entry(entry)) => Ok(Line {
+                text,
+                key: format!(
+                    "{} {} {:?} {}",
+                    entry.kind.symbol(),
+                    entry.amount.subunits(),
+                    entry.day,
+                    normalize(&entry.comment)
+                ),
+                entry: Some(entry),
+            }),
+            Ok(Parsed::Balance(_)) | Err(None) => Ok(Line {
+                key: logical.text.trim().into(),
+                text,
+                entry: None,
+            }),
+            Err(Some(Error::Parse {
+                offset, message, ..
+            })) => {
+                let (idx, offset) = logical.locate(offset);
+                Err(Error::Parse {
+                    file: None,
+                    line: Some(idx + 1),
+                    offset,
+                    message,
+                })
+            }
+            Err(Some(e)) => Err(e),
+        })
+        .collect()
+}
+
+/// Returns whether two different entries look like edits of the same one: the same
+/// kind and day, and the same amount or comment.
+fn edited(ours: &Entry, theirs: &Entry) -> bool {
+    ours.kind == theirs.kind
+        && ours.day == theirs.day
+        && (ours.amount == theirs.amount || normalize(&ours.comment) == normalize(&theirs.comment))
+}
+
+/// Returns the pairs of indices of the longest common subsequence of the two versions.
+fn common(ours: &[Line], theirs: &[Line]) -> Vec<(usize, usize)> {
+    let mut lengths = vec![vec![0usize; theirs.len() + 1]; ours.len() + 1];
+    for i in (0..ours.len()).rev() {
+        for j in (0..theirs.len()).rev() {
+            lengths[i][j] = match ours[i].key == theirs[j].key {
+                true => lengths[i + 1][j + 1] + 1,
+                false => lengths[i + 1][j].max(lengths[i][j + 1]),
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = vec![];
+    while i < ours.len() && j < theirs.len() {
+        if ours[i].key == theirs[j].key {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Merges two versions of a ledger. `labels` name the versions in conflict markers.
+pub fn merge(
+    ours: &str,
+    theirs: &str,
+    labels: (&str, &str),
+    options: &ParseOptions,
+) -> Result<Merged> {
+    let ours = lines(ours, options)?;
+    let theirs = lines(theirs, options)?;
+
+    let mut out: Vec<String> = vec![];
+    let mut conflicts = vec![];
+
+    let mut hunk = |out: &mut Vec<String>, ours: &[Line], theirs: &[Line]| {
+        let mut theirs: Vec<Option<&Line>> = theirs.iter().map(Some).collect();
+        for line in ours.iter() {
+            let edit = line.entry.as_ref().and_then(|entry| {
+                theirs.iter_mut().find(|t| {
+                    t.and_then(|t| t.entry.as_ref())
+                        .is_some_and(|other| edited(entry, other))
+                })
+            });
+
+            match edit.and_then(Option::take) {
+                Some(other) => {
+                    conflicts.push(Conflict {
+                        line: out.len() + 1,
+                        ours: line.entry.clone().unwrap(),
+                        theirs: other.entry.clone().unwrap(),
+                    });
+                    out.push(format!("# <<<<<<< {}", labels.0));
+                    out.push(line.text.clone());
+                    out.push("# =======".into());
+                    out.push(other.text.clone());
+                    out.push(format!("# >>>>>>> {}", labels.1));
+                }
+                None => out.push(line.text.clone()),
+            }
+        }
+        out.extend(theirs.into_iter().flatten().map(|t| t.text.clone()));
+    };
+
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in common(&ours, &theirs) {
+        hunk(&mut out, &ours[i..next_i], &theirs[j..next_j]);
+        out.push(ours[next_i].text.clone());
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+    hunk(&mut out, &ours[i..], &theirs[j..]);
+
+    let mut text = out.join("\n");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    Ok(Merged { text, conflicts })
+}
+
+/// Merges the ledger files at `ours` and `theirs`.
+pub fn merge_files(ours: &Path, theirs: &Path, options: &ParseOptions) -> Result<Merged> {
+    let read = |path: &Path| {
+        fs::read_to_string(path).map_err(Error::io(format!("couldn't read {}", path.display())))
+    };
+    let (ours_text, theirs_text) = (read(ours)?, read(theirs)?);
+
+    // Check each version on its own first, so that errors point at the right file.
+    lines(&ours_text, options).map_err(|e| e.with_file(ours))?;
+    lines(&theirs_text, options).map_err(|e| e.with_file(theirs))?;
+
+    let labels = (ours.display().to_string(), theirs.display().to_string());
+    merge(&ours_text, &theirs_text, (&labels.0, &labels.1), options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let ours = "C 100.00 #pay\nD 5.00 lunch #food\nD 3.00 @02 coffee #fun\n";
+        let theirs =
+            "C 100.00 #pay\nD 5.00  Lunch #food\nD 4.00 @02 coffee #fun\nD 9.00 movie #fun\n";
+
+        let merged = merge(ours, theirs, ("ours", "theirs"), &ParseOptions::default()).unwrap();
+        assert_eq!(
+            merged.text,
+            "C 100.00 #pay\nD 5.00 lunch #food\n# <<<<<<< ours\nD 3.00 @02 coffee #fun\n\
+             # =======\nD 4.00 @02 coffee #fun\n# >>>>>>> theirs\nD 9.00 movie #fun\n"
+        );
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].line, 3);
+        assert_eq!(merged.conflicts[0].theirs.amount.subunits(), 400);
+
+        // Entries only in one version, with nothing to conflict with, are just kept.
+        let merged = merge(
+            "D 1.00 a\n",
+            "D 2.00 b\nD 1.00 a\n",
+            ("ours", "theirs"),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(merged.text, "D 2.00 b\nD 1.00 a\n");
+        assert!(merged.conflicts.is_empty());
+
+        assert!(merge(
+            "D 1.00 a\n",
+            "X 1.00 b\n",
+            ("ours", "theirs"),
+            &ParseOptions::default()
+        )
+        .is_err());
+
+        // Continuation lines are part of their entry's line.
+        let merged = merge(
+            "D 5.00 lunch\n  with sam #food\n",
+            "D 5.00 lunch\n  with sam #food\nD 2.00 tea\n",
+            ("ours", "theirs"),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(merged.text, "D 5.00 lunch\n  with sam #food\nD 2.00 tea\n");
+    }
+}
@@ -0,0 +1,184 @@
+//! Prometheus metrics for a month's ledger, in the text exposition format.
+//!
+//! These are served on `/metrics` by `pledger serve`, and written by `pledger metrics`
+//! for node_exporter's textfile collector.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::pledger::aggregate::Summary;
+use crate::pledger::config::Config;
+use crate::pledger::{load_ledger, Amount, Error, Ledger, Result, Settings};
+
+/// Escapes a label value.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn family(out: &mut String, name: &str, help: &str) {
+    writeln!(out, "# HELP pledger_{} {}", name, help).unwrap();
+    writeln!(out, "# TYPE pledger_{} gauge", name).unwrap();
+}
+
+/// Renders the metrics for a month's summary, and the budget's utilization.
+pub fn render(summary: &Summary, budget: &BTreeMap<String, Amount>) -> String {
+    let mut out = String::new();
+    let month = label(&summary.date);
+
+    family(&mut out, "entries", "Entries in the month's ledger.");
+    writeln!(
+        out,
+        "pledger_entries{{month=\"{}\"}} {}",
+        month, summary.entries
+    )
+    .unwrap();
+
+    for (name, total, tags) in [
+        ("credits", summary.credits, &summary.credit_tags),
+        ("debits", summary.debits, &summary.debit_tags),
+    ] {
+        family(&mut out, name, &format!("Total {} in the month.", name));
+        writeln!(
+            out,
+            "pledger_{}{{month=\"{}\"}} {}",
+            name,
+            month,
+            total.as_f64()
+        )
+        .unwrap();
+
+        family(
+            &mut out,
+            &format!("tag_{}", name),
+            &format!("Total {} in the month, by tag.", name),
+        );
+        for (tag, amount) in tags.points.iter() {
+            writeln!(
+                out,
+                "pledger_tag_{}{{month=\"{}\",tag=\"{}\"}} {}",
+                name,
+                month,
+                label(tag),
+                amount.as_f64()
+            )
+            .unwrap();
+        }
+    }
+
+    if !budget.is_empty() {
+        family(
+            &mut out,
+            "budget_limit",
+            "The monthly budget limit, by tag.",
+        );
+        for (tag, limit) in budget.iter() {
+            writeln!(
+                out,
+                "pledger_budget_limit{{tag=\"{}\"}} {}",
+                label(tag),
+                limit.as_f64()
+            )
+            .unwrap();
+        }
+
+        family(
+            &mut out,
+            "budget_utilization_ratio",
+            "The share of the monthly budget spent, by tag.",
+        );
+        for (tag, limit) in budget.iter() {
+            let spent = summary
+                .debit_tags
+                .points
+                .iter()
+                .find(|(t, _)| t == tag)
+                .map(|(_, amount)| amount.as_f64())
+                .unwrap_or(0.0);
+            let ratio = match limit.subunits() {
+                0 => 1.0,
+                _ => spent / limit.as_f64(),
+            };
+            writeln!(
+                out,
+                "pledger_budget_utilization_ratio{{month=\"{}\",tag=\"{}\"}} {}",
+                month,
+                label(tag),
+                ratio
+            )
+            .unwrap();
+        }
+    }
+
+    out
+}
+
+/// Renders the metrics for the ledger for `date` in `directory`, with the directory's
+/// budget. A missing ledger has metrics of zero, rather than being an error.
+pub fn for_month(directory: &Path, date: &str, settings: &Settings) -> Result<String> {
+    let ledger = match load_ledger(directory, date, &settings.parse) {
+        Ok(ledger) => ledger,
+        Err(Error::NotFound(_)) => Ledger {
+            date: date.into(),
+            entries: vec![],
+            balances: vec![],
+            errors: vec![],
+            format: settings.parse.amount_format,
+        },
+        Err(e) => return Err(e),
+    };
+
+    let budget = Config::load(directory)?.budgets(&settings.parse);
+    Ok(render(&Summary::new(&ledger)?, &budget))
+}
+
+/// Writes metrics to `path` atomically, as the textfile collector needs.
+pub fn write_textfile(path: &Path, metrics: &str) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::NotFound(format!("not a file: {}", path.display())))?;
+    let tmp = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+
+    fs::write(&tmp, metrics).map_err(Error::io(format!("couldn't write {}", tmp.display())))?;
+    fs::rename(&tmp, path).map_err(Error::io(format!("couldn't write {}", path.display())))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+    use crate::pledger::parse_ledger;
+    use crate::pledger::ParseOptions;
+
+    #[test]
+    fn test_render() {
+        let ledger = parse_ledger(
+            "2023-05",
+            Box::new(
+                "C 100.00 #pay\nD 12.50 #food\nD 2.50 #fun"
+                    .as_bytes()
+                    .lines(),
+            ),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let budget = BTreeMap::from([("#food".into(), Amount::from_subunits(2500))]);
+
+        let metrics = render(&Summary::new(&ledger).unwrap(), &budget);
+        assert!(
+            metrics.contains("# TYPE pledger_debits gauge\npledger_debits{month=\"2023-05\"} 15\n")
+        );
+        assert!(metrics.contains("pledger_tag_debits{month=\"2023-05\",tag=\"#food\"} 12.5\n"));
+        assert!(metrics.contains("pledger_budget_limit{tag=\"#food\"} 25\n"));
+        assert!(metrics
+            .contains("pledger_budget_utilization_ratio{month=\"2023-05\",tag=\"#food\"} 0.5\n"));
+
+        assert_eq!(label("a\"b\\c"), "a\\\"b\\\\c");
+    }
+}
@@ -1,25 +1,95 @@
 #![forbid(unsafe_code)]
 
-use std::collections::HashMap;
-use std::env;
+pub mod aggregate;
+pub mod amount;
+pub mod archive;
+pub mod attachment;
+pub mod backup;
+pub mod balance;
+pub mod budget;
+pub mod cache;
+pub mod categorize;
+pub mod chart;
+pub mod compress;
+pub mod config;
+pub mod db;
+pub mod debt;
+pub mod diagnostics;
+pub mod dupes;
+pub mod editor;
+pub mod email;
+pub mod envelope;
+pub mod error;
+pub mod export;
+pub mod finance;
+pub mod forecast;
+pub mod format;
+pub mod goal;
+pub mod grammar;
+pub mod id;
+pub mod import;
+pub mod init;
+pub mod journal;
+pub mod ledger_cli;
+pub mod limits;
+pub mod lsp;
+pub mod manifest;
+pub mod merge;
+pub mod metrics;
+pub mod networth;
+pub mod notify;
+pub mod ofx;
+pub mod output;
+pub mod parser;
+pub mod plan;
+pub mod plot;
+pub mod profile;
+pub mod query;
+pub mod recent;
+pub mod reconcile;
+pub mod recurring;
+pub mod remote;
+pub mod report;
+pub mod retag;
+pub mod rewrite;
+pub mod serve;
+pub mod settings;
+pub mod signing;
+pub mod site;
+pub mod source;
+pub mod store;
+pub mod syntax;
+pub mod testing;
+pub mod trend;
+pub mod webhook;
+pub mod wizard;
+
+use std::collections::BTreeMap;
 use std::ffi::OsStr;
+use std::fmt;
 use std::fs;
-use std::io::{self, BufRead};
-use std::path::Path;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::str::FromStr;
 
-use anyhow::{anyhow, Result};
-use chrono::Utc;
+use chrono::{NaiveDate, Utc};
 use lazy_static::lazy_static;
 use phf::phf_map;
 use regex::Regex;
-use serde::ser::SerializeTuple;
-use serde::{Serialize, Serializer};
-
+use serde::{Deserialize, Serialize};
+
+use crate::pledger::aggregate::{Report, Summary, TagShare};
+pub use crate::pledger::amount::{Amount, AmountFormat};
+use crate::pledger::budget::Budget;
+pub use crate::pledger::error::{Error, Result};
+use crate::pledger::parser::parse_entry_ref;
+pub use crate::pledger::settings::{ParseOptions, Settings, Strictness};
 use crate::pledger::EntryKind::*;
-use crate::pledger::EntryParseState::*;
 
 type LedgerLines = Box<dyn Iterator<Item = io::Result<String>>>;
+type LedgerReader = Box<dyn BufRead>;
 
 pub static MONTH_MAP: phf::Map<&'static str, u8> = phf_map! {
     "jan" => 1,
@@ -51,59 +121,343 @@ lazy_static! {
     static ref DATE_PATTERN: Regex = Regex::new(r"^\d{4}-(0[1-9]|1[0-2])$").unwrap();
 }
 
-#[derive(Copy, Clone, Debug)]
-enum EntryParseState {
-    Whitespace,
-    EntryKind,
-    Amount,
-    Comment,
-    Tag,
-}
-
-#[derive(Clone, Debug, PartialEq, Serialize)]
-enum EntryKind {
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum EntryKind {
     Debit,
     Credit,
+    /// Money moved between two accounts, which is neither income nor spending.
+    Transfer,
+    /// Money set aside in an envelope (its tag) for debits to draw down. See
+    /// [`crate::pledger::envelope`].
+    Allocation,
+}
+
+impl EntryKind {
+    /// Returns the letter that begins entries of this kind.
+    pub fn symbol(&self) -> char {
+        match self {
+            Debit => 'D',
+            Credit => 'C',
+            Transfer => 'T',
+            Allocation => 'A',
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Debit => "debit",
+            Credit => "credit",
+            Transfer => "transfer",
+            Allocation => "allocation",
+        }
+    }
+
+    pub fn plural(&self) -> &'static str {
+        match self {
+            Debit => "debits",
+            Credit => "credits",
+            Transfer => "transfers",
+            Allocation => "allocations",
+        }
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
-struct Entry {
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Entry {
     kind: EntryKind,
-    #[serde(serialize_with = "amount_serialize")]
-    amount: u64,
+    amount: Amount,
+    /// The amount's currency, if it was written with a symbol (e.g. `$12.50`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
     comment: String,
     tags: Vec<String>,
+    /// The day of the month, if the comment starts with an `@DD` marker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    day: Option<u32>,
+    /// The account the entry is for, if the comment has an `@account` marker. For
+    /// transfers, this is the account the money came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    account: Option<String>,
+    /// For transfers, the account the money went to: the second `@account` marker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    to: Option<String>,
+    /// Whether the entry has been reconciled against a statement, marked with `*`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    cleared: bool,
+    /// The path of a file attached to the entry (e.g. a receipt), if the comment has an
+    /// `@file:PATH` marker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attachment: Option<String>,
+    /// The entry's explicit ID, if the comment has a `^id` marker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    /// Where the entry came from, if it was parsed from a ledger.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<Box<EntrySource>>,
+}
+
+/// Where an entry came from: its ledger's date and file, and its line in the ledger.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct EntrySource {
+    pub date: String,
+    /// The ledger's file, which is an archive for archived months (in which case the line
+    /// is within the month's ledger, not the archive).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// The entry's (first) line in its ledger, 1-based.
+    pub line: usize,
+    /// The name of the ledger directory, in a combined view of several.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+}
+
+impl fmt::Display for EntrySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}",
+            self.file.as_deref().unwrap_or(&self.date),
+            self.line
+        )
+    }
+}
+
+impl Entry {
+    /// Returns where the entry came from, if it was parsed from a ledger.
+    pub fn source(&self) -> Option<&EntrySource> {
+        self.source.as_deref()
+    }
+
+    /// Returns the entry's amount as it's written in a ledger, with its currency symbol
+    /// if it has one.
+    pub fn amount_text(&self) -> String {
+        let symbol = self.currency.as_deref().and_then(amount::currency_symbol);
+        match symbol {
+            Some(symbol) => format!("{}{}", symbol, self.amount.to_ledger_string()),
+            None => self.amount.to_ledger_string(),
+        }
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.kind.symbol(),
+            self.amount_text(),
+            self.comment
+        )
+    }
+}
+
+impl FromStr for Entry {
+    type Err = Error;
+
+    /// Parses a single entry, in ledger syntax.
+    fn from_str(line: &str) -> Result<Self> {
+        Entry::parse(line, &ParseOptions::default())
+    }
+}
+
+impl Entry {
+    /// Parses a single entry, in ledger syntax, under `options`.
+    pub fn parse(line: &str, options: &ParseOptions) -> Result<Self> {
+        parse_entry(line, options).map_err(|e| match e {
+            Some(Error::Parse {
+                file,
+                line: n,
+                offset,
+                message,
+            }) => Error::Parse {
+                file,
+                line: n,
+                offset: char_offset(line, offset),
+                message,
+            },
+            Some(e) => e,
+            None => Error::syntax(None, "not an entry"),
+        })
+    }
+}
+
+/// A byte range in a ledger line.
+pub type Span = Range<usize>;
+
+/// An entry as parsed from its line, borrowing from the line rather than copying out
+/// of it, along with the span of each of its parts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryRef<'a> {
+    pub kind: EntryKind,
+    pub amount: Amount,
+    /// The span of the amount, including its currency symbol if it has one.
+    pub amount_span: Span,
+    pub currency: Option<&'static str>,
+    pub comment: &'a str,
+    pub comment_span: Span,
+    /// Each tag in the comment, in order, including any duplicates.
+    pub tags: Vec<(&'a str, Span)>,
+    pub day: Option<u32>,
+    pub account: Option<&'a str>,
+    pub to: Option<&'a str>,
+    pub cleared: bool,
+    pub attachment: Option<&'a str>,
+    pub id: Option<&'a str>,
+}
+
+impl EntryRef<'_> {
+    /// The span of the entry's kind, which is always the line's first character.
+    pub const KIND_SPAN: Span = 0..1;
+
+    /// Returns an owned copy of the entry, with its tags canonicalized under `options`.
+    pub fn to_entry(&self, options: &ParseOptions) -> Entry {
+        // Tag order is not preserved, and duplicate tags are not preserved. Aliased tags
+        // become their canonical names.
+        let mut tags: Vec<String> = self
+            .tags
+            .iter()
+            .map(|(t, _)| options.canonical_tag(t))
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+
+        Entry {
+            kind: self.kind.clone(),
+            amount: self.amount,
+            currency: self.currency.map(Into::into),
+            comment: self.comment.into(),
+            tags,
+            day: self.day,
+            account: self.account.map(Into::into),
+            to: self.to.map(Into::into),
+            cleared: self.cleared,
+            attachment: self.attachment.map(Into::into),
+            id: self.id.map(Into::into),
+            source: None,
+        }
+    }
 }
 
-fn amount_serialize<S>(amount: &u64, s: S) -> std::result::Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    let subunits: u64 = amount % 100;
-    let units: u64 = amount / 100;
-    let mut tup = s.serialize_tuple(2)?;
-    tup.serialize_element(&units)?;
-    tup.serialize_element(&subunits)?;
-    tup.end()
+/// A `balance` directive: an account's stated balance, as of the directive's position
+/// in the ledger.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Balance {
+    account: String,
+    amount: Amount,
+    /// Whether the balance is negative, e.g. for a credit card.
+    negative: bool,
+    /// The number of the ledger's entries that come before the directive.
+    position: usize,
+}
+
+impl Balance {
+    /// Returns the balance in (signed) subunits.
+    pub fn subunits(&self) -> i128 {
+        match self.negative {
+            true => -(self.amount.subunits() as i128),
+            false => self.amount.subunits() as i128,
+        }
+    }
 }
 
-fn amount_format(amount: &u64) -> String {
-    let subunits: u64 = amount % 100;
-    let units: u64 = amount / 100;
+impl fmt::Display for Balance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.negative { "-" } else { "" };
+        write!(
+            f,
+            "balance {}{} @{}",
+            sign,
+            self.amount.to_ledger_string(),
+            self.account
+        )
+    }
+}
 
-    format!("{:02}.{:02}", units, subunits)
+/// A line of a ledger that isn't blank or a comment.
+#[derive(Clone, Debug, PartialEq)]
+enum Parsed {
+    Entry(Entry),
+    Balance(Balance),
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Ledger {
     date: String,
     entries: Vec<Entry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    balances: Vec<Balance>,
+    /// The lines that couldn't be parsed, when parsing leniently.
+    #[serde(skip)]
+    errors: Vec<Error>,
+    /// The format that the ledger's amounts were parsed in, which its totals take.
+    #[serde(skip)]
+    format: AmountFormat,
 }
 
 impl Ledger {
-    pub fn filter(&mut self, tags: &[&str]) {
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+
+    /// Returns the errors for the lines that were skipped, when parsing leniently.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    pub fn amount_format(&self) -> AmountFormat {
+        self.format
+    }
+
+    /// Appends another ledger's entries and balances to this one.
+    pub fn merge(&mut self, other: Ledger) {
+        let offset = self.entries.len();
+        self.format = other.format;
+        self.entries.extend(other.entries);
+        self.errors.extend(other.errors);
+        self.balances
+            .extend(other.balances.into_iter().map(|b| Balance {
+                position: b.position + offset,
+                ..b
+            }));
+    }
+
+    /// Retains only the entries with any of the given tags, which are canonicalized like
+    /// the entries' were.
+    pub fn filter(&mut self, tags: &[&str], options: &ParseOptions) {
+        let tags: Vec<String> = tags.iter().map(|t| options.canonical_tag(t)).collect();
+        self.entries
+            .retain(|e| e.tags.iter().any(|t| tags.contains(t)));
+    }
+
+    pub fn query(&mut self, query: &query::Query) {
+        self.entries.retain(|e| query.matches(e));
+    }
+
+    /// Retains only the entries for the given account.
+    pub fn account(&mut self, account: &str) {
         self.entries
-            .retain(|e| e.tags.iter().any(|t| tags.contains(&t.as_ref())));
+            .retain(|e| e.account.as_deref() == Some(account));
+    }
+
+    /// Retains only the entries that have no tags.
+    pub fn untagged(&mut self) {
+        self.entries.retain(|e| e.tags.is_empty());
+    }
+
+    /// Marks each of the ledger's entries as coming from the directory named `directory`,
+    /// for combined views of several ledger directories.
+    pub fn set_directory(&mut self, directory: &str) {
+        for entry in self.entries.iter_mut() {
+            entry.source.get_or_insert_with(Default::default).directory = Some(directory.into());
+        }
+    }
+
+    /// Marks each of the ledger's entries as coming from `file`.
+    fn set_file(&mut self, file: &Path) {
+        for entry in self.entries.iter_mut() {
+            entry.source.get_or_insert_with(Default::default).file =
+                Some(file.display().to_string());
+        }
     }
 }
 
@@ -127,43 +481,99 @@ pub fn parse_date(date: &str) -> Result<String> {
         Ok(month) if (1..=12).contains(&month) => {
             Ok(format!("{}-{:02}", Utc::now().format("%Y"), month))
         }
-        Ok(month) => Err(anyhow!("month out of range: {}", month)),
-        Err(_) => Err(anyhow!("failed to parse supplied date: {}", date)),
+        Ok(month) => Err(Error::Date(format!("month out of range: {}", month))),
+        Err(_) => Err(Error::Date(format!(
+            "failed to parse supplied date: {}",
+            date
+        ))),
     }
 }
 
-pub fn read_ledger(directory: &Path, date: &str) -> Result<LedgerLines> {
+/// Returns the month `offset` months after `date` (or before it, if negative), e.g.
+/// `2023-01` with an offset of `-1` is `2022-12`.
+pub fn offset_date(date: &str, offset: i32) -> Result<String> {
+    if !DATE_PATTERN.is_match(date) {
+        return Err(Error::Date(format!("invalid date: {}", date)));
+    }
+
+    // The pattern guarantees that both parts parse.
+    let (year, month): (i64, i64) = (date[..4].parse().unwrap(), date[5..].parse().unwrap());
+    let months = year * 12 + (month - 1) + i64::from(offset);
+    if !(0..10000 * 12).contains(&months) {
+        return Err(Error::Date(format!(
+            "{} offset by {} months is out of range",
+            date, offset
+        )));
+    }
+
+    Ok(format!("{:04}-{:02}", months / 12, months % 12 + 1))
+}
+
+/// Opens the ledger for `date`, wherever it is: in its own file, compressed, or archived.
+pub fn open_ledger(directory: &Path, date: &str) -> Result<LedgerReader> {
     if !directory.is_dir() {
-        return Err(anyhow!("invalid ledger directory: {}", directory.display()));
+        return Err(Error::NotFound(format!(
+            "invalid ledger directory: {}",
+            directory.display()
+        )));
     }
 
     let ledger_file = directory.join(format!("{date}.ledger"));
     if !ledger_file.is_file() {
-        return Err(anyhow!(
+        if let Some(compressed) = compress::find(&ledger_file) {
+            let contents = compress::decompress(&compressed)?;
+            return Ok(Box::new(io::Cursor::new(contents.into_bytes())));
+        }
+
+        if let Some(lines) = archive::read_archived(directory, date)? {
+            return Ok(Box::new(io::Cursor::new(lines.join("\n").into_bytes())));
+        }
+
+        return Err(Error::NotFound(format!(
             "missing requested ledger file: {}",
             ledger_file.display()
-        ));
+        )));
     }
 
     match fs::File::open(ledger_file) {
-        Ok(file) => Ok(Box::new(io::BufReader::new(file).lines())),
-        Err(e) => Err(anyhow!("ledger file read failed: {}", e)),
+        Ok(file) => Ok(Box::new(io::BufReader::new(file))),
+        Err(e) => Err(Error::io("ledger file read failed")(e)),
+    }
+}
+
+/// Returns the file that the ledger for `date` is read from: its own file, its compressed
+/// file, or its year's archive (which may itself be compressed).
+pub fn ledger_source(directory: &Path, date: &str) -> PathBuf {
+    let ledger_file = directory.join(format!("{date}.ledger"));
+    if ledger_file.is_file() {
+        return ledger_file;
     }
+
+    compress::find(&ledger_file).unwrap_or_else(|| {
+        let archive = archive::archive_path(directory, date.get(..4).unwrap_or(date));
+        compress::find(&archive).unwrap_or(archive)
+    })
+}
+
+pub fn read_ledger(directory: &Path, date: &str) -> Result<LedgerLines> {
+    Ok(Box::new(open_ledger(directory, date)?.lines()))
 }
 
-pub fn read_all_ledgers(directory: &Path) -> Result<LedgerLines> {
-    let mut ledger_iters = vec![];
+/// Returns the dates of every ledger in the given directory, in date order.
+///
+/// `read_dir`'s order differs between filesystems, so everything that reads
+/// more than one ledger relies on this sorting for deterministic output.
+pub fn ledger_dates(directory: &Path) -> Result<Vec<String>> {
+    let mut dates = vec![];
     for entry in fs::read_dir(directory)? {
         let entry = entry?.path();
 
-        let date: String = match entry.extension().and_then(OsStr::to_str) {
-            Some("ledger") => entry
-                .with_extension("")
-                .file_name()
-                .unwrap()
-                .to_string_lossy()
-                .into_owned(),
-            Some(_) => continue,
+        let name = match entry.file_name().and_then(OsStr::to_str) {
+            Some(name) => compress::strip_extension(name),
+            None => continue,
+        };
+        let date = match name.strip_suffix(".ledger") {
+            Some(date) => date.to_string(),
             None => continue,
         };
 
@@ -172,457 +582,1460 @@ pub fn read_all_ledgers(directory: &Path) -> Result<LedgerLines> {
             continue;
         }
 
-        ledger_iters.push(read_ledger(directory, &date)?);
+        dates.push(date);
     }
+    dates.extend(archive::archived_dates(directory)?);
 
-    Ok(ledger_iters
-        .into_iter()
-        .fold(Box::new(std::iter::empty()) as LedgerLines, |acc, e| {
-            Box::new(acc.chain(e))
-        }))
+    // A ledger can be both compressed and not (or archived), in which case
+    // it's only listed once.
+    dates.sort();
+    dates.dedup();
+
+    Ok(dates)
 }
 
-pub fn read_ledgers_for_year(directory: &Path, year: &str) -> Result<LedgerLines> {
-    let mut ledger_iters = vec![];
-    for entry in fs::read_dir(directory)? {
-        let entry = entry?.path();
-        let date: String = match entry.extension().and_then(OsStr::to_str) {
-            Some("ledger") => entry
-                .with_extension("")
-                .file_name()
-                .unwrap()
-                .to_string_lossy()
-                .into_owned(),
-            Some(_) => continue,
-            None => continue,
-        };
+/// Parses a period of months that ledgers can be selected by: a year (`2023`), a quarter
+/// (`2023-Q2`), or a half-year (`2023-H1`). Returns it with its `Q` or `H` uppercased.
+pub fn parse_period(period: &str) -> Result<String> {
+    let (year, part) = period.split_once('-').unwrap_or((period, ""));
+    if year.len() != 4 || !year.chars().all(|c| c.is_ascii_digit()) {
+        return Err(Error::Date(format!("invalid year in period: {}", period)));
+    }
 
-        if !DATE_PATTERN.is_match(&date) {
-            log::debug!("skipping non-date file: {:?}", entry);
-            continue;
-        }
+    let part = part.to_uppercase();
+    let valid = match part.split_at(part.len().min(1)) {
+        ("", _) => true,
+        ("Q", n) => matches!(n, "1" | "2" | "3" | "4"),
+        ("H", n) => matches!(n, "1" | "2"),
+        _ => false,
+    };
+    match valid {
+        true if part.is_empty() => Ok(year.into()),
+        true => Ok(format!("{}-{}", year, part)),
+        false => Err(Error::Date(format!(
+            "expected a quarter (Q1-Q4) or a half (H1-H2): {}",
+            period
+        ))),
+    }
+}
 
-        if !date.starts_with(year) {
-            continue;
-        }
+/// Returns whether the ledger for `date` is in a period, as returned by `parse_period`.
+/// Quarters and halves are of the fiscal year, which starts in the month `start` (from 1):
+/// see `Settings::fiscal_year_start`.
+pub fn in_period(date: &str, period: &str, start: u32) -> bool {
+    let (year, part) = period.split_once('-').unwrap_or((period, ""));
+    let (date_year, date_month): (u32, u32) = match (
+        date.get(..4).and_then(|y| y.parse().ok()),
+        date.get(5..).and_then(|m| m.parse().ok()),
+    ) {
+        (Some(year), Some(month)) => (year, month),
+        _ => return false,
+    };
+
+    // The year that the month is in, and the month's number in that year, from 1.
+    let (fiscal_year, month) = match date_month >= start {
+        true => (date_year, date_month - start + 1),
+        false => (date_year.saturating_sub(1), date_month + 13 - start),
+    };
+    if year.parse() != Ok(fiscal_year) {
+        return false;
+    }
 
-        ledger_iters.push(read_ledger(directory, &date)?);
+    match part.split_at(part.len().min(1)) {
+        ("Q", n) => n.parse() == Ok(month.div_ceil(3)),
+        ("H", n) => n.parse() == Ok(month.div_ceil(6)),
+        _ => true,
     }
+}
 
-    Ok(ledger_iters
+/// Like `parse_monthly_ledgers`, but reading and parsing each ledger only as it's needed.
+pub fn stream_monthly_ledgers<'a>(
+    directory: &'a Path,
+    period: Option<&str>,
+    settings: &'a Settings,
+) -> Result<impl Iterator<Item = Result<Ledger>> + 'a> {
+    let mut dates = ledger_dates(directory)?;
+    dates.retain(|d| {
+        period
+            .map(|p| in_period(d, p, settings.fiscal_year_start))
+            .unwrap_or(true)
+    });
+
+    Ok(dates
         .into_iter()
-        .fold(Box::new(std::iter::empty()) as LedgerLines, |acc, e| {
-            Box::new(acc.chain(e))
-        }))
+        .map(move |d| cache::load_ledger(directory, &d, &settings.parse)))
 }
 
-pub fn edit_ledger(date: &str, ledger_dir: &Path) -> Result<()> {
-    let editor = match env::var("EDITOR") {
-        Ok(e) => e,
-        Err(e) => return Err(anyhow!("EDITOR lookup failed: {}", e)),
-    };
+/// Reads and parses each ledger in the directory (or only those in `period`, if given)
+/// separately, ordered by date. Unchanged ledgers come from the cache (see `cache`).
+pub fn parse_monthly_ledgers(
+    directory: &Path,
+    period: Option<&str>,
+    settings: &Settings,
+) -> Result<Vec<Ledger>> {
+    stream_monthly_ledgers(directory, period, settings)?.collect()
+}
 
-    let ledger_file = Path::new(ledger_dir).join(format!("{date}.ledger"));
-    if let Ok(status) = Command::new(editor.clone()).arg(ledger_file).status() {
-        if status.success() {
-            Ok(())
-        } else {
-            Err(anyhow!("EDITOR exited with: {}", status))
-        }
-    } else {
-        Err(anyhow!("failed to execute EDITOR: {}", editor))
+/// Combines ledgers into one labeled `label`, with their entries in the order given.
+pub fn combine_ledgers(label: &str, ledgers: impl IntoIterator<Item = Ledger>) -> Ledger {
+    let mut combined = Ledger {
+        date: label.into(),
+        entries: vec![],
+        balances: vec![],
+        errors: vec![],
+        format: AmountFormat::DEFAULT,
+    };
+    for ledger in ledgers {
+        combined.merge(ledger);
     }
+
+    combined
 }
 
-// TODO(ww): Maybe use PEGs or combinators here. Or maybe not. It's not a very complicated parser.
-pub fn parse_ledger(date: &str, ledger_lines: LedgerLines) -> Result<Ledger> {
-    let mut entries = Vec::new();
-    for (idx, line) in ledger_lines.enumerate() {
-        let line = match line {
-            Ok(line) => line,
-            Err(e) => return Err(anyhow!("ledger read failed: {}", e)),
-        };
+/// Reads and parses the ledger for `date`. Parse errors include the ledger's path.
+pub fn load_ledger(directory: &Path, date: &str, options: &ParseOptions) -> Result<Ledger> {
+    let ledger_file = directory.join(format!("{date}.ledger"));
+    let mut ledger = parse_ledger_reader(date, open_ledger(directory, date)?, options)
+        .map_err(|e| e.with_file(&ledger_file))?;
+    ledger.errors = ledger
+        .errors
+        .into_iter()
+        .map(|e| e.with_file(&ledger_file))
+        .collect();
+    ledger.set_file(&ledger_source(directory, date));
 
-        match parse_entry(&line) {
-            Ok(entry) => {
-                log::debug!("entry: {:?}", entry);
-                entries.push(entry);
-            }
-            Err(o) => match o {
-                None => continue, // No error, just an empty line or comment.
-                Some(e) => {
-                    return Err(anyhow!("parse error on line {}: {}", idx + 1, e));
-                }
-            },
-        }
+    Ok(ledger)
+}
+
+/// Parses a single ledger file, outside of any ledger directory. The ledger is labeled
+/// with the file's name, e.g. `2023-05` for `2023-05.ledger`.
+pub fn load_ledger_file(path: &Path, options: &ParseOptions) -> Result<Ledger> {
+    if !path.is_file() {
+        return Err(Error::NotFound(format!(
+            "missing requested ledger file: {}",
+            path.display()
+        )));
     }
 
-    #[allow(clippy::redundant_field_names)]
-    Ok(Ledger {
-        date: String::from(date),
-        entries: entries,
-    })
+    let label = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file = fs::File::open(path).map_err(Error::io("ledger file read failed"))?;
+
+    let mut ledger = parse_ledger_reader(&label, io::BufReader::new(file), options)
+        .map_err(|e| e.with_file(path))?;
+    ledger.errors = ledger
+        .errors
+        .into_iter()
+        .map(|e| e.with_file(path))
+        .collect();
+    ledger.set_file(path);
+
+    Ok(ledger)
 }
 
-fn parse_entry(line: &str) -> std::result::Result<Entry, Option<String>> {
-    lazy_static! {
-        static ref LOOKS_LIKE_COMMENT: Regex = Regex::new(r"^\s*#.*$").unwrap();
-    }
-
-    if line.is_empty() || LOOKS_LIKE_COMMENT.is_match(line) {
-        log::debug!("comment or blank: {}", line);
-        return Err(None);
-    }
-
-    // Parser transitions.
-    let (mut prev_state, mut cur_state) = (EntryKind, EntryKind);
-
-    // Parser state.
-    // NOTE(ww): kind is (pointlessly) initialized to Debit because Rust isn't clever enough to see
-    // that we always initialize it below.
-    let mut kind = Debit;
-    let mut amount = 0_u64;
-    let mut in_decimal_place = false;
-    let mut decimal_place = 0;
-    let mut comment = String::new();
-    let mut tags: Vec<String> = Vec::new();
-
-    for (idx, chr) in line.char_indices() {
-        log::debug!("parser transition: {:?} => {:?}", prev_state, cur_state);
-        match (prev_state, cur_state) {
-            (EntryKind, EntryKind) => {
-                kind = match chr {
-                    'C' => Credit,
-                    'D' => Debit,
-                    _ => {
-                        return Err(Some(format!(
-                            "offset {}: unexpected entry kind {}",
-                            idx, chr
-                        )))
-                    }
-                };
-                cur_state = Whitespace;
-            }
-            (EntryKind, Whitespace) => {
-                if chr.is_ascii_whitespace() {
-                    prev_state = Whitespace;
-                    cur_state = Amount;
-                } else {
-                    return Err(Some(format!(
-                        "offset {}: expected whitespace, got {}",
-                        idx, chr
-                    )));
-                }
-            }
-            (Whitespace, Amount) => {
-                if chr.is_ascii_digit() {
-                    amount *= 10;
-                    amount += chr as u64 - '0' as u64;
-                    prev_state = Amount;
-                } else {
-                    return Err(Some(format!("offset {}: expected digit, got {}", idx, chr)));
-                }
-            }
-            (Amount, Amount) => {
-                if chr.is_ascii_digit() {
-                    if in_decimal_place {
-                        decimal_place += 1;
-                    }
-                    if decimal_place > 2 {
-                        return Err(Some(format!(
-                            "offset {}: more than two decimal places in value",
-                            idx
-                        )));
-                    }
-                    amount *= 10;
-                    amount += chr as u64 - '0' as u64;
-                } else if chr == '.' {
-                    if in_decimal_place {
-                        return Err(Some(format!(
-                            "offset {}: more than one decimal supplied in value",
-                            idx
-                        )));
-                    } else {
-                        in_decimal_place = true;
-                    }
-                } else if chr == ',' {
-                    // NOTE(ww): We could count places here to make sure that commas
-                    // are inserted in reasonable locations, but that would complicate the parser.
-                    continue;
-                } else if chr.is_ascii_whitespace() {
-                    if in_decimal_place && decimal_place < 2 {
-                        return Err(Some(format!(
-                            "offset {}: one or more decimals missing from decimal place",
-                            idx
-                        )));
-                    }
-                    // NOTE(ww): More state transition cheating -- we've just consumed
-                    // the whitespace, so there's no point in wasting another state on it.
-                    prev_state = Comment;
-                    cur_state = Comment;
-                } else {
-                    return Err(Some(format!(
-                        "offset {}: expected digit or whitespace, got {}",
-                        idx, chr
-                    )));
-                }
-            }
-            (Comment, Comment) => {
-                if chr == '#' {
-                    let tag = String::from("#");
-                    tags.push(tag);
-                    cur_state = Tag;
-                }
-                comment.push(chr);
-            }
-            (Comment, Tag) => {
-                if chr.is_ascii_whitespace() {
-                    return Err(Some(format!("offset {}: premature tag ending", idx)));
-                } else if chr.is_ascii_graphic() {
-                    // Add the current character to both the comment and
-                    // the most recent tag.
-                    comment.push(chr);
-                    tags.last_mut().unwrap().push(chr);
-
-                    prev_state = Tag;
-                } else {
-                    return Err(Some(format!(
-                        "offset {}: invalid tag character: {}",
-                        idx, chr
-                    )));
-                }
-            }
-            (Tag, Tag) => {
-                if chr.is_ascii_whitespace() {
-                    comment.push(chr);
-
-                    // NOTE(ww): Again, a little cheating: we pretend we've already begun
-                    // the comment to avoid a completely duplicated (Tag, Comment)
-                    // transition.
-                    prev_state = Comment;
-                    cur_state = Comment;
-                } else if chr.is_ascii_graphic() {
-                    comment.push(chr);
-                    tags.last_mut().unwrap().push(chr);
-                } else {
-                    return Err(Some(format!(
-                        "offset {}: invalid tag character: {}",
-                        idx, chr
-                    )));
-                }
-            }
-            (_, _) => {
-                return Err(Some(format!(
-                    "unexpected parser state transition: {:?} => {:?}! probable bug.",
-                    prev_state, cur_state
-                )))
-            }
-        }
+pub fn edit_ledger(
+    date: &str,
+    ledger_dir: &Path,
+    template: Option<&Path>,
+    editor: Option<&str>,
+    settings: &Settings,
+) -> Result<()> {
+    let editor = editor::find_editor(editor)?;
+
+    if !ledger_dir.is_dir() {
+        return Err(Error::NotFound(format!(
+            "invalid ledger directory: {}",
+            ledger_dir.display()
+        )));
     }
 
-    // Tag order is not preserved, and duplicate tags are not preserved.
-    tags.sort_unstable();
-    tags.dedup();
+    ensure_unarchived(ledger_dir, date)?;
 
-    match (prev_state, cur_state) {
-        (Comment, Comment) | (Tag, Tag) => Ok(Entry {
-            kind,
-            amount,
-            comment,
-            tags,
-        }),
-        (_, _) => Err(Some("unexpected EOL; missing comment?".into())),
+    let ledger_file = ledger_dir.join(format!("{date}.ledger"));
+    if !ledger_file.exists() {
+        create_ledger(ledger_dir, date, template)?;
     }
-}
 
-pub fn summarize(ledger: &Ledger) {
-    println!("Ledger for {}\n", ledger.date);
-    println!("Summary:");
+    // In a dry run, a new ledger's creation is only planned, so there's nothing to open
+    // the editor on; the plan is all there is to show.
+    if plan::dry_run() {
+        return Ok(());
+    }
 
-    let num_entries = ledger.entries.len();
-    let total_credits = ledger
-        .entries
-        .iter()
-        .filter(|e| e.kind == Credit)
-        .fold(0, |acc, e| acc + e.amount);
-    let total_debits = ledger
-        .entries
-        .iter()
-        .filter(|e| e.kind == Debit)
-        .fold(0, |acc, e| acc + e.amount);
+    let backup_file = backup::backup_ledger(ledger_dir, date)?;
+    if let Some(backup_file) = &backup_file {
+        log::info!("backed up {} to {}", date, backup_file.display());
+    }
 
-    let (net, kind) = if total_credits >= total_debits {
-        (total_credits - total_debits, "credit")
-    } else {
-        (total_debits - total_credits, "debit")
-    };
+    // Each edit is journaled (and recorded in the manifest) as it's made, rather
+    // than once at the end, since restoring the pre-edit version is a change of its own.
+    let name = format!("{date}.ledger");
+    let mut before = fs::read_to_string(&ledger_file).ok();
+    loop {
+        match Command::new(&editor[0])
+            .args(&editor[1..])
+            .arg(&ledger_file)
+            .status()
+        {
+            Ok(status) if status.success() => {}
+            Ok(status) => return Err(Error::Editor(format!("editor exited with: {}", status))),
+            Err(e) => {
+                return Err(Error::Editor(format!(
+                    "failed to execute editor {}: {}",
+                    editor[0], e
+                )))
+            }
+        }
 
-    println!(
-        "\t{} entries, totaling {} in credits and {} in debits for a net of {} in {}\n",
-        num_entries,
-        amount_format(&total_credits),
-        amount_format(&total_debits),
-        amount_format(&net),
-        kind
-    );
+        // Nothing to validate if the editor didn't leave a file behind.
+        if !ledger_file.is_file() {
+            return manifest::record(ledger_dir, &name, None);
+        }
 
-    let mut tags_by_credit = HashMap::new();
-    let mut tags_by_debit = HashMap::new();
+        let after =
+            fs::read_to_string(&ledger_file).map_err(Error::io("ledger file read failed"))?;
+        journal::record(ledger_dir, date, before.as_deref(), &after)?;
+        manifest::record(ledger_dir, &name, Some(&after))?;
+        before = Some(after);
 
-    for entry in ledger.entries.iter() {
-        let map = match entry.kind {
-            Credit => &mut tags_by_credit,
-            Debit => &mut tags_by_debit,
+        let err = match parse_ledger(date, read_ledger(ledger_dir, date)?, &settings.parse) {
+            Ok(_) => return Ok(()),
+            Err(e) => e,
         };
 
-        for tag in entry.tags.iter() {
-            let tag_value = map.entry(tag).or_insert(0);
-            *tag_value += entry.amount;
+        eprintln!("{} has errors: {}", ledger_file.display(), err);
+
+        let question = match backup_file {
+            Some(_) => "[e]dit again, [r]estore the pre-edit version, or [k]eep anyway? [e] ",
+            None => "[e]dit again, or [k]eep anyway? [e] ",
+        };
+
+        match prompt(question)?.as_deref().map(str::trim) {
+            Some("" | "e") => continue,
+            Some("r") if backup_file.is_some() => {
+                let name = backup_file.unwrap().file_name().unwrap().to_owned();
+                backup::restore_ledger(ledger_dir, date, settings, Some(&name.to_string_lossy()))?;
+                eprintln!("restored the pre-edit version of {}", date);
+                return Ok(());
+            }
+            Some("k") => return Ok(()),
+            Some(_) => eprintln!("unrecognized choice, editing again"),
+            // No answer means that we aren't interactive, so don't loop forever.
+            None => return Err(err.with_file(&ledger_file)),
         }
     }
+}
 
-    let mut sorted_credits: Vec<_> = tags_by_credit.iter().collect();
-    sorted_credits.sort_by(|a, b| b.1.cmp(a.1));
+/// The file in a ledger directory that its new ledgers are seeded from, if it exists.
+pub const TEMPLATE_FILE: &str = "template.ledger";
+
+/// Expands the variables in a ledger template for the ledger for `date`: `{{date}}`
+/// (e.g. `2023-11`), `{{year}}`, `{{month}}` (`11`), and `{{month_name}}` (`November`).
+/// Anything else in braces is left as it is.
+fn expand_template(template: &str, date: &str) -> String {
+    let month_name = NaiveDate::parse_from_str(&format!("{date}-01"), "%Y-%m-%d")
+        .map(|d| d.format("%B").to_string())
+        .unwrap_or_default();
+
+    template
+        .replace("{{date}}", date)
+        .replace("{{year}}", date.get(..4).unwrap_or_default())
+        .replace("{{month}}", date.get(5..).unwrap_or_default())
+        .replace("{{month_name}}", &month_name)
+}
 
-    let mut sorted_debits: Vec<_> = tags_by_debit.iter().collect();
-    sorted_debits.sort_by(|a, b| b.1.cmp(a.1));
+/// Returns the initial contents of a new ledger for `date`: `template` (or else the
+/// directory's `TEMPLATE_FILE`, if it has one) with its variables expanded.
+fn new_ledger_contents(directory: &Path, date: &str, template: Option<&Path>) -> Result<String> {
+    let contents = match template {
+        Some(template) => fs::read_to_string(template).map_err(Error::io(format!(
+            "template read failed: {}",
+            template.display()
+        )))?,
+        None => match fs::read_to_string(directory.join(TEMPLATE_FILE)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::io("template read failed")(e)),
+        },
+    };
 
-    println!("Top credit tags:");
-    for credit in sorted_credits.iter() {
-        println!("{:<16} {:>10}", credit.0, amount_format(credit.1));
+    Ok(expand_template(&contents, date))
+}
+
+/// Creates a new ledger file for `date`, seeding it from a template.
+fn create_ledger(directory: &Path, date: &str, template: Option<&Path>) -> Result<()> {
+    let ledger_file = directory.join(format!("{date}.ledger"));
+    let contents = new_ledger_contents(directory, date, template)?;
+
+    if plan::change(&ledger_file, None, Some(&contents)) {
+        return Ok(());
     }
 
-    println!("\nTop debit tags:");
-    for credit in sorted_debits.iter() {
-        println!("{:<16} {:>10}", credit.0, amount_format(credit.1));
+    log::info!("creating new ledger: {}", ledger_file.display());
+    write_atomically(&ledger_file, &contents, false)?;
+    journal::record(directory, date, None, &contents)
+}
+
+/// Fails if the ledger for `date` is archived or compressed, rather than in its own file.
+fn ensure_unarchived(directory: &Path, date: &str) -> Result<()> {
+    let ledger_file = directory.join(format!("{date}.ledger"));
+    if ledger_file.is_file() {
+        return Ok(());
+    }
+
+    if let Some(compressed) = compress::find(&ledger_file) {
+        return Err(Error::Archived(format!(
+            "the ledger for {} is compressed, in {}",
+            date,
+            compressed.display()
+        )));
+    }
+
+    if archive::read_archived(directory, date)?.is_some() {
+        return Err(Error::Archived(format!(
+            "the ledger for {} is archived in {}.{}",
+            date,
+            &date[..4],
+            archive::ARCHIVE_EXTENSION
+        )));
     }
+
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Where per-ledger lock files live, relative to the ledger directory.
+const LOCK_DIR: &str = ".pledger/locks";
+
+/// Takes an exclusive lock on the ledger for `date`, blocking until it's available.
+/// The lock is released when the returned file is dropped.
+///
+/// The lock is on a separate file, since writes replace the ledger itself.
+fn lock_ledger(directory: &Path, date: &str) -> Result<fs::File> {
+    let lock_dir = directory.join(LOCK_DIR);
+    fs::create_dir_all(&lock_dir)
+        .map_err(Error::io(format!("couldn't create {}", lock_dir.display())))?;
+
+    let lock_file = fs::File::create(lock_dir.join(format!("{date}.lock")))
+        .map_err(Error::io("ledger lock file creation failed"))?;
+    lock_file
+        .lock()
+        .map_err(Error::io("couldn't lock the ledger"))?;
+
+    Ok(lock_file)
+}
 
-    #[test]
-    fn test_parse_date() {
-        let current_year = Utc::now().format("%Y").to_string();
+/// Writes `contents` to `path` atomically: to a temporary file next to it, which is synced
+/// and then renamed into place, so that a crash leaves either the old version or the new
+/// one, and never a truncated one. A file that's replaced keeps its permissions, and with
+/// `backup` (see `Settings::keep_backups`), its old version is kept as `<path>.bak`. Ledger files' new hashes are recorded
+/// in the manifest, if there is one.
+fn write_atomically(path: &Path, contents: &str, backup: bool) -> Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_file = path.with_file_name(format!(".{}.tmp", name));
+    let existing = fs::metadata(path).ok();
+
+    let write = || -> io::Result<()> {
+        let mut file = fs::File::create(&temp_file)?;
+        file.write_all(contents.as_bytes())?;
+        if let Some(metadata) = &existing {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
 
-        assert_eq!(
-            parse_date(format!("{}-01", current_year).as_str()).unwrap(),
-            format!("{}-01", current_year)
-        );
-        assert_eq!(
-            parse_date("january").unwrap(),
-            format!("{}-01", current_year)
-        );
-        assert_eq!(parse_date("jan").unwrap(), format!("{}-01", current_year));
-        assert_eq!(parse_date("1").unwrap(), format!("{}-01", current_year));
-        assert_eq!(parse_date("01").unwrap(), format!("{}-01", current_year));
+        if backup && existing.is_some() {
+            fs::copy(path, path.with_file_name(format!("{}.bak", name)))?;
+        }
+        fs::rename(&temp_file, path)?;
 
-        assert_eq!(
-            parse_date("13").unwrap_err().to_string(),
-            "month out of range: 13"
-        );
-        assert_eq!(
-            parse_date("not_a_real_month").unwrap_err().to_string(),
-            "failed to parse supplied date: not_a_real_month"
-        );
-    }
+        // The rename itself is only durable once the directory is synced, which
+        // can only be done (and only needs to be) on Unix.
+        #[cfg(unix)]
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    };
 
-    #[test]
-    fn test_parse_entry() {
-        // Whitespace and comments.
-        assert_eq!(parse_entry(""), Err(None));
-        assert_eq!(parse_entry("# this is a comment"), Err(None));
-        assert_eq!(parse_entry("   # this is a comment"), Err(None));
+    write().map_err(|e| {
+        let _ = fs::remove_file(&temp_file);
+        Error::io(format!("couldn't write {}", path.display()))(e)
+    })?;
 
-        // Misc. syntax errors.
-        assert_eq!(
-            parse_entry("D1.00"),
-            Err(Some("offset 1: expected whitespace, got 1".to_string()))
-        );
-        assert_eq!(
-            parse_entry("D 1.00foo"),
-            Err(Some(
-                "offset 6: expected digit or whitespace, got f".to_string()
-            ))
-        );
+    match path.parent() {
+        Some(directory) if manifest::is_tracked(&name) => {
+            manifest::record(directory, &name, Some(contents))
+        }
+        _ => Ok(()),
+    }
+}
 
-        // Entry kinds.
-        assert_eq!(
-            parse_entry("X 1.00 test"),
-            Err(Some("offset 0: unexpected entry kind X".to_string()))
-        );
+/// Replaces the contents of the ledger for `date` with `update`'s result, given its current
+/// contents (or, if it doesn't exist yet, the directory's template).
+///
+/// The ledger is locked for the duration, and the new version is written atomically, so
+/// concurrent writers (or a crash) can't corrupt it. The ledger is left
+/// alone if it's unchanged, and in a dry run the new version is only planned. Changes to its
+/// entries are recorded in the journal, if the directory has one.
+pub fn update_ledger(
+    directory: &Path,
+    date: &str,
+    settings: &Settings,
+    update: impl FnOnce(String) -> Result<String>,
+) -> Result<()> {
+    if !directory.is_dir() {
+        return Err(Error::NotFound(format!(
+            "invalid ledger directory: {}",
+            directory.display()
+        )));
+    }
 
-        let entry = parse_entry("C 1.00 test").unwrap();
-        assert_eq!(entry.kind, EntryKind::Credit);
+    let _lock = lock_ledger(directory, date)?;
+    ensure_unarchived(directory, date)?;
 
-        let entry = parse_entry("D 1.00 test").unwrap();
-        assert_eq!(entry.kind, EntryKind::Debit);
+    let ledger_file = directory.join(format!("{date}.ledger"));
+    let current = match plan::planned(&ledger_file) {
+        Some(planned) => planned,
+        None => match fs::read_to_string(&ledger_file) {
+            Ok(contents) => Some(contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(Error::io("ledger file read failed")(e)),
+        },
+    };
+    let contents = match &current {
+        Some(contents) => update(contents.clone())?,
+        None => update(new_ledger_contents(directory, date, None)?)?,
+    };
 
-        // Amounts.
-        assert_eq!(
-            parse_entry("D abc"),
-            Err(Some("offset 2: expected digit, got a".to_string()))
-        );
-        assert_eq!(
-            parse_entry("D 1.000"),
-            Err(Some(
-                "offset 6: more than two decimal places in value".to_string()
-            ))
-        );
-        assert_eq!(
-            parse_entry("D 1.0.0"),
-            Err(Some(
-                "offset 5: more than one decimal supplied in value".to_string()
-            ))
-        );
+    if current.as_deref() == Some(contents.as_str())
+        || plan::change(&ledger_file, current.as_deref(), Some(&contents))
+    {
+        return Ok(());
+    }
 
-        let entry = parse_entry("C 1.00 test").unwrap();
-        assert_eq!(entry.amount, 100);
+    write_atomically(&ledger_file, &contents, settings.keep_backups)?;
+    journal::record(directory, date, current.as_deref(), &contents)
+}
 
-        let entry = parse_entry("D 100.00 test").unwrap();
-        assert_eq!(entry.amount, 10000);
+/// Appends an entry to the ledger for `date`, creating the ledger if it doesn't exist.
+/// Like `update_ledger`, this is safe to do concurrently.
+pub fn append_entry(
+    directory: &Path,
+    date: &str,
+    settings: &Settings,
+    entry: &Entry,
+) -> Result<()> {
+    store::append_entry(
+        &store::DirectoryStore::new(directory, settings.clone()),
+        date,
+        entry,
+    )
+}
 
-        let entry = parse_entry("C 100 test").unwrap();
-        assert_eq!(entry.amount, 100);
+/// Asks a question on stderr, returning the answer or `None` if stdin is closed.
+fn prompt(question: &str) -> Result<Option<String>> {
+    eprint!("{}", question);
+    io::stderr().flush()?;
 
-        // Comments and tags.
-        assert_eq!(
-            parse_entry("D 1"),
-            Err(Some("unexpected EOL; missing comment?".to_string()))
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer)? {
+        0 => Ok(None),
+        _ => Ok(Some(answer)),
+    }
+}
+
+pub fn parse_ledger(
+    date: &str,
+    ledger_lines: LedgerLines,
+    options: &ParseOptions,
+) -> Result<Ledger> {
+    let mut ledger = Ledger {
+        date: String::from(date),
+        entries: vec![],
+        balances: vec![],
+        errors: vec![],
+        format: options.amount_format,
+    };
+    let mut pending = LogicalLine::default();
+    for (idx, line) in ledger_lines.enumerate() {
+        let line = line.map_err(Error::io("ledger read failed"))?;
+        ledger.push_line(&mut pending, idx, &line, options)?;
+    }
+    ledger.flush(&mut pending, options)?;
+
+    Ok(ledger)
+}
+
+/// Like `parse_ledger`, but reading each line into the same buffer rather than
+/// allocating a new one. Leniently parsed ledgers keep the errors for the lines that they
+/// skipped, rather than failing on the first one.
+pub fn parse_ledger_reader(
+    date: &str,
+    mut reader: impl BufRead,
+    options: &ParseOptions,
+) -> Result<Ledger> {
+    let mut ledger = Ledger {
+        date: String::from(date),
+        entries: vec![],
+        balances: vec![],
+        errors: vec![],
+        format: options.amount_format,
+    };
+    let mut line = String::new();
+    let mut pending = LogicalLine::default();
+    for idx in 0.. {
+        line.clear();
+        if reader
+            .read_line(&mut line)
+            .map_err(Error::io("ledger read failed"))?
+            == 0
+        {
+            break;
+        }
+
+        let trimmed = line.strip_suffix('\n').unwrap_or(&line);
+        ledger.push_line(
+            &mut pending,
+            idx,
+            trimmed.strip_suffix('\r').unwrap_or(trimmed),
+            options,
+        )?;
+    }
+    ledger.flush(&mut pending, options)?;
+
+    Ok(ledger)
+}
+
+/// Returns whether `line` continues the entry before it: it's indented, and isn't blank
+/// or a comment. Since tags can't be empty, a `#` followed by whitespace (or nothing)
+/// begins a comment, and any other `#` begins a tag.
+fn is_continuation(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let comment = trimmed
+        .strip_prefix('#')
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace));
+
+    trimmed.len() < line.len() && !trimmed.is_empty() && !comment
+}
+
+/// Where a line of a ledger starts in a `LogicalLine`.
+#[derive(Debug)]
+struct Part {
+    /// The line's (0-based) index in the ledger.
+    idx: usize,
+    /// The line's byte offset in the logical line's text.
+    start: usize,
+    /// How many characters of indentation were stripped from the line.
+    indent: usize,
+}
+
+/// A logical line of a ledger: a line, along with any continuation lines after it, which
+/// are joined onto it with a space (and without their indentation).
+#[derive(Debug, Default)]
+struct LogicalLine {
+    text: String,
+    parts: Vec<Part>,
+}
+
+impl LogicalLine {
+    fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.text.clear();
+        self.parts.clear();
+    }
+
+    /// Adds the (0-based) `idx`th line of the ledger, as the first line or a continuation.
+    fn push(&mut self, idx: usize, line: &str) {
+        if self.is_empty() {
+            self.text.push_str(line);
+            self.parts.push(Part {
+                idx,
+                start: 0,
+                indent: 0,
+            });
+            return;
+        }
+
+        let trimmed = line.trim_start();
+        self.text.push(' ');
+        self.parts.push(Part {
+            idx,
+            start: self.text.len(),
+            indent: line[..line.len() - trimmed.len()].chars().count(),
+        });
+        self.text.push_str(trimmed);
+    }
+
+    /// Returns whether continuation lines can be joined onto this one, which is
+    /// only the case for entries (and directives).
+    fn continues(&self) -> bool {
+        !self.text.is_empty()
+            && !self.text.starts_with(char::is_whitespace)
+            && !self.text.starts_with('#')
+    }
+
+    /// Returns the text of each of the lines that make up this one, without indentation.
+    fn lines(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().enumerate().map(|(i, part)| {
+            let end = self
+                .parts
+                .get(i + 1)
+                .map_or(self.text.len(), |p| p.start - 1);
+            &self.text[part.start..end]
+        })
+    }
+
+    /// Returns the (0-based) index of the line that a byte offset in the text is on, and
+    /// the offset as a character offset in that line.
+    fn locate(&self, offset: Option<usize>) -> (usize, Option<usize>) {
+        let offset = match offset {
+            Some(offset) => offset,
+            None => return (self.parts[0].idx, None),
+        };
+        let part = self
+            .parts
+            .iter()
+            .rev()
+            .find(|p| p.start <= offset)
+            .unwrap_or(&self.parts[0]);
+        let column = self
+            .text
+            .get(part.start..offset)
+            .map_or(0, |prefix| prefix.chars().count());
+
+        (part.idx, Some(part.indent + column))
+    }
+
+    /// Returns the (0-based) index of the line that a span of the text is in, and the
+    /// span in that line, given the ledger's `lines`.
+    fn physical(&self, lines: &[&str], span: &Span) -> (usize, Span) {
+        let part = self
+            .parts
+            .iter()
+            .rev()
+            .find(|p| p.start <= span.start)
+            .unwrap();
+        let line = lines[part.idx];
+        let indent = match part.start {
+            0 => 0,
+            _ => line.len() - line.trim_start().len(),
+        };
+        let start = span.start - part.start + indent;
+        (part.idx, start..start + span.len())
+    }
+}
+
+/// Splits a ledger's text into its logical lines.
+fn logical_lines(text: &str) -> Vec<LogicalLine> {
+    let mut lines: Vec<LogicalLine> = vec![];
+    for (idx, line) in text.lines().enumerate() {
+        match lines.last_mut() {
+            Some(last) if last.continues() && is_continuation(line) => last.push(idx, line),
+            _ => {
+                let mut logical = LogicalLine::default();
+                logical.push(idx, line);
+                lines.push(logical);
+            }
+        }
+    }
+    lines
+}
+
+impl Ledger {
+    /// Adds the (0-based) `idx`th line of the ledger to the pending logical line if it's
+    /// a continuation of it, or else parses the pending line and starts a new one.
+    fn push_line(
+        &mut self,
+        pending: &mut LogicalLine,
+        idx: usize,
+        line: &str,
+        options: &ParseOptions,
+    ) -> Result<()> {
+        if pending.continues() && is_continuation(line) {
+            pending.push(idx, line);
+            return Ok(());
+        }
+
+        self.flush(pending, options)?;
+        pending.push(idx, line);
+        Ok(())
+    }
+
+    /// Parses the pending logical line, if there is one, adding its entry or directive.
+    fn flush(&mut self, pending: &mut LogicalLine, options: &ParseOptions) -> Result<()> {
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let result = self.push_logical(pending, options);
+        pending.clear();
+        result
+    }
+
+    fn push_logical(&mut self, logical: &LogicalLine, options: &ParseOptions) -> Result<()> {
+        let parsed = match parse_line(&logical.text, options) {
+            Ok(Parsed::Entry(entry))
+                if options.strictness == Strictness::Strict && entry.tags.is_empty() =>
+            {
+                Err(Some(Error::syntax(None, "entry has no tags")))
+            }
+            parsed => parsed,
+        };
+
+        let err = match parsed {
+            Ok(Parsed::Entry(mut entry)) => {
+                log::debug!("entry: {:?}", entry);
+                entry.source = Some(Box::new(EntrySource {
+                    date: self.date.clone(),
+                    line: logical.parts[0].idx + 1,
+                    ..Default::default()
+                }));
+                self.entries.push(entry);
+                return Ok(());
+            }
+            Ok(Parsed::Balance(mut balance)) => {
+                log::debug!("balance: {:?}", balance);
+                balance.position = self.entries.len();
+                self.balances.push(balance);
+                return Ok(());
+            }
+            // No error, just an empty line or comment.
+            Err(None) => return Ok(()),
+            Err(Some(Error::Parse {
+                offset, message, ..
+            })) => {
+                let (idx, offset) = logical.locate(offset);
+                Error::Parse {
+                    file: None,
+                    line: Some(idx + 1),
+                    offset,
+                    message,
+                }
+            }
+            Err(Some(e)) => e,
+        };
+
+        match options.strictness {
+            Strictness::Lenient => {
+                log::debug!("skipping line {}: {}", logical.parts[0].idx + 1, err);
+                self.errors.push(err);
+                Ok(())
+            }
+            _ => Err(err),
+        }
+    }
+}
+
+/// Parses a single line of a ledger: an entry, or a directive. Like `parse_entry`, blank
+/// lines and comments produce `Err(None)`.
+fn parse_line(line: &str, options: &ParseOptions) -> std::result::Result<Parsed, Option<Error>> {
+    match line.split_whitespace().next() {
+        Some("balance") => parse_balance(line, options).map(Parsed::Balance),
+        _ => parse_entry(line, options).map(Parsed::Entry),
+    }
+}
+
+/// Parses a `balance [-]AMOUNT @account` directive. The amount has to have its decimal
+/// places (if amounts have any): an entry's `15` is fifteen subunits, so a balance's
+/// isn't allowed to mean fifteen units.
+fn parse_balance(
+    line: &str,
+    options: &ParseOptions,
+) -> std::result::Result<Balance, Option<Error>> {
+    let mut words = line.split_whitespace().skip(1);
+    let (amount, account) = match (words.next(), words.next(), words.next()) {
+        (Some(amount), Some(account), None) => (amount, account),
+        _ => {
+            return Err(Some(Error::syntax(
+                None,
+                "expected balance AMOUNT @account",
+            )))
+        }
+    };
+
+    let offset = line.find(amount);
+    let (negative, amount) = match amount.strip_prefix('-') {
+        Some(amount) => (true, amount),
+        None => (false, amount),
+    };
+    let format = options.amount_format;
+    if format.places > 0 && !amount.contains(format.decimal) {
+        return Err(Some(Error::syntax(
+            offset,
+            format!(
+                "expected {} in value: {}",
+                amount::describe_places(format.places),
+                amount
+            ),
+        )));
+    }
+    let amount = Amount::parse_with(amount, format, options.loose_grouping)
+        .map_err(|e| Some(Error::syntax(offset, e.to_string())))?;
+
+    let account = match account.strip_prefix('@') {
+        Some(account) if !account.is_empty() && !account.chars().all(|c| c.is_ascii_digit()) => {
+            account
+        }
+        _ => {
+            return Err(Some(Error::syntax(
+                line.rfind(account),
+                format!("expected an @account, got {}", account),
+            )))
+        }
+    };
+
+    Ok(Balance {
+        account: account.into(),
+        amount,
+        negative: negative && amount != Amount::ZERO,
+        position: 0,
+    })
+}
+
+fn parse_entry(line: &str, options: &ParseOptions) -> std::result::Result<Entry, Option<Error>> {
+    parse_entry_ref(line, options).map(|entry| entry.to_entry(options))
+}
+
+/// The markers in an entry's comment: whether it's cleared (a leading `*`), its leading
+/// `@DD` day, any `@account`s, its `@file:PATH` attachment, and its trailing `^id`.
+#[derive(Default)]
+struct Markers<'a> {
+    day: Option<u32>,
+    accounts: Vec<&'a str>,
+    cleared: bool,
+    attachment: Option<&'a str>,
+    id: Option<&'a str>,
+}
+
+/// Returns whether `chr` can be part of a tag: anything but whitespace and control
+/// characters, so that e.g. `#café` and `#🍕` are tags.
+fn is_tag_char(chr: char) -> bool {
+    !chr.is_whitespace() && !chr.is_control()
+}
+
+/// Returns whether `word` is a whole tag, e.g. `#food`.
+pub fn is_tag(word: &str) -> bool {
+    word.len() > 1 && word.starts_with('#') && word.chars().all(is_tag_char)
+}
+
+/// Returns a byte offset in `line` (as the parser reports them) as a character offset,
+/// for errors that people read.
+fn char_offset(line: &str, offset: Option<usize>) -> Option<usize> {
+    offset.map(|o| line.get(..o).map_or(o, |prefix| prefix.chars().count()))
+}
+
+/// Returns the markers in a comment.
+fn parse_markers(comment: &str) -> std::result::Result<Markers<'_>, Option<Error>> {
+    let mut markers = Markers::default();
+    let words: Vec<_> = comment.split_whitespace().collect();
+
+    // A `*` only clears an entry right after its amount, and a day only comes
+    // right after that, so that e.g. `5 * 3` or `coffee @3 shops` elsewhere are just text.
+    let rest = match words.split_first() {
+        Some((&"*", rest)) => {
+            markers.cleared = true;
+            rest
+        }
+        _ => &words,
+    };
+    if let Some(day) = rest
+        .first()
+        .and_then(|w| w.strip_prefix('@'))
+        .filter(|d| !d.is_empty() && d.chars().all(|c| c.is_ascii_digit()))
+    {
+        markers.day = match day.parse::<u32>() {
+            Ok(d) if (1..=31).contains(&d) => Some(d),
+            _ => {
+                return Err(Some(Error::syntax(
+                    None,
+                    format!("day out of range: @{}", day),
+                )))
+            }
+        };
+    }
+
+    for word in words {
+        let marker = match word.strip_prefix('@') {
+            Some(marker) if !marker.is_empty() => marker,
+            _ => continue,
+        };
+
+        if let Some(path) = marker.strip_prefix("file:") {
+            if path.is_empty() {
+                return Err(Some(Error::syntax(None, "expected a path after @file:")));
+            }
+            if markers.attachment.replace(path).is_some() {
+                return Err(Some(Error::syntax(
+                    None,
+                    "more than one attachment in entry",
+                )));
+            }
+            continue;
+        }
+
+        if !marker.chars().all(|c| c.is_ascii_digit()) {
+            markers.accounts.push(marker);
+        }
+    }
+
+    // Only the comment's last word can be its ID, so that a `^` anywhere else
+    // (e.g. `x^2`, or `^^`) is just text.
+    markers.id = comment
+        .split_whitespace()
+        .last()
+        .and_then(|word| word.strip_prefix('^'))
+        .filter(|id| id::is_valid(id));
+
+    Ok(markers)
+}
+
+/// Prints each of the ledger's entries, in ledger syntax, after their directory's name
+/// if they're from a combined view of several.
+pub fn list_entries(ledger: &Ledger) {
+    for entry in ledger.entries.iter() {
+        match entry.source.as_ref().and_then(|s| s.directory.as_ref()) {
+            Some(directory) => println!("{}: {}", directory, entry),
+            None => println!("{}", entry),
+        }
+    }
+}
+
+/// Prints the summary report. If `top` is given, only that many of the largest tags
+/// are listed individually, with the rest combined into an `(other)` row. `exceeded` is
+/// how many amounts are over the config's sanity limits.
+pub fn summarize(summary: &Summary, top: Option<usize>, exceeded: usize) {
+    let report = Report::new(summary, top);
+
+    println!("Ledger for {}\n", report.date);
+    println!("Summary:");
+
+    println!(
+        "\t{} entries, totaling {} in credits and {} in debits for a net of {} in {}\n",
+        report.entries,
+        report.credits,
+        report.debits,
+        report.net,
+        report.net_kind.name()
+    );
+
+    if report.errors > 0 {
+        println!(
+            "\t{} line(s) couldn't be parsed, and were skipped\n",
+            report.errors
+        );
+    }
+    if exceeded > 0 {
+        println!(
+            "\t{} amount(s) are over the config's limits, and may be typos\n",
+            exceeded
+        );
+    }
+
+    // The ratios are all relative to credits, so they're meaningless without any.
+    if report.credits > Amount::ZERO {
+        println!(
+            "\t{:.1}% of credits saved, and {:.1}% spent",
+            report.ratios.savings_rate, report.ratios.expense_ratio
+        );
+        if let Some(top) = &report.ratios.top_expense {
+            println!(
+                "\tthe largest debit tag, {}, was {:.1}% of credits",
+                top.tag, top.percent
+            );
+        }
+        println!();
+    }
+
+    let print_shares = |shares: &[TagShare]| {
+        for share in shares.iter() {
+            println!(
+                "{:<16} {:>10} {:>5.1}%",
+                share.tag, share.amount, share.percent
+            );
+        }
+    };
+
+    println!("Top credit tags:");
+    print_shares(&report.credit_tags);
+
+    println!("\nTop debit tags:");
+    print_shares(&report.debit_tags);
+
+    // Ledgers that don't use accounts don't need an accounts section.
+    if report
+        .accounts
+        .iter()
+        .any(|a| a.account != aggregate::NO_ACCOUNT)
+    {
+        println!("\nAccounts:");
+        for account in report.accounts.iter() {
+            println!(
+                "{:<16} {:>10} in, {:>10} out",
+                account.account, account.credits, account.debits
+            );
+        }
+    }
+}
+
+/// Returns how much was spent on a budgeted tag.
+fn budget_spent(summary: &Summary, tag: &str) -> Amount {
+    summary
+        .debit_tags
+        .points
+        .iter()
+        .find(|(t, _)| t == tag)
+        .map(|(_, amount)| *amount)
+        .unwrap_or(Amount::ZERO)
+}
+
+/// Returns the budgeted tags that are over their budgets.
+pub fn over_budget<'a>(summary: &Summary, budgets: &'a BTreeMap<String, Budget>) -> Vec<&'a str> {
+    budgets
+        .iter()
+        .filter(|(tag, budget)| budget.is_over(budget_spent(summary, tag)))
+        .map(|(tag, _)| tag.as_str())
+        .collect()
+}
+
+pub fn summarize_budget(summary: &Summary, budgets: &BTreeMap<String, Budget>) {
+    if budgets.is_empty() {
+        return;
+    }
+
+    println!("\nBudget:");
+    for (tag, budget) in budgets.iter() {
+        let spent = budget_spent(summary, tag);
+
+        let percent = match budget.effective() {
+            ..=0 => 100,
+            effective => spent.subunits() as i128 * 100 / effective,
+        };
+
+        // Budgets that carried something over say so, since the effective
+        // budget isn't the one in the config.
+        let carried = match budget.carried {
+            0 => String::new(),
+            _ => format!(" ({})", budget.describe()),
+        };
+
+        println!(
+            "{:<16} {:>10} / {:>10} {:>4}%{}{}",
+            tag,
+            spent,
+            balance::signed(budget.effective(), budget.limit.format()),
+            percent,
+            if budget.is_over(spent) { " (over)" } else { "" },
+            carried
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the offset and message of the parse error for `line`, or `None` if it's
+    /// blank or a comment.
+    fn entry_error(line: &str) -> Option<(Option<usize>, String)> {
+        match parse_entry(line, &ParseOptions::default()) {
+            Ok(entry) => panic!("unexpectedly parsed: {:?}", entry),
+            Err(None) => None,
+            Err(Some(Error::Parse {
+                offset, message, ..
+            })) => Some((offset, message)),
+            Err(Some(e)) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let current_year = Utc::now().format("%Y").to_string();
+
+        assert_eq!(
+            parse_date(format!("{}-01", current_year).as_str()).unwrap(),
+            format!("{}-01", current_year)
+        );
+        assert_eq!(
+            parse_date("january").unwrap(),
+            format!("{}-01", current_year)
+        );
+        assert_eq!(parse_date("jan").unwrap(), format!("{}-01", current_year));
+        assert_eq!(parse_date("1").unwrap(), format!("{}-01", current_year));
+        assert_eq!(parse_date("01").unwrap(), format!("{}-01", current_year));
+
+        assert!(matches!(parse_date("13"), Err(Error::Date(m)) if m == "month out of range: 13"));
+        assert!(matches!(
+            parse_date("not_a_real_month"),
+            Err(Error::Date(_))
+        ));
+    }
+
+    #[test]
+    fn test_period() {
+        assert_eq!(parse_period("2023").unwrap(), "2023");
+        assert_eq!(parse_period("2023-q2").unwrap(), "2023-Q2");
+        assert_eq!(parse_period("2023-H1").unwrap(), "2023-H1");
+        for invalid in ["23", "2023-Q5", "2023-H3", "2023-Q", "2023-05", "2023-X1"] {
+            assert!(
+                matches!(parse_period(invalid), Err(Error::Date(_))),
+                "{}",
+                invalid
+            );
+        }
+
+        assert!(in_period("2023-05", "2023", 1));
+        assert!(!in_period("2022-05", "2023", 1));
+        assert!(in_period("2023-04", "2023-Q2", 1));
+        assert!(in_period("2023-06", "2023-Q2", 1));
+        assert!(!in_period("2023-07", "2023-Q2", 1));
+        assert!(in_period("2023-12", "2023-Q4", 1));
+        assert!(in_period("2023-06", "2023-H1", 1));
+        assert!(!in_period("2023-07", "2023-H1", 1));
+        assert!(in_period("2023-07", "2023-H2", 1));
+
+        assert!(in_period("2023-04", "2023", 4));
+        assert!(in_period("2024-03", "2023", 4));
+        assert!(!in_period("2023-03", "2023", 4));
+        assert!(in_period("2023-03", "2022-Q4", 4));
+        assert!(in_period("2023-06", "2023-Q1", 4));
+        assert!(in_period("2023-10", "2023-H2", 4));
+    }
+
+    #[test]
+    fn test_offset_date() {
+        assert_eq!(offset_date("2023-05", 0).unwrap(), "2023-05");
+        assert_eq!(offset_date("2023-01", -1).unwrap(), "2022-12");
+        assert_eq!(offset_date("2023-03", -3).unwrap(), "2022-12");
+        assert_eq!(offset_date("2023-12", 1).unwrap(), "2024-01");
+        assert_eq!(offset_date("2023-05", -29).unwrap(), "2020-12");
+        assert_eq!(offset_date("2023-05", 24).unwrap(), "2025-05");
+
+        assert!(matches!(offset_date("0000-01", -1), Err(Error::Date(_))));
+        assert!(matches!(offset_date("9999-12", 1), Err(Error::Date(_))));
+        assert!(matches!(offset_date("2023-13", 1), Err(Error::Date(_))));
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        // Whitespace and comments.
+        assert_eq!(entry_error(""), None);
+        assert_eq!(entry_error("# this is a comment"), None);
+        assert_eq!(entry_error("   # this is a comment"), None);
+
+        // Misc. syntax errors.
+        assert_eq!(
+            entry_error("D1.00"),
+            Some((Some(1), "expected whitespace, got 1".into()))
+        );
+        assert_eq!(
+            entry_error("D 1.00foo"),
+            Some((Some(6), "expected digit or whitespace, got f".into()))
         );
+
+        // Entry kinds.
+        assert_eq!(
+            entry_error("X 1.00 test"),
+            Some((Some(0), "unexpected entry kind X".into()))
+        );
+
+        let entry = parse_entry("C 1.00 test", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.kind, EntryKind::Credit);
+
+        let entry = parse_entry("D 1.00 test", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.kind, EntryKind::Debit);
+
+        // Amounts.
+        assert_eq!(
+            entry_error("D abc"),
+            Some((Some(2), "expected digit, got a".into()))
+        );
+        assert_eq!(
+            entry_error("D 1.000"),
+            Some((Some(6), "more than two decimal places in value".into()))
+        );
+        assert_eq!(
+            entry_error("D 1.0.0"),
+            Some((Some(5), "more than one decimal supplied in value".into()))
+        );
+
         assert_eq!(
-            parse_entry("D 1 # bar"),
-            Err(Some("offset 5: premature tag ending".to_string()))
+            entry_error("D 9,99 brot").map(|(offset, _)| offset),
+            Some(Some(3))
         );
         assert_eq!(
-            parse_entry("D 1 foo # bar"),
-            Err(Some("offset 9: premature tag ending".to_string()))
+            entry_error("D 1,2,3.45 typo"),
+            Some((
+                Some(3),
+                "misplaced ',' in amount; digits are grouped in threes, e.g. 1,234,567".into()
+            ))
         );
         assert_eq!(
-            parse_entry("D 1 foo #\x01"),
-            Err(Some("offset 9: invalid tag character: \x01".to_string()))
+            entry_error("D 1.00,5 typo"),
+            Some((Some(6), "unexpected ',' in decimal place".into()))
         );
+        assert!(parse_entry("D $1,234,567.00 windfall", &ParseOptions::default()).is_ok());
+        let loose = ParseOptions {
+            loose_grouping: true,
+            ..ParseOptions::default()
+        };
+        let entry = parse_entry("D 1,2,3.45 typo", &loose).unwrap();
+        assert_eq!(entry.amount, Amount::from_subunits(12345));
+
         assert_eq!(
-            parse_entry("D 1 #foo #\x01"),
-            Err(Some("offset 10: invalid tag character: \x01".to_string()))
+            entry_error("D 184467440737095516.16 too much"),
+            Some((Some(22), "amount out of range".into()))
         );
 
-        let entry = parse_entry("C 1.00 foo bar baz").unwrap();
+        let entry = parse_entry("C 1.00 test", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.amount, Amount::from_subunits(100));
+
+        let entry = parse_entry("D 100.00 test", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.amount, Amount::from_subunits(10000));
+
+        let entry = parse_entry("C 100 test", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.amount, Amount::from_subunits(100));
+
+        // Comments and tags.
+        assert_eq!(
+            entry_error("D 1"),
+            Some((None, "unexpected EOL; missing comment?".into()))
+        );
+        assert_eq!(
+            entry_error("D 1 # bar"),
+            Some((Some(5), "premature tag ending".into()))
+        );
+        assert_eq!(
+            entry_error("D 1 foo # bar"),
+            Some((Some(9), "premature tag ending".into()))
+        );
+        assert_eq!(
+            entry_error("D 1 foo #\x01"),
+            Some((Some(9), "invalid tag character: \x01".into()))
+        );
+        assert_eq!(
+            entry_error("D 1 #foo #\x01"),
+            Some((Some(10), "invalid tag character: \x01".into()))
+        );
+
+        let entry = parse_entry("C 1.00 foo bar baz", &ParseOptions::default()).unwrap();
         assert_eq!(entry.comment, "foo bar baz".to_string());
         assert_eq!(entry.tags, Vec::<String>::new());
 
-        let entry = parse_entry("C 1.00 foo #bar baz").unwrap();
+        let entry = parse_entry("C 1.00 foo #bar baz", &ParseOptions::default()).unwrap();
         assert_eq!(entry.comment, "foo #bar baz".to_string());
         assert_eq!(entry.tags, vec!["#bar"]);
 
-        let entry = parse_entry("C 1.00 foo #bar #baz").unwrap();
+        let entry = parse_entry("C 1.00 foo #bar #baz", &ParseOptions::default()).unwrap();
         assert_eq!(entry.comment, "foo #bar #baz".to_string());
         assert_eq!(entry.tags, vec!["#bar", "#baz"]);
 
-        let entry = parse_entry("C 1.00 #foo").unwrap();
+        let entry = parse_entry("C 1.00 #foo", &ParseOptions::default()).unwrap();
         assert_eq!(entry.comment, "#foo".to_string());
         assert_eq!(entry.tags, vec!["#foo"]);
+        assert_eq!(entry.day, None);
+
+        // Days.
+        let entry = parse_entry("D 1.00 @05 lunch #food", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.day, Some(5));
+        assert_eq!(entry.tags, vec!["#food"]);
+        assert_eq!(
+            parse_entry("D 1.00 * @5 lunch", &ParseOptions::default())
+                .unwrap()
+                .day,
+            Some(5)
+        );
+
+        // Anywhere else, an all-digit `@` word is just text.
+        let entry = parse_entry("D 3.00 coffee x2 @3 shops", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.day, None);
+        assert_eq!(entry.account, None);
+
+        let entry = parse_entry("D 1.00 dinner @ joe's", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.day, None);
+        assert_eq!(entry.account, None);
+
+        // Accounts.
+        let entry = parse_entry(
+            "D 20.00 @05 @checking lunch #food",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(entry.account.as_deref(), Some("checking"));
+        assert_eq!(entry.day, Some(5));
+
+        // Only the first account is the entry's; the rest are just comment text.
+        let entry = parse_entry("D 3.00 @cash coffee for @sam", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.account.as_deref(), Some("cash"));
+        assert_eq!(entry.comment, "@cash coffee for @sam");
+        let entry = parse_entry("T 100.00 @a @b for @c", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.account.as_deref(), Some("a"));
+        assert_eq!(entry.to.as_deref(), Some("b"));
+
+        // Cleared entries.
+        let entry = parse_entry("D 1.00 * lunch #food", &ParseOptions::default()).unwrap();
+        assert!(entry.cleared);
+        assert!(
+            parse_entry("D 1.00 *", &ParseOptions::default())
+                .unwrap()
+                .cleared
+        );
+        assert!(
+            !parse_entry("D 1.00 lunch *nice* #food", &ParseOptions::default())
+                .unwrap()
+                .cleared
+        );
+        assert!(
+            !parse_entry("D 1.00 lunch * 3 #food", &ParseOptions::default())
+                .unwrap()
+                .cleared
+        );
+
+        // Transfers.
+        let entry = parse_entry(
+            "T 100.00 @checking @savings rainy day",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(entry.kind, EntryKind::Transfer);
+        assert_eq!(entry.account.as_deref(), Some("checking"));
+        assert_eq!(entry.to.as_deref(), Some("savings"));
+        assert_eq!(
+            entry_error("T 100.00 @checking rainy day"),
+            Some((
+                None,
+                "transfers need exactly two accounts: @from @to".into()
+            ))
+        );
+
+        assert_eq!(
+            entry_error("D 1.00 @32 lunch"),
+            Some((None, "day out of range: @32".into()))
+        );
+        assert_eq!(
+            parse_entry("D 1.00 @1 lunch @2", &ParseOptions::default())
+                .unwrap()
+                .day,
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_parse_entry_unicode() {
+        let entry: Entry = "D 4.50 crème brûlée @café #café #🍕".parse().unwrap();
+        assert_eq!(entry.comment, "crème brûlée @café #café #🍕");
+        assert_eq!(entry.tags, vec!["#café", "#🍕"]);
+        assert_eq!(entry.account.as_deref(), Some("café"));
+
+        // Offsets are in characters, not bytes.
+        match "D 1.00 naïve #\u{7}".parse::<Entry>() {
+            Err(Error::Parse { offset, .. }) => assert_eq!(offset, Some(14)),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match parse_ledger_reader(
+            "2023-05",
+            "D 1.00 ok\nD 1.00 é # x\n".as_bytes(),
+            &ParseOptions::default().with_strictness(Strictness::Normal),
+        ) {
+            Err(Error::Parse { line, offset, .. }) => {
+                assert_eq!(line, Some(2));
+                assert_eq!(offset, Some(10));
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let entry = parse_entry_ref("D 1.00 é #ü", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.tags, vec![("#ü", 10..13)]);
+    }
+
+    #[test]
+    fn test_parse_entry_currency() {
+        let entry: Entry = "D $12.50 lunch #food".parse().unwrap();
+        assert_eq!(entry.amount.subunits(), 1250);
+        assert_eq!(entry.currency.as_deref(), Some("USD"));
+        assert_eq!(entry.comment, "lunch #food");
+        assert_eq!(entry.to_string(), "D $12.50 lunch #food");
+
+        let entry = parse_entry_ref("D €9.99 brot", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.currency, Some("EUR"));
+        assert_eq!(entry.amount_span, 2..9);
+
+        let entry: Entry = "D 3.00 coffee".parse().unwrap();
+        assert_eq!(entry.currency, None);
+        assert!("D % 3.00 coffee".parse::<Entry>().is_err());
+        assert!("D $ 3.00 coffee".parse::<Entry>().is_err());
+    }
+
+    #[test]
+    fn test_parse_balance() {
+        let balance = parse_balance("balance -1,234.50 @card", &ParseOptions::default()).unwrap();
+        assert_eq!(balance.account, "card");
+        assert_eq!(balance.subunits(), -123450);
+        assert_eq!(balance.to_string(), "balance -1234.50 @card");
+        assert_eq!(
+            parse_balance("balance 15.00 @cash", &ParseOptions::default())
+                .unwrap()
+                .subunits(),
+            1500
+        );
+        let yen = ParseOptions {
+            amount_format: AmountFormat {
+                decimal: '.',
+                places: 0,
+            },
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            parse_balance("balance 1,500 @cash", &yen)
+                .unwrap()
+                .subunits(),
+            1500
+        );
+
+        let error = |line| match parse_line(line, &ParseOptions::default()) {
+            Err(Some(Error::Parse {
+                offset, message, ..
+            })) => Some((offset, message)),
+            _ => None,
+        };
+        assert_eq!(
+            error("balance 1.00"),
+            Some((None, "expected balance AMOUNT @account".into()))
+        );
+        assert_eq!(
+            error("balance 15 @cash"),
+            Some((Some(8), "expected two decimal places in value: 15".into()))
+        );
+        assert_eq!(
+            error("balance 1.0 @cash"),
+            Some((Some(8), "expected two decimal places in value: 1.0".into()))
+        );
+        assert_eq!(
+            error("balance 1.00 cash"),
+            Some((Some(13), "expected an @account, got cash".into()))
+        );
+
+        let ledger = parse_ledger(
+            "2023-05",
+            Box::new(
+                "balance 10.00 @bank\nD 1.00 @bank a\nbalance 9.00 @bank"
+                    .as_bytes()
+                    .lines(),
+            ),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(ledger.entries.len(), 1);
+        assert_eq!(
+            ledger
+                .balances
+                .iter()
+                .map(|b| b.position)
+                .collect::<Vec<_>>(),
+            vec![0, 1]
+        );
     }
 
     #[test]
@@ -631,11 +2044,382 @@ mod tests {
         let ledger = parse_ledger(
             "01-01-1970",
             Box::new("C 1.00 #foo\nD 1.00 #bar".as_bytes().lines()),
+            &ParseOptions::default(),
         )
         .unwrap();
 
         assert_eq!(ledger.entries.len(), 2);
         assert_eq!(ledger.date, "01-01-1970");
+
+        let err = parse_ledger(
+            "01-01-1970",
+            Box::new("C 1.00 #foo\n\nD 1.00foo".as_bytes().lines()),
+            &ParseOptions::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Parse {
+                file: None,
+                line: Some(3),
+                offset: Some(6),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_parse_entry_ref() {
+        let line = "T 1,200.00  rent @checking @landlord #rent #home #rent";
+        let entry = parse_entry_ref(line, &ParseOptions::default()).unwrap();
+        assert_eq!(&line[entry.amount_span.clone()], "1,200.00");
+        assert_eq!(entry.comment, " rent @checking @landlord #rent #home #rent");
+        assert_eq!(&line[entry.comment_span.clone()], entry.comment);
+        assert_eq!(
+            entry
+                .tags
+                .iter()
+                .map(|(t, span)| (*t, &line[span.clone()]))
+                .collect::<Vec<_>>(),
+            [("#rent", "#rent"), ("#home", "#home"), ("#rent", "#rent")]
+        );
+        assert_eq!(
+            (entry.account, entry.to),
+            (Some("checking"), Some("landlord"))
+        );
+
+        let owned = entry.to_entry(&ParseOptions::default());
+        assert_eq!(owned.tags, ["#home", "#rent"]);
+        assert_eq!(owned, parse_entry(line, &ParseOptions::default()).unwrap());
+    }
+
+    #[test]
+    fn test_parse_ledger_reader() {
+        let contents = "C 1.00 #foo\r\n# comment\nbalance 1.00 @cash\nD 1.00 #bar";
+        let ledger =
+            parse_ledger_reader("2023-05", contents.as_bytes(), &ParseOptions::default()).unwrap();
+        assert_eq!(ledger.entries.len(), 2);
+        assert_eq!(ledger.entries[0].comment, "#foo");
+        assert_eq!(ledger.balances[0].position, 1);
+
+        assert!(matches!(
+            parse_ledger_reader(
+                "2023-05",
+                "C 1.00 #foo\nD 1.00foo".as_bytes(),
+                &ParseOptions::default()
+            ),
+            Err(Error::Parse { line: Some(2), .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_ledger_continuation() {
+        let text = "D 45.00 @12 dinner\n    with sam and alex #food\n\t#birthday\n  # a comment\nC 1.00 pay #job\n";
+        let ledger = parse_ledger_reader(
+            "2023-05",
+            text.as_bytes(),
+            &ParseOptions::default().with_strictness(Strictness::Strict),
+        )
+        .unwrap();
+        assert_eq!(ledger.entries.len(), 2);
+        assert_eq!(
+            ledger.entries[0].comment,
+            "@12 dinner with sam and alex #food #birthday"
+        );
+        assert_eq!(ledger.entries[0].tags, vec!["#birthday", "#food"]);
+        assert_eq!(ledger.entries[0].day, Some(12));
+
+        // Errors on continuation lines point at them.
+        match parse_ledger_reader(
+            "2023-05",
+            "D 1.00 a\n  b #é c\n  d # e\n".as_bytes(),
+            &ParseOptions::default().with_strictness(Strictness::Normal),
+        ) {
+            Err(Error::Parse { line, offset, .. }) => {
+                assert_eq!(line, Some(3));
+                assert_eq!(offset, Some(5));
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        // Indented lines that don't follow an entry are still errors.
+        assert!(parse_ledger_reader(
+            "2023-05",
+            "# x\n  more\n".as_bytes(),
+            &ParseOptions::default().with_strictness(Strictness::Normal)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_ledger_strictness() {
+        let contents = "D 1.00 lunch #food\nX 2.00 oops\nD 3.00 untagged\n";
+        let parse = |strictness| {
+            parse_ledger_reader(
+                "2023-05",
+                contents.as_bytes(),
+                &ParseOptions::default().with_strictness(strictness),
+            )
+        };
+
+        match parse(Strictness::Normal) {
+            Err(Error::Parse { line, .. }) => assert_eq!(line, Some(2)),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let ledger = parse(Strictness::Lenient).unwrap();
+        assert_eq!(ledger.entries.len(), 2);
+        assert_eq!(ledger.errors().len(), 1);
+        assert!(matches!(
+            ledger.errors()[0],
+            Error::Parse { line: Some(2), .. }
+        ));
+
+        let contents = "D 1.00 lunch #food\nD 3.00 untagged\n";
+        assert!(parse_ledger_reader(
+            "2023-05",
+            contents.as_bytes(),
+            &ParseOptions::default().with_strictness(Strictness::Normal)
+        )
+        .is_ok());
+        match parse_ledger_reader(
+            "2023-05",
+            contents.as_bytes(),
+            &ParseOptions::default().with_strictness(Strictness::Strict),
+        ) {
+            Err(Error::Parse { line, message, .. }) => {
+                assert_eq!(line, Some(2));
+                assert_eq!(message, "entry has no tags");
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_create_ledger() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+
+        let template = directory.join("template");
+        fs::write(&template, "# a template for {{date}}\n").unwrap();
+
+        create_ledger(directory, "2023-05", None).unwrap();
+        create_ledger(directory, "2023-06", Some(&template)).unwrap();
+        assert!(matches!(
+            create_ledger(directory, "2023-07", Some(directory)),
+            Err(Error::Io { .. })
+        ));
+        assert!(matches!(
+            read_ledger(directory, "2023-08"),
+            Err(Error::NotFound(_))
+        ));
+
+        let ledger = parse_ledger(
+            "2023-05",
+            read_ledger(directory, "2023-05").unwrap(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        assert!(ledger.entries.is_empty());
+        assert_eq!(
+            fs::read_to_string(directory.join("2023-06.ledger")).unwrap(),
+            "# a template for 2023-06\n"
+        );
+
+        // The directory's template seeds new ledgers, including ones made by appending.
+        fs::write(
+            directory.join(TEMPLATE_FILE),
+            "# {{month_name}} {{year}} ({{month}}) {{other}}\n",
+        )
+        .unwrap();
+        create_ledger(directory, "2023-09", None).unwrap();
+        append_entry(
+            directory,
+            "2023-11",
+            &Settings::default(),
+            &"D 1.00 a".parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(directory.join("2023-09.ledger")).unwrap(),
+            "# September 2023 (09) {{other}}\n"
+        );
+        assert_eq!(
+            fs::read_to_string(directory.join("2023-11.ledger")).unwrap(),
+            "# November 2023 (11) {{other}}\nD 1.00 a\n"
+        );
+        assert_eq!(
+            ledger_dates(directory).unwrap(),
+            ["2023-05", "2023-06", "2023-09", "2023-11"]
+        );
+    }
+
+    #[test]
+    fn test_append_entry() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(directory.join("2023-05.ledger"), "# no newline").unwrap();
+
+        let threads: Vec<_> = (0..8)
+            .map(|n| {
+                let directory = directory.to_path_buf();
+                std::thread::spawn(move || {
+                    let entry: Entry = format!("D 1.0{} entry {}", n, n).parse().unwrap();
+                    append_entry(&directory, "2023-05", &Settings::default(), &entry).unwrap();
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let ledger = load_ledger(directory, "2023-05", &ParseOptions::default()).unwrap();
+        assert_eq!(ledger.entries.len(), 8);
+
+        append_entry(
+            directory,
+            "2023-06",
+            &Settings::default(),
+            &"C 5 pay".parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(directory.join("2023-06.ledger")).unwrap(),
+            "C 0.05 pay\n"
+        );
+
+        assert!("# a comment".parse::<Entry>().is_err());
+        assert!(append_entry(
+            &directory.join("nope"),
+            "2023-05",
+            &Settings::default(),
+            &ledger.entries[0]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_write_atomically() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        let path = directory.join("2023-05.ledger");
+
+        write_atomically(&path, "D 1.00 a\n", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "D 1.00 a\n");
+        assert!(!directory.join("2023-05.ledger.bak").exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        write_atomically(&path, "D 2.00 b\n", true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "D 2.00 b\n");
+        assert_eq!(
+            fs::read_to_string(directory.join("2023-05.ledger.bak")).unwrap(),
+            "D 1.00 a\n"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // Neither the temporary file nor the backup is a ledger.
+        assert_eq!(ledger_dates(directory).unwrap(), vec!["2023-05"]);
+        assert!(write_atomically(&directory.join("nope/2023-05.ledger"), "", false).is_err());
+    }
+
+    #[test]
+    fn test_load_ledger_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("groceries.ledger");
+        fs::write(&path, "D 1.00 a\nX 2.00 b\n").unwrap();
+
+        match load_ledger_file(&path, &ParseOptions::default()) {
+            Err(Error::Parse { file, line, .. }) => {
+                assert_eq!(file.as_deref(), Some(path.as_path()));
+                assert_eq!(line, Some(2));
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        fs::write(&path, "D 1.00 a\n").unwrap();
+        let ledger = load_ledger_file(&path, &ParseOptions::default()).unwrap();
+        assert_eq!(ledger.date, "groceries");
+        assert_eq!(ledger.entries.len(), 1);
+
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            load_ledger_file(&path, &ParseOptions::default()),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_ledgers() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        for (date, entry) in [("2021-03", "c"), ("2020-12", "a"), ("2021-01", "b")] {
+            fs::write(
+                directory.join(format!("{date}.ledger")),
+                format!("D 1.00 {entry}\n"),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            ledger_dates(directory).unwrap(),
+            ["2020-12", "2021-01", "2021-03"]
+        );
+
+        let store = store::DirectoryStore::new(directory, Settings::default());
+        let ledger = store::load_ledgers(&store, None, &Settings::default()).unwrap();
+        let comments: Vec<_> = ledger.entries.iter().map(|e| e.comment.as_str()).collect();
+        assert_eq!(comments, ["a", "b", "c"]);
+        assert_eq!(
+            store::load_ledgers(&store, Some("2021"), &Settings::default())
+                .unwrap()
+                .date,
+            "2021"
+        );
+    }
+
+    #[test]
+    fn test_entry_display() {
+        let entry = parse_entry("D 1,234.50 lunch   #food", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.to_string(), "D 1234.50 lunch   #food");
+        assert_eq!(
+            parse_entry(&entry.to_string(), &ParseOptions::default()).unwrap(),
+            entry
+        );
+    }
+
+    #[test]
+    fn test_over_budget() {
+        let ledger = parse_ledger_reader(
+            "2023-05",
+            "D 12.00 #food\nD 5.00 #fun\n".as_bytes(),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let summary = Summary::new(&ledger).unwrap();
+        let budget = |limit, carried| Budget {
+            limit: Amount::from_subunits(limit),
+            carried,
+        };
+        let mut budgets = BTreeMap::from([
+            ("#food".to_string(), budget(1000, 0)),
+            ("#fun".to_string(), budget(500, 0)),
+            ("#rent".to_string(), budget(0, 0)),
+        ]);
+        assert_eq!(over_budget(&summary, &budgets), vec!["#food"]);
+
+        // What carried over counts too.
+        budgets.insert("#food".into(), budget(1000, 200));
+        budgets.insert("#fun".into(), budget(500, -1));
+        assert_eq!(over_budget(&summary, &budgets), vec!["#fun"]);
     }
 
     #[test]
@@ -643,12 +2427,174 @@ mod tests {
         let mut ledger = parse_ledger(
             "01-01-1970",
             Box::new("C 1.00 #foo\nD 1.00 #bar".as_bytes().lines()),
+            &ParseOptions::default(),
         )
         .unwrap();
 
-        ledger.filter(&["#foo"]);
+        ledger.filter(&["#foo"], &ParseOptions::default());
 
         assert_eq!(ledger.entries.len(), 1);
         assert_eq!(ledger.entries[0].kind, EntryKind::Credit);
+
+        let mut ledger = parse_ledger(
+            "01-01-1970",
+            Box::new("C 1.00 #foo\nD 1.00 bar".as_bytes().lines()),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        ledger.untagged();
+
+        assert_eq!(ledger.entries.len(), 1);
+        assert_eq!(ledger.entries[0].comment, "bar");
+
+        let mut ledger = parse_ledger(
+            "01-01-1970",
+            Box::new(
+                "C 1.00 @bank #foo\nD 1.00 @cash bar\nD 2.00 baz"
+                    .as_bytes()
+                    .lines(),
+            ),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        ledger.account("cash");
+
+        assert_eq!(ledger.entries.len(), 1);
+        assert_eq!(ledger.entries[0].amount, Amount::from_subunits(100));
+    }
+
+    #[test]
+    fn test_merge_ledger() {
+        let mut ledger = parse_ledger(
+            "2023-01",
+            Box::new(
+                "D 1.00 a
+balance 5.00 @bank"
+                    .as_bytes()
+                    .lines(),
+            ),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+        let mut other = parse_ledger(
+            "2023-01",
+            Box::new(
+                "D 2.00 b
+balance 3.00 @bank"
+                    .as_bytes()
+                    .lines(),
+            ),
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        other.set_directory("work");
+        ledger.merge(other);
+        assert_eq!(ledger.date(), "2023-01");
+        assert_eq!(ledger.entries[0].source().unwrap().directory, None);
+        assert_eq!(
+            ledger.entries[1].source(),
+            Some(&EntrySource {
+                date: "2023-01".into(),
+                file: None,
+                line: 1,
+                directory: Some("work".into()),
+            })
+        );
+        let json = serde_json::to_string(&ledger.entries).unwrap();
+        assert!(json.contains("\"source\":{\"date\":\"2023-01\",\"line\":1}"));
+        assert_eq!(json.matches("\"directory\":\"work\"").count(), 1);
+        assert_eq!(ledger.entries.len(), 2);
+        assert_eq!(
+            ledger
+                .balances
+                .iter()
+                .map(|b| b.position)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+    }
+
+    mod properties {
+        use proptest::prelude::*;
+
+        use super::*;
+
+        /// A comment's word: plain, or a tag, with some non-ASCII for good measure.
+        fn word() -> impl Strategy<Value = String> {
+            prop_oneof!["[a-zA-Z0-9éü🍕,.!-]{1,8}", "#[a-z0-9_éü🍕-]{1,8}",]
+        }
+
+        /// An entry's line: a kind, an amount (written the way pledger writes them, or as
+        /// large as they get), and a comment of words and tags.
+        fn entry_line() -> impl Strategy<Value = (String, Amount, Vec<String>)> {
+            (
+                prop_oneof![Just('C'), Just('D')],
+                prop_oneof![0_u64..100_000_000, Just(u64::MAX / 2)],
+                prop::collection::vec(word(), 1..6),
+            )
+                .prop_map(|(kind, subunits, words)| {
+                    let amount = Amount::from_subunits(subunits);
+                    let line =
+                        format!("{} {} {}", kind, amount.to_ledger_string(), words.join(" "));
+                    (line, amount, words)
+                })
+        }
+
+        proptest! {
+            #[test]
+            fn prop_entry_round_trip((line, amount, words) in entry_line()) {
+                let entry: Entry = line.parse().unwrap();
+                prop_assert_eq!(entry.amount, amount);
+                prop_assert_eq!(&entry.comment, &words.join(" "));
+
+                let mut tags: Vec<_> = words.iter().filter(|w| w.starts_with('#')).cloned().collect();
+                tags.sort();
+                tags.dedup();
+                prop_assert_eq!(&entry.tags, &tags);
+
+                let reparsed: Entry = entry.to_string().parse().unwrap();
+                prop_assert_eq!(reparsed, entry);
+            }
+
+            #[test]
+            fn prop_ledger_round_trip(lines in prop::collection::vec(entry_line(), 0..10)) {
+                let text: String = lines.iter().map(|(line, _, _)| format!("{}\n", line)).collect();
+                let ledger = parse_ledger_reader("2023-05", text.as_bytes(), &ParseOptions::default().with_strictness(Strictness::Normal)).unwrap();
+                prop_assert_eq!(ledger.entries.len(), lines.len());
+
+                let written: String = ledger.entries.iter().map(|e| format!("{}\n", e)).collect();
+                let reparsed =
+                    parse_ledger_reader("2023-05", written.as_bytes(), &ParseOptions::default().with_strictness(Strictness::Normal)).unwrap();
+                for (a, b) in reparsed.entries.iter().zip(ledger.entries.iter()) {
+                    prop_assert_eq!(a.amount, b.amount);
+                    prop_assert_eq!(&a.comment, &b.comment);
+                    prop_assert_eq!(&a.tags, &b.tags);
+                }
+            }
+
+            #[test]
+            fn prop_parse_entry_never_panics(line in "\\PC{0,40}") {
+                let _ = parse_entry_ref(&line, &ParseOptions::default());
+                if let Ok(entry) = line.parse::<Entry>() {
+                    prop_assert_eq!(entry.to_string().parse::<Entry>().unwrap(), entry);
+                }
+            }
+
+            #[test]
+            fn prop_parse_entry_amounts(line in "[CDT] [$€£]?[0-9.,]{0,30} [a-z#@ ]{0,8}") {
+                if let Ok(entry) = line.parse::<Entry>() {
+                    prop_assert_eq!(entry.to_string().parse::<Entry>().unwrap(), entry);
+                }
+            }
+
+            #[test]
+            fn prop_parse_ledger_never_panics(text in "(\\PC{0,30}\n|[ \t]+\\PC{0,10}\n){0,8}") {
+                let _ = parse_ledger_reader("2023-05", text.as_bytes(), &ParseOptions::default().with_strictness(Strictness::Lenient));
+                let _ = parse_ledger_reader("2023-05", text.as_bytes(), &ParseOptions::default().with_strictness(Strictness::Strict));
+            }
+        }
     }
 }
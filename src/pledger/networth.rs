@@ -0,0 +1,235 @@
+//! Net worth over time: the total of every account's balance at the end of each month.
+//!
+//! Accounts' balances come from the ledgers' entries and `balance` directives (see
+//! [`crate::pledger::balance`]), and from the snapshots in `assets.ledger`, which record
+//! the value of things that aren't tracked entry by entry, like a house or a brokerage
+//! account. Each of its lines is a month and a `balance` directive:
+//!
+//! ```text
+//! 2023-01 balance 250,000.00 @house
+//! 2023-06 balance 12,400.50 @brokerage
+//! ```
+//!
+//! An account with snapshots is worth its latest one, as of each month, regardless of
+//! what the ledgers compute for it.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::pledger::aggregate::Summary;
+use crate::pledger::balance::Balances;
+use crate::pledger::{
+    offset_date, parse_balance, Error, Ledger, ParseOptions, Result, Settings, DATE_PATTERN,
+};
+
+/// The file in a ledger directory that holds asset snapshots.
+pub const ASSETS_FILE: &str = "assets.ledger";
+
+/// An account's stated value as of a month, from `assets.ledger`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub date: String,
+    pub account: String,
+    /// The account's value, in subunits.
+    pub value: i128,
+}
+
+/// A month's net worth, at its end, and its cash flow: credits less debits.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Month {
+    pub date: String,
+    /// In subunits.
+    pub net_worth: i128,
+    /// In subunits.
+    pub cash_flow: i128,
+}
+
+/// Parses asset snapshots, one `YYYY-MM balance AMOUNT @account` per line. Blank lines
+/// and `#` comments are ignored. The snapshots are returned ordered by month.
+pub fn parse_snapshots(
+    reader: impl BufRead,
+    options: &ParseOptions,
+) -> crate::pledger::Result<Vec<Snapshot>> {
+    let mut snapshots = vec![];
+    for (idx, line) in reader.lines().enumerate() {
+        let line = line.map_err(Error::io("assets read failed"))?;
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let on_line = |offset: Option<usize>, message: String| Error::Parse {
+            file: None,
+            line: Some(idx + 1),
+            offset,
+            message,
+        };
+
+        let (date, directive) = line.split_once(char::is_whitespace).unwrap_or((&line, ""));
+        let directive = directive.trim_start();
+        if !DATE_PATTERN.is_match(date) || directive.split_whitespace().next() != Some("balance") {
+            return Err(on_line(
+                None,
+                "expected YYYY-MM balance AMOUNT @account".into(),
+            ));
+        }
+
+        // The directive's offsets are relative to it, not to the line.
+        let prefix = line.len() - directive.len();
+        let balance = parse_balance(directive, options).map_err(|e| match e {
+            Some(Error::Parse {
+                offset, message, ..
+            }) => on_line(offset.map(|o| o + prefix), message),
+            Some(e) => e,
+            None => on_line(None, "expected balance AMOUNT @account".into()),
+        })?;
+
+        snapshots.push(Snapshot {
+            date: date.into(),
+            value: balance.subunits(),
+            account: balance.account,
+        });
+    }
+
+    // The sort is stable, so a month's later snapshot of an account wins.
+    snapshots.sort_by(|a, b| a.date.cmp(&b.date));
+    Ok(snapshots)
+}
+
+/// Loads the directory's asset snapshots, if it has any.
+pub fn load_snapshots(
+    directory: &Path,
+    settings: &Settings,
+) -> crate::pledger::Result<Vec<Snapshot>> {
+    let path = directory.join(ASSETS_FILE);
+    if !path.is_file() {
+        return Ok(vec![]);
+    }
+
+    let file = File::open(&path).map_err(Error::io("assets open failed"))?;
+    parse_snapshots(BufReader::new(file), &settings.parse).map_err(|e| e.with_file(&path))
+}
+
+/// Returns the net worth and cash flow of every month from the first ledger or snapshot
+/// through `through`. `ledgers` should be every month's, in order.
+pub fn net_worth(ledgers: &[Ledger], snapshots: &[Snapshot], through: &str) -> Result<Vec<Month>> {
+    let first = [
+        ledgers.first().map(|l| l.date.as_str()),
+        snapshots.first().map(|s| s.date.as_str()),
+    ]
+    .into_iter()
+    .flatten()
+    .min();
+    let mut date = match first {
+        Some(first) => first.to_string(),
+        None => return Ok(vec![]),
+    };
+
+    let mut balances = Balances::default();
+    let mut values = BTreeMap::new();
+    let mut ledgers = ledgers.iter().peekable();
+    let mut snapshots = snapshots.iter().peekable();
+    let mut months = vec![];
+    while date.as_str() <= through {
+        let mut cash_flow = 0;
+        while let Some(ledger) = ledgers.next_if(|l| l.date <= date) {
+            balances.add(ledger);
+            let summary = Summary::new(ledger)?;
+            cash_flow += summary.credits.subunits() as i128 - summary.debits.subunits() as i128;
+        }
+        while let Some(snapshot) = snapshots.next_if(|s| s.date <= date) {
+            values.insert(snapshot.account.as_str(), snapshot.value);
+        }
+
+        let tracked: i128 = balances
+            .accounts
+            .iter()
+            .filter(|(account, _)| !values.contains_key(account.as_str()))
+            .map(|(_, balance)| balance)
+            .sum();
+        months.push(Month {
+            date: date.clone(),
+            net_worth: tracked + values.values().sum::<i128>(),
+            cash_flow,
+        });
+
+        date = offset_date(&date, 1)?;
+    }
+
+    Ok(months)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::{parse_ledger_reader, ParseOptions};
+
+    #[test]
+    fn test_net_worth() {
+        let snapshots = parse_snapshots(
+            "# the house\n2023-02 balance 1000.00 @house\n\n2023-01 balance 50.00 @checking\n2023-04 balance 1100.00 @house\n"
+                .as_bytes(), &ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(snapshots.len(), 3);
+        assert_eq!(snapshots[0].date, "2023-01");
+
+        let ledgers = [
+            parse_ledger_reader(
+                "2023-01",
+                "balance 500.00 @checking\nC 100.00 pay @checking\nD 30.00 lunch\n".as_bytes(),
+                &ParseOptions::default(),
+            )
+            .unwrap(),
+            parse_ledger_reader(
+                "2023-03",
+                "D 20.00 @checking groceries\n".as_bytes(),
+                &ParseOptions::default(),
+            )
+            .unwrap(),
+        ];
+
+        // @checking is snapshotted, so its entries don't count.
+        let months = net_worth(&ledgers, &snapshots, "2023-04").unwrap();
+        assert_eq!(
+            months
+                .iter()
+                .map(|m| (m.date.as_str(), m.net_worth, m.cash_flow))
+                .collect::<Vec<_>>(),
+            [
+                ("2023-01", 5000, 7000),
+                ("2023-02", 105000, 0),
+                ("2023-03", 105000, -2000),
+                ("2023-04", 115000, 0),
+            ]
+        );
+
+        let months = net_worth(&ledgers, &[], "2023-03").unwrap();
+        assert_eq!(months[0].net_worth, 60000);
+        assert_eq!(months[2].net_worth, 58000);
+        assert!(net_worth(&[], &[], "2023-03").unwrap().is_empty());
+
+        for invalid in [
+            "balance 10.00 @house\n",
+            "2023-13 balance 10.00 @house\n",
+            "2023-01 D 10.00 @house\n",
+            "2023-01 balance 10.00 house\n",
+            "2023-01 balance 10 @house\n",
+        ] {
+            assert!(parse_snapshots(invalid.as_bytes(), &ParseOptions::default()).is_err());
+        }
+        match parse_snapshots(
+            "\n2023-01 balance 1.0.0 @house".as_bytes(),
+            &ParseOptions::default(),
+        ) {
+            Err(Error::Parse { line, offset, .. }) => {
+                assert_eq!(line, Some(2));
+                assert_eq!(offset, Some(16));
+            }
+            _ => panic!("expected a parse error"),
+        }
+    }
+}
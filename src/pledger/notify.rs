@@ -0,0 +1,242 @@
+//! Notifications, from the config's `[notify]`, e.g. for `pledger notify`.
+//!
+//! `pledger notify` is meant to be run from cron: it checks the configured conditions for
+//! a month, and if any of them hold, runs the configured command with a JSON payload
+//! describing them on its stdin. A webhook is just a command, e.g.
+//! `curl -s -H 'Content-Type: application/json' --data @- https://...`.
+
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+
+use crate::pledger::aggregate::Summary;
+use crate::pledger::budget;
+use crate::pledger::config::Config;
+use crate::pledger::{
+    budget_spent, ledger_dates, ledger_source, load_ledger, Amount, Error, Ledger, Result, Settings,
+};
+
+/// A condition that holds, and that's worth notifying about.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "condition", rename_all = "snake_case")]
+pub enum Notification {
+    /// A tag is over its budget for the month.
+    OverBudget {
+        tag: String,
+        spent: Amount,
+        budget: Amount,
+    },
+    /// The month's debits are over the configured total.
+    OverMonth { debits: Amount, limit: Amount },
+    /// None of the ledgers have changed in at least `days` days.
+    Quiet { days: u64 },
+}
+
+impl fmt::Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Notification::OverBudget { tag, spent, budget } => {
+                write!(f, "{} is over its budget, at {} of {}", tag, spent, budget)
+            }
+            Notification::OverMonth { debits, limit } => {
+                write!(f, "{} in debits is over the limit of {}", debits, limit)
+            }
+            Notification::Quiet { days } => write!(f, "no new entries in {} days", days),
+        }
+    }
+}
+
+/// Each notification in a payload, with its message.
+#[derive(Serialize)]
+struct Notified<'a> {
+    #[serde(flatten)]
+    notification: &'a Notification,
+    message: String,
+}
+
+/// What the notification command gets on its stdin.
+#[derive(Serialize)]
+struct Payload<'a> {
+    date: &'a str,
+    /// A summary of everything in `notifications`, e.g. for a push notification's text.
+    message: String,
+    notifications: Vec<Notified<'a>>,
+}
+
+/// Returns the JSON payload for a month's notifications.
+pub fn payload(date: &str, notifications: &[Notification]) -> String {
+    let payload = Payload {
+        date,
+        message: format!(
+            "pledger, {}: {}",
+            date,
+            notifications
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; ")
+        ),
+        notifications: notifications
+            .iter()
+            .map(|notification| Notified {
+                notification,
+                message: notification.to_string(),
+            })
+            .collect(),
+    };
+    serde_json::to_string(&payload).unwrap()
+}
+
+/// Returns the notifications for the ledger for `date` in `directory`, as of `now`. A
+/// missing ledger is an empty one, rather than an error, since the month may not have
+/// any entries yet.
+pub fn check(
+    directory: &Path,
+    date: &str,
+    settings: &Settings,
+    now: SystemTime,
+) -> Result<Vec<Notification>> {
+    let config = Config::load(directory)?;
+    let ledger = match load_ledger(directory, date, &settings.parse) {
+        Ok(ledger) => ledger,
+        Err(Error::NotFound(_)) => Ledger {
+            date: date.into(),
+            entries: vec![],
+            balances: vec![],
+            errors: vec![],
+            format: settings.parse.amount_format,
+        },
+        Err(e) => return Err(e),
+    };
+    let summary = Summary::new(&ledger)?;
+
+    let mut notifications = vec![];
+    if config.notify.budget {
+        // With rollover, the budget is the effective one, which is zero rather
+        // than negative if earlier months went over by more than its limit.
+        for (tag, budget) in budget::budgets(directory, date, &config, settings)? {
+            let spent = budget_spent(&summary, &tag);
+            if budget.is_over(spent) {
+                notifications.push(Notification::OverBudget {
+                    tag,
+                    spent,
+                    budget: budget.limit.with_subunits(budget.effective().max(0) as u64),
+                });
+            }
+        }
+    }
+
+    if let Some(limit) = config.notify.month {
+        if summary.debits > limit {
+            notifications.push(Notification::OverMonth {
+                debits: summary.debits,
+                limit,
+            });
+        }
+    }
+
+    if let Some(quiet_days) = config.notify.quiet_days {
+        // Entries don't always have days, so the last time that any ledger
+        // changed stands in for the last entry.
+        let changed = ledger_dates(directory)?
+            .iter()
+            .filter_map(|d| {
+                fs::metadata(ledger_source(directory, d))
+                    .ok()?
+                    .modified()
+                    .ok()
+            })
+            .max()
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let days = now
+            .duration_since(changed)
+            .unwrap_or(Duration::ZERO)
+            .as_secs()
+            / 86400;
+        if days >= quiet_days {
+            notifications.push(Notification::Quiet { days });
+        }
+    }
+
+    Ok(notifications)
+}
+
+/// Runs the notification command with the payload on its stdin, through the shell.
+pub fn send(command: &str, payload: &str) -> Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Notify(format!("couldn't run the notify command: {}", e)))?;
+
+    // A command that exits without reading its stdin isn't an error, as long
+    // as it succeeds.
+    let _ = child.stdin.take().unwrap().write_all(payload.as_bytes());
+    let status = child.wait()?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(Error::Notify(format!(
+            "the notify command failed: {}",
+            status
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(
+            directory.join("2023-05.ledger"),
+            "D 12.00 #food\nD 900.00 #rent\n",
+        )
+        .unwrap();
+        fs::write(
+            directory.join("pledger.toml"),
+            "[budget]\n\"#food\" = \"10.00\"\n\"#rent\" = \"1,000.00\"\n\n\
+             [notify]\ncommand = \"cat > /dev/null\"\nbudget = true\nmonth = \"500.00\"\nquiet_days = 3\n",
+        )
+        .unwrap();
+
+        let now = SystemTime::now();
+        let notifications = check(directory, "2023-05", &Settings::default(), now).unwrap();
+        assert_eq!(
+            notifications,
+            vec![
+                Notification::OverBudget {
+                    tag: "#food".into(),
+                    spent: Amount::from_subunits(1200),
+                    budget: Amount::from_subunits(1000),
+                },
+                Notification::OverMonth {
+                    debits: Amount::from_subunits(91200),
+                    limit: Amount::from_subunits(50000),
+                },
+            ]
+        );
+
+        let later = now + Duration::from_secs(4 * 86400);
+        assert_eq!(
+            check(directory, "2023-06", &Settings::default(), later).unwrap(),
+            vec![Notification::Quiet { days: 4 }]
+        );
+
+        let payload = payload("2023-06", &[Notification::Quiet { days: 4 }]);
+        assert_eq!(
+            payload,
+            r#"{"date":"2023-06","message":"pledger, 2023-06: no new entries in 4 days","notifications":[{"condition":"quiet","days":4,"message":"no new entries in 4 days"}]}"#
+        );
+        send("cat > /dev/null", &payload).unwrap();
+        assert!(send("exit 1", &payload).is_err());
+    }
+}
@@ -0,0 +1,190 @@
+//! A lenient parser for the transactions in OFX (and QFX) statements.
+//!
+//! OFX 1.x is SGML, where most tags are never closed, and 2.x is XML. Both are handled
+//! by reading each tag's value as the text up to the next tag.
+
+use crate::pledger::amount::{describe_places, AmountFormat};
+use crate::pledger::EntryKind::{self, *};
+use crate::pledger::{Amount, Error, Result};
+
+/// A single `<STMTTRN>` transaction from a statement.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transaction {
+    /// The bank's unique ID for the transaction.
+    pub fitid: String,
+    /// The transaction's date, as `YYYY-MM`, and its day of the month.
+    pub date: String,
+    pub day: u32,
+    pub kind: EntryKind,
+    pub amount: Amount,
+    pub payee: String,
+}
+
+/// Returns the value of the first `tag` in `block`, if it has one.
+fn value<'a>(block: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let start = block.find(&open)? + open.len();
+    let end = block[start..]
+        .find('<')
+        .map(|e| start + e)
+        .unwrap_or(block.len());
+
+    Some(block[start..end].trim()).filter(|v| !v.is_empty())
+}
+
+/// Parses an OFX amount, like `-12.50` or `1000`, into a kind and an amount in `format`.
+pub fn parse_amount(amount: &str, format: AmountFormat) -> Result<(EntryKind, Amount)> {
+    let (kind, amount) = match amount.strip_prefix('-') {
+        Some(amount) => (Debit, amount),
+        None => (Credit, amount.trim_start_matches('+')),
+    };
+
+    // Banks are inconsistent about decimal places, so pad (or trim zeros)
+    // to the ones that we expect.
+    let places = format.places;
+    let (units, subunits) = amount.split_once('.').unwrap_or((amount, ""));
+    let amount = match subunits.trim_end_matches('0') {
+        "" if places == 0 => units.to_string(),
+        s if s.len() <= places as usize => {
+            format!("{}.{:0<width$}", units, s, width = places as usize)
+        }
+        _ => {
+            return Err(Error::Import(format!(
+                "more than {} in amount: {}",
+                describe_places(places),
+                amount
+            )))
+        }
+    };
+
+    let amount = Amount::parse_with(&amount, format.with_point(), false)
+        .map_err(|e| Error::Import(format!("invalid amount in statement: {}", e)))?
+        .with_format(format);
+    Ok((kind, amount))
+}
+
+/// Decodes the handful of entities that show up in payee names.
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Returns the ID of the account that the statement is for, if it says.
+pub fn account_id(ofx: &str) -> Option<&str> {
+    value(ofx, "ACCTID")
+}
+
+/// Parses every transaction in an OFX statement, with amounts in `format`.
+pub fn parse(ofx: &str, format: AmountFormat) -> Result<Vec<Transaction>> {
+    let mut transactions = vec![];
+
+    for block in ofx.split("<STMTTRN>").skip(1) {
+        let block = block.split("</STMTTRN>").next().unwrap_or(block);
+        let field = |tag| {
+            value(block, tag).ok_or_else(|| Error::Import(format!("transaction without {}", tag)))
+        };
+
+        let fitid = field("FITID")?;
+        let posted = field("DTPOSTED")?;
+        if posted.len() < 8 || !posted[..8].chars().all(|c| c.is_ascii_digit()) {
+            return Err(Error::Import(format!(
+                "invalid DTPOSTED for {}: {}",
+                fitid, posted
+            )));
+        }
+
+        let (kind, amount) = parse_amount(field("TRNAMT")?, format)?;
+        let payee = value(block, "NAME")
+            .or_else(|| value(block, "MEMO"))
+            .map(unescape)
+            .unwrap_or_else(|| "(no payee)".into());
+
+        transactions.push(Transaction {
+            fitid: fitid.into(),
+            date: format!("{}-{}", &posted[..4], &posted[4..6]),
+            day: posted[6..8].parse().unwrap(),
+            kind,
+            amount,
+            payee,
+        });
+    }
+
+    Ok(transactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let ofx =
+            "OFXHEADER:100\nDATA:OFXSGML\n\n<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><BANKTRANLIST>\n\
+                   <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20230105120000[-5:EST]<TRNAMT>-12.5\
+                   <FITID>a1<NAME>Joe&amp;s Diner</STMTTRN>\n\
+                   <STMTTRN>\n<TRNTYPE>CREDIT</TRNTYPE>\n<DTPOSTED>20230131</DTPOSTED>\n\
+                   <TRNAMT>1000.00</TRNAMT>\n<FITID>a2</FITID>\n<MEMO>PAYROLL</MEMO>\n</STMTTRN>\n\
+                   </BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>";
+
+        assert_eq!(account_id(ofx), None);
+        assert_eq!(account_id("<ACCTID>1234<ACCTTYPE>"), Some("1234"));
+
+        let transactions = parse(ofx, AmountFormat::DEFAULT).unwrap();
+        assert_eq!(
+            transactions,
+            vec![
+                Transaction {
+                    fitid: "a1".into(),
+                    date: "2023-01".into(),
+                    day: 5,
+                    kind: Debit,
+                    amount: Amount::from_subunits(1250),
+                    payee: "Joe&s Diner".into(),
+                },
+                Transaction {
+                    fitid: "a2".into(),
+                    date: "2023-01".into(),
+                    day: 31,
+                    kind: Credit,
+                    amount: Amount::from_subunits(100000),
+                    payee: "PAYROLL".into(),
+                },
+            ]
+        );
+
+        assert!(parse(
+            "<STMTTRN><DTPOSTED>20230105<TRNAMT>1.00</STMTTRN>",
+            AmountFormat::DEFAULT
+        )
+        .is_err());
+        assert!(parse(
+            "<STMTTRN><FITID>x<DTPOSTED>2023<TRNAMT>1.00</STMTTRN>",
+            AmountFormat::DEFAULT
+        )
+        .is_err());
+        assert!(parse(
+            "<STMTTRN><FITID>x<DTPOSTED>20230105<TRNAMT>1.001</STMTTRN>",
+            AmountFormat::DEFAULT
+        )
+        .is_err());
+        assert_eq!(
+            parse_amount("-5", AmountFormat::DEFAULT).unwrap(),
+            (Debit, Amount::from_subunits(500))
+        );
+
+        // Amounts are padded to the ledgers' decimal places.
+        let dinars = AmountFormat {
+            decimal: ',',
+            places: 3,
+        };
+        let (_, amount) = parse_amount("12.5", dinars).unwrap();
+        assert_eq!(
+            (amount.subunits(), amount.to_ledger_string()),
+            (12500, "12,500".into())
+        );
+        assert!(parse_amount("1.2345", dinars).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! Output formats for ledgers and reports, e.g. for `--format`.
+//!
+//! The structured formats (JSON, TOML, and YAML) are all rendered from the same
+//! serializable structures, so they always have the same fields. TOML has no null, so
+//! missing values are left out of it entirely.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::pledger::{Error, Result};
+
+/// The names of the output formats, for `--format`.
+pub const FORMATS: [&str; 7] = ["text", "markdown", "org", "json", "csv", "toml", "yaml"];
+
+/// How a ledger or report is output.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    /// For the terminal.
+    #[default]
+    Text,
+    /// The summary report as GitHub-flavored Markdown, e.g. for notes.
+    Markdown,
+    /// The summary report and the entries as org-mode, e.g. for org-babel.
+    Org,
+    Json,
+    /// A double-entry CSV, like `--export csv`.
+    Csv,
+    Toml,
+    Yaml,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(format: &str) -> Result<Self> {
+        match format {
+            "text" => Ok(OutputFormat::Text),
+            "markdown" => Ok(OutputFormat::Markdown),
+            "org" => Ok(OutputFormat::Org),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "toml" => Ok(OutputFormat::Toml),
+            "yaml" => Ok(OutputFormat::Yaml),
+            format => Err(Error::Output(format!("unknown output format: {}", format))),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Returns whether the format is one of the structured formats that `render` supports.
+    pub fn is_structured(&self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Json | OutputFormat::Toml | OutputFormat::Yaml
+        )
+    }
+
+    /// Renders a value in the format, which must be a structured one. The rendering ends
+    /// with a newline.
+    pub fn render<T: Serialize>(&self, value: &T) -> Result<String> {
+        let to_value = |value| {
+            serde_json::to_value(value)
+                .map_err(|e| Error::Output(format!("couldn't serialize output: {}", e)))
+        };
+        match self {
+            OutputFormat::Json => serde_json::to_string(value)
+                .map(|json| format!("{}\n", json))
+                .map_err(|e| Error::Output(format!("couldn't serialize output: {}", e))),
+            OutputFormat::Toml => {
+                let value = without_nulls(to_value(value)?);
+                if !value.is_object() {
+                    return Err(Error::Output("only tables can be output as TOML".into()));
+                }
+                toml::to_string(&value)
+                    .map_err(|e| Error::Output(format!("couldn't output TOML: {}", e)))
+            }
+            OutputFormat::Yaml => {
+                let mut out = String::new();
+                write_yaml(&mut out, &to_value(value)?, 0);
+                Ok(out)
+            }
+            format => Err(Error::Output(format!(
+                "{:?} isn't a structured output format",
+                format
+            ))),
+        }
+    }
+}
+
+/// Removes the null values from a value's tables and arrays.
+fn without_nulls(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| (k, without_nulls(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .filter(|v| !v.is_null())
+                .map(without_nulls)
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+/// Returns a string as a YAML scalar: bare when it can't be mistaken for anything else,
+/// and double-quoted otherwise. JSON's quoting is also YAML's.
+fn yaml_string(s: &str) -> String {
+    let bare = s.starts_with(|c: char| c.is_ascii_alphabetic())
+        && !s.ends_with(' ')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || " _./-".contains(c))
+        && !["true", "false", "yes", "no", "on", "off", "null"]
+            .contains(&s.to_lowercase().as_str());
+    match bare {
+        true => s.into(),
+        false => Value::String(s.into()).to_string(),
+    }
+}
+
+/// Returns a value as a YAML scalar, if it's a scalar or an empty collection.
+fn yaml_scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some("null".into()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::String(s) => Some(yaml_string(s)),
+        Value::Array(items) if items.is_empty() => Some("[]".into()),
+        Value::Object(map) if map.is_empty() => Some("{}".into()),
+        _ => None,
+    }
+}
+
+/// Writes a value as block-style YAML, indented by `indent` spaces.
+fn write_yaml(out: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    if let Some(scalar) = yaml_scalar(value) {
+        writeln!(out, "{}{}", pad, scalar).unwrap();
+        return;
+    }
+
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter() {
+                write!(out, "{}{}:", pad, yaml_string(key)).unwrap();
+                match yaml_scalar(value) {
+                    Some(scalar) => writeln!(out, " {}", scalar).unwrap(),
+                    None => {
+                        writeln!(out).unwrap();
+                        write_yaml(out, value, indent + 2);
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items.iter() {
+                // Each item goes on the same line as its dash, e.g. `- kind: Debit`
+                // for the first field of a table.
+                let mut rendered = String::new();
+                write_yaml(&mut rendered, item, indent + 2);
+                writeln!(out, "{}- {}", pad, rendered[indent + 2..].trim_end()).unwrap();
+            }
+        }
+        _ => unreachable!("scalars are handled above"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let value = serde_json::json!({
+            "date": "2023-05",
+            "entries": [
+                {"kind": "Debit", "amount": [12, 50], "comment": "lunch: #food", "to": null},
+                {"kind": "Credit", "amount": [3, 0], "comment": "", "tags": []},
+            ],
+            "totals": {"debits": 12.5},
+        });
+
+        assert_eq!(
+            OutputFormat::Yaml.render(&value).unwrap(),
+            [
+                "date: \"2023-05\"",
+                "entries:",
+                "  - kind: Debit",
+                "    amount:",
+                "      - 12",
+                "      - 50",
+                "    comment: \"lunch: #food\"",
+                "    to: null",
+                "  - kind: Credit",
+                "    amount:",
+                "      - 3",
+                "      - 0",
+                "    comment: \"\"",
+                "    tags: []",
+                "totals:",
+                "  debits: 12.5",
+                "",
+            ]
+            .join("\n")
+        );
+
+        let toml = OutputFormat::Toml.render(&value).unwrap();
+        assert!(toml.starts_with("date = \"2023-05\"\n"));
+        assert!(toml.contains("[[entries]]\nkind = \"Debit\"\namount = [12, 50]\n"));
+        assert!(!toml.contains("to ="));
+
+        assert_eq!(
+            OutputFormat::Json.render(&value).unwrap(),
+            format!("{}\n", value)
+        );
+        assert!(OutputFormat::Toml.render(&[1, 2]).is_err());
+        assert!(OutputFormat::Csv.render(&value).is_err());
+        assert_eq!("yaml".parse::<OutputFormat>().unwrap(), OutputFormat::Yaml);
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+}
@@ -0,0 +1,388 @@
+//! The entry grammar, as small parsers that each consume one part of a line and leave
+//! the rest to the next:
+//!
+//! ```text
+//! entry    = kind WS {WS} amount WS comment
+//! kind     = "A" | "C" | "D" | "T"
+//! amount   = [currency] units [decimal {DIGIT}]
+//! units    = DIGIT {DIGIT | grouping}
+//! comment  = {tag | CHAR}
+//! tag      = "#" TAGCHAR {TAGCHAR}
+//! ```
+//!
+//! Where a parser fails, it reports what it expected there, e.g. `expected digit or
+//! whitespace, got f`. Grouping separators are checked once the units are over (see
+//! `amount::check_grouping`), and the comment's markers (days, accounts, and so on) once
+//! the comment is (see `parse_markers`).
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::pledger::amount::{self, Amount};
+use crate::pledger::EntryKind::*;
+use crate::pledger::{
+    is_tag_char, parse_markers, EntryKind, EntryRef, Error, Markers, ParseOptions, Span,
+};
+
+type PResult<T> = std::result::Result<T, Option<Error>>;
+
+/// Where a parser is in its line.
+struct Input<'a> {
+    line: &'a str,
+    pos: usize,
+}
+
+impl Input<'_> {
+    fn peek(&self) -> Option<char> {
+        self.line[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let chr = self.peek()?;
+        self.pos += chr.len_utf8();
+        Some(chr)
+    }
+
+    /// Consumes the next character, if it's one that `pred` accepts.
+    fn eat(&mut self, pred: impl Fn(char) -> bool) -> Option<char> {
+        match self.peek() {
+            Some(chr) if pred(chr) => self.bump(),
+            _ => None,
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> Option<Error> {
+        Some(Error::syntax(Some(self.pos), message))
+    }
+
+    /// Fails at the current position, with what was expected there instead.
+    fn expected(&self, expected: &[&str]) -> Option<Error> {
+        match self.peek() {
+            Some(chr) if chr.is_whitespace() => {
+                self.error(format!("expected {}, got whitespace", one_of(expected)))
+            }
+            Some(chr) => self.error(format!("expected {}, got {}", one_of(expected), chr)),
+            None => unexpected_eol(),
+        }
+    }
+}
+
+fn unexpected_eol() -> Option<Error> {
+    Some(Error::syntax(None, "unexpected EOL; missing comment?"))
+}
+
+/// Joins alternatives for an error, e.g. `digit or whitespace`.
+fn one_of(expected: &[&str]) -> String {
+    match expected {
+        [] => String::new(),
+        [one] => one.to_string(),
+        [a, b] => format!("{} or {}", a, b),
+        [init @ .., last] => format!("{}, or {}", init.join(", "), last),
+    }
+}
+
+/// Appends a decimal digit to an amount being parsed, failing if the amount overflows.
+fn push_digit(amount: u64, digit: char, idx: usize) -> PResult<u64> {
+    amount
+        .checked_mul(10)
+        .and_then(|a| a.checked_add(digit as u64 - '0' as u64))
+        .ok_or_else(|| Some(Error::syntax(Some(idx), "amount out of range")))
+}
+
+fn kind(input: &mut Input) -> PResult<EntryKind> {
+    let idx = input.pos;
+    if input.peek().is_some_and(char::is_whitespace) {
+        return Err(input.expected(&["A", "C", "D", "T"]));
+    }
+    match input.bump() {
+        Some('C') => Ok(Credit),
+        Some('D') => Ok(Debit),
+        Some('T') => Ok(Transfer),
+        Some('A') => Ok(Allocation),
+        Some(chr) => Err(Some(Error::syntax(
+            Some(idx),
+            format!("unexpected entry kind {}", chr),
+        ))),
+        None => Err(unexpected_eol()),
+    }
+}
+
+fn whitespace(input: &mut Input) -> PResult<()> {
+    match input.eat(char::is_whitespace) {
+        Some(_) => Ok(()),
+        None => Err(input.expected(&["whitespace"])),
+    }
+}
+
+/// An entry's amount: its value in subunits (every digit, so that `100` is 1.00), its
+/// span, and its currency, if it has a symbol.
+struct AmountPart {
+    amount: Amount,
+    span: Span,
+    currency: Option<&'static str>,
+}
+
+fn amount(input: &mut Input, options: &ParseOptions) -> PResult<AmountPart> {
+    let start = input.pos;
+    let currency = input
+        .peek()
+        .and_then(|chr| amount::CURRENCY_SYMBOLS.get(&chr).copied());
+    if currency.is_some() {
+        input.bump();
+    }
+
+    let decimal = options.amount_format.decimal;
+    let grouping = options.amount_format.grouping();
+    let places = options.amount_format.places;
+
+    let units_start = input.pos;
+    let mut value = 0_u64;
+    match input.peek() {
+        Some(chr) if chr.is_ascii_digit() => {}
+        _ => return Err(input.expected(&["digit"])),
+    }
+    while let Some(chr) = input.peek() {
+        if chr.is_ascii_digit() {
+            value = push_digit(value, chr, input.pos)?;
+        } else if chr != grouping {
+            break;
+        }
+        input.bump();
+    }
+
+    let units = &input.line[units_start..input.pos];
+    let check_grouping = |has_decimal| {
+        amount::check_grouping(units, grouping, has_decimal, places, options.loose_grouping)
+            .map_err(|(offset, e)| Some(Error::syntax(Some(units_start + offset), e)))
+    };
+    match input.peek() {
+        Some(chr) if chr == decimal => {
+            if places == 0 {
+                return Err(input.error("amounts have no decimal places"));
+            }
+            check_grouping(true)?;
+            input.bump();
+        }
+        Some(chr) if chr.is_whitespace() => {
+            check_grouping(false)?;
+            return Ok(AmountPart {
+                amount: Amount::new(value, options.amount_format),
+                span: start..input.pos,
+                currency,
+            });
+        }
+        Some(_) => return Err(input.expected(&["digit", "whitespace"])),
+        None => return Err(unexpected_eol()),
+    }
+
+    let mut decimal_places = 0;
+    loop {
+        match input.peek() {
+            Some(chr) if chr.is_ascii_digit() => {
+                decimal_places += 1;
+                if decimal_places > places {
+                    return Err(input.error(format!(
+                        "more than {} in value",
+                        amount::describe_places(places)
+                    )));
+                }
+                value = push_digit(value, chr, input.pos)?;
+                input.bump();
+            }
+            Some(chr) if chr == decimal => {
+                return Err(input.error("more than one decimal supplied in value"))
+            }
+            Some(chr) if chr == grouping => {
+                return Err(input.error(format!("unexpected '{}' in decimal place", chr)))
+            }
+            Some(chr) if chr.is_whitespace() => {
+                if decimal_places < places {
+                    return Err(input.error("one or more decimals missing from decimal place"));
+                }
+                return Ok(AmountPart {
+                    amount: Amount::new(value, options.amount_format),
+                    span: start..input.pos,
+                    currency,
+                });
+            }
+            Some(_) => return Err(input.expected(&["digit", "whitespace"])),
+            None => return Err(unexpected_eol()),
+        }
+    }
+}
+
+/// Parses the comment, the rest of the line, returning its tags' spans.
+fn comment(input: &mut Input) -> PResult<Vec<Span>> {
+    let mut tags = vec![];
+    while let Some(chr) = input.peek() {
+        match chr {
+            '#' => tags.push(tag(input)?),
+            _ => {
+                input.bump();
+            }
+        }
+    }
+    Ok(tags)
+}
+
+fn tag(input: &mut Input) -> PResult<Span> {
+    let start = input.pos;
+    input.bump();
+
+    let mut empty = true;
+    while let Some(chr) = input.peek() {
+        if chr.is_whitespace() {
+            break;
+        } else if !is_tag_char(chr) {
+            return Err(input.error(format!("invalid tag character: {}", chr)));
+        }
+        input.bump();
+        empty = false;
+    }
+
+    match empty {
+        true => Err(input.error("premature tag ending")),
+        false => Ok(start..input.pos),
+    }
+}
+
+/// Parses an entry without copying any of it out of `line`. Blank lines and comments
+/// produce `Err(None)`.
+pub(super) fn parse_entry_ref<'a>(line: &'a str, options: &ParseOptions) -> PResult<EntryRef<'a>> {
+    lazy_static! {
+        static ref LOOKS_LIKE_COMMENT: Regex = Regex::new(r"^\s*#.*$").unwrap();
+    }
+
+    if line.is_empty() || LOOKS_LIKE_COMMENT.is_match(line) {
+        log::debug!("comment or blank: {}", line);
+        return Err(None);
+    }
+
+    let mut input = Input { line, pos: 0 };
+    let kind = kind(&mut input)?;
+    whitespace(&mut input)?;
+    // Amounts can be padded on the left, so that `pledger fmt` can right-align
+    // them.
+    while input.eat(char::is_whitespace).is_some() {}
+    let amount = amount(&mut input, options)?;
+    whitespace(&mut input)?;
+    let comment_start = input.pos;
+    let tags = comment(&mut input)?;
+
+    let comment = &line[comment_start..];
+    let Markers {
+        day,
+        accounts,
+        cleared,
+        attachment,
+        id,
+    } = parse_markers(comment)?;
+
+    // Transfers are from one account to another; anything else has at most one. Any
+    // other `@account`s are just part of the comment, e.g. `@ joe's @home`.
+    let (account, to) = match (&kind, accounts.len()) {
+        (Transfer, 2..) => (Some(accounts[0]), Some(accounts[1])),
+        (Transfer, _) => {
+            return Err(Some(Error::syntax(
+                None,
+                "transfers need exactly two accounts: @from @to",
+            )))
+        }
+        (Allocation, 0) => (None, None),
+        (Allocation, _) => {
+            return Err(Some(Error::syntax(
+                None,
+                "allocations are to envelopes, not accounts",
+            )))
+        }
+        (_, _) => (accounts.first().copied(), None),
+    };
+
+    // An allocation's tag is its envelope.
+    if kind == Allocation && tags.len() != 1 {
+        return Err(Some(Error::syntax(
+            None,
+            "allocations need exactly one tag, their envelope: A AMOUNT #envelope",
+        )));
+    }
+
+    Ok(EntryRef {
+        kind,
+        amount: amount.amount,
+        amount_span: amount.span,
+        currency: amount.currency,
+        comment,
+        comment_span: comment_start..line.len(),
+        tags: tags
+            .into_iter()
+            .map(|span| (&line[span.clone()], span))
+            .collect(),
+        day,
+        account,
+        to,
+        cleared,
+        attachment,
+        id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(line: &str) -> (Option<usize>, String) {
+        match parse_entry_ref(line, &ParseOptions::default()) {
+            Err(Some(Error::Parse {
+                offset, message, ..
+            })) => (offset, message),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_expected() {
+        assert_eq!(one_of(&["digit"]), "digit");
+        assert_eq!(one_of(&["digit", "whitespace"]), "digit or whitespace");
+        assert_eq!(one_of(&["C", "D", "T"]), "C, D, or T");
+
+        assert_eq!(
+            error("  D 1.00 x"),
+            (Some(0), "expected A, C, D, or T, got whitespace".into())
+        );
+        assert_eq!(
+            error("D $$1.00 x"),
+            (Some(3), "expected digit, got $".into())
+        );
+        assert_eq!(
+            error("D 1.0 x"),
+            (
+                Some(5),
+                "one or more decimals missing from decimal place".into()
+            )
+        );
+        assert_eq!(
+            error("D"),
+            (None, "unexpected EOL; missing comment?".into())
+        );
+        assert_eq!(
+            error("D 1.00 lunch #"),
+            (Some(14), "premature tag ending".into())
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let entry =
+            parse_entry_ref("D $1,234.50  lunch #food#x", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.amount, Amount::from_subunits(123450));
+        assert_eq!(entry.amount_span, 2..11);
+        assert_eq!(entry.comment, " lunch #food#x");
+        assert_eq!(entry.comment_span, 12..26);
+        assert_eq!(entry.tags, vec![("#food#x", 19..26)]);
+
+        // Right-aligned amounts are padded on the left.
+        let entry = parse_entry_ref("D    4.50 tea", &ParseOptions::default()).unwrap();
+        assert_eq!(entry.amount_span, 5..9);
+        assert_eq!(entry.comment_span, 10..13);
+    }
+}
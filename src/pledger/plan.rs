@@ -0,0 +1,228 @@
+//! Planned changes to files, for `--dry-run`.
+//!
+//! Every write to a ledger (or archive) goes through here. Normally that's a no-op, but
+//! in a dry run the new contents are kept in the plan instead of being written, and
+//! later reads of the same files see them, so that e.g. an import that appends to the
+//! same month several times plans all of its entries. Once the command is done, the
+//! plan is shown as a unified diff of each changed file.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+/// How many unchanged lines to show around each change.
+const CONTEXT: usize = 3;
+
+/// A planned change to a file: its original contents (if it existed) and its planned
+/// contents (if it will still exist).
+#[derive(Clone, Debug, PartialEq)]
+struct Change {
+    old: Option<String>,
+    new: Option<String>,
+}
+
+lazy_static! {
+    /// The planned changes, by path, in a dry run, and `None` otherwise.
+    static ref PLAN: Mutex<Option<BTreeMap<PathBuf, Change>>> = Mutex::new(None);
+}
+
+/// Makes every later write part of the plan, rather than actually writing, for the rest
+/// of the process.
+pub fn set_dry_run() {
+    PLAN.lock().unwrap().get_or_insert_with(BTreeMap::new);
+}
+
+/// Returns whether this is a dry run.
+pub fn dry_run() -> bool {
+    PLAN.lock().unwrap().is_some()
+}
+
+/// Returns the planned contents of `path`, if there's a planned change to it: `Some(None)`
+/// if it's planned to be removed.
+pub fn planned(path: &Path) -> Option<Option<String>> {
+    PLAN.lock()
+        .unwrap()
+        .as_ref()?
+        .get(path)
+        .map(|change| change.new.clone())
+}
+
+/// Plans to change `path` from `old` (its current contents, if it exists) to `new`, or
+/// to remove it if `new` is `None`. Returns whether the change was planned, i.e. whether
+/// this is a dry run; otherwise, the caller makes the change itself.
+pub fn change(path: &Path, old: Option<&str>, new: Option<&str>) -> bool {
+    let mut plan = PLAN.lock().unwrap();
+    let plan = match plan.as_mut() {
+        Some(plan) => plan,
+        None => return false,
+    };
+
+    // A file that's changed more than once keeps its original contents, so
+    // that the diff is of everything that's planned for it.
+    plan.entry(path.into())
+        .or_insert_with(|| Change {
+            old: old.map(String::from),
+            new: None,
+        })
+        .new = new.map(String::from);
+    true
+}
+
+/// Returns the plan as a unified diff of each changed file, in path order.
+pub fn diff() -> String {
+    let plan = PLAN.lock().unwrap();
+    let mut out = String::new();
+    for (path, change) in plan.iter().flatten() {
+        let path = path.display().to_string();
+        out.push_str(&unified_diff(
+            change.old.as_ref().map(|_| path.as_str()),
+            change.new.as_ref().map(|_| path.as_str()),
+            change.old.as_deref().unwrap_or_default(),
+            change.new.as_deref().unwrap_or_default(),
+        ));
+    }
+    out
+}
+
+/// An edit between two sequences of lines, with the indices that it's at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edit {
+    Same(usize, usize),
+    Remove(usize),
+    Add(usize),
+}
+
+/// Returns a unified diff from `old` to `new`, which are named `old_name` and `new_name`
+/// (or `/dev/null`, if the file doesn't exist on that side). Returns nothing if they're
+/// the same.
+pub fn unified_diff(
+    old_name: Option<&str>,
+    new_name: Option<&str>,
+    old: &str,
+    new: &str,
+) -> String {
+    if old == new && old_name.is_some() == new_name.is_some() {
+        return String::new();
+    }
+
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // The length of the longest common subsequence of each pair of suffixes.
+    let mut lcs = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = match a[i] == b[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+
+    let mut edits = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            edits.push(Edit::Same(i, j));
+            (i, j) = (i + 1, j + 1);
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            edits.push(Edit::Remove(i));
+            i += 1;
+        } else {
+            edits.push(Edit::Add(j));
+            j += 1;
+        }
+    }
+
+    let mut out = format!(
+        "--- {}\n+++ {}\n",
+        old_name.unwrap_or("/dev/null"),
+        new_name.unwrap_or("/dev/null")
+    );
+
+    // Each hunk is the edits around changes that are close enough to share context.
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|idx| !matches!(edits[*idx], Edit::Same(..)))
+        .collect();
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for idx in changes {
+        let (start, end) = (
+            idx.saturating_sub(CONTEXT),
+            (idx + CONTEXT + 1).min(edits.len()),
+        );
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        let hunk = &edits[start..end];
+        // The number of lines before the hunk, on each side.
+        let (old_before, new_before) = edits[..start].iter().fold((0, 0), |(o, n), e| match e {
+            Edit::Same(..) => (o + 1, n + 1),
+            Edit::Remove(_) => (o + 1, n),
+            Edit::Add(_) => (o, n + 1),
+        });
+        let old_len = hunk.iter().filter(|e| !matches!(e, Edit::Add(_))).count();
+        let new_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Remove(_)))
+            .count();
+        // An empty side's range starts at the line before it, per diff(1).
+        let range = |before: usize, len: usize| match len {
+            0 => format!("{},0", before),
+            len => format!("{},{}", before + 1, len),
+        };
+        writeln!(
+            out,
+            "@@ -{} +{} @@",
+            range(old_before, old_len),
+            range(new_before, new_len)
+        )
+        .unwrap();
+
+        for edit in hunk.iter() {
+            match edit {
+                Edit::Same(i, _) => writeln!(out, " {}", a[*i]).unwrap(),
+                Edit::Remove(i) => writeln!(out, "-{}", a[*i]).unwrap(),
+                Edit::Add(j) => writeln!(out, "+{}", b[*j]).unwrap(),
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+        assert_eq!(
+            unified_diff(Some("x"), Some("x"), old, new),
+            "--- x\n+++ x\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -8,3 +8,4 @@\n h\n i\n j\n+k\n"
+        );
+        assert_eq!(
+            unified_diff(None, Some("x"), "", "a\n"),
+            "--- /dev/null\n+++ x\n@@ -0,0 +1,1 @@\n+a\n"
+        );
+        assert_eq!(
+            unified_diff(Some("x"), None, "a\n", ""),
+            "--- x\n+++ /dev/null\n@@ -1,1 +0,0 @@\n-a\n"
+        );
+        assert_eq!(unified_diff(Some("x"), Some("x"), old, old), "");
+
+        // Outside of a dry run, nothing is planned.
+        assert!(!change(Path::new("x"), None, Some("a\n")));
+        assert_eq!(planned(Path::new("x")), None);
+    }
+}
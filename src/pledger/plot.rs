@@ -0,0 +1,155 @@
+use std::env;
+use std::fmt::Write;
+
+use crate::pledger::aggregate::Series;
+
+/// Partial blocks, in eighths of a character cell.
+const BLOCKS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+
+/// The shading of negative bars, in whole character cells.
+const NEGATIVE: char = '░';
+
+/// Sparkline levels, from lowest to highest.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Returns the width to render charts at: `$COLUMNS` if set, or 80 otherwise.
+pub fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(80)
+}
+
+fn bar(eighths: usize) -> String {
+    let mut bar = BLOCKS[7].to_string().repeat(eighths / 8);
+    match eighths % 8 {
+        0 => {}
+        partial => bar.push(BLOCKS[partial - 1]),
+    }
+    bar
+}
+
+/// Renders a series as a horizontal bar chart, one bar per point, at most `width`
+/// characters wide.
+pub fn bar_chart(series: &Series, width: usize) -> String {
+    let mut chart = String::new();
+
+    let mut title = series.name.chars();
+    if let Some(first) = title.next() {
+        writeln!(chart, "{}{}:", first.to_uppercase(), title.as_str()).unwrap();
+    }
+
+    let amounts: Vec<_> = series.points.iter().map(|(_, v)| v.to_string()).collect();
+    let label_width = series
+        .points
+        .iter()
+        .map(|(l, _)| l.chars().count())
+        .max()
+        .unwrap_or(0);
+    let amount_width = amounts.iter().map(String::len).max().unwrap_or(0);
+    let bar_width = width.saturating_sub(label_width + amount_width + 2).max(1);
+
+    let max = series.max().subunits() as u128;
+    for ((label, value), amount) in series.points.iter().zip(amounts.iter()) {
+        let eighths = match max {
+            0 => 0,
+            _ => (value.subunits() as u128 * bar_width as u128 * 8 / max) as usize,
+        };
+
+        writeln!(
+            chart,
+            "{:<label_width$} {:<bar_width$} {:>amount_width$}",
+            label,
+            bar(eighths),
+            amount
+        )
+        .unwrap();
+    }
+
+    chart
+}
+
+/// Renders each signed value as a bar, scaled so that the largest magnitude is `width`
+/// characters wide. Negative values' bars are shaded, rather than solid.
+pub fn signed_bars(values: &[i128], width: usize) -> Vec<String> {
+    let max = values.iter().map(|v| v.unsigned_abs()).max().unwrap_or(0);
+    values
+        .iter()
+        .map(|value| {
+            let eighths = match max {
+                0 => 0,
+                _ => (value.unsigned_abs() * width as u128 * 8 / max) as usize,
+            };
+            match *value < 0 {
+                true => NEGATIVE.to_string().repeat(eighths.div_ceil(8)),
+                false => bar(eighths),
+            }
+        })
+        .collect()
+}
+
+/// Renders a series as a sparkline, one character per point. Zero points are blank.
+pub fn sparkline(series: &Series) -> String {
+    let max = series.max().subunits() as u128;
+    series
+        .points
+        .iter()
+        .map(|(_, value)| match value.subunits() as u128 {
+            0 => ' ',
+            value => SPARKS[((value * 8 - 1) / max) as usize],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::Amount;
+
+    #[test]
+    fn test_bar_chart() {
+        let series = Series {
+            name: "debits by tag".into(),
+            points: vec![
+                ("#a".into(), Amount::from_subunits(400)),
+                ("#bb".into(), Amount::from_subunits(100)),
+                ("#c".into(), Amount::ZERO),
+            ],
+        };
+
+        // 20 columns, minus 3 for labels, 5 for amounts, and 2 for spacing.
+        assert_eq!(
+            bar_chart(&series, 20),
+            "Debits by tag:\n\
+             #a  ██████████ 04.00\n\
+             #bb ██▌        01.00\n\
+             #c             00.00\n"
+        );
+
+        let empty = Series {
+            name: "credits by month".into(),
+            points: vec![],
+        };
+        assert_eq!(bar_chart(&empty, 20), "Credits by month:\n");
+    }
+
+    #[test]
+    fn test_signed_bars() {
+        assert_eq!(signed_bars(&[400, -100, 0, 50], 4), ["████", "░", "", "▌"]);
+        assert!(signed_bars(&[0, 0], 4).iter().all(String::is_empty));
+    }
+
+    #[test]
+    fn test_sparkline() {
+        let series = Series {
+            name: "debits by day".into(),
+            points: [800, 0, 100, 400, 101]
+                .iter()
+                .map(|v| (String::new(), Amount::from_subunits(*v)))
+                .collect(),
+        };
+
+        assert_eq!(sparkline(&series), "█ ▁▄▂");
+        assert_eq!(sparkline(&Series::default()), "");
+    }
+}
@@ -0,0 +1,134 @@
+//! Named profiles, each with its own ledger directory, from the user's profiles file.
+//!
+//! The profiles file is `$PLEDGER_PROFILES` if set, or `pledger/profiles.toml` in
+//! `$XDG_CONFIG_HOME` (or `~/.config`). Each profile's budget and other settings come
+//! from the `pledger.toml` in its directory, as usual.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::pledger::{remote, Error, Result};
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// The profile's ledger directory. A leading `~` is the home directory, and relative
+    /// paths are relative to the profiles file. It can also be a remote directory's URL
+    /// (see `remote`).
+    pub directory: PathBuf,
+    /// The currency that the profile's ledgers are in, for exports.
+    pub currency: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profiles {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Returns the path of the profiles file, if there's anywhere to look for one.
+pub fn profiles_file() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("PLEDGER_PROFILES") {
+        return Some(path.into());
+    }
+
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| Path::new(&h).join(".config")))?;
+    Some(config_home.join("pledger").join("profiles.toml"))
+}
+
+impl Profiles {
+    /// Parses the profiles in `contents`, resolving their directories against `base`.
+    fn parse(contents: &str, base: &Path) -> Result<Self> {
+        let mut profiles: Profiles =
+            toml::from_str(contents).map_err(|e| Error::Config(e.to_string()))?;
+
+        for profile in profiles.profiles.values_mut() {
+            if remote::is_remote(&profile.directory) {
+                continue;
+            }
+
+            let directory = match profile.directory.strip_prefix("~") {
+                Ok(rest) => env::var_os("HOME")
+                    .map(|h| Path::new(&h).join(rest))
+                    .ok_or_else(|| Error::Config("can't expand ~ without $HOME".into()))?,
+                Err(_) => base.join(&profile.directory),
+            };
+            profile.directory = directory;
+        }
+
+        Ok(profiles)
+    }
+
+    /// Loads the profiles file.
+    pub fn load() -> Result<Self> {
+        let path = profiles_file()
+            .ok_or_else(|| Error::NotFound("couldn't find a profiles file".into()))?;
+        let contents = fs::read_to_string(&path).map_err(Error::io(format!(
+            "couldn't read profiles from {}",
+            path.display()
+        )))?;
+
+        Self::parse(&contents, path.parent().unwrap_or(Path::new(".")))
+            .map_err(|e| Error::Config(format!("invalid profiles file {}: {}", path.display(), e)))
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Profile> {
+        self.profiles.get(name).ok_or_else(|| {
+            Error::NotFound(format!(
+                "no such profile: {} (known: {})",
+                name,
+                self.profiles
+                    .keys()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiles() {
+        let profiles = Profiles::parse(
+            "[profiles.personal]\ndirectory = \"personal\"\ncurrency = \"EUR\"\n\n\
+             [profiles.household]\ndirectory = \"/srv/household\"\n\n\
+             [profiles.shared]\ndirectory = \"s3://bucket/ledgers\"\n",
+            Path::new("/home/me/.config/pledger"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            profiles.get("personal").unwrap(),
+            &Profile {
+                directory: "/home/me/.config/pledger/personal".into(),
+                currency: Some("EUR".into()),
+            }
+        );
+        assert_eq!(
+            profiles.get("household").unwrap().directory,
+            Path::new("/srv/household")
+        );
+        assert_eq!(
+            profiles.get("shared").unwrap().directory,
+            Path::new("s3://bucket/ledgers")
+        );
+        assert!(profiles
+            .get("work")
+            .unwrap_err()
+            .to_string()
+            .contains("known: household, personal, shared"));
+
+        assert!(Profiles::parse("[profiles.x]\ndir = \"a\"", Path::new("/")).is_err());
+    }
+}
@@ -0,0 +1,402 @@
+//! A small expression language for selecting ledger entries, e.g.
+//! `kind == debit && amount > 50 && has_tag("#food")`.
+//!
+//! Expressions are comparisons (`kind`, `amount`, or `comment` against a value) and
+//! function calls (`has_tag("#tag")`, `contains("text")`), combined with `&&`, `||`,
+//! `!`, and parentheses. `&&` binds more tightly than `||`.
+
+use std::str::FromStr;
+
+use crate::pledger::{Amount, Entry, EntryKind, Error, ParseOptions, Result};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Str(String),
+    Op(CmpOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn apply<T: PartialOrd>(&self, lhs: T, rhs: T) -> bool {
+        match self {
+            CmpOp::Eq => lhs == rhs,
+            CmpOp::Ne => lhs != rhs,
+            CmpOp::Lt => lhs < rhs,
+            CmpOp::Le => lhs <= rhs,
+            CmpOp::Gt => lhs > rhs,
+            CmpOp::Ge => lhs >= rhs,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Query {
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+    Kind(CmpOp, EntryKind),
+    Amount(CmpOp, Amount),
+    Comment(CmpOp, String),
+    HasTag(String),
+    Contains(String),
+}
+
+impl Query {
+    pub fn matches(&self, entry: &Entry) -> bool {
+        match self {
+            Query::And(lhs, rhs) => lhs.matches(entry) && rhs.matches(entry),
+            Query::Or(lhs, rhs) => lhs.matches(entry) || rhs.matches(entry),
+            Query::Not(query) => !query.matches(entry),
+            Query::Kind(op, kind) => op.apply(&entry.kind == kind, true),
+            Query::Amount(op, amount) => op.apply(entry.amount, *amount),
+            Query::Comment(op, comment) => op.apply(entry.comment.as_str(), comment.as_str()),
+            Query::HasTag(tag) => entry.tags.contains(tag),
+            Query::Contains(text) => entry.comment.contains(text.as_str()),
+        }
+    }
+}
+
+impl Query {
+    /// Parses a query, with its tags canonicalized under `options` like entries' are.
+    pub fn parse(query: &str, options: &ParseOptions) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(query)?,
+            pos: 0,
+            options,
+        };
+
+        let query = parser.or()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some(token) => Err(Error::Query(format!("unexpected {:?} in query", token))),
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    fn from_str(query: &str) -> Result<Self> {
+        Self::parse(query, &ParseOptions::default())
+    }
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = query.chars().peekable();
+
+    while let Some(&chr) = chars.peek() {
+        match chr {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if chr == '(' {
+                    Token::LParen
+                } else {
+                    Token::RParen
+                });
+            }
+            '&' | '|' => {
+                chars.next();
+                if chars.next() != Some(chr) {
+                    return Err(Error::Query(format!("expected {0}{0} in query", chr)));
+                }
+                tokens.push(if chr == '&' { Token::And } else { Token::Or });
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                tokens.push(match (chr, eq) {
+                    ('=', true) => Token::Op(CmpOp::Eq),
+                    ('!', true) => Token::Op(CmpOp::Ne),
+                    ('!', false) => Token::Not,
+                    ('<', true) => Token::Op(CmpOp::Le),
+                    ('<', false) => Token::Op(CmpOp::Lt),
+                    ('>', true) => Token::Op(CmpOp::Ge),
+                    ('>', false) => Token::Op(CmpOp::Gt),
+                    _ => return Err(Error::Query("expected == in query".into())),
+                });
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some(c) if c == chr => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => string.push(c),
+                            None => {
+                                return Err(Error::Query("unterminated string in query".into()))
+                            }
+                        },
+                        Some(c) => string.push(c),
+                        None => return Err(Error::Query("unterminated string in query".into())),
+                    }
+                }
+                tokens.push(Token::Str(string));
+            }
+            c if c.is_ascii_digit() => {
+                let mut number = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+                {
+                    number.push(c);
+                }
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    ident.push(c);
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            c => {
+                return Err(Error::Query(format!(
+                    "unexpected character in query: {}",
+                    c
+                )))
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    options: &'a ParseOptions,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(Error::Query(format!(
+                "expected {:?} in query, got {:?}",
+                expected, token
+            ))),
+            None => Err(Error::Query(format!(
+                "expected {:?} in query, got end of query",
+                expected
+            ))),
+        }
+    }
+
+    fn or(&mut self) -> Result<Query> {
+        let mut lhs = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            lhs = Query::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Query> {
+        let mut lhs = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            lhs = Query::And(Box::new(lhs), Box::new(self.unary()?));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Query> {
+        match self.next() {
+            Some(Token::Not) => Ok(Query::Not(Box::new(self.unary()?))),
+            Some(Token::LParen) => {
+                let query = self.or()?;
+                self.expect(Token::RParen)?;
+                Ok(query)
+            }
+            Some(Token::Ident(ident)) => match self.peek() {
+                Some(Token::LParen) => self.call(&ident),
+                Some(Token::Op(_)) => self.comparison(&ident),
+                _ => Err(Error::Query(format!(
+                    "expected a comparison or call after {}",
+                    ident
+                ))),
+            },
+            Some(token) => Err(Error::Query(format!("unexpected {:?} in query", token))),
+            None => Err(Error::Query("unexpected end of query".into())),
+        }
+    }
+
+    fn call(&mut self, function: &str) -> Result<Query> {
+        self.expect(Token::LParen)?;
+        let arg = match self.next() {
+            Some(Token::Str(arg)) => arg,
+            _ => {
+                return Err(Error::Query(format!(
+                    "{}() takes a single string argument",
+                    function
+                )))
+            }
+        };
+        self.expect(Token::RParen)?;
+
+        match function {
+            // Entries' tags are canonical, so the queried tag is too.
+            "has_tag" => Ok(Query::HasTag(self.options.canonical_tag(&arg))),
+            "contains" => Ok(Query::Contains(arg)),
+            _ => Err(Error::Query(format!(
+                "unknown function in query: {}",
+                function
+            ))),
+        }
+    }
+
+    fn comparison(&mut self, field: &str) -> Result<Query> {
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => unreachable!(),
+        };
+        let value = self
+            .next()
+            .ok_or_else(|| Error::Query(format!("expected a value after {}", field)))?;
+
+        match (field, value) {
+            ("kind", Token::Ident(kind) | Token::Str(kind)) => {
+                if !matches!(op, CmpOp::Eq | CmpOp::Ne) {
+                    return Err(Error::Query(
+                        "kind can only be compared with == or !=".into(),
+                    ));
+                }
+                let kind = match kind.to_lowercase().as_str() {
+                    "debit" => EntryKind::Debit,
+                    "credit" => EntryKind::Credit,
+                    "transfer" => EntryKind::Transfer,
+                    "allocation" => EntryKind::Allocation,
+                    _ => {
+                        return Err(Error::Query(format!(
+                            "unknown entry kind in query: {}",
+                            kind
+                        )))
+                    }
+                };
+                Ok(Query::Kind(op, kind))
+            }
+            ("amount", Token::Number(amount)) => {
+                let format = self.options.amount_format.with_point();
+                Ok(Query::Amount(
+                    op,
+                    Amount::parse_with(&amount, format, false)?,
+                ))
+            }
+            ("comment", Token::Str(comment)) => Ok(Query::Comment(op, comment)),
+            ("kind" | "amount" | "comment", value) => Err(Error::Query(format!(
+                "can't compare {} with {:?}",
+                field, value
+            ))),
+            _ => Err(Error::Query(format!("unknown field in query: {}", field))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::{parse_entry, AmountFormat};
+
+    fn matches(query: &str, entry: &str) -> bool {
+        query
+            .parse::<Query>()
+            .unwrap()
+            .matches(&parse_entry(entry, &ParseOptions::default()).unwrap())
+    }
+
+    #[test]
+    fn test_query_matches() {
+        assert!(matches("kind == debit", "D 1.00 test"));
+        assert!(!matches("kind == debit", "C 1.00 test"));
+        assert!(matches("kind != 'credit'", "D 1.00 test"));
+
+        assert!(matches("amount > 50", "D 50.01 test"));
+        assert!(!matches("amount > 50", "D 50.00 test"));
+        assert!(matches("amount <= 1,000.00", "D 1,000.00 test"));
+
+        // Queries' amounts have the ledgers' decimal places.
+        let yen = ParseOptions {
+            amount_format: AmountFormat {
+                decimal: ',',
+                places: 0,
+            },
+            ..ParseOptions::default()
+        };
+        let query = Query::parse("amount > 1,000", &yen).unwrap();
+        assert!(query.matches(&parse_entry("D 1.500 test", &yen).unwrap()));
+        assert!(!query.matches(&parse_entry("D 999 test", &yen).unwrap()));
+        assert!(Query::parse("amount > 1.50", &yen).is_err());
+
+        assert!(matches(r##"has_tag("#food")"##, "D 1.00 lunch #food"));
+        assert!(matches(r##"!has_tag("#food")"##, "D 1.00 lunch #fun"));
+        assert!(matches(r##"contains("lunch")"##, "D 1.00 lunch #food"));
+        assert!(matches(
+            r##"comment == "lunch #food""##,
+            "D 1.00 lunch #food"
+        ));
+
+        let query = r##"kind == debit && amount > 50 && has_tag("#food")"##;
+        assert!(matches(query, "D 51.00 groceries #food"));
+        assert!(!matches(query, "D 49.00 groceries #food"));
+        assert!(!matches(query, "C 51.00 refund #food"));
+
+        // && binds more tightly than ||.
+        let query = r##"kind == credit || amount > 50 && has_tag("#food")"##;
+        assert!(matches(query, "C 1.00 pay"));
+        assert!(matches(&format!("({})", query), "C 1.00 pay"));
+        assert!(!matches(
+            r##"(kind == credit || amount > 50) && has_tag("#food")"##,
+            "C 1.00 pay"
+        ));
+    }
+
+    #[test]
+    fn test_query_errors() {
+        for query in [
+            "",
+            "kind",
+            "kind == refund",
+            "kind > debit",
+            "amount == 'x'",
+            "amount == 1.0",
+            "size > 1",
+            r##"has_tag(1)"##,
+            r##"frobulate("x")"##,
+            "kind == debit &",
+            "kind == debit)",
+            "(kind == debit",
+            "comment == 'unterminated",
+        ] {
+            assert!(query.parse::<Query>().is_err(), "{}", query);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! The entries that were entered most recently, for `pledger last-entries`.
+//!
+//! With a journal (see `journal`), entries are ordered by when they were last added or
+//! modified. Entries that the journal doesn't know about (e.g. from before it was
+//! enabled) come after those, ordered as though the most recently modified ledger files
+//! were appended to last: newest file first, and then from the bottom of each ledger up.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+use crate::pledger::{id, journal, ledger_source, parse_monthly_ledgers, Entry, Result, Settings};
+
+/// One of the most recent entries, and where it is.
+#[derive(Debug, Serialize)]
+pub struct Recent {
+    pub date: String,
+    /// The entry's position in its ledger, from 1.
+    pub number: usize,
+    pub id: String,
+    pub entry: Entry,
+    /// When the entry was last added or modified, if the journal knows.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+}
+
+/// Returns up to `count` of the directory's most recently entered entries, most recent
+/// first.
+pub fn last_entries(directory: &Path, count: usize, settings: &Settings) -> Result<Vec<Recent>> {
+    let ledgers = parse_monthly_ledgers(directory, None, settings)?;
+
+    // Every entry, as (date, number, ID, entry), with each ledger's file's mtime.
+    let mut ledgers: Vec<_> = ledgers
+        .into_iter()
+        .map(|ledger| {
+            let mtime = fs::metadata(ledger_source(directory, &ledger.date))
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            let ids = id::entry_ids(&ledger);
+            let entries: Vec<_> = ledger
+                .entries
+                .into_iter()
+                .zip(ids)
+                .enumerate()
+                .map(|(idx, (entry, id))| (ledger.date.clone(), idx + 1, id, entry))
+                .collect();
+            (mtime, entries)
+        })
+        .collect();
+
+    let mut recent = vec![];
+    let mut seen = HashSet::new();
+    for record in journal::load(directory)?.unwrap_or_default().iter().rev() {
+        if recent.len() >= count {
+            break;
+        }
+        if record.change == journal::Change::Removed {
+            continue;
+        }
+
+        let key = (record.ledger.clone(), record.id.clone());
+        if seen.contains(&key) {
+            continue;
+        }
+        let found = ledgers
+            .iter()
+            .flat_map(|(_, entries)| entries.iter())
+            .find(|(date, _, id, _)| *date == record.ledger && *id == record.id);
+        if let Some((date, number, id, entry)) = found {
+            seen.insert(key);
+            recent.push(Recent {
+                date: date.clone(),
+                number: *number,
+                id: id.clone(),
+                entry: entry.clone(),
+                time: Some(record.time.clone()),
+            });
+        }
+    }
+
+    // The sort is stable, so ledgers with the same mtime stay in date order, and
+    // are reversed along with it.
+    ledgers.sort_by_key(|(mtime, _)| *mtime);
+    for (date, number, id, entry) in ledgers
+        .into_iter()
+        .rev()
+        .flat_map(|(_, entries)| entries.into_iter().rev())
+    {
+        if recent.len() >= count {
+            break;
+        }
+        if seen.contains(&(date.clone(), id.clone())) {
+            continue;
+        }
+        recent.push(Recent {
+            date,
+            number,
+            id,
+            entry,
+            time: None,
+        });
+    }
+
+    Ok(recent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::update_ledger;
+
+    #[test]
+    fn test_last_entries() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+
+        let comments = |recent: Vec<Recent>| -> Vec<String> {
+            recent
+                .into_iter()
+                .map(|r| r.entry.comment.trim().to_string())
+                .collect()
+        };
+
+        // Without a journal, the bottom of the ledgers is the most recent.
+        fs::write(directory.join("2023-04.ledger"), "D 12.00 c\n").unwrap();
+        fs::write(directory.join("2023-05.ledger"), "D 10.00 a\nD 11.00 b\n").unwrap();
+        let recent = last_entries(directory, 10, &Settings::default()).unwrap();
+        assert_eq!(recent.len(), 3);
+        assert_eq!(recent[0].number, 2);
+        assert!(recent.iter().all(|r| r.time.is_none()));
+
+        // With one, what was entered since comes first, even in an older ledger.
+        journal::enable(directory).unwrap();
+        update_ledger(directory, "2023-04", &Settings::default(), |t| {
+            Ok(t + "D 13.00 d\n")
+        })
+        .unwrap();
+        update_ledger(directory, "2023-05", &Settings::default(), |t| {
+            Ok(t.replace("D 10.00 a", "D 10.50 a"))
+        })
+        .unwrap();
+        let recent = last_entries(directory, 3, &Settings::default()).unwrap();
+        assert_eq!(comments(recent), ["a", "d", "b"]);
+    }
+}
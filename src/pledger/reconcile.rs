@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use crate::pledger::syntax::{tokenize, TokenKind};
+use crate::pledger::{
+    parse_entry, prompt, update_ledger, Entry, Error, Ledger, ParseOptions, Result, Settings,
+};
+
+/// Marks an entry's line as cleared, by putting a `*` at the start of its comment.
+fn mark_cleared(line: &str, options: &ParseOptions) -> String {
+    let start = tokenize(line, options)
+        .into_iter()
+        .skip_while(|t| t.kind != TokenKind::Amount)
+        .find(|t| !matches!(t.kind, TokenKind::Amount | TokenKind::Whitespace))
+        .map(|t| t.span.start)
+        .unwrap_or(line.len());
+
+    format!("{}* {}", &line[..start], &line[start..])
+}
+
+/// Walks the uncleared entries in the ledger for `date`, asking `ask` about each. Entries
+/// that `ask` accepts are marked as cleared; if it returns `None`, the rest are skipped.
+/// Returns the entries that are still uncleared, as a ledger.
+pub fn reconcile(
+    directory: &Path,
+    date: &str,
+    settings: &Settings,
+    mut ask: impl FnMut(&Entry) -> Result<Option<bool>>,
+) -> Result<Ledger> {
+    let ledger_file = directory.join(format!("{date}.ledger"));
+    if !ledger_file.is_file() {
+        return Err(Error::NotFound(format!(
+            "missing requested ledger file: {}",
+            ledger_file.display()
+        )));
+    }
+
+    let mut uncleared = vec![];
+    update_ledger(directory, date, settings, |contents| {
+        let mut asking = true;
+        let mut reconciled = String::with_capacity(contents.len());
+        for line in contents.lines() {
+            let entry = match parse_entry(line, &settings.parse) {
+                Ok(entry) if !entry.cleared => entry,
+                _ => {
+                    reconciled.push_str(line);
+                    reconciled.push('\n');
+                    continue;
+                }
+            };
+
+            let answer = match asking {
+                true => ask(&entry)?,
+                false => None,
+            };
+            match answer {
+                Some(true) => reconciled.push_str(&mark_cleared(line, &settings.parse)),
+                answer => {
+                    asking = answer.is_some();
+                    reconciled.push_str(line);
+                    uncleared.push(entry);
+                }
+            }
+            reconciled.push('\n');
+        }
+
+        Ok(reconciled)
+    })?;
+
+    Ok(Ledger {
+        date: date.into(),
+        entries: uncleared,
+        balances: vec![],
+        errors: vec![],
+        format: settings.parse.amount_format,
+    })
+}
+
+/// Asks on the terminal whether `entry` has cleared.
+pub fn ask(entry: &Entry) -> Result<Option<bool>> {
+    loop {
+        let answer = prompt(&format!("{}\n  cleared? [y]es, [n]o, or [q]uit: ", entry))?;
+        match answer.as_deref().map(str::trim) {
+            Some("y") => return Ok(Some(true)),
+            Some("n" | "") => return Ok(Some(false)),
+            Some("q") | None => return Ok(None),
+            Some(_) => eprintln!("unrecognized choice"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::pledger::Amount;
+
+    #[test]
+    fn test_reconcile() {
+        assert_eq!(
+            mark_cleared("D 1.00 lunch #food", &ParseOptions::default()),
+            "D 1.00 * lunch #food"
+        );
+        assert_eq!(
+            mark_cleared("D 1.00   #food", &ParseOptions::default()),
+            "D 1.00   * #food"
+        );
+
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(
+            directory.join("2023-05.ledger"),
+            "# may\nD 1.00 a\nD 2.00 * b\nD 3.00 c\nD 4.00 d\nD 5.00 e",
+        )
+        .unwrap();
+
+        let mut answers = vec![Some(true), Some(false), None].into_iter();
+        let uncleared = reconcile(directory, "2023-05", &Settings::default(), |_| {
+            Ok(answers.next().unwrap())
+        })
+        .unwrap();
+
+        let amounts: Vec<_> = uncleared.entries.iter().map(|e| e.amount).collect();
+        assert_eq!(
+            amounts,
+            vec![
+                Amount::from_subunits(300),
+                Amount::from_subunits(400),
+                Amount::from_subunits(500)
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(directory.join("2023-05.ledger")).unwrap(),
+            "# may\nD 1.00 * a\nD 2.00 * b\nD 3.00 c\nD 4.00 d\nD 5.00 e\n"
+        );
+    }
+}
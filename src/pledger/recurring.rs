@@ -0,0 +1,216 @@
+//! Recurring entries, like rent or insurance, from the config's `[[recurring]]`.
+//!
+//! Each one is an entry that's due on a day of the month, every month, quarter, or
+//! year. `pledger ical` exports them as an iCalendar file of reminders.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::pledger::{Entry, Error, Result};
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl Frequency {
+    fn months(&self) -> u32 {
+        match self {
+            Frequency::Monthly => 1,
+            Frequency::Quarterly => 3,
+            Frequency::Yearly => 12,
+        }
+    }
+}
+
+/// A recurring entry, as written in the config.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Recurring {
+    /// The entry, in ledger syntax.
+    pub entry: String,
+    pub every: Frequency,
+    /// The day of the month that the entry is due on.
+    pub day: u32,
+    /// For quarterly and yearly entries, a month (1-12) that the entry is due in.
+    /// Defaults to January.
+    pub month: Option<u32>,
+}
+
+impl Recurring {
+    /// Checks the definition, returning its parsed entry.
+    pub fn validate(&self) -> Result<Entry> {
+        let entry: Entry = self.entry.parse().map_err(|e| {
+            Error::Config(format!("invalid recurring entry {:?}: {}", self.entry, e))
+        })?;
+
+        // Later days don't fall in every month, and calendars skip those
+        // months rather than moving the reminder.
+        if !(1..=28).contains(&self.day) {
+            return Err(Error::Config(format!(
+                "recurring entry {:?}: day must be between 1 and 28, not {}",
+                self.entry, self.day
+            )));
+        }
+
+        match self.month {
+            Some(month) if !(1..=12).contains(&month) => Err(Error::Config(format!(
+                "recurring entry {:?}: invalid month {}",
+                self.entry, month
+            ))),
+            Some(_) if self.every == Frequency::Monthly => Err(Error::Config(format!(
+                "recurring entry {:?}: monthly entries don't have a month",
+                self.entry
+            ))),
+            _ => Ok(entry),
+        }
+    }
+
+    /// Returns the first date on or after `date` that the entry is due.
+    pub fn next(&self, date: NaiveDate) -> NaiveDate {
+        let interval = self.every.months();
+        let anchor = self.month.unwrap_or(1) - 1;
+
+        // Days are at most 28 and months are 1-12, so these always exist.
+        let mut due = date.with_day(1).unwrap();
+        loop {
+            if (due.month0() + 12 - anchor % interval).is_multiple_of(interval) {
+                let candidate = due.with_day(self.day).unwrap();
+                if candidate >= date {
+                    return candidate;
+                }
+            }
+            due = due + Months::new(1);
+        }
+    }
+}
+
+/// Escapes iCalendar text.
+fn text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Renders the recurring entries as an iCalendar file, with an all-day event on each
+/// due date and a reminder the day before. Each event starts on its first due date on
+/// or after `today`; `now` is the file's timestamp.
+pub fn ical(recurring: &[Recurring], today: NaiveDate, now: DateTime<Utc>) -> Result<String> {
+    let mut out = String::new();
+    let mut line = |l: String| {
+        out.push_str(&l);
+        out.push_str("\r\n");
+    };
+
+    line("BEGIN:VCALENDAR".into());
+    line("VERSION:2.0".into());
+    line("PRODID:-//pledger//recurring entries//EN".into());
+
+    for definition in recurring.iter() {
+        let entry = definition.validate()?;
+
+        let summary = format!("{} {}", entry.comment.trim(), entry.amount);
+        let rule = match definition.every {
+            Frequency::Monthly => format!("FREQ=MONTHLY;BYMONTHDAY={}", definition.day),
+            Frequency::Quarterly => {
+                format!("FREQ=MONTHLY;INTERVAL=3;BYMONTHDAY={}", definition.day)
+            }
+            Frequency::Yearly => format!(
+                "FREQ=YEARLY;BYMONTH={};BYMONTHDAY={}",
+                definition.month.unwrap_or(1),
+                definition.day
+            ),
+        };
+
+        // The UID is stable across exports, so that calendars update events in place.
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{:?}", definition));
+        let mut uid = String::new();
+        for byte in hasher.finalize().iter().take(16) {
+            write!(uid, "{:02x}", byte).unwrap();
+        }
+
+        line("BEGIN:VEVENT".into());
+        line(format!("UID:{}@pledger", uid));
+        line(format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ")));
+        line(format!(
+            "DTSTART;VALUE=DATE:{}",
+            definition.next(today).format("%Y%m%d")
+        ));
+        line(format!("RRULE:{}", rule));
+        line(format!("SUMMARY:{}", text(&summary)));
+        line(format!("DESCRIPTION:{}", text(&definition.entry)));
+        line("BEGIN:VALARM".into());
+        line("ACTION:DISPLAY".into());
+        line("TRIGGER:-P1D".into());
+        line(format!("DESCRIPTION:{}", text(&summary)));
+        line("END:VALARM".into());
+        line("END:VEVENT".into());
+    }
+
+    line("END:VCALENDAR".into());
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recurring(entry: &str, every: Frequency, day: u32, month: Option<u32>) -> Recurring {
+        Recurring {
+            entry: entry.into(),
+            every,
+            day,
+            month,
+        }
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_recurring() {
+        let rent = recurring("D 1200.00 rent #rent", Frequency::Monthly, 1, None);
+        assert_eq!(rent.next(date("2023-05-01")), date("2023-05-01"));
+        assert_eq!(rent.next(date("2023-12-02")), date("2024-01-01"));
+
+        let insurance = recurring("D 300.00 car insurance", Frequency::Quarterly, 15, Some(2));
+        assert_eq!(insurance.next(date("2023-01-20")), date("2023-02-15"));
+        assert_eq!(insurance.next(date("2023-02-16")), date("2023-05-15"));
+        assert_eq!(insurance.next(date("2023-12-01")), date("2024-02-15"));
+
+        let domain = recurring("D 12.00 domain, renewal", Frequency::Yearly, 3, Some(7));
+        assert_eq!(domain.next(date("2023-07-04")), date("2024-07-03"));
+
+        let now = "2023-05-01T12:00:00Z".parse().unwrap();
+        let ical = ical(&[rent, domain], date("2023-05-01"), now).unwrap();
+        assert!(ical.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ical.contains("DTSTART;VALUE=DATE:20230501\r\nRRULE:FREQ=MONTHLY;BYMONTHDAY=1\r\nSUMMARY:rent #rent 1200.00\r\n"));
+        assert!(ical.contains(
+            "RRULE:FREQ=YEARLY;BYMONTH=7;BYMONTHDAY=3\r\nSUMMARY:domain\\, renewal 12.00\r\n"
+        ));
+        assert!(ical.ends_with("END:VCALENDAR\r\n"));
+
+        assert!(recurring("D 1.00 x", Frequency::Monthly, 31, None)
+            .validate()
+            .is_err());
+        assert!(recurring("D 1.00 x", Frequency::Monthly, 1, Some(2))
+            .validate()
+            .is_err());
+        assert!(recurring("D 1.00 x", Frequency::Yearly, 1, Some(13))
+            .validate()
+            .is_err());
+        assert!(recurring("X 1.00 x", Frequency::Yearly, 1, None)
+            .validate()
+            .is_err());
+    }
+}
@@ -0,0 +1,346 @@
+use std::cmp::Reverse;
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::pledger::aggregate::{tags_by_month, Ratios, Summary, TagRow};
+use crate::pledger::EntryKind::*;
+use crate::pledger::{Amount, Entry, EntryKind, Error, Ledger, Result};
+
+/// How many of the year's largest debits the report lists.
+pub const LARGEST: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MonthTotals {
+    pub date: String,
+    pub credits: Amount,
+    pub debits: Amount,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Transaction {
+    pub date: String,
+    pub entry: Entry,
+}
+
+/// A report on a whole year's ledgers, as output by `pledger report`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AnnualReport {
+    pub year: String,
+    pub months: Vec<MonthTotals>,
+    pub credits: Amount,
+    pub debits: Amount,
+    /// The change in net worth over the year, and whether it's a gain (credit) or a
+    /// loss (debit).
+    pub net: Amount,
+    pub net_kind: EntryKind,
+    #[serde(flatten)]
+    pub ratios: Ratios,
+    pub debit_tags: Vec<TagRow>,
+    pub largest: Vec<Transaction>,
+}
+
+fn checked_sum(acc: Amount, amount: Amount) -> Result<Amount> {
+    acc.checked_add(amount)
+        .ok_or_else(|| Error::Amount(format!("total out of range: {} + {}", acc, amount)))
+}
+
+impl AnnualReport {
+    /// Builds the report for `year` from its monthly ledgers, which should be in order.
+    pub fn new(year: &str, ledgers: &[Ledger]) -> Result<Self> {
+        let summaries = ledgers
+            .iter()
+            .map(Summary::new)
+            .collect::<Result<Vec<_>>>()?;
+
+        let months: Vec<_> = summaries
+            .iter()
+            .map(|s| MonthTotals {
+                date: s.date.clone(),
+                credits: s.credits,
+                debits: s.debits,
+            })
+            .collect();
+
+        let credits = months
+            .iter()
+            .try_fold(Amount::ZERO, |acc, m| checked_sum(acc, m.credits))?;
+        let debits = months
+            .iter()
+            .try_fold(Amount::ZERO, |acc, m| checked_sum(acc, m.debits))?;
+        let net = credits.abs_diff(debits);
+        let net_kind = if credits >= debits { Credit } else { Debit };
+
+        let debit_tags = tags_by_month(ledgers, &Debit)?;
+        let ratios = Ratios::new(
+            credits,
+            debits,
+            debit_tags.first().map(|row| (row.tag.as_str(), row.total)),
+        );
+
+        let mut largest: Vec<_> = ledgers
+            .iter()
+            .flat_map(|l| {
+                l.entries
+                    .iter()
+                    .filter(|e| e.kind == Debit)
+                    .map(|e| Transaction {
+                        date: l.date.clone(),
+                        entry: e.clone(),
+                    })
+            })
+            .collect();
+        // The sort is stable, so ties stay in ledger order.
+        largest.sort_by_key(|t| Reverse(t.entry.amount));
+        largest.truncate(LARGEST);
+
+        Ok(Self {
+            year: year.into(),
+            months,
+            credits,
+            debits,
+            net,
+            net_kind,
+            ratios,
+            debit_tags,
+            largest,
+        })
+    }
+
+    fn net_label(&self) -> &'static str {
+        match self.net_kind {
+            Credit => "gain",
+            _ => "loss",
+        }
+    }
+
+    /// Describes the ratios other than the savings rate, e.g. "spent 40.0% of credits;
+    /// the largest debit tag, #home, was 25.0% of credits".
+    fn ratios_text(&self) -> String {
+        let mut text = format!("spent {:.1}% of credits", self.ratios.expense_ratio);
+        if let Some(top) = &self.ratios.top_expense {
+            write!(
+                text,
+                "; the largest debit tag, {}, was {:.1}% of credits",
+                top.tag, top.percent
+            )
+            .unwrap();
+        }
+        text
+    }
+
+    /// Renders the report for the terminal.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "Report for {}\n", self.year).unwrap();
+        writeln!(
+            out,
+            "\t{} in credits and {} in debits, for a net {} of {} ({:.1}% saved)\n\t{}\n",
+            self.credits,
+            self.debits,
+            self.net_label(),
+            self.net,
+            self.ratios.savings_rate,
+            self.ratios_text()
+        )
+        .unwrap();
+
+        writeln!(out, "Months:").unwrap();
+        writeln!(out, "{:<16} {:>10} {:>10}", "", "credits", "debits").unwrap();
+        for month in self.months.iter() {
+            writeln!(
+                out,
+                "{:<16} {:>10} {:>10}",
+                month.date, month.credits, month.debits
+            )
+            .unwrap();
+        }
+
+        let dates: Vec<_> = self.months.iter().map(|m| m.date.as_str()).collect();
+        writeln!(out, "\nDebit tags by month:").unwrap();
+        out.push_str(&matrix(&dates, &self.debit_tags));
+
+        writeln!(out, "\nLargest debits:").unwrap();
+        for transaction in self.largest.iter() {
+            writeln!(out, "{:<16} {}", transaction.date, transaction.entry).unwrap();
+        }
+
+        out
+    }
+
+    /// Renders the report as a standalone HTML document.
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "<!DOCTYPE html>\n<html>\n<head>").unwrap();
+        writeln!(out, "<meta charset=\"utf-8\">").unwrap();
+        writeln!(out, "<title>Report for {}</title>", escape(&self.year)).unwrap();
+        writeln!(
+            out,
+            "<style>body {{ font-family: sans-serif; }} td, th {{ padding: 0 0.5em; }} \
+             td.amount {{ text-align: right; font-family: monospace; }}</style>"
+        )
+        .unwrap();
+        writeln!(out, "</head>\n<body>").unwrap();
+
+        writeln!(out, "<h1>Report for {}</h1>", escape(&self.year)).unwrap();
+        writeln!(
+            out,
+            "<p>{} in credits and {} in debits, for a net {} of {} ({:.1}% saved).</p>\n<p>{}.</p>",
+            self.credits,
+            self.debits,
+            self.net_label(),
+            self.net,
+            self.ratios.savings_rate,
+            escape(&self.ratios_text())
+        )
+        .unwrap();
+
+        writeln!(out, "<h2>Months</h2>\n<table>").unwrap();
+        writeln!(out, "<tr><th></th><th>credits</th><th>debits</th></tr>").unwrap();
+        for month in self.months.iter() {
+            writeln!(
+                out,
+                "<tr><td>{}</td><td class=\"amount\">{}</td><td class=\"amount\">{}</td></tr>",
+                escape(&month.date),
+                month.credits,
+                month.debits
+            )
+            .unwrap();
+        }
+        writeln!(out, "</table>").unwrap();
+
+        writeln!(out, "<h2>Debit tags by month</h2>\n<table>").unwrap();
+        write!(out, "<tr><th></th>").unwrap();
+        for month in self.months.iter() {
+            write!(out, "<th>{}</th>", escape(&month.date)).unwrap();
+        }
+        writeln!(out, "<th>total</th></tr>").unwrap();
+        for row in self.debit_tags.iter() {
+            write!(out, "<tr><td>{}</td>", escape(&row.tag)).unwrap();
+            for amount in row.months.iter().chain(std::iter::once(&row.total)) {
+                write!(out, "<td class=\"amount\">{}</td>", amount).unwrap();
+            }
+            writeln!(out, "</tr>").unwrap();
+        }
+        writeln!(out, "</table>").unwrap();
+
+        writeln!(out, "<h2>Largest debits</h2>\n<table>").unwrap();
+        for transaction in self.largest.iter() {
+            writeln!(
+                out,
+                "<tr><td>{}</td><td class=\"amount\">{}</td><td>{}</td></tr>",
+                escape(&transaction.date),
+                transaction.entry.amount,
+                escape(transaction.entry.comment.trim())
+            )
+            .unwrap();
+        }
+        writeln!(out, "</table>\n</body>\n</html>").unwrap();
+
+        out
+    }
+}
+
+/// Renders tags-by-month rows as a table, with a column for each of `dates` and a
+/// final column for the tags' totals.
+pub fn matrix(dates: &[&str], rows: &[TagRow]) -> String {
+    let mut out = String::new();
+
+    write!(out, "{:<16}", "").unwrap();
+    for date in dates.iter() {
+        write!(out, " {:>10}", date).unwrap();
+    }
+    writeln!(out, " {:>10}", "total").unwrap();
+
+    for row in rows.iter() {
+        write!(out, "{:<16}", row.tag).unwrap();
+        for amount in row.months.iter() {
+            write!(out, " {:>10}", amount).unwrap();
+        }
+        writeln!(out, " {:>10}", row.total).unwrap();
+    }
+
+    out
+}
+
+/// Escapes text for inclusion in HTML.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for chr in text.chars() {
+        match chr {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            chr => escaped.push(chr),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+    use crate::pledger::parse_ledger;
+    use crate::pledger::ParseOptions;
+
+    fn ledger(date: &str, contents: &'static str) -> Ledger {
+        parse_ledger(
+            date,
+            Box::new(contents.as_bytes().lines()),
+            &ParseOptions::default(),
+        )
+        .unwrap()
+    }
+
+    fn amt(subunits: u64) -> Amount {
+        Amount::from_subunits(subunits)
+    }
+
+    #[test]
+    fn test_annual_report() {
+        let ledgers = vec![
+            ledger("2023-01", "C 100.00 #pay\nD 20.00 #food\nD 5.00 #fun"),
+            ledger(
+                "2023-02",
+                "C 100.00 #pay\nD 50.00 rent <&> #home\nD 5.00 #food",
+            ),
+        ];
+
+        let report = AnnualReport::new("2023", &ledgers).unwrap();
+        assert_eq!(report.credits, amt(20000));
+        assert_eq!(report.debits, amt(8000));
+        assert_eq!(report.net, amt(12000));
+        assert_eq!(report.net_kind, Credit);
+        assert_eq!(report.ratios.savings_rate, 60.0);
+        assert_eq!(report.ratios.expense_ratio, 40.0);
+        assert_eq!(report.ratios.top_expense.as_ref().unwrap().percent, 25.0);
+
+        assert_eq!(report.debit_tags[0].tag, "#home");
+        assert_eq!(report.largest.len(), 4);
+        assert_eq!(report.largest[0].date, "2023-02");
+        assert_eq!(report.largest[1].entry.amount, amt(2000));
+
+        assert!(report
+            .to_text()
+            .contains("net gain of 120.00 (60.0% saved)"));
+        assert!(report.to_text().contains(
+            "spent 40.0% of credits; the largest debit tag, #home, was 25.0% of credits"
+        ));
+        assert!(report.to_html().contains("rent &lt;&amp;&gt; #home"));
+
+        let report =
+            AnnualReport::new("2023", &[ledger("2023-01", "C 1.00 #a\nD 3.00 #b")]).unwrap();
+        assert_eq!(report.net_kind, Debit);
+        assert_eq!(report.ratios.savings_rate, -200.0);
+
+        let report = AnnualReport::new("2023", &[]).unwrap();
+        assert_eq!(report.ratios.savings_rate, 0.0);
+        assert_eq!(report.ratios.top_expense, None);
+    }
+}
@@ -0,0 +1,170 @@
+//! Tag aliases, and rewriting the tags in ledgers, e.g. for `pledger retag`.
+//!
+//! Aliases come from the config's `[aliases]`, e.g. `"#grocery" = "#food"`, and are
+//! passed to the parser in `ParseOptions`. Entries are parsed with each aliased tag
+//! replaced by its canonical name, so that reports count them together; the ledgers
+//! themselves are only changed by `retag`.
+//!
+//! Tags can also be matched without regard to case (with the config's `ignore_tag_case`
+//! or `--ignore-tag-case`), in which case every tag's canonical name is lowercase.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::pledger::categorize::Change;
+use crate::pledger::{
+    logical_lines, parse_entry_ref, update_ledger, Error, ParseOptions, Result, Settings,
+};
+
+/// Returns a tag's canonical name: the tag it's an alias for, or else the tag itself.
+/// Both are lowercased when tags are matched case-insensitively.
+pub fn canonical(tag: &str, aliases: &BTreeMap<String, String>, ignore_case: bool) -> String {
+    if !ignore_case {
+        return aliases.get(tag).cloned().unwrap_or_else(|| tag.into());
+    }
+
+    let tag = tag.to_lowercase();
+    let canonical = aliases
+        .iter()
+        .find(|(alias, _)| alias.to_lowercase() == tag)
+        .map_or(tag, |(_, canonical)| canonical.to_lowercase());
+    canonical
+}
+
+/// Renames the tags in a ledger's text with `rename`, which returns a tag's new name (or
+/// `None` to leave it alone). Returns the new text and the lines that changed.
+///
+/// Everything but the renamed tags is left byte-for-byte as it was, including line
+/// endings and trailing whitespace.
+pub fn retag_text(
+    text: &str,
+    options: &ParseOptions,
+    rename: impl Fn(&str) -> Option<String>,
+) -> (String, Vec<Change>) {
+    let old: Vec<&str> = text.lines().collect();
+    // The byte offset of each line in the text.
+    let starts: Vec<usize> = text
+        .split_inclusive('\n')
+        .scan(0, |start, line| {
+            let this = *start;
+            *start += line.len();
+            Some(this)
+        })
+        .collect();
+
+    let mut retagged = text.to_string();
+    let mut changed = vec![];
+    // Tags are renamed from the end of the text, so that the offsets of the
+    // ones before stay valid.
+    for logical in logical_lines(text).iter().rev() {
+        let entry = match parse_entry_ref(&logical.text, options) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+
+        for (tag, span) in entry.tags.iter().rev() {
+            if let Some(renamed) = rename(tag).filter(|r| r != tag) {
+                let (idx, span) = logical.physical(&old, span);
+                let start = starts[idx] + span.start;
+                retagged.replace_range(start..start + span.len(), &renamed);
+                changed.push(idx);
+            }
+        }
+    }
+
+    changed.sort_unstable();
+    changed.dedup();
+    let new: Vec<&str> = retagged.lines().collect();
+    let changes = changed
+        .into_iter()
+        .map(|idx| Change {
+            line: idx + 1,
+            old: old[idx].into(),
+            new: new[idx].into(),
+        })
+        .collect();
+
+    (retagged, changes)
+}
+
+/// Renames the tags in the ledger for `date` like `retag_text`, writing it back (or
+/// planning to, in a dry run). Returns the lines that changed.
+pub fn retag_ledger_file(
+    directory: &Path,
+    date: &str,
+    settings: &Settings,
+    rename: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<Change>> {
+    let ledger_file = directory.join(format!("{}.ledger", date));
+    if !ledger_file.is_file() {
+        return Err(Error::NotFound(format!(
+            "no ledger for {} in {}",
+            date,
+            directory.display()
+        )));
+    }
+
+    let mut changes = vec![];
+    update_ledger(directory, date, settings, |text| {
+        let (retagged, c) = retag_text(&text, &settings.parse, &rename);
+        changes = c;
+        // Leave the file alone when nothing changed.
+        Ok(match changes.is_empty() {
+            true => text,
+            false => retagged,
+        })
+    })
+    .map_err(|e| e.with_file(&ledger_file))?;
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retag_text() {
+        let aliases: BTreeMap<String, String> = [("#grocery", "#food"), ("#uber", "#transit")]
+            .into_iter()
+            .map(|(a, c)| (a.into(), c.into()))
+            .collect();
+        let rename = |tag: &str| aliases.get(tag).cloned();
+
+        let text = "# #grocery\nD 12.00 milk  #grocery #x\nD 9.00 ride home\n  #uber #grocery\nD 1.00 a #food\n";
+        let (retagged, changes) = retag_text(text, &ParseOptions::default(), rename);
+        assert_eq!(
+            retagged,
+            "# #grocery\nD 12.00 milk  #food #x\nD 9.00 ride home\n  #transit #food\nD 1.00 a #food\n"
+        );
+        assert_eq!(
+            changes.iter().map(|c| c.line).collect::<Vec<_>>(),
+            vec![2, 4]
+        );
+        assert_eq!(
+            retag_text(&retagged, &ParseOptions::default(), rename),
+            (retagged.clone(), vec![])
+        );
+
+        let text = "D 1.00 a #uber \r\nD 2.00 b #x\r\n\r\n# end";
+        assert_eq!(
+            retag_text(text, &ParseOptions::default(), rename).0,
+            "D 1.00 a #transit \r\nD 2.00 b #x\r\n\r\n# end"
+        );
+
+        // Parsing applies the aliases, without changing the comment.
+        let options = ParseOptions {
+            aliases: aliases.clone(),
+            ..ParseOptions::default()
+        };
+        let entry = parse_entry_ref("D 12.00 milk #grocery #food", &ParseOptions::default())
+            .unwrap()
+            .to_entry(&options);
+        assert_eq!(entry.tags, vec!["#food"]);
+        assert_eq!(entry.comment, "milk #grocery #food");
+
+        assert_eq!(canonical("#Grocery", &aliases, false), "#Grocery");
+        assert_eq!(canonical("#Grocery", &aliases, true), "#food");
+        assert_eq!(canonical("#Rent", &aliases, true), "#rent");
+    }
+}
@@ -0,0 +1,289 @@
+//! Rewriting or removing a single entry in place, e.g. for `pledger edit-entry`.
+//!
+//! Only the parts of the entry's lines that change are rewritten, so that the rest of the
+//! ledger, and the entry's own formatting (including its continuation lines), stay as
+//! they were.
+
+use std::path::Path;
+
+use crate::pledger::amount::{currency_symbol, Amount};
+use crate::pledger::{
+    is_tag, logical_lines, parse_entry_ref, parse_line, prompt, update_ledger, Entry, Error,
+    LogicalLine, ParseOptions, Parsed, Result, Settings,
+};
+
+/// The changes to make to an entry.
+#[derive(Debug, Default)]
+pub struct Edit {
+    pub amount: Option<Amount>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+}
+
+/// Returns the `index`th (from 0) entry's logical line in a ledger's text.
+fn nth_entry(text: &str, index: usize, options: &ParseOptions) -> Result<LogicalLine> {
+    logical_lines(text)
+        .into_iter()
+        .filter(|l| matches!(parse_line(&l.text, options), Ok(Parsed::Entry(_))))
+        .nth(index)
+        .ok_or_else(|| Error::NotFound(format!("no entry {} in the ledger", index + 1)))
+}
+
+/// Applies an edit to the `index`th (from 0) entry in a ledger's text, returning the new
+/// text and the edited entry.
+pub fn edit_text(
+    text: &str,
+    index: usize,
+    edit: &Edit,
+    options: &ParseOptions,
+) -> Result<(String, Entry)> {
+    if let Some(tag) = edit
+        .add_tags
+        .iter()
+        .chain(&edit.remove_tags)
+        .find(|t| !is_tag(t))
+    {
+        return Err(Error::syntax(None, format!("invalid tag: {}", tag)));
+    }
+
+    let logical = nth_entry(text, index, options)?;
+    let entry = parse_entry_ref(&logical.text, options)
+        .map_err(|_| Error::syntax(None, format!("entry {} couldn't be parsed", index + 1)))?;
+    let old: Vec<&str> = text.lines().collect();
+
+    let mut edits = vec![];
+    if let Some(amount) = edit.amount {
+        let symbol = entry.currency.and_then(currency_symbol);
+        let text = match symbol {
+            Some(symbol) => format!("{}{}", symbol, amount.to_ledger_string()),
+            None => amount.to_ledger_string(),
+        };
+        let (idx, span) = logical.physical(&old, &entry.amount_span);
+        edits.push((idx, span, text));
+    }
+
+    for tag in edit.remove_tags.iter() {
+        let spans: Vec<_> = entry.tags.iter().filter(|(t, _)| t == tag).collect();
+        if spans.is_empty() {
+            return Err(Error::NotFound(format!("the entry has no {} tag", tag)));
+        }
+
+        for (_, span) in spans {
+            // Each tag goes along with the whitespace after it, or else before it.
+            let (idx, mut span) = logical.physical(&old, span);
+            let line = old[idx];
+            match (
+                line[span.end..].chars().next(),
+                line[..span.start].chars().last(),
+            ) {
+                (Some(c), _) if c.is_whitespace() => span.end += c.len_utf8(),
+                (_, Some(c)) if c.is_whitespace() => span.start -= c.len_utf8(),
+                _ => {}
+            }
+            edits.push((idx, span, String::new()));
+        }
+    }
+
+    let mut new: Vec<String> = old.iter().map(|l| l.to_string()).collect();
+    edits.sort_by_key(|(idx, span, _)| (*idx, span.start));
+    for (idx, span, text) in edits.into_iter().rev() {
+        new[idx].replace_range(span, &text);
+    }
+
+    let last = logical.parts[logical.parts.len() - 1].idx;
+    for tag in edit.add_tags.iter() {
+        if entry.tags.iter().any(|(t, _)| t == tag) && !edit.remove_tags.contains(tag) {
+            continue;
+        }
+        let end = new[last].trim_end().len();
+        new[last].truncate(end);
+        new[last].push(' ');
+        new[last].push_str(tag);
+    }
+
+    // A continuation line that only had removed tags on it goes away entirely.
+    for part in logical.parts.iter().skip(1).rev() {
+        if new[part.idx].trim().is_empty() {
+            new.remove(part.idx);
+        }
+    }
+
+    let mut text_out = new.join("\n");
+    if text.ends_with('\n') {
+        text_out.push('\n');
+    }
+
+    let edited = nth_entry(&text_out, index, options)?;
+    let entry = match parse_line(&edited.text, options) {
+        Ok(Parsed::Entry(entry)) => entry,
+        _ => unreachable!("nth_entry only returns entries"),
+    };
+    Ok((text_out, entry))
+}
+
+/// Applies an edit to the `index`th (from 0) entry in the ledger for `date`, returning
+/// the edited entry.
+pub fn edit_entry(
+    directory: &Path,
+    date: &str,
+    settings: &Settings,
+    index: usize,
+    edit: &Edit,
+) -> Result<Entry> {
+    let mut edited = None;
+    update_ledger(directory, date, settings, |contents| {
+        let (text, entry) = edit_text(&contents, index, edit, &settings.parse)?;
+        edited = Some(entry);
+        Ok(text)
+    })
+    .map_err(|e| e.with_file(&directory.join(format!("{date}.ledger"))))?;
+
+    Ok(edited.unwrap())
+}
+
+/// Removes the `index`th (from 0) entry from a ledger's text, returning the new text and
+/// the removed entry. If `void` is set, the entry's lines are commented out instead.
+pub fn remove_text(
+    text: &str,
+    index: usize,
+    void: bool,
+    options: &ParseOptions,
+) -> Result<(String, Entry)> {
+    let logical = nth_entry(text, index, options)?;
+    let entry = match parse_line(&logical.text, options) {
+        Ok(Parsed::Entry(entry)) => entry,
+        _ => unreachable!("nth_entry only returns entries"),
+    };
+
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    for part in logical.parts.iter().rev() {
+        match void {
+            true => lines[part.idx].insert_str(0, "# "),
+            false => {
+                lines.remove(part.idx);
+            }
+        }
+    }
+
+    let mut removed = lines.join("\n");
+    if text.ends_with('\n') && !lines.is_empty() {
+        removed.push('\n');
+    }
+    Ok((removed, entry))
+}
+
+/// Removes (or voids) the `index`th (from 0) entry in the ledger for `date`, returning
+/// the removed entry.
+pub fn remove_entry(
+    directory: &Path,
+    date: &str,
+    settings: &Settings,
+    index: usize,
+    void: bool,
+) -> Result<Entry> {
+    let mut removed = None;
+    update_ledger(directory, date, settings, |contents| {
+        let (text, entry) = remove_text(&contents, index, void, &settings.parse)?;
+        removed = Some(entry);
+        Ok(text)
+    })
+    .map_err(|e| e.with_file(&directory.join(format!("{date}.ledger"))))?;
+
+    Ok(removed.unwrap())
+}
+
+/// Asks a yes-or-no question on the terminal, defaulting to no.
+pub fn confirm(question: &str) -> Result<bool> {
+    let answer = prompt(&format!("{} [y/N]: ", question))?;
+    Ok(matches!(
+        answer.as_deref().map(str::trim),
+        Some("y" | "Y" | "yes")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_text() {
+        let text =
+            "# may\nD 12.00 lunch  #food #work\nbalance 5.00 @bank\nD 3.00 tea\n  with sam #fun\n";
+
+        let edit = Edit {
+            amount: Some(Amount::from_subunits(1300)),
+            add_tags: vec!["#team".into()],
+            remove_tags: vec!["#work".into()],
+        };
+        let (edited, entry) = edit_text(text, 0, &edit, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            edited,
+            "# may\nD 13.00 lunch  #food #team\nbalance 5.00 @bank\nD 3.00 tea\n  with sam #fun\n"
+        );
+        assert_eq!(entry.amount.subunits(), 1300);
+        assert_eq!(entry.tags, vec!["#food", "#team"]);
+
+        // Tags are added to an entry's last line, and removing a continuation line's only
+        // tag removes the line.
+        let edit = Edit {
+            add_tags: vec!["#food".into()],
+            ..Default::default()
+        };
+        let (edited, _) = edit_text(text, 1, &edit, &ParseOptions::default()).unwrap();
+        assert!(edited.ends_with("D 3.00 tea\n  with sam #fun #food\n"));
+        let text = "D 3.00 tea\n  #fun\n";
+        let edit = Edit {
+            remove_tags: vec!["#fun".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            edit_text(text, 0, &edit, &ParseOptions::default())
+                .unwrap()
+                .0,
+            "D 3.00 tea\n"
+        );
+
+        let missing = Edit {
+            remove_tags: vec!["#nope".into()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            edit_text(text, 0, &missing, &ParseOptions::default()),
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            edit_text(text, 1, &edit, &ParseOptions::default()),
+            Err(Error::NotFound(_))
+        ));
+        let invalid = Edit {
+            add_tags: vec!["food".into()],
+            ..Default::default()
+        };
+        assert!(edit_text(text, 0, &invalid, &ParseOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_remove_text() {
+        let text = "# may\nD 12.00 lunch #food\nD 3.00 tea\n  with sam #fun\nC 1.00 pay\n";
+
+        let (removed, entry) = remove_text(text, 1, false, &ParseOptions::default()).unwrap();
+        assert_eq!(removed, "# may\nD 12.00 lunch #food\nC 1.00 pay\n");
+        assert_eq!(entry.comment, "tea with sam #fun");
+
+        let (voided, _) = remove_text(text, 1, true, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            voided,
+            "# may\nD 12.00 lunch #food\n# D 3.00 tea\n#   with sam #fun\nC 1.00 pay\n"
+        );
+        assert_eq!(
+            remove_text("D 1.00 a\n", 0, false, &ParseOptions::default())
+                .unwrap()
+                .0,
+            ""
+        );
+        assert!(matches!(
+            remove_text(text, 3, false, &ParseOptions::default()),
+            Err(Error::NotFound(_))
+        ));
+    }
+}
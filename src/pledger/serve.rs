@@ -0,0 +1,590 @@
+//! A small HTTP/1.1 API over a ledger directory.
+//!
+//! The read endpoints are `GET /ledgers`, `GET /ledgers/{date}`, `GET /summary` (with
+//! an optional `date` or `year` parameter) and `GET /tags`. `POST /ledgers/{date}`
+//! appends the entry in its body, and needs the server's bearer token. `GET /metrics`
+//! has the current month's Prometheus metrics.
+//!
+//! Connections are handled one at a time, with one request each, so each has a timeout
+//! and its request's size is bounded. Everything but the metrics goes through the
+//! directory's `LedgerStore`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::Local;
+use serde_json::{json, Value};
+
+use crate::pledger::aggregate::{Report, Summary};
+use crate::pledger::metrics;
+use crate::pledger::store::{append_entry, load_ledgers, DirectoryStore, LedgerStore};
+use crate::pledger::{parse_date, Entry, Error, Result, Settings};
+
+/// The largest request body that the server will read.
+const MAX_BODY: usize = 64 * 1024;
+
+/// The most headers that the server will read in a request.
+const MAX_HEADERS: usize = 64;
+
+/// The longest request or header line that the server will read, in bytes.
+const MAX_LINE: u64 = 8192;
+
+/// How long the server waits on a connection's reads and writes before giving up on it.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The content type of Prometheus' text exposition format.
+const METRICS_TYPE: &str = "text/plain; version=0.0.4";
+
+#[derive(Debug, Default)]
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    /// Header values, by lowercased name.
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Reads a line of a request into `line`, returning its length. Lines longer than
+/// `MAX_LINE` are an error.
+fn read_line(input: &mut impl BufRead, line: &mut String) -> Result<usize> {
+    let read = input
+        .take(MAX_LINE)
+        .read_line(line)
+        .map_err(Error::io("request read failed"))?;
+    if read as u64 == MAX_LINE && !line.ends_with('\n') {
+        return Err(Error::Serve("request line too long".into()));
+    }
+    Ok(read)
+}
+
+/// Decodes a query string's percent-encoded component, where `+` is a space too.
+fn percent_decode(text: &str) -> Result<String> {
+    let mut bytes = vec![];
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = rest
+                    .get(..2)
+                    .and_then(|h| std::str::from_utf8(h).ok())
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                    .ok_or_else(|| Error::Serve(format!("malformed percent-encoding: {}", text)))?;
+                bytes.push(hex);
+                rest = &rest[2..];
+            }
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| Error::Serve(format!("query isn't UTF-8: {}", text)))
+}
+
+/// Reads a single request, or `None` if the connection closed first.
+fn read_request(input: &mut impl BufRead) -> Result<Option<Request>> {
+    let mut line = String::new();
+    if read_line(input, &mut line)? == 0 {
+        return Ok(None);
+    }
+
+    let mut parts = line.split_ascii_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => {
+            return Err(Error::Serve(format!(
+                "malformed request line: {}",
+                line.trim_end()
+            )))
+        }
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut request = Request {
+        method: method.into(),
+        path: path.into(),
+        query: query
+            .split('&')
+            .filter_map(|p| p.split_once('='))
+            .map(|(k, v)| Ok((percent_decode(k)?, percent_decode(v)?)))
+            .collect::<Result<_>>()?,
+        ..Default::default()
+    };
+
+    loop {
+        let mut header = String::new();
+        read_line(input, &mut header)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if request.headers.len() >= MAX_HEADERS {
+            return Err(Error::Serve("too many headers".into()));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            request
+                .headers
+                .insert(name.to_ascii_lowercase(), value.trim().into());
+        }
+    }
+
+    let length = match request.headers.get("content-length") {
+        Some(length) => length
+            .parse::<usize>()
+            .map_err(|_| Error::Serve(format!("invalid Content-Length: {}", length)))?,
+        None => 0,
+    };
+    if length > MAX_BODY {
+        return Err(Error::Serve(format!(
+            "request body too large: {} bytes",
+            length
+        )));
+    }
+    request.body = vec![0; length];
+    input
+        .read_exact(&mut request.body)
+        .map_err(Error::io("request read failed"))?;
+
+    Ok(Some(request))
+}
+
+fn write_response(
+    output: &mut impl Write,
+    status: u16,
+    content_type: &str,
+    body: &str,
+) -> Result<()> {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+
+    write!(
+        output,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        content_type,
+        body.len(),
+        body
+    )
+    .and_then(|()| output.flush())
+    .map_err(Error::io("response write failed"))
+}
+
+/// Compares two tokens in time that depends only on their lengths.
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+fn error(status: u16, message: impl std::fmt::Display) -> (u16, Value) {
+    (status, json!({ "error": message.to_string() }))
+}
+
+/// Maps a library error to a response: missing ledgers are 404s, and everything else
+/// is the server's fault.
+fn library_error(e: Error) -> (u16, Value) {
+    match e {
+        Error::NotFound(_) => error(404, e),
+        e => error(500, e),
+    }
+}
+
+fn summary(
+    store: &dyn LedgerStore,
+    settings: &Settings,
+    query: &HashMap<String, String>,
+) -> std::result::Result<Value, (u16, Value)> {
+    let ledger = match (query.get("date"), query.get("year")) {
+        (Some(date), None) => {
+            let date = parse_date(date).map_err(|e| error(400, e))?;
+            store.load(&date, &settings.parse)
+        }
+        (None, Some(year)) => load_ledgers(store, Some(year), settings),
+        (None, None) => load_ledgers(store, None, settings),
+        (Some(_), Some(_)) => return Err(error(400, "only one of date or year")),
+    }
+    .map_err(library_error)?;
+
+    let summary = Summary::new(&ledger).map_err(|e| error(500, e))?;
+    Ok(json!(Report::new(&summary, None)))
+}
+
+fn tags(store: &dyn LedgerStore, settings: &Settings) -> std::result::Result<Value, (u16, Value)> {
+    let ledger = load_ledgers(store, None, settings).map_err(library_error)?;
+
+    let mut counts = BTreeMap::<&str, usize>::new();
+    for tag in ledger.entries.iter().flat_map(|e| e.tags.iter()) {
+        *counts.entry(tag).or_default() += 1;
+    }
+    Ok(json!(counts))
+}
+
+fn append(
+    store: &dyn LedgerStore,
+    date: &str,
+    body: &[u8],
+) -> std::result::Result<Value, (u16, Value)> {
+    let date = parse_date(date).map_err(|e| error(400, e))?;
+    let line = std::str::from_utf8(body).map_err(|e| error(400, e))?;
+    let entry: Entry = line.trim().parse().map_err(|e| error(400, e))?;
+
+    append_entry(store, &date, &entry).map_err(library_error)?;
+    Ok(json!({ "date": date, "entry": entry }))
+}
+
+/// Handles a request, returning its response's status and body. POSTs are refused
+/// unless `token` is set and given as a bearer token.
+fn handle(
+    store: &dyn LedgerStore,
+    settings: &Settings,
+    token: Option<&str>,
+    request: &Request,
+) -> (u16, Value) {
+    let segments: Vec<_> = request.path.trim_matches('/').split('/').collect();
+    let ok = |body| (200, body);
+
+    let result = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["ledgers"]) => store
+            .dates()
+            .map(|dates| ok(json!(dates)))
+            .map_err(library_error),
+        ("GET", ["ledgers", date]) => parse_date(date)
+            .map_err(|e| error(400, e))
+            .and_then(|date| store.load(&date, &settings.parse).map_err(library_error))
+            .map(|ledger| ok(json!(ledger))),
+        ("GET", ["summary"]) => summary(store, settings, &request.query).map(ok),
+        ("GET", ["tags"]) => tags(store, settings).map(ok),
+        ("POST", ["ledgers", date]) => {
+            let given = request
+                .headers
+                .get("authorization")
+                .and_then(|a| a.strip_prefix("Bearer "));
+            match (token, given) {
+                (None, _) => Err(error(403, "appending is disabled without a token")),
+                (Some(token), Some(given)) if tokens_match(given, token) => {
+                    append(store, date, &request.body).map(|body| (201, body))
+                }
+                _ => Err(error(401, "missing or invalid token")),
+            }
+        }
+        (_, ["ledgers"] | ["ledgers", _] | ["summary"] | ["tags"]) => {
+            Err(error(405, "method not allowed"))
+        }
+        _ => Err(error(404, "no such endpoint")),
+    };
+
+    result.unwrap_or_else(|e| e)
+}
+
+/// Responds to a request, returning the response's status, content type, and body.
+fn respond(
+    directory: &Path,
+    settings: &Settings,
+    token: Option<&str>,
+    request: &Request,
+) -> (u16, &'static str, String) {
+    if request.method == "GET" && request.path == "/metrics" {
+        let month = Local::now().format("%Y-%m").to_string();
+        return match metrics::for_month(directory, &month, settings) {
+            Ok(metrics) => (200, METRICS_TYPE, metrics),
+            Err(e) => (500, "text/plain", e.to_string()),
+        };
+    }
+
+    let (status, body) = handle(
+        &DirectoryStore::new(directory, settings.clone()),
+        settings,
+        token,
+        request,
+    );
+    (status, "application/json", body.to_string())
+}
+
+/// Serves the API for `directory` on `listen`, e.g. `127.0.0.1:8080`, until killed.
+pub fn serve(
+    directory: &Path,
+    settings: &Settings,
+    listen: &str,
+    token: Option<&str>,
+) -> Result<()> {
+    let listener =
+        TcpListener::bind(listen).map_err(Error::io(format!("couldn't listen on {}", listen)))?;
+    log::info!("listening on {}", listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("connection failed: {}", e);
+                continue;
+            }
+        };
+        if let Err(e) = stream
+            .set_read_timeout(Some(TIMEOUT))
+            .and_then(|_| stream.set_write_timeout(Some(TIMEOUT)))
+        {
+            log::warn!("couldn't set the connection's timeouts: {}", e);
+            continue;
+        }
+
+        let request = match read_request(&mut BufReader::new(&mut stream)) {
+            Ok(Some(request)) => request,
+            Ok(None) => continue,
+            Err(e) => {
+                let body = json!({ "error": e.to_string() }).to_string();
+                let _ = write_response(&mut stream, 400, "application/json", &body);
+                continue;
+            }
+        };
+
+        let (status, content_type, body) = respond(directory, settings, token, &request);
+        log::info!("{} {} => {}", request.method, request.path, status);
+        if let Err(e) = write_response(&mut stream, status, content_type, &body) {
+            log::warn!("response failed: {}", e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::pledger::testing::{LedgerBuilder, MemoryStore};
+
+    fn request(method: &str, target: &str, headers: &str, body: &str) -> Request {
+        let raw = format!(
+            "{} {} HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            target,
+            headers,
+            body.len(),
+            body
+        );
+        read_request(&mut raw.as_bytes()).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_handle() {
+        let store = MemoryStore::new().with(LedgerBuilder::new("2023-01").debit(5, "lunch #food"));
+        let get = |target| {
+            handle(
+                &store,
+                &Settings::default(),
+                Some("secret"),
+                &request("GET", target, "", ""),
+            )
+        };
+        assert_eq!(get("/ledgers"), (200, json!(["2023-01"])));
+        assert_eq!(
+            get("/ledgers/2023-01").1["entries"][0]["comment"],
+            "lunch #food"
+        );
+        assert_eq!(get("/ledgers/2023-02").0, 404);
+        assert_eq!(get("/ledgers/nope").0, 400);
+        assert_eq!(get("/summary?date=2023-01").1["debits"], json!([5, 0]));
+        assert_eq!(get("/summary?date=2023%2D01").1["debits"], json!([5, 0]));
+        assert_eq!(get("/tags"), (200, json!({"#food": 1})));
+        assert_eq!(get("/nope").0, 404);
+
+        let post = |token: Option<&str>, headers| {
+            handle(
+                &store,
+                &Settings::default(),
+                token,
+                &request("POST", "/ledgers/2023-01", headers, "D 2.00 coffee #food"),
+            )
+        };
+        assert_eq!(post(None, "Authorization: Bearer secret\r\n").0, 403);
+        assert_eq!(post(Some("secret"), "").0, 401);
+        assert_eq!(
+            post(Some("secret"), "Authorization: Bearer wrong\r\n").0,
+            401
+        );
+        assert_eq!(
+            post(Some("secret"), "Authorization: Bearer secret\r\n").0,
+            201
+        );
+        assert_eq!(get("/tags"), (200, json!({"#food": 2})));
+    }
+
+    #[test]
+    fn test_read_request() {
+        let request = request("GET", "/summary?year=2023&x=a+b%20c%C3%A9", "", "");
+        assert_eq!(request.query["year"], "2023");
+        assert_eq!(request.query["x"], "a b cé");
+
+        for raw in [
+            "GET /summary?date=%2 HTTP/1.1\r\n\r\n".to_string(),
+            "GET /summary?date=%ff HTTP/1.1\r\n\r\n".to_string(),
+            format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE as usize)),
+            format!(
+                "GET / HTTP/1.1\r\nX: {}\r\n\r\n",
+                "a".repeat(MAX_LINE as usize)
+            ),
+        ] {
+            assert!(read_request(&mut raw.as_bytes()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_rejected_appends() {
+        let store = MemoryStore::new().with(LedgerBuilder::new("2023-01").debit(5, "lunch #food"));
+        let post = |headers, body| {
+            handle(
+                &store,
+                &Settings::default(),
+                Some("secret"),
+                &request("POST", "/ledgers/2023-01", headers, body),
+            )
+        };
+
+        // Only a bearer token that's exactly the server's is accepted.
+        for headers in [
+            "Authorization: secret\r\n",
+            "Authorization: Basic secret\r\n",
+            "Authorization: Bearer secre\r\n",
+            "Authorization: Bearer secrets\r\n",
+            "Authorization: Bearer SECRET\r\n",
+        ] {
+            assert_eq!(
+                post(headers, "D 2.00 coffee #food"),
+                error(401, "missing or invalid token")
+            );
+        }
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secreT", "secret"));
+        assert!(!tokens_match("", "secret"));
+
+        // An authorized request with a bad entry is still rejected.
+        let authorized = "Authorization: Bearer secret\r\n";
+        assert_eq!(post(authorized, "D coffee").0, 400);
+        assert_eq!(
+            handle(
+                &store,
+                &Settings::default(),
+                Some("secret"),
+                &request("POST", "/ledgers/nope", authorized, "D 2.00 coffee"),
+            )
+            .0,
+            400
+        );
+        assert_eq!(
+            handle(
+                &store,
+                &Settings::default(),
+                Some("secret"),
+                &request("DELETE", "/ledgers/2023-01", authorized, ""),
+            )
+            .0,
+            405
+        );
+
+        // None of which appended anything.
+        assert_eq!(
+            store.read("2023-01").unwrap().unwrap(),
+            "D 5.00 lunch #food\n"
+        );
+    }
+
+    #[test]
+    fn test_request_limits() {
+        let read = |raw: String| read_request(&mut raw.as_bytes());
+
+        // A closed connection isn't a request, or an error.
+        assert!(read(String::new()).unwrap().is_none());
+
+        let headers = |n: usize| {
+            (0..n)
+                .map(|i| format!("X-{}: {}\r\n", i, i))
+                .collect::<String>()
+        };
+        assert_eq!(
+            read(format!("GET / HTTP/1.1\r\n{}\r\n", headers(MAX_HEADERS)))
+                .unwrap()
+                .unwrap()
+                .headers
+                .len(),
+            MAX_HEADERS
+        );
+        let body = "a".repeat(MAX_BODY);
+        assert_eq!(
+            read(format!(
+                "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                MAX_BODY, body
+            ))
+            .unwrap()
+            .unwrap()
+            .body
+            .len(),
+            MAX_BODY
+        );
+
+        // Requests past the limits, or that aren't HTTP at all, are rejected before
+        // anything else is read.
+        for (raw, message) in [
+            (
+                format!("GET / HTTP/1.1\r\n{}\r\n", headers(MAX_HEADERS + 1)),
+                "too many headers".to_string(),
+            ),
+            (
+                format!(
+                    "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}a",
+                    MAX_BODY + 1,
+                    body
+                ),
+                format!("request body too large: {} bytes", MAX_BODY + 1),
+            ),
+            (
+                "POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n".into(),
+                "invalid Content-Length: -1".into(),
+            ),
+            ("GET\r\n\r\n".into(), "malformed request line: GET".into()),
+        ] {
+            match read(raw) {
+                Err(Error::Serve(e)) => assert_eq!(e, message),
+                other => panic!("expected {:?}, got {:?}", message, other),
+            }
+        }
+
+        // A body that's shorter than its Content-Length is a failed read, not a bad request.
+        assert!(matches!(
+            read("POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc".into()),
+            Err(Error::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn test_metrics() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(directory.join("2023-01.ledger"), "D 5.00 lunch #food\n").unwrap();
+
+        let (status, content_type, body) = respond(
+            directory,
+            &Settings::default(),
+            None,
+            &request("GET", "/metrics", "", ""),
+        );
+        assert_eq!((status, content_type), (200, METRICS_TYPE));
+        assert!(body.contains("# TYPE pledger_debits gauge"));
+    }
+}
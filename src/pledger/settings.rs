@@ -0,0 +1,75 @@
+//! How a ledger directory is read and written: `ParseOptions` for parsing its ledgers,
+//! within `Settings` for everything else. Both come from the directory's `pledger.toml`
+//! (see `config`) and the command line, and are passed to whatever needs them.
+
+use std::collections::BTreeMap;
+
+use crate::pledger::amount::AmountFormat;
+use crate::pledger::retag;
+
+/// How ledgers are parsed, for `--strict` and `--lenient`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Strictness {
+    /// Untagged entries are errors, along with everything that normally is.
+    Strict,
+    #[default]
+    Normal,
+    /// Lines that can't be parsed are skipped, and their errors kept in the ledger.
+    Lenient,
+}
+
+/// How a ledger's lines are parsed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParseOptions {
+    pub strictness: Strictness,
+    /// Tag aliases, from the config's `[aliases]`: entries' aliased tags are parsed as
+    /// the tags they're aliases for.
+    pub aliases: BTreeMap<String, String>,
+    /// Whether tags are matched without regard to case, in which case they're parsed
+    /// as lowercase.
+    pub ignore_tag_case: bool,
+    /// Whether grouping separators can go anywhere in amounts, rather than only between
+    /// groups of three digits. See `amount::check_grouping`.
+    pub loose_grouping: bool,
+    /// How amounts are written, from the config's `decimal`. Parsed amounts keep it.
+    pub amount_format: AmountFormat,
+}
+
+impl ParseOptions {
+    /// Returns these options, with the given strictness.
+    pub fn with_strictness(&self, strictness: Strictness) -> Self {
+        let mut options = self.clone();
+        options.strictness = strictness;
+        options
+    }
+
+    /// Returns a tag's canonical name under these options' aliases and case sensitivity,
+    /// like `retag::canonical`.
+    pub fn canonical_tag(&self, tag: &str) -> String {
+        retag::canonical(tag, &self.aliases, self.ignore_tag_case)
+    }
+}
+
+/// The settings for a ledger directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// How its ledgers are parsed.
+    pub parse: ParseOptions,
+    /// The month (from 1) that years start in, for `--year`, `--quarter`, and `--half`.
+    /// Years are named for the calendar year they start in, so with a start of 4, the
+    /// year 2023 is April 2023 through March 2024.
+    pub fiscal_year_start: u32,
+    /// Whether the previous version of each ledger or archive that's replaced is kept
+    /// next to it as a `.bak` file.
+    pub keep_backups: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            parse: ParseOptions::default(),
+            fiscal_year_start: 1,
+            keep_backups: false,
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Signatures of ledger files, for `pledger sign` and `pledger verify --signatures`.
+//!
+//! Signing is done by an external program, either `ssh-keygen -Y` or `minisign`, so that
+//! a household can use the keys they already have. Each ledger file's signature is kept
+//! in `.pledger/signatures/`, and verifying it says who signed it: the principal in the
+//! allowed signers file for SSH keys, and the name in the config for minisign keys.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::pledger::config::{Signing, SigningTool};
+use crate::pledger::{write_atomically, Error, Result};
+
+/// Where signatures are kept, relative to the ledger directory.
+pub const SIGNATURE_DIR: &str = ".pledger/signatures";
+
+/// The namespace that SSH signatures are made in, so that they can't be confused with
+/// signatures of anything else.
+const SSH_NAMESPACE: &str = "pledger";
+
+/// The result of verifying a ledger file's signature.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verified {
+    /// The file was signed by this signer.
+    Signed(String),
+    /// The file doesn't have a signature.
+    Unsigned,
+    /// The file's signature isn't valid, or isn't by any of the known signers: usually,
+    /// because the file was changed after it was signed.
+    Invalid,
+}
+
+fn signature_path(directory: &Path, name: &str) -> PathBuf {
+    directory.join(SIGNATURE_DIR).join(format!("{}.sig", name))
+}
+
+/// Runs a signing program, returning its stdout if it succeeds.
+fn run(command: &mut Command, stdin: Option<&Path>) -> Result<Option<String>> {
+    if let Some(stdin) = stdin {
+        let file = fs::File::open(stdin)
+            .map_err(Error::io(format!("couldn't read {}", stdin.display())))?;
+        command.stdin(file);
+    }
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|e| Error::Command(format!("couldn't run {}: {}", program, e)))?;
+
+    match output.status.success() {
+        true => Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned())),
+        false => {
+            log::debug!(
+                "{}: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Signs the ledger file `name` in `directory` with the secret key at `key`.
+pub fn sign(signing: &Signing, directory: &Path, name: &str, key: &Path) -> Result<()> {
+    let file = directory.join(name);
+    let signature = signature_path(directory, name);
+    fs::create_dir_all(signature.parent().unwrap())?;
+
+    let signed = match signing.tool {
+        SigningTool::Ssh => run(
+            Command::new("ssh-keygen")
+                .args(["-Y", "sign", "-n", SSH_NAMESPACE, "-f"])
+                .arg(key),
+            Some(&file),
+        )?
+        .map(|signature_text| write_atomically(&signature, &signature_text, false))
+        .transpose()?
+        .is_some(),
+        // minisign asks for the key's password itself, if it has one.
+        SigningTool::Minisign => Command::new("minisign")
+            .arg("-S")
+            .arg("-s")
+            .arg(key)
+            .arg("-m")
+            .arg(&file)
+            .arg("-x")
+            .arg(&signature)
+            .status()
+            .map_err(|e| Error::Command(format!("couldn't run minisign: {}", e)))?
+            .success(),
+    };
+
+    match signed {
+        true => Ok(()),
+        false => Err(Error::Command(format!("couldn't sign {}", name))),
+    }
+}
+
+/// Verifies the signature of the ledger file `name` in `directory`.
+pub fn verify(signing: &Signing, directory: &Path, name: &str) -> Result<Verified> {
+    let file = directory.join(name);
+    let signature = signature_path(directory, name);
+    if !signature.is_file() {
+        return Ok(Verified::Unsigned);
+    }
+
+    match signing.tool {
+        SigningTool::Ssh => {
+            let allowed_signers =
+                directory.join(signing.allowed_signers.as_ref().ok_or_else(|| {
+                    Error::Config("signing with ssh needs an allowed_signers file".into())
+                })?);
+
+            // The signature says which key made it, and the allowed signers say whose key
+            // that is; the signature is then checked against each of them.
+            let principals = run(
+                Command::new("ssh-keygen")
+                    .args(["-Y", "find-principals", "-s"])
+                    .arg(&signature)
+                    .arg("-f")
+                    .arg(&allowed_signers),
+                None,
+            )?
+            .unwrap_or_default();
+            for principal in principals.lines().filter(|p| !p.is_empty()) {
+                let verified = run(
+                    Command::new("ssh-keygen")
+                        .args(["-Y", "verify", "-n", SSH_NAMESPACE, "-s"])
+                        .arg(&signature)
+                        .arg("-f")
+                        .arg(&allowed_signers)
+                        .args(["-I", principal]),
+                    Some(&file),
+                )?;
+                if verified.is_some() {
+                    return Ok(Verified::Signed(principal.into()));
+                }
+            }
+        }
+        SigningTool::Minisign => {
+            for (signer, public_key) in signing.public_keys.iter() {
+                let verified = run(
+                    Command::new("minisign")
+                        .args(["-V", "-q", "-p"])
+                        .arg(directory.join(public_key))
+                        .arg("-m")
+                        .arg(&file)
+                        .arg("-x")
+                        .arg(&signature),
+                    None,
+                )?;
+                if verified.is_some() {
+                    return Ok(Verified::Signed(signer.clone()));
+                }
+            }
+        }
+    }
+
+    Ok(Verified::Invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ssh_signing() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(directory.join("2023-05.ledger"), "D 1.00 a #x\n").unwrap();
+        fs::write(directory.join("2023-06.ledger"), "D 2.00 b #x\n").unwrap();
+
+        let key = directory.join("key");
+        assert!(Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+            .arg(&key)
+            .status()
+            .unwrap()
+            .success());
+        let public_key = fs::read_to_string(directory.join("key.pub")).unwrap();
+        fs::write(
+            directory.join("allowed_signers"),
+            format!("alice@example.com {}", public_key),
+        )
+        .unwrap();
+
+        let signing = Signing {
+            tool: SigningTool::Ssh,
+            allowed_signers: Some("allowed_signers".into()),
+            public_keys: Default::default(),
+        };
+        sign(&signing, directory, "2023-05.ledger", &key).unwrap();
+        assert_eq!(
+            verify(&signing, directory, "2023-05.ledger").unwrap(),
+            Verified::Signed("alice@example.com".into())
+        );
+        assert_eq!(
+            verify(&signing, directory, "2023-06.ledger").unwrap(),
+            Verified::Unsigned
+        );
+
+        fs::write(directory.join("2023-05.ledger"), "D 9.00 a #x\n").unwrap();
+        assert_eq!(
+            verify(&signing, directory, "2023-05.ledger").unwrap(),
+            Verified::Invalid
+        );
+    }
+}
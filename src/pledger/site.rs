@@ -0,0 +1,320 @@
+//! A static HTML dashboard of a ledger directory, as generated by `pledger site`.
+//!
+//! The site has an index with an overview of every year, a page for each year (the
+//! annual report) and month, and a drill-down page for each tag. Charts are SVGs from
+//! the chart subsystem, so the site needs nothing but a static file server.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::pledger::aggregate::{monthly_totals, Series, Summary};
+use crate::pledger::chart;
+use crate::pledger::report::{escape, AnnualReport};
+use crate::pledger::EntryKind::*;
+use crate::pledger::{Amount, AmountFormat, Entry, Error, Ledger, Result};
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 60em; margin: auto; } \
+                     td, th { padding: 0 0.5em; } \
+                     td.amount { text-align: right; font-family: monospace; } \
+                     img { max-width: 100%; }";
+
+/// Wraps a page's body in a document, with a link back to the index.
+fn page(title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>{STYLE}</style>\n</head>\n<body>\n<nav><a href=\"{root}index.html\">all years</a></nav>\n\
+         <h1>{title}</h1>\n{body}</body>\n</html>\n",
+        title = escape(title),
+    )
+}
+
+/// Returns the file name of a tag's page, under `tags/`.
+fn tag_page(tag: &str) -> String {
+    let slug: String = tag
+        .trim_start_matches('#')
+        .chars()
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() || c == '-' || c == '_' => c,
+            _ => '_',
+        })
+        .collect();
+    format!("{}.html", slug)
+}
+
+/// Renders a table of tags and their totals, linking each tag to its page.
+fn tag_table(series: &Series, root: &str) -> String {
+    let mut out = format!("<h2>{}</h2>\n<table>\n", escape(&series.name));
+    for (tag, amount) in series.points.iter() {
+        // `(untagged)` isn't a real tag, so it doesn't get a page.
+        match tag.starts_with('#') {
+            true => write!(
+                out,
+                "<tr><td><a href=\"{}tags/{}\">{}</a></td>",
+                root,
+                tag_page(tag),
+                escape(tag)
+            ),
+            false => write!(out, "<tr><td>{}</td>", escape(tag)),
+        }
+        .unwrap();
+        writeln!(out, "<td class=\"amount\">{}</td></tr>", amount).unwrap();
+    }
+    out.push_str("</table>\n");
+    out
+}
+
+fn entry_row(date: &str, entry: &Entry) -> String {
+    format!(
+        "<tr><td>{}</td><td>{}</td><td class=\"amount\">{}</td><td>{}</td></tr>\n",
+        escape(date),
+        entry.kind.name(),
+        entry.amount,
+        escape(entry.comment.trim())
+    )
+}
+
+/// Returns a ledger of every entry in `ledgers`, labeled `date`.
+fn merged(date: &str, ledgers: &[Ledger]) -> Ledger {
+    Ledger {
+        date: date.into(),
+        entries: ledgers
+            .iter()
+            .flat_map(|l| l.entries.iter().cloned())
+            .collect(),
+        balances: vec![],
+        errors: vec![],
+        format: ledgers
+            .first()
+            .map_or(AmountFormat::DEFAULT, Ledger::amount_format),
+    }
+}
+
+fn month_page(outdir: &Path, ledger: &Ledger) -> Result<()> {
+    let summary = Summary::new(ledger)?;
+    chart::render(&outdir.join(format!("{}.svg", ledger.date)), &summary, &[])?;
+
+    let year = &ledger.date[..4];
+    let mut body = String::new();
+    writeln!(
+        body,
+        "<p><a href=\"{year}.html\">{year}</a></p>\n\
+         <p>{} entries: {} in credits and {} in debits.</p>\n\
+         <img src=\"{}.svg\" alt=\"charts for {}\">",
+        summary.entries, summary.credits, summary.debits, ledger.date, ledger.date
+    )
+    .unwrap();
+    body.push_str(&tag_table(&summary.debit_tags, ""));
+    body.push_str(&tag_table(&summary.credit_tags, ""));
+
+    body.push_str("<h2>Entries</h2>\n<table>\n");
+    for entry in ledger.entries.iter() {
+        let day = entry
+            .day
+            .map(|d| format!("{}-{:02}", ledger.date, d))
+            .unwrap_or_else(|| ledger.date.clone());
+        body.push_str(&entry_row(&day, entry));
+    }
+    body.push_str("</table>\n");
+
+    fs::write(
+        outdir.join(format!("{}.html", ledger.date)),
+        page(&ledger.date, "", &body),
+    )?;
+    Ok(())
+}
+
+fn year_page(outdir: &Path, year: &str, ledgers: &[Ledger]) -> Result<()> {
+    let summary = Summary::new(&merged(year, ledgers))?;
+    let trend = [
+        monthly_totals(ledgers, &Debit)?,
+        monthly_totals(ledgers, &Credit)?,
+    ];
+    chart::render(&outdir.join(format!("{}.svg", year)), &summary, &trend)?;
+
+    let mut body = String::from("<p>");
+    for ledger in ledgers.iter() {
+        write!(
+            body,
+            "<a href=\"{}.html\">{}</a> ",
+            ledger.date, ledger.date
+        )
+        .unwrap();
+    }
+    writeln!(
+        body,
+        "</p>\n<img src=\"{}.svg\" alt=\"charts for {}\">",
+        year, year
+    )
+    .unwrap();
+
+    // The annual report is a standalone document, so only its body is used.
+    let report = AnnualReport::new(year, ledgers)?.to_html();
+    let report = report
+        .split_once("<body>\n")
+        .and_then(|(_, rest)| rest.split_once("</body>"))
+        .map(|(body, _)| body)
+        .unwrap_or_default();
+    // Skip the report's own title, since the page has one.
+    body.push_str(
+        report
+            .split_once("</h1>\n")
+            .map(|(_, r)| r)
+            .unwrap_or(report),
+    );
+
+    fs::write(outdir.join(format!("{}.html", year)), page(year, "", &body))?;
+    Ok(())
+}
+
+fn tag_pages(outdir: &Path, ledgers: &[Ledger]) -> Result<usize> {
+    let tags_dir = outdir.join("tags");
+    fs::create_dir_all(&tags_dir)?;
+
+    let mut entries: BTreeMap<&str, Vec<(&str, &Entry)>> = BTreeMap::new();
+    for ledger in ledgers.iter() {
+        for entry in ledger.entries.iter() {
+            for tag in entry.tags.iter() {
+                entries.entry(tag).or_default().push((&ledger.date, entry));
+            }
+        }
+    }
+
+    for (tag, entries) in entries.iter() {
+        let mut months: BTreeMap<&str, (Amount, Amount)> = BTreeMap::new();
+        for (date, entry) in entries.iter() {
+            let totals = months.entry(date).or_default();
+            let total = match entry.kind {
+                Credit => &mut totals.0,
+                Debit => &mut totals.1,
+                Transfer | Allocation => continue,
+            };
+            *total = total
+                .checked_add(entry.amount)
+                .ok_or_else(|| Error::Amount(format!("total out of range for {}", tag)))?;
+        }
+
+        let mut body = String::from(
+            "<h2>By month</h2>\n<table>\n<tr><th></th><th>credits</th><th>debits</th></tr>\n",
+        );
+        for (date, (credits, debits)) in months.iter() {
+            writeln!(
+                body,
+                "<tr><td><a href=\"../{date}.html\">{date}</a></td><td class=\"amount\">{}</td>\
+                 <td class=\"amount\">{}</td></tr>",
+                credits, debits,
+            )
+            .unwrap();
+        }
+        body.push_str("</table>\n<h2>Entries</h2>\n<table>\n");
+        for (date, entry) in entries.iter() {
+            body.push_str(&entry_row(date, entry));
+        }
+        body.push_str("</table>\n");
+
+        fs::write(tags_dir.join(tag_page(tag)), page(tag, "../", &body))?;
+    }
+
+    Ok(entries.len())
+}
+
+/// Generates the site for `ledgers`, which should be in order, into `outdir`. Returns
+/// the number of pages written.
+pub fn generate(outdir: &Path, ledgers: &[Ledger]) -> Result<usize> {
+    if ledgers.is_empty() {
+        return Err(Error::NotFound("no ledgers to generate a site from".into()));
+    }
+
+    fs::create_dir_all(outdir)
+        .map_err(Error::io(format!("couldn't create {}", outdir.display())))?;
+
+    // The ledgers are in order, so each year's are contiguous.
+    let years: Vec<_> = ledgers
+        .chunk_by(|a, b| a.date[..4] == b.date[..4])
+        .map(|ledgers| (&ledgers[0].date[..4], ledgers))
+        .collect();
+
+    let mut pages = 0;
+    for ledger in ledgers.iter() {
+        month_page(outdir, ledger)?;
+        pages += 1;
+    }
+    for (year, ledgers) in years.iter() {
+        year_page(outdir, year, ledgers)?;
+        pages += 1;
+    }
+    pages += tag_pages(outdir, ledgers)?;
+
+    let summary = Summary::new(&merged("*", ledgers))?;
+    let trend = [
+        monthly_totals(ledgers, &Debit)?,
+        monthly_totals(ledgers, &Credit)?,
+    ];
+    chart::render(&outdir.join("index.svg"), &summary, &trend)?;
+
+    let mut body =
+        String::from("<table>\n<tr><th></th><th>credits</th><th>debits</th><th>months</th></tr>\n");
+    for (year, ledgers) in years.iter() {
+        let summary = Summary::new(&merged(year, ledgers))?;
+        writeln!(
+            body,
+            "<tr><td><a href=\"{year}.html\">{year}</a></td><td class=\"amount\">{}</td>\
+             <td class=\"amount\">{}</td><td class=\"amount\">{}</td></tr>",
+            summary.credits,
+            summary.debits,
+            ledgers.len()
+        )
+        .unwrap();
+    }
+    body.push_str("</table>\n<img src=\"index.svg\" alt=\"charts for all years\">\n");
+    fs::write(outdir.join("index.html"), page("All years", "", &body))?;
+    pages += 1;
+
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+    use crate::pledger::parse_ledger;
+    use crate::pledger::ParseOptions;
+
+    fn ledger(date: &str, contents: &'static str) -> Ledger {
+        parse_ledger(
+            date,
+            Box::new(contents.as_bytes().lines()),
+            &ParseOptions::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_generate() {
+        assert_eq!(tag_page("#eating/out"), "eating_out.html");
+
+        let temp = tempfile::tempdir().unwrap();
+        let outdir = temp.path().join("site");
+        let ledgers = vec![
+            ledger("2022-12", "D 1.00 gift #fun"),
+            ledger("2023-01", "C 10.00 #pay\nD 2.00 @05 <lunch> #food"),
+        ];
+
+        assert_eq!(generate(&outdir, &ledgers).unwrap(), 8);
+
+        let index = fs::read_to_string(outdir.join("index.html")).unwrap();
+        assert!(index.contains("<a href=\"2023.html\">2023</a>"));
+        let month = fs::read_to_string(outdir.join("2023-01.html")).unwrap();
+        assert!(month.contains("<a href=\"tags/food.html\">#food</a>"));
+        assert!(month.contains("2023-01-05"));
+        let tag = fs::read_to_string(outdir.join("tags/food.html")).unwrap();
+        assert!(tag.contains("&lt;lunch&gt;"));
+        assert!(fs::read_to_string(outdir.join("2022.html"))
+            .unwrap()
+            .contains("Largest debits"));
+
+        assert!(generate(&outdir, &[]).is_err());
+    }
+}
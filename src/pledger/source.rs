@@ -0,0 +1,129 @@
+//! Reading the lines of several ledgers in turn, e.g. a year's worth of months.
+//!
+//! Each line comes with its source (the ledger's date and file, and the line's number in
+//! it), so that anything reported about the line can point at where it came from.
+
+use std::io::{BufRead, Lines};
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::pledger::{ledger_source, open_ledger, Error, LedgerReader, Result};
+
+/// Where a line of a ledger came from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Source {
+    pub date: Rc<str>,
+    /// The file that the line was read from, which is an archive for archived months.
+    pub file: Rc<Path>,
+    /// The line's number in its ledger, 1-based.
+    pub line: usize,
+}
+
+/// The lines of each of a directory's ledgers, in date order.
+pub struct LedgerSource<'a> {
+    directory: &'a Path,
+    dates: std::vec::IntoIter<String>,
+    current: Option<(Source, Lines<LedgerReader>)>,
+}
+
+impl<'a> LedgerSource<'a> {
+    /// Reads the ledgers for `dates` from the directory, in date order. Ledgers are only
+    /// opened once their lines are needed.
+    pub fn new(directory: &'a Path, mut dates: Vec<String>) -> Self {
+        dates.sort();
+        dates.dedup();
+
+        Self {
+            directory,
+            dates: dates.into_iter(),
+            current: None,
+        }
+    }
+
+    fn open(&self, date: String) -> Result<(Source, Lines<LedgerReader>)> {
+        let reader = open_ledger(self.directory, &date)?;
+        let file = ledger_source(self.directory, &date);
+
+        Ok((
+            Source {
+                date: date.into(),
+                file: file.into(),
+                line: 0,
+            },
+            reader.lines(),
+        ))
+    }
+}
+
+impl Iterator for LedgerSource<'_> {
+    type Item = Result<(Source, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((source, lines)) = self.current.as_mut() {
+                match lines.next() {
+                    Some(Ok(line)) => {
+                        source.line += 1;
+                        return Some(Ok((source.clone(), line)));
+                    }
+                    Some(Err(e)) => {
+                        let context = format!(
+                            "couldn't read line {} of {}",
+                            source.line + 1,
+                            source.file.display()
+                        );
+                        self.current = None;
+                        return Some(Err(Error::io(context)(e)));
+                    }
+                    None => self.current = None,
+                }
+            }
+
+            let date = self.dates.next()?;
+            match self.open(date) {
+                Ok(current) => self.current = Some(current),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_ledger_source() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(directory.join("2020-02.ledger"), "D 2.00 b #x\n").unwrap();
+        fs::write(directory.join("2020-01.ledger"), "# jan\nD 1.00 a #x\n").unwrap();
+
+        let lines: Vec<_> = LedgerSource::new(directory, vec!["2020-02".into(), "2020-01".into()])
+            .map(|l| {
+                let (source, line) = l.unwrap();
+                (source.date.to_string(), source.line, line)
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                ("2020-01".into(), 1, "# jan".into()),
+                ("2020-01".into(), 2, "D 1.00 a #x".into()),
+                ("2020-02".into(), 1, "D 2.00 b #x".into()),
+            ]
+        );
+
+        let (source, _) = LedgerSource::new(directory, vec!["2020-02".into()])
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(&*source.file, directory.join("2020-02.ledger"));
+
+        let mut missing = LedgerSource::new(directory, vec!["2020-03".into()]);
+        assert!(matches!(missing.next(), Some(Err(Error::NotFound(_)))));
+        assert!(missing.next().is_none());
+    }
+}
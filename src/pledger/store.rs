@@ -0,0 +1,189 @@
+//! Where ledgers are stored, behind the `LedgerStore` trait: a ledger directory on the
+//! filesystem (`DirectoryStore`), or anything else that can list, read, and write a
+//! month's ledger by its date.
+//!
+//! Stores only deal in ledgers' text; parsing (and, for directories, caching the parsed
+//! ledgers) happens here, the same way for every store.
+
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+use crate::pledger::{
+    cache, combine_ledgers, in_period, ledger_dates, ledger_source, open_ledger,
+    parse_ledger_reader, update_ledger, Entry, Error, Ledger, ParseOptions, Result, Settings,
+};
+
+/// A place that monthly ledgers are kept, by date (e.g. `2023-05`).
+pub trait LedgerStore {
+    /// Returns the dates of every ledger in the store, in date order.
+    fn dates(&self) -> Result<Vec<String>>;
+
+    /// Returns the text of the ledger for `date`, or `None` if there isn't one.
+    fn read(&self, date: &str) -> Result<Option<String>>;
+
+    /// Replaces the text of the ledger for `date`, creating the ledger if needed.
+    fn write(&self, date: &str, contents: &str) -> Result<()>;
+
+    /// Returns what the ledger for `date` is called in errors and entries' sources, e.g.
+    /// its file.
+    fn source(&self, date: &str) -> PathBuf;
+
+    /// Replaces the text of the ledger for `date` with `update`'s result, given its
+    /// current text (or an empty one, if it doesn't exist yet).
+    ///
+    /// Stores that can be written to concurrently should override this, so that
+    /// the read and the write can't be interleaved with another writer's.
+    fn update(&self, date: &str, update: &mut dyn FnMut(String) -> Result<String>) -> Result<()> {
+        let current = self.read(date)?;
+        let contents = update(current.clone().unwrap_or_default())?;
+        match current.as_deref() == Some(contents.as_str()) {
+            true => Ok(()),
+            false => self.write(date, &contents),
+        }
+    }
+
+    /// Reads and parses the ledger for `date`. Parse errors include its source.
+    fn load(&self, date: &str, options: &ParseOptions) -> Result<Ledger> {
+        let source = self.source(date);
+        let contents = self.read(date)?.ok_or_else(|| {
+            Error::NotFound(format!("missing requested ledger: {}", source.display()))
+        })?;
+
+        let mut ledger = parse_ledger_reader(date, io::Cursor::new(contents), options)
+            .map_err(|e| e.with_file(&source))?;
+        ledger.errors = ledger
+            .errors
+            .into_iter()
+            .map(|e| e.with_file(&source))
+            .collect();
+        ledger.set_file(&source);
+
+        Ok(ledger)
+    }
+}
+
+/// A ledger directory, with a file for each month (which may be compressed or archived).
+/// Its ledgers are cached when they're loaded (see `cache`), and are locked and written
+/// atomically when they're updated (see `update_ledger`). New ledgers start out as the
+/// directory's template, rather than empty.
+pub struct DirectoryStore {
+    directory: PathBuf,
+    settings: Settings,
+}
+
+impl DirectoryStore {
+    pub fn new(directory: impl Into<PathBuf>, settings: Settings) -> Self {
+        Self {
+            directory: directory.into(),
+            settings,
+        }
+    }
+}
+
+impl LedgerStore for DirectoryStore {
+    fn dates(&self) -> Result<Vec<String>> {
+        ledger_dates(&self.directory)
+    }
+
+    fn read(&self, date: &str) -> Result<Option<String>> {
+        let mut reader = match open_ledger(&self.directory, date) {
+            Ok(reader) => reader,
+            Err(Error::NotFound(_)) if self.directory.is_dir() => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut contents = String::new();
+        reader
+            .read_to_string(&mut contents)
+            .map_err(Error::io("ledger file read failed"))?;
+        Ok(Some(contents))
+    }
+
+    fn write(&self, date: &str, contents: &str) -> Result<()> {
+        update_ledger(&self.directory, date, &self.settings, |_| {
+            Ok(contents.into())
+        })
+    }
+
+    fn source(&self, date: &str) -> PathBuf {
+        ledger_source(&self.directory, date)
+    }
+
+    fn update(&self, date: &str, update: &mut dyn FnMut(String) -> Result<String>) -> Result<()> {
+        update_ledger(&self.directory, date, &self.settings, update)
+    }
+
+    fn load(&self, date: &str, options: &ParseOptions) -> Result<Ledger> {
+        cache::load_ledger(&self.directory, date, options)
+    }
+}
+
+/// Reads and parses every ledger in the store (or only those in `period`, if given),
+/// combined into one ledger labeled `*` (or the period).
+pub fn load_ledgers(
+    store: &dyn LedgerStore,
+    period: Option<&str>,
+    settings: &Settings,
+) -> Result<Ledger> {
+    let mut dates = store.dates()?;
+    dates.retain(|d| {
+        period
+            .map(|p| in_period(d, p, settings.fiscal_year_start))
+            .unwrap_or(true)
+    });
+
+    let ledgers = dates
+        .iter()
+        .map(|d| store.load(d, &settings.parse))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(combine_ledgers(period.unwrap_or("*"), ledgers))
+}
+
+/// Appends an entry to the ledger for `date`, creating the ledger if it doesn't exist.
+pub fn append_entry(store: &dyn LedgerStore, date: &str, entry: &Entry) -> Result<()> {
+    store.update(date, &mut |mut contents| {
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents.push_str(&format!("{}\n", entry));
+        log::debug!("appending {:?} to {}", entry, date);
+
+        Ok(contents)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_directory_store() {
+        let temp = tempfile::tempdir().unwrap();
+        let directory = temp.path();
+        fs::write(directory.join("2023-01.ledger"), "D 5.00 lunch #food\n").unwrap();
+        fs::write(directory.join("notes.txt"), "not a ledger\n").unwrap();
+
+        let store = DirectoryStore::new(directory, Settings::default());
+        assert_eq!(store.dates().unwrap(), ["2023-01"]);
+        assert_eq!(store.read("2023-02").unwrap(), None);
+        assert!(matches!(
+            store.load("2023-02", &ParseOptions::default()),
+            Err(Error::NotFound(_))
+        ));
+
+        let entry: Entry = "D 3.00 coffee #fun".parse().unwrap();
+        append_entry(&store, "2023-01", &entry).unwrap();
+        append_entry(&store, "2023-02", &entry).unwrap();
+        assert_eq!(
+            store.read("2023-01").unwrap().as_deref(),
+            Some("D 5.00 lunch #food\nD 3.00 coffee #fun\n")
+        );
+        assert_eq!(store.dates().unwrap(), ["2023-01", "2023-02"]);
+
+        let ledger = load_ledgers(&store, Some("2023"), &Settings::default()).unwrap();
+        assert_eq!(ledger.date(), "2023");
+        assert_eq!(ledger.entries.len(), 3);
+    }
+}
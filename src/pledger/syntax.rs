@@ -0,0 +1,374 @@
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::pledger::{is_continuation, parse_entry_ref, parse_line, EntryRef, Error, ParseOptions};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    EntryKind,
+    /// A directive's keyword, e.g. `balance`.
+    Directive,
+    Amount,
+    Comment,
+    Tag,
+    /// A whole-line `#` comment.
+    LineComment,
+    Whitespace,
+    /// Anything from the point where the line stops parsing.
+    Invalid,
+}
+
+/// A span of a ledger line, as a byte range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Range<usize>,
+}
+
+/// Splits `text` at each whitespace boundary, returning every run's span and whether
+/// it's whitespace.
+fn runs(text: &str) -> Vec<(Range<usize>, bool)> {
+    let mut runs: Vec<(Range<usize>, bool)> = vec![];
+    for (idx, chr) in text.char_indices() {
+        let ws = chr.is_whitespace();
+        let (start, end) = (idx, idx + chr.len_utf8());
+        match runs.last_mut() {
+            Some((span, last_ws)) if *last_ws == ws => span.end = end,
+            _ => runs.push((start..end, ws)),
+        }
+    }
+    runs
+}
+
+/// Returns the tokens for the text in `span` of a comment: its words and whitespace.
+fn comment_tokens(line: &str, span: Range<usize>) -> impl Iterator<Item = Token> {
+    runs(&line[span.clone()])
+        .into_iter()
+        .map(move |(run, ws)| Token {
+            kind: if ws {
+                TokenKind::Whitespace
+            } else {
+                TokenKind::Comment
+            },
+            span: run.start + span.start..run.end + span.start,
+        })
+}
+
+/// Returns the tokens of a valid entry, from the parser's own spans.
+fn entry_tokens(line: &str, entry: &EntryRef) -> Vec<Token> {
+    let mut tokens = vec![
+        Token {
+            kind: TokenKind::EntryKind,
+            span: EntryRef::KIND_SPAN,
+        },
+        Token {
+            kind: TokenKind::Whitespace,
+            span: EntryRef::KIND_SPAN.end..entry.amount_span.start,
+        },
+        Token {
+            kind: TokenKind::Amount,
+            span: entry.amount_span.clone(),
+        },
+        Token {
+            kind: TokenKind::Whitespace,
+            span: entry.amount_span.end..entry.comment_span.start,
+        },
+    ];
+
+    let mut cursor = entry.comment_span.start;
+    for (_, span) in entry.tags.iter() {
+        tokens.extend(comment_tokens(line, cursor..span.start));
+        tokens.push(Token {
+            kind: TokenKind::Tag,
+            span: span.clone(),
+        });
+        cursor = span.end;
+    }
+    tokens.extend(comment_tokens(line, cursor..entry.comment_span.end));
+
+    // The comment can start with whitespace of its own.
+    tokens.dedup_by(|next, prev| {
+        let merge = prev.kind == TokenKind::Whitespace && next.kind == TokenKind::Whitespace;
+        if merge {
+            prev.span.end = next.span.end;
+        }
+        merge
+    });
+    tokens
+}
+
+/// Splits a single ledger line into tokens that cover it completely.
+pub fn tokenize(line: &str, options: &ParseOptions) -> Vec<Token> {
+    if let Ok(entry) = parse_entry_ref(line, options) {
+        return entry_tokens(line, &entry);
+    }
+
+    // Lines are tokenized on their own, so an indented line is taken to be a
+    // continuation of whatever comes before it: the rest of a comment, with its tags.
+    if is_continuation(line) {
+        return runs(line)
+            .into_iter()
+            .map(|(span, ws)| Token {
+                kind: match ws {
+                    true => TokenKind::Whitespace,
+                    false if line[span.clone()].starts_with('#') => TokenKind::Tag,
+                    false => TokenKind::Comment,
+                },
+                span,
+            })
+            .collect();
+    }
+
+    let mut tokens: Vec<Token> = vec![];
+    let trimmed = line.trim_start();
+
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        let start = line.len() - trimmed.len();
+        if start > 0 {
+            tokens.push(Token {
+                kind: TokenKind::Whitespace,
+                span: 0..start,
+            });
+        }
+        if !trimmed.is_empty() {
+            tokens.push(Token {
+                kind: TokenKind::LineComment,
+                span: start..line.len(),
+            });
+        }
+        return tokens;
+    }
+
+    // Kind, whitespace, amount, whitespace, and then the comment (with its tags).
+    let mut words = 0;
+    for (span, ws) in runs(line) {
+        if ws {
+            tokens.push(Token {
+                kind: TokenKind::Whitespace,
+                span,
+            });
+            continue;
+        }
+
+        words += 1;
+        if words == 1 && span.len() > 1 && &line[span.clone()] != "balance" {
+            // The kind and amount aren't separated, which is a syntax error; let the
+            // parser's offset sort it out below.
+            tokens.push(Token {
+                kind: TokenKind::EntryKind,
+                span: span.start..span.start + 1,
+            });
+            tokens.push(Token {
+                kind: TokenKind::Amount,
+                span: span.start + 1..span.end,
+            });
+            words += 1;
+            continue;
+        }
+
+        let kind = match words {
+            1 if &line[span.clone()] == "balance" => TokenKind::Directive,
+            1 => TokenKind::EntryKind,
+            2 => TokenKind::Amount,
+            _ if line[span.clone()].starts_with('#') => TokenKind::Tag,
+            _ => TokenKind::Comment,
+        };
+        tokens.push(Token { kind, span });
+    }
+
+    // Everything from the parser's error onwards is invalid.
+    let offset = match parse_line(line, options) {
+        Err(Some(Error::Parse { offset, .. })) => Some(offset.unwrap_or(0)),
+        _ => None,
+    };
+    if let Some(offset) = offset {
+        let mut split = vec![];
+        for token in tokens.into_iter() {
+            if token.span.end <= offset {
+                split.push(token);
+            } else if token.span.start >= offset {
+                split.push(Token {
+                    kind: TokenKind::Invalid,
+                    span: token.span,
+                });
+            } else {
+                split.push(Token {
+                    kind: token.kind,
+                    span: token.span.start..offset,
+                });
+                split.push(Token {
+                    kind: TokenKind::Invalid,
+                    span: offset..token.span.end,
+                });
+            }
+        }
+        tokens = split;
+    }
+
+    tokens
+}
+
+fn color(kind: TokenKind) -> &'static str {
+    match kind {
+        TokenKind::EntryKind | TokenKind::Directive => "\x1b[1;35m",
+        TokenKind::Amount => "\x1b[36m",
+        TokenKind::Comment | TokenKind::Whitespace => "",
+        TokenKind::Tag => "\x1b[33m",
+        TokenKind::LineComment => "\x1b[2m",
+        TokenKind::Invalid => "\x1b[4;31m",
+    }
+}
+
+fn push_token(out: &mut String, text: &str, kind: TokenKind, colored: bool) {
+    match color(kind) {
+        code if colored && !code.is_empty() => write!(out, "{}{}\x1b[0m", code, text).unwrap(),
+        _ => out.push_str(text),
+    }
+}
+
+/// Renders a ledger for display: amounts are aligned, and (if `colored`) each token is
+/// highlighted with ANSI colors. Lines that don't parse are left as they are.
+pub fn highlight(text: &str, colored: bool, options: &ParseOptions) -> String {
+    let lines: Vec<_> = text.lines().map(|l| (l, tokenize(l, options))).collect();
+    let valid = |tokens: &[Token]| !tokens.iter().any(|t| t.kind == TokenKind::Invalid);
+
+    let width = lines
+        .iter()
+        .filter(|(_, tokens)| valid(tokens))
+        .flat_map(|(line, tokens)| {
+            tokens
+                .iter()
+                .filter(|t| t.kind == TokenKind::Amount)
+                .map(|t| line[t.span.clone()].chars().count())
+        })
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::new();
+    for (line, tokens) in lines.iter() {
+        let entry = valid(tokens) && tokens.iter().any(|t| t.kind == TokenKind::EntryKind);
+        if !entry {
+            for token in tokens.iter() {
+                push_token(&mut out, &line[token.span.clone()], token.kind, colored);
+            }
+            out.push('\n');
+            continue;
+        }
+
+        // Entries are rendered as the kind, the right-aligned amount, and then the
+        // rest of the line from the comment onwards.
+        let rest = tokens
+            .iter()
+            .skip_while(|t| t.kind != TokenKind::Amount)
+            .skip(1)
+            .skip_while(|t| t.kind == TokenKind::Whitespace);
+
+        for token in tokens.iter().take_while(|t| t.kind != TokenKind::Amount) {
+            if token.kind == TokenKind::EntryKind {
+                push_token(&mut out, &line[token.span.clone()], token.kind, colored);
+            }
+        }
+
+        let amount = tokens.iter().find(|t| t.kind == TokenKind::Amount).unwrap();
+        let amount = &line[amount.span.clone()];
+        out.push(' ');
+        out.push_str(&" ".repeat(width - amount.chars().count()));
+        push_token(&mut out, amount, TokenKind::Amount, colored);
+
+        out.push(' ');
+        for token in rest {
+            push_token(&mut out, &line[token.span.clone()], token.kind, colored);
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TokenKind::*;
+
+    fn kinds(line: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(line, &ParseOptions::default())
+            .into_iter()
+            .map(|t| (t.kind, &line[t.span]))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            kinds("D 1.00 lunch #food"),
+            vec![
+                (EntryKind, "D"),
+                (Whitespace, " "),
+                (Amount, "1.00"),
+                (Whitespace, " "),
+                (Comment, "lunch"),
+                (Whitespace, " "),
+                (Tag, "#food"),
+            ]
+        );
+        // Tags don't have to start a word.
+        assert_eq!(
+            kinds("C 1,000.00  pay#work"),
+            vec![
+                (EntryKind, "C"),
+                (Whitespace, " "),
+                (Amount, "1,000.00"),
+                (Whitespace, "  "),
+                (Comment, "pay"),
+                (Tag, "#work"),
+            ]
+        );
+        assert_eq!(
+            kinds("  # a comment"),
+            vec![(Whitespace, "  "), (LineComment, "# a comment")]
+        );
+        assert_eq!(kinds(""), vec![]);
+        assert_eq!(
+            kinds("balance 1.00 @bank"),
+            vec![
+                (Directive, "balance"),
+                (Whitespace, " "),
+                (Amount, "1.00"),
+                (Whitespace, " "),
+                (Comment, "@bank"),
+            ]
+        );
+        assert_eq!(
+            kinds("D 1.0x bad"),
+            vec![
+                (EntryKind, "D"),
+                (Whitespace, " "),
+                (Amount, "1.0"),
+                (Invalid, "x"),
+                (Invalid, " "),
+                (Invalid, "bad"),
+            ]
+        );
+        assert_eq!(
+            kinds("D1.00 x"),
+            vec![
+                (EntryKind, "D"),
+                (Invalid, "1.00"),
+                (Invalid, " "),
+                (Invalid, "x")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight() {
+        let text = "# header\nD 1.00   lunch #food\nC 1,200.00 pay\nD 1.0x bad\n";
+        assert_eq!(
+            highlight(text, false, &ParseOptions::default()),
+            "# header\nD     1.00 lunch #food\nC 1,200.00 pay\nD 1.0x bad\n"
+        );
+
+        assert!(highlight("D 1.00 #food", true, &ParseOptions::default())
+            .contains("\x1b[33m#food\x1b[0m"));
+    }
+}
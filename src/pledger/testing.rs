@@ -0,0 +1,217 @@
+//! Fixtures for testing code that reads or writes ledgers, without a ledger directory:
+//! `LedgerBuilder`, for writing a month's ledger entry by entry, and `MemoryStore`, a
+//! `LedgerStore` that keeps its ledgers in memory.
+//!
+//! ```
+//! use pledger::pledger::store::{load_ledgers, LedgerStore};
+//! use pledger::pledger::Settings;
+//! use pledger::testing::{LedgerBuilder, MemoryStore};
+//!
+//! let store = MemoryStore::new().with(
+//!     LedgerBuilder::new("2023-05")
+//!         .credit(100, "pay #salary")
+//!         .debit(12, "lunch #food"),
+//! );
+//! assert_eq!(
+//!     store.read("2023-05").unwrap().as_deref(),
+//!     Some("C 100.00 pay #salary\nD 12.00 lunch #food\n")
+//! );
+//! assert_eq!(
+//!     load_ledgers(&store, Some("2023"), &Settings::default()).unwrap().date(),
+//!     "2023"
+//! );
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::pledger::store::LedgerStore;
+use crate::pledger::{parse_ledger_reader, AmountFormat, Ledger, ParseOptions, Result};
+
+/// A month's ledger, written entry by entry.
+#[derive(Clone, Debug)]
+pub struct LedgerBuilder {
+    date: String,
+    lines: Vec<String>,
+    places: u32,
+    decimal: char,
+}
+
+impl LedgerBuilder {
+    /// Starts an empty ledger for `date`, e.g. `2023-05`, whose amounts are written as
+    /// the default config has them: with two decimal places, after a decimal point.
+    pub fn new(date: &str) -> Self {
+        Self {
+            date: date.into(),
+            lines: vec![],
+            places: 2,
+            decimal: '.',
+        }
+    }
+
+    /// Writes the entries added after this with `places` decimal places, after `decimal`,
+    /// e.g. to match a config with `decimal = ","`.
+    pub fn formatted(mut self, places: u32, decimal: char) -> Self {
+        self.places = places;
+        self.decimal = decimal;
+        self
+    }
+
+    /// Adds a credit of `units` whole units, with its comment (and tags).
+    pub fn credit(self, units: u64, comment: &str) -> Self {
+        self.entry('C', units, comment)
+    }
+
+    /// Adds a debit of `units` whole units, with its comment (and tags).
+    pub fn debit(self, units: u64, comment: &str) -> Self {
+        self.entry('D', units, comment)
+    }
+
+    /// Adds a line as it is, e.g. an entry with a fractional amount, a transfer, a
+    /// balance, or a comment.
+    pub fn line(mut self, line: &str) -> Self {
+        self.lines.push(line.into());
+        self
+    }
+
+    fn entry(self, kind: char, units: u64, comment: &str) -> Self {
+        let amount = match self.places {
+            0 => units.to_string(),
+            places => format!("{}{}{}", units, self.decimal, "0".repeat(places as usize)),
+        };
+        self.line(&format!("{} {} {}", kind, amount, comment))
+    }
+
+    pub fn date(&self) -> &str {
+        &self.date
+    }
+
+    /// Returns the ledger's text.
+    pub fn text(&self) -> String {
+        self.lines.iter().map(|l| format!("{}\n", l)).collect()
+    }
+
+    /// Parses the ledger, in the format that its amounts are written in.
+    ///
+    /// Panics if any of its lines don't parse, since that's a mistake in the test.
+    pub fn build(&self) -> Ledger {
+        let options = ParseOptions {
+            amount_format: AmountFormat {
+                decimal: self.decimal,
+                places: self.places,
+            },
+            ..Default::default()
+        };
+        parse_ledger_reader(&self.date, self.text().as_bytes(), &options)
+            .unwrap_or_else(|e| panic!("invalid ledger for {}: {}", self.date, e))
+    }
+}
+
+/// A `LedgerStore` that keeps its ledgers' text in memory, by date.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    ledgers: Mutex<BTreeMap<String, String>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds (or replaces) a ledger.
+    pub fn with(self, ledger: LedgerBuilder) -> Self {
+        self.insert(ledger.date(), &ledger.text());
+        self
+    }
+
+    /// Adds (or replaces) the ledger for `date`, with its text.
+    pub fn insert(&self, date: &str, contents: &str) {
+        self.ledgers
+            .lock()
+            .unwrap()
+            .insert(date.into(), contents.into());
+    }
+}
+
+impl LedgerStore for MemoryStore {
+    fn dates(&self) -> Result<Vec<String>> {
+        Ok(self.ledgers.lock().unwrap().keys().cloned().collect())
+    }
+
+    fn read(&self, date: &str) -> Result<Option<String>> {
+        Ok(self.ledgers.lock().unwrap().get(date).cloned())
+    }
+
+    fn write(&self, date: &str, contents: &str) -> Result<()> {
+        self.insert(date, contents);
+        Ok(())
+    }
+
+    fn source(&self, date: &str) -> PathBuf {
+        format!("{}.ledger", date).into()
+    }
+
+    fn update(&self, date: &str, update: &mut dyn FnMut(String) -> Result<String>) -> Result<()> {
+        // Held throughout, so that updates can't interleave.
+        let mut ledgers = self.ledgers.lock().unwrap();
+        let contents = update(ledgers.get(date).cloned().unwrap_or_default())?;
+        ledgers.insert(date.into(), contents);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::store::{append_entry, load_ledgers};
+    use crate::pledger::{Amount, Entry, Error, Settings};
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryStore::new()
+            .with(
+                LedgerBuilder::new("2023-05")
+                    .credit(100, "pay #salary")
+                    .line("D 12.50 lunch #food"),
+            )
+            .with(LedgerBuilder::new("2023-04").debit(3, "coffee #fun"));
+        assert_eq!(store.dates().unwrap(), ["2023-04", "2023-05"]);
+        assert_eq!(
+            store.read("2023-05").unwrap().as_deref(),
+            Some("C 100.00 pay #salary\nD 12.50 lunch #food\n")
+        );
+        assert!(matches!(
+            store.load("2023-06", &ParseOptions::default()),
+            Err(Error::NotFound(_))
+        ));
+
+        let entry: Entry = "D 2.00 tea #fun".parse().unwrap();
+        append_entry(&store, "2023-06", &entry).unwrap();
+        let ledger = load_ledgers(&store, None, &Settings::default()).unwrap();
+        assert_eq!(ledger.entries.len(), 4);
+        assert_eq!(
+            ledger.entries[0].source().unwrap().file.as_deref(),
+            Some("2023-04.ledger")
+        );
+    }
+
+    #[test]
+    fn test_ledger_builder() {
+        let ledger = LedgerBuilder::new("2023-05")
+            .credit(100, "pay #salary")
+            .debit(12, "lunch #food")
+            .build();
+        assert_eq!(ledger.date(), "2023-05");
+        assert_eq!(ledger.entries.len(), 2);
+        assert_eq!(ledger.entries[1].amount, Amount::from_subunits(1200));
+
+        let text = LedgerBuilder::new("2023-05")
+            .formatted(0, '.')
+            .debit(12, "lunch #food")
+            .formatted(3, ',')
+            .credit(100, "pay #salary")
+            .text();
+        assert_eq!(text, "D 12 lunch #food\nC 100,000 pay #salary\n");
+    }
+}
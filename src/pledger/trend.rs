@@ -0,0 +1,319 @@
+//! Trends in spending across months, e.g. for `--rolling` and `pledger anomalies`.
+//!
+//! Rolling averages smooth out one-off spikes: each month's average is over that month
+//! and the ones before it in the window. The first months of a selection have fewer
+//! months before them, and are averaged over the months there are.
+//!
+//! Anomalies are the opposite: the spikes themselves, which are often typos (e.g. a
+//! missing decimal point turning `12.00` into `1200`). A tag's month is anomalous when
+//! its debits are several times the tag's average over the months before, and an entry
+//! is when it's one of the largest debits of all.
+
+use std::fmt::{self, Write};
+
+use crate::pledger::aggregate::{monthly_totals, tags_by_month};
+use crate::pledger::{Amount, Entry, EntryKind, Ledger, Result};
+
+/// The windows, in months, that rolling averages are taken over.
+pub const WINDOWS: [usize; 2] = [3, 6];
+
+/// Returns the rolling average of each amount over the `window` amounts ending with it.
+pub fn rolling_average(amounts: &[Amount], window: usize) -> Vec<Amount> {
+    (0..amounts.len())
+        .map(|idx| {
+            let months = &amounts[(idx + 1).saturating_sub(window)..=idx];
+            let sum: u128 = months.iter().map(|a| a.subunits() as u128).sum();
+            let count = months.len() as u128;
+            // Rounded to the nearest subunit, with halves rounded up.
+            amounts[idx].with_subunits(((sum * 2 + count) / (count * 2)) as u64)
+        })
+        .collect()
+}
+
+/// A tag's rolling averages for each month, over one of the `WINDOWS`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagAverages {
+    pub tag: String,
+    pub averages: Vec<Amount>,
+}
+
+/// The rolling averages of the ledgers' debits, in total and by tag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rolling {
+    pub dates: Vec<String>,
+    pub debits: Vec<Amount>,
+    /// The total debits' averages, for each of the `WINDOWS`.
+    pub averages: Vec<Vec<Amount>>,
+    /// Each tag's averages, for each of the `WINDOWS`. Tags are ordered by their total
+    /// debits, largest first.
+    pub tags: Vec<Vec<TagAverages>>,
+}
+
+impl Rolling {
+    /// Computes the rolling averages for the monthly ledgers, which should be in order.
+    pub fn new(ledgers: &[Ledger]) -> Result<Self> {
+        let (dates, debits): (Vec<_>, Vec<_>) = monthly_totals(ledgers, &EntryKind::Debit)?
+            .points
+            .into_iter()
+            .unzip();
+        let rows = tags_by_month(ledgers, &EntryKind::Debit)?;
+
+        Ok(Self {
+            averages: WINDOWS
+                .iter()
+                .map(|w| rolling_average(&debits, *w))
+                .collect(),
+            tags: WINDOWS
+                .iter()
+                .map(|w| {
+                    rows.iter()
+                        .map(|row| TagAverages {
+                            tag: row.tag.clone(),
+                            averages: rolling_average(&row.months, *w),
+                        })
+                        .collect()
+                })
+                .collect(),
+            dates,
+            debits,
+        })
+    }
+
+    /// Renders the averages for the terminal: a table of each month's debits and their
+    /// averages, then a table of each tag's averages by month for each window.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        writeln!(out, "Debits by month:").unwrap();
+        write!(out, "{:<16} {:>10}", "", "debits").unwrap();
+        for window in WINDOWS.iter() {
+            write!(out, " {:>10}", format!("{}-month", window)).unwrap();
+        }
+        writeln!(out).unwrap();
+        for (idx, date) in self.dates.iter().enumerate() {
+            write!(out, "{:<16} {:>10}", date, self.debits[idx]).unwrap();
+            for averages in self.averages.iter() {
+                write!(out, " {:>10}", averages[idx]).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        for (window, tags) in WINDOWS.iter().zip(self.tags.iter()) {
+            writeln!(out, "\nDebit tags, {}-month average:", window).unwrap();
+            write!(out, "{:<16}", "").unwrap();
+            for date in self.dates.iter() {
+                write!(out, " {:>10}", date).unwrap();
+            }
+            writeln!(out).unwrap();
+            for tag in tags.iter() {
+                write!(out, "{:<16}", tag.tag).unwrap();
+                for average in tag.averages.iter() {
+                    write!(out, " {:>10}", average).unwrap();
+                }
+                writeln!(out).unwrap();
+            }
+        }
+
+        out
+    }
+}
+
+/// How many times its trailing average a tag's month has to be, to be an anomaly.
+pub const TAG_FACTOR: u64 = 3;
+
+/// How many of the months before a tag's month its trailing average is over.
+const TRAILING: usize = 6;
+
+/// How many months of history a tag needs before its months can be anomalies.
+const MIN_HISTORY: usize = 3;
+
+/// The percentile of all debits that an entry has to be above, to be an anomaly.
+pub const PERCENTILE: usize = 99;
+
+/// How many debits there need to be before the percentile means anything.
+const MIN_DEBITS: usize = 100;
+
+/// Something that deviates strongly from the ledgers' history.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Anomaly {
+    /// A tag's debits in a month, compared to its trailing average.
+    Tag {
+        date: String,
+        tag: String,
+        amount: Amount,
+        average: Amount,
+    },
+    /// A debit above the percentile of all debits.
+    Entry {
+        date: String,
+        /// The entry's number in its ledger, from 1.
+        number: usize,
+        entry: Entry,
+        threshold: Amount,
+    },
+}
+
+impl Anomaly {
+    /// Returns the date of the month that the anomaly is in.
+    pub fn date(&self) -> &str {
+        match self {
+            Anomaly::Tag { date, .. } | Anomaly::Entry { date, .. } => date,
+        }
+    }
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anomaly::Tag {
+                date,
+                tag,
+                amount,
+                average,
+            } => write!(
+                f,
+                "{}: {} was {}, {:.1}x its trailing average of {}",
+                date,
+                tag,
+                amount,
+                amount.as_f64() / average.as_f64(),
+                average
+            ),
+            Anomaly::Entry {
+                date,
+                number,
+                entry,
+                threshold,
+            } => write!(
+                f,
+                "{}:{}: {} is above the {}th percentile of debits ({})",
+                date, number, entry, PERCENTILE, threshold
+            ),
+        }
+    }
+}
+
+/// Returns the anomalies in the monthly ledgers, which should be in order, with the
+/// tag anomalies first.
+pub fn anomalies(ledgers: &[Ledger]) -> Result<Vec<Anomaly>> {
+    let mut anomalies = vec![];
+
+    for row in tags_by_month(ledgers, &EntryKind::Debit)? {
+        // A tag's history starts with its first month, rather than with the
+        // first ledger, so that new tags aren't compared against zeroes.
+        let first = match row.months.iter().position(|a| *a > Amount::ZERO) {
+            Some(first) => first,
+            None => continue,
+        };
+
+        for (idx, amount) in row.months.iter().enumerate().skip(first + MIN_HISTORY) {
+            let history = &row.months[idx.saturating_sub(TRAILING).max(first)..idx];
+
+            let average = rolling_average(history, history.len())
+                .pop()
+                .unwrap_or(Amount::ZERO);
+            if average > Amount::ZERO && amount.subunits() > average.subunits() * TAG_FACTOR {
+                anomalies.push(Anomaly::Tag {
+                    date: ledgers[idx].date.clone(),
+                    tag: row.tag.clone(),
+                    amount: *amount,
+                    average,
+                });
+            }
+        }
+    }
+
+    let mut debits: Vec<Amount> = ledgers
+        .iter()
+        .flat_map(|l| l.entries.iter())
+        .filter(|e| e.kind == EntryKind::Debit)
+        .map(|e| e.amount)
+        .collect();
+    if debits.len() >= MIN_DEBITS {
+        // The nearest-rank percentile.
+        debits.sort_unstable();
+        let threshold = debits[(debits.len() * PERCENTILE).div_ceil(100) - 1];
+
+        for ledger in ledgers.iter() {
+            for (idx, entry) in ledger.entries.iter().enumerate() {
+                if entry.kind == EntryKind::Debit && entry.amount > threshold {
+                    anomalies.push(Anomaly::Entry {
+                        date: ledger.date.clone(),
+                        number: idx + 1,
+                        entry: entry.clone(),
+                        threshold,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(anomalies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pledger::{parse_ledger_reader, ParseOptions};
+
+    fn amt(subunits: u64) -> Amount {
+        Amount::from_subunits(subunits)
+    }
+
+    #[test]
+    fn test_rolling() {
+        let amounts: Vec<_> = [300, 600, 900, 0, 1].into_iter().map(amt).collect();
+        assert_eq!(
+            rolling_average(&amounts, 3),
+            [amt(300), amt(450), amt(600), amt(500), amt(300)]
+        );
+        assert_eq!(rolling_average(&amounts, 1), amounts);
+        assert_eq!(rolling_average(&[], 3), []);
+
+        let ledger = |date: &str, contents: &str| {
+            parse_ledger_reader(date, contents.as_bytes(), &ParseOptions::default()).unwrap()
+        };
+        let ledgers = vec![
+            ledger("2023-01", "D 3.00 #food\nC 100.00 #pay"),
+            ledger("2023-02", "D 6.00 #food\nD 3.00 #fun"),
+            ledger("2023-03", "D 30.00 #rent"),
+        ];
+        let rolling = Rolling::new(&ledgers).unwrap();
+        assert_eq!(rolling.debits, [amt(300), amt(900), amt(3000)]);
+        assert_eq!(rolling.averages[0], [amt(300), amt(600), amt(1400)]);
+        assert_eq!(rolling.tags[0][0].tag, "#rent");
+        assert_eq!(rolling.tags[0][1].averages, [amt(300), amt(450), amt(300)]);
+
+        let text = rolling.to_text();
+        assert!(text.contains("Debit tags, 6-month average:"));
+        assert!(text.contains("2023-03               30.00      14.00      14.00"));
+    }
+
+    #[test]
+    fn test_anomalies() {
+        let ledger = |date: &str, contents: String| {
+            parse_ledger_reader(date, contents.as_bytes(), &ParseOptions::default()).unwrap()
+        };
+
+        // A steady tag with a typo (12.00 as 1200.00), and a new tag.
+        let mut ledgers: Vec<_> = (1..=4)
+            .map(|m| ledger(&format!("2023-0{}", m), "D 12.00 lunch #food\n".repeat(30)))
+            .collect();
+        ledgers.push(ledger(
+            "2023-05",
+            format!(
+                "{}D 1200.00 lunch #food\nD 5.00 #new\n",
+                "D 12.00 #food\n".repeat(29)
+            ),
+        ));
+
+        let anomalies = anomalies(&ledgers).unwrap();
+        assert_eq!(anomalies.len(), 2);
+        assert_eq!(anomalies[0].date(), "2023-05");
+        assert!(matches!(&anomalies[0], Anomaly::Tag { tag, .. } if tag == "#food"));
+        assert_eq!(
+            anomalies[1].to_string(),
+            "2023-05:30: D 1200.00 lunch #food is above the 99th percentile of debits (12.00)"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! Sending reports to a webhook, from the config's `[webhook]`, for `pledger report --send`.
+//!
+//! Each style is the JSON body that a kind of service expects: Slack's and Discord's
+//! incoming webhooks, Matrix webhook bridges, and Pushover's message API. Actually sending
+//! needs pledger to be built with the `http` feature; the bodies are built either way.
+
+use serde_json::json;
+
+use crate::pledger::config::{Webhook, WebhookStyle};
+use crate::pledger::{Error, Result};
+
+/// The longest message that Pushover accepts, in characters.
+const PUSHOVER_LIMIT: usize = 1024;
+
+/// Returns the JSON body for a report with `title`, rendered as `text`, in the webhook's
+/// style. The chat styles show the text in a code block, so that its tables line up.
+pub fn body(webhook: &Webhook, title: &str, text: &str) -> Result<String> {
+    let block = format!("*{}*\n```\n{}\n```", title, text.trim_end());
+    let body = match webhook.style {
+        WebhookStyle::Slack => json!({ "text": block }),
+        WebhookStyle::Discord => json!({ "content": block }),
+        WebhookStyle::Matrix => json!({ "msgtype": "m.text", "body": block }),
+        WebhookStyle::Pushover => {
+            let (token, user) = match (&webhook.token, &webhook.user) {
+                (Some(token), Some(user)) => (token, user),
+                _ => {
+                    return Err(Error::Config(
+                        "pushover webhooks need a token and a user".into(),
+                    ))
+                }
+            };
+            json!({
+                "token": token,
+                "user": user,
+                "title": title,
+                "message": text.trim_end().chars().take(PUSHOVER_LIMIT).collect::<String>(),
+                "monospace": 1,
+            })
+        }
+    };
+    Ok(body.to_string())
+}
+
+/// Posts a report to the webhook, like `body`.
+pub fn send(webhook: &Webhook, title: &str, text: &str) -> Result<()> {
+    post(&webhook.url, &body(webhook, title, text)?)
+}
+
+#[cfg(feature = "http")]
+fn post(url: &str, body: &str) -> Result<()> {
+    ureq::post(url)
+        .set("Content-Type", "application/json")
+        .send_string(body)
+        .map_err(|e| Error::Notify(format!("couldn't send the report to the webhook: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "http"))]
+fn post(_url: &str, _body: &str) -> Result<()> {
+    Err(Error::Notify(
+        "pledger was built without the http feature, which sending reports needs".into(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body() {
+        let mut webhook = Webhook {
+            url: "https://example.com/hook".into(),
+            style: WebhookStyle::Slack,
+            token: None,
+            user: None,
+        };
+        assert_eq!(
+            body(&webhook, "pledger: 2023-05", "| a | b |\n").unwrap(),
+            r#"{"text":"*pledger: 2023-05*\n```\n| a | b |\n```"}"#
+        );
+
+        webhook.style = WebhookStyle::Pushover;
+        assert!(body(&webhook, "pledger: 2023-05", "x").is_err());
+        webhook.token = Some("app".into());
+        webhook.user = Some("me".into());
+        let pushover: serde_json::Value =
+            serde_json::from_str(&body(&webhook, "t", &"x".repeat(2000)).unwrap()).unwrap();
+        assert_eq!(pushover["user"], "me");
+        assert_eq!(pushover["message"].as_str().unwrap().len(), PUSHOVER_LIMIT);
+    }
+}
@@ -0,0 +1,197 @@
+//! Building an entry by answering questions, for `pledger add -i`.
+//!
+//! Each answer is checked as it's given (by the parser, so that the wizard accepts
+//! exactly what a ledger does), and asked again if it's invalid. Tags are completed from
+//! the ones that are already in the ledgers: a tag that's the start of exactly one
+//! existing tag becomes that tag.
+
+use std::io::{BufRead, Write};
+
+use crate::pledger::{is_tag, Entry, Error, Ledger, Result};
+
+/// Asks a question, returning the trimmed answer, or `None` once the input is closed.
+fn ask(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    question: &str,
+) -> Result<Option<String>> {
+    write!(output, "{}", question)?;
+    output.flush()?;
+
+    let mut answer = String::new();
+    match input.read_line(&mut answer)? {
+        0 => Ok(None),
+        _ => Ok(Some(answer.trim().into())),
+    }
+}
+
+/// Returns every tag in the ledgers, for completing from.
+pub fn existing_tags(ledgers: &[Ledger]) -> Vec<String> {
+    let mut tags: Vec<_> = ledgers
+        .iter()
+        .flat_map(|l| l.entries.iter())
+        .flat_map(|e| e.tags.iter().cloned())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Completes a tag from the existing ones: a tag is kept if it exists, and otherwise
+/// becomes the only existing tag that starts with it. Returns the candidates instead if
+/// there's more than one.
+fn complete(tag: &str, existing: &[String]) -> std::result::Result<String, Vec<String>> {
+    let tag = match tag.starts_with('#') {
+        true => tag.to_string(),
+        false => format!("#{}", tag),
+    };
+    if existing.contains(&tag) {
+        return Ok(tag);
+    }
+
+    let candidates: Vec<_> = existing
+        .iter()
+        .filter(|t| t.starts_with(&tag))
+        .cloned()
+        .collect();
+    match candidates.len() {
+        0 => Ok(tag),
+        1 => Ok(candidates.into_iter().next().unwrap()),
+        _ => Err(candidates),
+    }
+}
+
+/// Asks for each part of an entry in turn, returning the entry, or `None` if the input
+/// closes first. `existing` are the tags to complete from.
+pub fn ask_entry(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    existing: &[String],
+) -> Result<Option<Entry>> {
+    let kind = loop {
+        let answer = match ask(
+            input,
+            output,
+            "kind ([d]ebit, [c]redit, [t]ransfer, or [a]llocation) [d]: ",
+        )? {
+            Some(answer) => answer.to_lowercase(),
+            None => return Ok(None),
+        };
+        match answer.as_str() {
+            "" | "d" | "debit" => break 'D',
+            "c" | "credit" => break 'C',
+            "t" | "transfer" => break 'T',
+            "a" | "allocation" => break 'A',
+            _ => writeln!(output, "expected d, c, t, or a")?,
+        }
+    };
+
+    let amount = loop {
+        let answer = match ask(input, output, "amount: ")? {
+            Some(answer) => answer,
+            None => return Ok(None),
+        };
+        // The amount is checked with a placeholder comment, so that it's parsed
+        // exactly as it would be in a ledger.
+        match format!("D {} -", answer).parse::<Entry>() {
+            Ok(_) if !answer.is_empty() => break answer,
+            Ok(_) => writeln!(output, "an amount is required")?,
+            Err(e) => writeln!(output, "invalid amount: {}", e)?,
+        }
+    };
+
+    let question = match kind {
+        'T' => "comment (with @from and @to accounts): ",
+        'A' => "comment (with the envelope's #tag): ",
+        _ => "comment: ",
+    };
+    let comment = loop {
+        let answer = match ask(input, output, question)? {
+            Some(answer) => answer,
+            None => return Ok(None),
+        };
+        if answer.is_empty() {
+            writeln!(output, "a comment is required")?;
+            continue;
+        }
+        match format!("{} {} {}", kind, amount, answer).parse::<Entry>() {
+            Ok(_) => break answer,
+            Err(e) => writeln!(output, "invalid comment: {}", e)?,
+        }
+    };
+
+    let tags = 'tags: loop {
+        let answer = match ask(input, output, "tags (e.g. #food, or the start of one): ")? {
+            Some(answer) => answer,
+            None => return Ok(None),
+        };
+
+        let mut tags = vec![];
+        for word in answer.split_whitespace() {
+            match complete(word, existing) {
+                Ok(tag) if is_tag(&tag) => tags.push(tag),
+                Ok(tag) => {
+                    writeln!(output, "invalid tag: {}", tag)?;
+                    continue 'tags;
+                }
+                Err(candidates) => {
+                    writeln!(output, "{} could be {}", word, candidates.join(", "))?;
+                    continue 'tags;
+                }
+            }
+        }
+        break tags;
+    };
+
+    let mut line = format!("{} {} {}", kind, amount, comment);
+    for tag in tags.iter() {
+        line.push(' ');
+        line.push_str(tag);
+    }
+
+    match line.parse::<Entry>() {
+        Ok(entry) => Ok(Some(entry)),
+        Err(Error::Parse { message, .. }) => Err(Error::syntax(
+            None,
+            format!("invalid entry: {}: {}", line, message),
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ask_entry() {
+        let existing = vec!["#food".to_string(), "#fun".into(), "#rent".into()];
+        let mut output = vec![];
+
+        // Invalid answers are asked again, and tags are completed.
+        let mut input = "x\nd\n1.0\n12.50\n\nlunch\n#f\nfood #re #new\n".as_bytes();
+        let entry = ask_entry(&mut input, &mut output, &existing)
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.to_string(), "D 12.50 lunch #food #rent #new");
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("expected d, c, t, or a"));
+        assert!(output.contains("invalid amount"));
+        assert!(output.contains("#f could be #food, #fun"));
+
+        let mut input = "c\n5.00\n".as_bytes();
+        assert!(ask_entry(&mut input, &mut vec![], &existing)
+            .unwrap()
+            .is_none());
+
+        let mut output = vec![];
+        let mut input = "t\n5.00\nsavings\n@checking @savings\n\n".as_bytes();
+        let entry = ask_entry(&mut input, &mut output, &existing)
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.to_string(), "T 5.00 @checking @savings");
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("invalid comment"));
+    }
+}
@@ -0,0 +1,212 @@
+//! Golden-file tests for the CLI: each case runs `pledger` against a copy of a fixture
+//! ledger directory (from `tests/fixtures`), and compares its output to the case's file
+//! in `tests/golden`.
+//!
+//! After an intended change to the output, `cargo test --test cli -- --bless` rewrites the
+//! golden files with the current output, for review in the diff. Other arguments are
+//! substrings of the cases to run.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{self, Command};
+
+/// Each case: its golden file, its fixture directory, and the arguments after `pledger`.
+/// The fixture is passed as the last argument, i.e. as the ledger directory.
+const CASES: &[(&str, &str, &[&str])] = &[
+    ("summary.txt", "basic", &["-d", "2023-02"]),
+    ("summary.json", "basic", &["-d", "2023-02", "--json"]),
+    (
+        "summary.md",
+        "basic",
+        &["-d", "2023-02", "--format", "markdown"],
+    ),
+    (
+        "summary-filter.txt",
+        "basic",
+        &["-d", "2023-02", "-f", "#food"],
+    ),
+    (
+        "query.txt",
+        "basic",
+        &["--year", "2023", "-q", "amount > 50"],
+    ),
+    ("by-day.txt", "basic", &["-d", "2023-01", "--by-day"]),
+    ("pivot.txt", "basic", &["--year", "2023", "--pivot"]),
+    ("year.csv", "basic", &["--year", "2023", "--format", "csv"]),
+    (
+        "export.csv",
+        "basic",
+        &["--year", "2023", "--export", "csv"],
+    ),
+    (
+        "export.beancount",
+        "basic",
+        &["--year", "2023", "--export", "beancount"],
+    ),
+    ("report.txt", "basic", &["report", "--year", "2023"]),
+    ("balance.txt", "basic", &["balance"]),
+    (
+        "rm-void.txt",
+        "basic",
+        &["rm", "--void", "--yes", "2023-01:3"],
+    ),
+    ("rm.txt", "basic", &["rm", "--yes", "2023-01:3"]),
+    (
+        "fmt-dry-run.txt",
+        "basic",
+        &["--dry-run", "fmt", "--year", "2023"],
+    ),
+    (
+        "add-dry-run.txt",
+        "basic",
+        &[
+            "--dry-run",
+            "add",
+            "-d",
+            "2023-01",
+            "D 4.00 tea #food",
+            "--dir",
+        ],
+    ),
+    (
+        "edit-dry-run.txt",
+        "basic",
+        &["--dry-run", "-d", "2023-03", "--edit", "--editor", "false"],
+    ),
+    (
+        "retag.txt",
+        "basic",
+        &[
+            "--dry-run",
+            "retag",
+            "--year",
+            "2023",
+            "#food",
+            "#groceries",
+            "--dir",
+        ],
+    ),
+    ("invalid.txt", "invalid", &["-d", "2023-03"]),
+    ("compressed.txt", "compressed", &["-d", "2023-01"]),
+];
+
+/// The output of a case: its stdout, then its stderr and exit status if it has any.
+fn run(fixture: &str, args: &[&str], scratch: &Path) -> String {
+    let directory = scratch.join(fixture);
+    copy_dir(&root().join("tests/fixtures").join(fixture), &directory);
+
+    // Run from the scratch directory, with the fixture's relative name, so that
+    // sources in the output are the same from run to run.
+    let output = Command::new(env!("CARGO_BIN_EXE_pledger"))
+        .args(args)
+        .arg(fixture)
+        .current_dir(scratch)
+        .env_remove("PLEDGER_DIR")
+        .env_remove("PLEDGER_PROFILE")
+        .env("PLEDGER_PROFILES", scratch.join("profiles.toml"))
+        .env("XDG_CACHE_HOME", scratch.join("cache"))
+        .env("COLUMNS", "80")
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("couldn't run pledger");
+
+    let mut result = String::from_utf8_lossy(&output.stdout).into_owned();
+    if !output.stderr.is_empty() {
+        result.push_str("--- stderr ---\n");
+        result.push_str(&String::from_utf8_lossy(&output.stderr));
+    }
+    if !output.status.success() {
+        result.push_str(&format!("--- exit: {:?} ---\n", output.status.code()));
+    }
+
+    fs::remove_dir_all(&directory).unwrap();
+    result
+}
+
+fn root() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        // Skips e.g. a .pledger cache, from running pledger on the fixture itself.
+        if !entry.file_type().unwrap().is_file() {
+            continue;
+        }
+        fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+    }
+}
+
+fn main() {
+    let mut bless = false;
+    let mut filters = vec![];
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--bless" => bless = true,
+            // Ignore the rest of libtest's flags, e.g. --nocapture.
+            arg if arg.starts_with('-') => {}
+            arg => filters.push(arg.to_string()),
+        }
+    }
+
+    let scratch = tempfile::tempdir().unwrap();
+
+    let mut failures = vec![];
+    let mut ran = 0;
+    for (golden, fixture, args) in CASES {
+        if !filters.is_empty() && !filters.iter().any(|f| golden.contains(f.as_str())) {
+            continue;
+        }
+        ran += 1;
+
+        let actual = run(fixture, args, scratch.path());
+        let path = root().join("tests/golden").join(golden);
+        if bless {
+            fs::write(&path, &actual).unwrap();
+            println!("blessed {}", path.display());
+            continue;
+        }
+
+        match fs::read_to_string(&path) {
+            Ok(expected) if expected == actual => println!("test {} ... ok", golden),
+            Ok(expected) => {
+                println!("test {} ... FAILED", golden);
+                failures.push(format!(
+                    "{} (pledger {} {}):\n--- expected ---\n{}--- actual ---\n{}",
+                    path.display(),
+                    args.join(" "),
+                    fixture,
+                    expected,
+                    actual
+                ));
+            }
+            Err(e) => {
+                println!("test {} ... FAILED", golden);
+                failures.push(format!(
+                    "{}: {} (run with --bless to create it)",
+                    path.display(),
+                    e
+                ));
+            }
+        }
+    }
+
+    // Clean up explicitly, since exiting below skips the drop.
+    scratch.close().unwrap();
+
+    for failure in failures.iter() {
+        eprintln!("\n{}", failure);
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        ran - failures.len(),
+        failures.len()
+    );
+    if !failures.is_empty() {
+        process::exit(1);
+    }
+}